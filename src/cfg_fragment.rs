@@ -0,0 +1,187 @@
+use std::{
+    fs::{read_to_string, write},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::is_generated_plugin_name;
+
+/// The only file OpenMW reads from a directory chained in with `config=`
+pub const FRAGMENT_NAME: &str = "openmw.cfg";
+
+/// Where the fragment is written, given the path passed to `--enable-via-fragment`,
+/// which may be the directory chained in with `config=` rather than the file itself.
+/// The path is made absolute, as `config=` needs its directory to be
+pub fn fragment_path(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+
+    match path.is_dir() {
+        true => path.join(FRAGMENT_NAME),
+        false => path,
+    }
+}
+
+/// A `data=` line, quoted and escaped the same way OpenMW writes them
+fn data_line(dir: &Path) -> String {
+    let dir = dir
+        .display()
+        .to_string()
+        .replace('&', "&&")
+        .replace('"', "&\"");
+
+    format!("data=\"{dir}\"")
+}
+
+fn read_fragment(path: &Path) -> io::Result<String> {
+    match read_to_string(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        contents => contents,
+    }
+}
+
+/// Adds the `data=` line of data_dir, if any, and a `content=` line for each content file to the fragment,
+/// creating it if needed. Lines it already has aren't added again, so this may be run any number of times.
+/// Returns whether the fragment changed
+pub fn write_fragment(
+    path: &Path,
+    data_dir: Option<&Path>,
+    content_files: &[String],
+) -> io::Result<bool> {
+    let contents = read_fragment(path)?;
+    let mut lines: Vec<String> = contents.lines().map(str::to_string).collect();
+    let mut changed = false;
+
+    let wanted_lines = data_dir.map(data_line).into_iter().chain(
+        content_files
+            .iter()
+            .map(|content_file| format!("content={content_file}")),
+    );
+
+    for wanted_line in wanted_lines {
+        if lines
+            .iter()
+            .any(|line| line.trim().eq_ignore_ascii_case(&wanted_line))
+        {
+            continue;
+        }
+
+        lines.push(wanted_line);
+        changed = true;
+    }
+
+    if changed {
+        write(path, lines.join("\n") + "\n")?;
+    }
+
+    Ok(changed)
+}
+
+/// Takes the lines `write_fragment` adds back out of the fragment, being the `data=` line of data_dir
+/// and the `content=` lines of generated plugins. Anything else in it is left alone.
+/// Returns the generated plugins which were removed
+pub fn revert_fragment(path: &Path, data_dir: &Path) -> io::Result<Vec<String>> {
+    let contents = read_fragment(path)?;
+    let data_line = data_line(data_dir);
+
+    let mut removed_plugins = Vec::new();
+    let mut removed_any = false;
+    let mut kept_lines = Vec::new();
+
+    for line in contents.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.eq_ignore_ascii_case(&data_line) {
+            removed_any = true;
+        } else if let Some(content_file) = trimmed.strip_prefix("content=")
+            && is_generated_plugin_name(content_file.trim())
+        {
+            removed_plugins.push(content_file.trim().to_string());
+            removed_any = true;
+        } else {
+            kept_lines.push(line);
+        }
+    }
+
+    if removed_any {
+        let mut contents = kept_lines.join("\n");
+        if !contents.is_empty() {
+            contents.push('\n');
+        }
+
+        write(path, contents)?;
+    }
+
+    Ok(removed_plugins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PLUGIN_NAME, test_fixtures::temp_dir};
+
+    #[test]
+    fn fragment_directories_hold_an_openmw_cfg() {
+        let dir = temp_dir("fragment-path");
+
+        assert_eq!(fragment_path(&dir), dir.join(FRAGMENT_NAME));
+        assert_eq!(
+            fragment_path(&dir.join("lightfixes.cfg")),
+            dir.join("lightfixes.cfg")
+        );
+        assert!(fragment_path(Path::new("relative.cfg")).is_absolute());
+    }
+
+    #[test]
+    fn data_lines_are_escaped_like_openmw_writes_them() {
+        assert_eq!(
+            data_line(Path::new("mods/\"Lights\" & Fires")),
+            "data=\"mods/&\"Lights&\" && Fires\""
+        );
+    }
+
+    #[test]
+    fn fragments_are_written_once_and_reverted() {
+        let dir = temp_dir("fragment-write");
+        let fragment = dir.join(FRAGMENT_NAME);
+        let data_dir = dir.join("s3lightfixes-output");
+        std::fs::write(&fragment, "content=Fires.esp\n").unwrap();
+
+        let content_files = [PLUGIN_NAME.to_string()];
+        assert!(write_fragment(&fragment, Some(&data_dir), &content_files).unwrap());
+        assert!(!write_fragment(&fragment, Some(&data_dir), &content_files).unwrap());
+
+        assert_eq!(
+            read_to_string(&fragment).unwrap(),
+            format!(
+                "content=Fires.esp\n{}\ncontent={PLUGIN_NAME}\n",
+                data_line(&data_dir)
+            )
+        );
+
+        // Only the lines lightfixes added are taken back out
+        assert_eq!(
+            revert_fragment(&fragment, &data_dir).unwrap(),
+            [PLUGIN_NAME]
+        );
+        assert_eq!(read_to_string(&fragment).unwrap(), "content=Fires.esp\n");
+        assert!(revert_fragment(&fragment, &data_dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn missing_fragments_are_created() {
+        let fragment = temp_dir("fragment-missing").join(FRAGMENT_NAME);
+
+        assert!(
+            revert_fragment(&fragment, Path::new("output"))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(!fragment.exists());
+
+        assert!(write_fragment(&fragment, None, &[PLUGIN_NAME.to_string()]).unwrap());
+        assert_eq!(
+            read_to_string(&fragment).unwrap(),
+            format!("content={PLUGIN_NAME}\n")
+        );
+    }
+}
@@ -18,6 +18,10 @@ pub fn standard_radius() -> f32 {
     1.2
 }
 
+pub fn standard_radius_add() -> i32 {
+    0
+}
+
 pub fn colored_hue() -> f32 {
     1.0
 }
@@ -34,10 +38,71 @@ pub fn colored_radius() -> f32 {
     1.1
 }
 
+pub fn colored_radius_add() -> i32 {
+    0
+}
+
+/// Radius which the radius curve pivots around
+pub fn radius_curve_base() -> f32 {
+    256.0
+}
+
+/// Morrowind's own timescale, at which light durations are in real seconds
+pub fn timescale() -> f32 {
+    30.0
+}
+
 pub fn duration_mult() -> f32 {
     2.5
 }
 
+pub fn carryable_radius_mult() -> f32 {
+    1.0
+}
+
+pub fn carryable_value_mult() -> f32 {
+    1.0
+}
+
+pub fn carryable_duration_mult() -> f32 {
+    1.0
+}
+
+pub fn weight_mult() -> f32 {
+    1.0
+}
+
+pub fn gold_value_mult() -> f32 {
+    1.0
+}
+
+/// Normalized lights may be brightened or dimmed by up to this factor
+pub fn normalize_max_adjustment() -> f32 {
+    2.0
+}
+
+/// How far lights are blended toward a tint when no strength is given
+pub fn tint_strength() -> f32 {
+    0.5
+}
+
+pub fn interior_sunlight_mult() -> f32 {
+    1.0
+}
+
+pub fn fog_density_mult() -> f32 {
+    1.0
+}
+
+pub fn weather_mult() -> f32 {
+    1.0
+}
+
+/// No floor by default, so that output is unchanged
+pub fn min_value() -> f32 {
+    0.0
+}
+
 pub fn disable_flicker() -> bool {
     true
 }
@@ -46,6 +111,16 @@ pub fn disable_pulse() -> bool {
     false
 }
 
+/// Zero-radius lights are usually sound emitters or script markers, which lightfixes has no effect on
+pub fn skip_zero_radius() -> bool {
+    true
+}
+
+/// Cells only need their atmosphere overridden, and the engine merges references from the original plugins
+pub fn clear_references() -> bool {
+    true
+}
+
 pub fn save_log() -> bool {
     false
 }
@@ -54,6 +129,27 @@ pub fn auto_enable() -> bool {
     false
 }
 
+pub fn write_metadata() -> bool {
+    false
+}
+
+pub fn keep_backups() -> u8 {
+    1
+}
+
+/// Most masters the generated plugin may have before lightfixes warns about it
+pub fn max_masters() -> usize {
+    255
+}
+
+pub fn plugin_author() -> String {
+    "S3".into()
+}
+
+pub fn plugin_description() -> String {
+    "Plugin generated by s3-lightfixes".into()
+}
+
 pub fn excluded_plugins() -> Vec<String> {
     vec![
         // Unable to resolve moved reference (1, 7028) for cell Sadrith Mora (18, 4)
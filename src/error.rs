@@ -0,0 +1,507 @@
+use std::{fmt, io, path::PathBuf};
+
+use crate::VerifyError;
+
+/// Something which went wrong while generating the plugin. Most stop lightfixes,
+/// but plugins which couldn't be loaded or saved are only kept as warnings of the run
+#[derive(Debug)]
+pub enum LightFixesError {
+    /// No plugin in the load order had anything worth overriding, so the plugin would have no masters
+    NoMasters,
+    /// A light override copies from a light which isn't in the load order, or was excluded
+    MissingCopySource { light_id: String, source_id: String },
+    /// Light overrides copy from each other in a loop, so none of these lights has anything to copy
+    CopyCycle(Vec<String>),
+    /// Morrowind.ini was given in place of openmw.cfg, but couldn't be read
+    MorrowindIni { path: PathBuf, source: io::Error },
+    /// There's no Data Files directory next to Morrowind.ini
+    MissingDataFiles { ini_path: PathBuf },
+    /// An option which only works with openmw.cfg was used with Morrowind.ini
+    NeedsOpenMWConfig,
+    /// No openmw.cfg could be found, and none was picked instead
+    ConfigNotFound(ConfigPathError),
+    /// lightconfig.toml isn't valid toml, or has settings of the wrong type
+    ConfigParse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    /// lightconfig.toml couldn't be serialized to be saved or printed
+    ConfigSerialize(toml::ser::Error),
+    /// lightconfig.toml has settings which can't be used together, or are out of range
+    InvalidConfig(String),
+    /// --profile names a profile which isn't in lightconfig.toml
+    UnknownProfile(String),
+    /// A pattern of one of the tables of lightconfig.toml couldn't be compiled
+    InvalidPattern {
+        pattern: String,
+        table: &'static str,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// openmw.cfg was found, but couldn't be read
+    ConfigRead(String),
+    /// Another run of lightfixes holds the lock of the config directory
+    AlreadyRunning(io::Error),
+    /// The lock file couldn't be created or locked
+    Lock { dir: PathBuf, source: io::Error },
+    /// A data directory given with --data-dir does not exist
+    MissingDataDir(PathBuf),
+    /// The output directory does not exist, or couldn't be created, in which case the error is kept
+    OutputDir {
+        path: PathBuf,
+        source: Option<io::Error>,
+    },
+    /// The current working directory couldn't be read to write the plugin to
+    NoWorkingDirectory(io::Error),
+    /// Nothing is enabled in the load order
+    NoPlugins { load_order_name: &'static str },
+    /// Content files the VFS couldn't resolve to any data directory, which --strict doesn't allow
+    MissingContent {
+        load_order_name: &'static str,
+        missing: Vec<String>,
+    },
+    /// The header of a generated plugin didn't count its records correctly
+    BadRecordCount { plugin_name: String },
+    /// `--output -` was used with a plugin split into several
+    SplitStream,
+    /// The plugin couldn't be written to stdout
+    Stream(io::Error),
+    /// A plugin of the load order couldn't be loaded, so its lights weren't fixed
+    PluginLoad { path: PathBuf, source: io::Error },
+    /// The plugin exists, and overwriting it isn't allowed
+    PluginExists(PathBuf),
+    /// The plugin couldn't be written
+    PluginSave { path: PathBuf, source: io::Error },
+    /// The saved plugin failed `--verify`
+    VerifyFailed { path: PathBuf, source: VerifyError },
+    /// The plugin couldn't be enabled in openmw.cfg
+    EnableFailed(String),
+    /// The config fragment couldn't be rewritten by --revert
+    FragmentRevert { path: PathBuf, source: io::Error },
+    /// openmw.cfg couldn't be saved
+    ConfigSave(String),
+    /// Anything else which went wrong reading or writing files
+    Io(io::Error),
+}
+
+impl LightFixesError {
+    /// Title of the dialog the error is shown in
+    pub fn title(&self) -> &'static str {
+        use LightFixesError::*;
+        match self {
+            NoMasters => "No masters found!",
+            MissingCopySource { .. } => "Missing copy_from light!",
+            CopyCycle(_) => "copy_from loop!",
+            MorrowindIni { .. } => "Failed to read Morrowind.ini!",
+            MissingDataFiles { .. } => "Can't find Data Files!",
+            NeedsOpenMWConfig => "Can't use Morrowind.ini!",
+            ConfigNotFound(_) => "Can't find openmw.cfg!",
+            ConfigParse { .. } => "Failed to read light config!",
+            ConfigSerialize(_) => "Failed to write light config!",
+            InvalidConfig(_) => "Invalid light config!",
+            UnknownProfile(_) => "Unknown profile!",
+            InvalidPattern { .. } => "Invalid pattern in light config!",
+            ConfigRead(_) => "Failed to read configuration file!",
+            AlreadyRunning(_) => "Lightfixes is already running!",
+            Lock { .. } => "Can't lock lightfixes!",
+            MissingDataDir(_) => "Can't find data directory!",
+            OutputDir {
+                source: Some(_), ..
+            } => "Can't create output location!",
+            OutputDir { source: None, .. } => "Can't find output location!",
+            NoWorkingDirectory(_) => "Can't get workdir!",
+            NoPlugins { .. } => "No Plugins!",
+            MissingContent { .. } => "Missing content files!",
+            BadRecordCount { .. } => "Bad record count!",
+            SplitStream => "Can't stream a split plugin!",
+            Stream(_) => "Failed to save plugin!",
+            PluginLoad { .. } => "Failed to load plugin!",
+            PluginExists(_) => "Plugin already exists!",
+            PluginSave { .. } => "Failed to save plugin!",
+            VerifyFailed { .. } => "Generated plugin is broken!",
+            EnableFailed(_) => "Failed to enable lightfixes!",
+            FragmentRevert { .. } => "Failed to revert the config fragment!",
+            ConfigSave(_) => "Failed to resave openmw.cfg!",
+            Io(_) => "Lightfixes failed!",
+        }
+    }
+
+    /// Code the command line exits with, which scripts may rely on.
+    /// Only the low byte of an exit status survives on Unix, so every code is in 1..=255
+    pub fn exit_code(&self) -> i32 {
+        use LightFixesError::*;
+        match self {
+            NoMasters => 2,
+            NoPlugins { .. } => 4,
+            MissingCopySource { .. } | CopyCycle(_) => 5,
+            PluginExists(_) => 6,
+            BadRecordCount { .. } => 7,
+            VerifyFailed { .. } => 8,
+            MissingContent { .. } => 9,
+            ConfigNotFound(_) => 10,
+            AlreadyRunning(_) | Lock { .. } => 11,
+            ConfigParse { .. } | ConfigSerialize(_) | InvalidConfig(_) => 12,
+            InvalidPattern { .. } => 13,
+            NoWorkingDirectory(_) => 14,
+            EnableFailed(_) => 15,
+            MorrowindIni { .. } | ConfigRead(_) => 127,
+            MissingDataFiles { .. }
+            | NeedsOpenMWConfig
+            | UnknownProfile(_)
+            | MissingDataDir(_)
+            | OutputDir { .. }
+            | SplitStream
+            | Stream(_)
+            | PluginLoad { .. }
+            | PluginSave { .. }
+            | FragmentRevert { .. }
+            | ConfigSave(_)
+            | Io(_) => 1,
+        }
+    }
+}
+
+impl From<io::Error> for LightFixesError {
+    fn from(err: io::Error) -> Self {
+        LightFixesError::Io(err)
+    }
+}
+
+impl From<ConfigPathError> for LightFixesError {
+    fn from(err: ConfigPathError) -> Self {
+        LightFixesError::ConfigNotFound(err)
+    }
+}
+
+impl fmt::Display for LightFixesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use LightFixesError::*;
+        match self {
+            NoMasters => write!(
+                f,
+                "The generated plugin was not found to have any master files! It's empty! Try running lightfixes again using the S3L_DEBUG environment variable"
+            ),
+            MissingCopySource {
+                light_id,
+                source_id,
+            } => write!(
+                f,
+                "Light {light_id} copies from {source_id}, which does not exist in the load order or was excluded!"
+            ),
+            CopyCycle(light_ids) => write!(
+                f,
+                "Lights {} copy from each other in a loop, so none of them has anything to copy!",
+                light_ids.join(", ")
+            ),
+            MorrowindIni { path, source } => {
+                write!(f, "{} couldn't be read: {source}", path.display())
+            }
+            MissingDataFiles { ini_path } => write!(
+                f,
+                "There's no {} next to {}! Terminating.",
+                crate::MorrowindIni::DATA_FILES_NAME,
+                ini_path.display()
+            ),
+            NeedsOpenMWConfig => write!(
+                f,
+                "--output-as-data-dir, --revert and --enable-via-fragment only work with openmw.cfg, which Morrowind.ini can't be chained into."
+            ),
+            ConfigNotFound(err) => write!(f, "{err}"),
+            ConfigParse { path, source } => {
+                write!(f, "{} couldn't be read: {source}", path.display())
+            }
+            ConfigSerialize(err) => write!(f, "Lightconfig.toml couldn't be written: {err}"),
+            InvalidConfig(err) => {
+                write!(f, "Lightconfig.toml contains invalid settings: {err}")
+            }
+            UnknownProfile(profile_name) => write!(
+                f,
+                "There's no profile named {profile_name} in lightconfig.toml"
+            ),
+            InvalidPattern {
+                pattern,
+                table,
+                source,
+            } => write!(f, "Couldn't compile {pattern} of {table}: {source}"),
+            ConfigRead(err) | ConfigSave(err) | EnableFailed(err) => write!(f, "{err}"),
+            AlreadyRunning(err) | Stream(err) | Io(err) => write!(f, "{err}"),
+            PluginLoad { path, source } => write!(
+                f,
+                "Plugin {} could not be loaded due to error: {source}",
+                path.display()
+            ),
+            PluginExists(path) => write!(
+                f,
+                "{} already exists, and the overwrite policy is never!",
+                path.display()
+            ),
+            PluginSave { path, source } => {
+                write!(f, "{} couldn't be saved: {source}", path.display())
+            }
+            Lock { dir, source } => write!(
+                f,
+                "Couldn't lock the lock file in {}: {source}",
+                dir.display()
+            ),
+            MissingDataDir(dir) => write!(
+                f,
+                "The data directory {} given with --data-dir does not exist! Terminating.",
+                dir.display()
+            ),
+            OutputDir {
+                path,
+                source: Some(source),
+            } => write!(f, "Couldn't create {}: {source}", path.display()),
+            OutputDir { path, source: None } => write!(
+                f,
+                "WARNING: The requested output path {} does not exist! Terminating.",
+                path.display()
+            ),
+            NoWorkingDirectory(_) => write!(
+                f,
+                "[ CRITICAL FAILURE ]: FAILED TO READ CURRENT WORKING DIRECTORY!"
+            ),
+            NoPlugins { load_order_name } => write!(
+                f,
+                "No plugins were found in {load_order_name}! No lights to fix!"
+            ),
+            MissingContent {
+                load_order_name,
+                missing,
+            } => write!(
+                f,
+                "These content files are enabled in {load_order_name}, but couldn't be found in any data directory: {}",
+                missing.join(", ")
+            ),
+            BadRecordCount { plugin_name } => write!(
+                f,
+                "The header of {plugin_name} didn't count its records correctly! This is a bug in lightfixes, please report it."
+            ),
+            SplitStream => write!(
+                f,
+                "Only one plugin can be written to stdout, so --output - can't be used with split_masters or split_output!"
+            ),
+            VerifyFailed { path, source } => {
+                write!(f, "{} failed verification: {source}", path.display())
+            }
+            FragmentRevert { path, source } => {
+                write!(f, "{} couldn't be rewritten: {source}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LightFixesError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use LightFixesError::*;
+        match self {
+            ConfigNotFound(err) => Some(err),
+            ConfigParse { source, .. } => Some(source),
+            ConfigSerialize(err) => Some(err),
+            InvalidPattern { source, .. } => Some(&**source),
+            VerifyFailed { source, .. } => Some(source),
+            MorrowindIni { source, .. }
+            | Lock { source, .. }
+            | FragmentRevert { source, .. }
+            | PluginLoad { source, .. }
+            | PluginSave { source, .. }
+            | OutputDir {
+                source: Some(source),
+                ..
+            } => Some(source),
+            AlreadyRunning(err) | NoWorkingDirectory(err) | Stream(err) | Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Why no usable openmw.cfg could be found
+#[derive(Debug)]
+pub enum ConfigPathError {
+    /// The given path is a directory, but holds no openmw.cfg
+    NoConfigInDirectory { path: PathBuf, source: &'static str },
+    /// The given path is neither a file nor a directory
+    DoesNotExist { path: PathBuf, source: &'static str },
+    /// Nothing pointed at an openmw.cfg, and there was none in the working directory or the default location
+    NoConfigFound { default_path: PathBuf },
+    /// The current working directory couldn't be read to look for openmw.cfg in
+    NoWorkingDirectory(io::Error),
+}
+
+impl fmt::Display for ConfigPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ConfigPathError::*;
+        match self {
+            NoConfigInDirectory { path, source } => write!(
+                f,
+                "The directory {} from {source} contains no openmw.cfg! Point it at the directory holding your openmw.cfg, or at the file itself.",
+                path.display()
+            ),
+            DoesNotExist { path, source } => write!(
+                f,
+                "The path {} from {source} does not exist! Check it for typos.",
+                path.display()
+            ),
+            NoConfigFound { default_path } => write!(
+                f,
+                "No openmw.cfg was found in the current working directory, nor at the default location {}! Use --openmw-cfg to point at yours.",
+                default_path.display()
+            ),
+            NoWorkingDirectory(err) => {
+                write!(f, "The current working directory couldn't be read: {err}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigPathError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigPathError::NoWorkingDirectory(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn io_error() -> io::Error {
+        io::Error::other("test")
+    }
+
+    /// One of each variant, so that a new one can't be left out of the exit code checks
+    fn every_error() -> Vec<LightFixesError> {
+        use LightFixesError::*;
+        vec![
+            NoMasters,
+            MissingCopySource {
+                light_id: "light".into(),
+                source_id: "source".into(),
+            },
+            CopyCycle(vec!["light".into(), "source".into()]),
+            MorrowindIni {
+                path: "Morrowind.ini".into(),
+                source: io_error(),
+            },
+            MissingDataFiles {
+                ini_path: "Morrowind.ini".into(),
+            },
+            NeedsOpenMWConfig,
+            ConfigNotFound(ConfigPathError::NoConfigFound {
+                default_path: "openmw.cfg".into(),
+            }),
+            ConfigParse {
+                path: "lightconfig.toml".into(),
+                source: toml::from_str::<toml::Table>("=").unwrap_err(),
+            },
+            ConfigSerialize(<toml::ser::Error as serde::ser::Error>::custom("test")),
+            InvalidConfig("max_masters must be at least 1".into()),
+            UnknownProfile("profile".into()),
+            InvalidPattern {
+                pattern: "(".into(),
+                table: "excluded_ids",
+                source: Box::new(regex::Regex::new("(").unwrap_err()),
+            },
+            ConfigRead("test".into()),
+            AlreadyRunning(io_error()),
+            Lock {
+                dir: "dir".into(),
+                source: io_error(),
+            },
+            MissingDataDir("dir".into()),
+            OutputDir {
+                path: "dir".into(),
+                source: Some(io_error()),
+            },
+            OutputDir {
+                path: "dir".into(),
+                source: None,
+            },
+            NoWorkingDirectory(io_error()),
+            NoPlugins {
+                load_order_name: "openmw.cfg",
+            },
+            MissingContent {
+                load_order_name: "openmw.cfg",
+                missing: vec!["Missing.esp".into()],
+            },
+            BadRecordCount {
+                plugin_name: "S3LightFixes.omwaddon".into(),
+            },
+            SplitStream,
+            Stream(io_error()),
+            PluginLoad {
+                path: "Plugin.esp".into(),
+                source: io_error(),
+            },
+            PluginExists("S3LightFixes.omwaddon".into()),
+            PluginSave {
+                path: "S3LightFixes.omwaddon".into(),
+                source: io_error(),
+            },
+            VerifyFailed {
+                path: "S3LightFixes.omwaddon".into(),
+                source: VerifyError::MissingHeader,
+            },
+            EnableFailed("test".into()),
+            FragmentRevert {
+                path: "openmw.cfg".into(),
+                source: io_error(),
+            },
+            ConfigSave("test".into()),
+            Io(io_error()),
+        ]
+    }
+
+    #[test]
+    fn exit_codes_survive_unix_truncation() {
+        for err in every_error() {
+            let code = err.exit_code();
+            assert!(
+                (1..=255).contains(&code),
+                "{err:?} exits with {code}, which isn't a failing status on Unix"
+            );
+        }
+    }
+
+    #[test]
+    fn config_and_pattern_errors_have_their_own_codes() {
+        let pattern_code = LightFixesError::InvalidPattern {
+            pattern: "(".into(),
+            table: "excluded_ids",
+            source: Box::new(regex::Regex::new("(").unwrap_err()),
+        }
+        .exit_code();
+        let config_code = LightFixesError::InvalidConfig("test".into()).exit_code();
+
+        assert_ne!(pattern_code, config_code);
+        assert_ne!(config_code, LightFixesError::Io(io_error()).exit_code());
+        assert_ne!(pattern_code, LightFixesError::Io(io_error()).exit_code());
+    }
+
+    #[test]
+    fn errors_have_titles_and_messages() {
+        for err in every_error() {
+            assert!(!err.title().is_empty());
+            assert!(!err.to_string().is_empty(), "{err:?} has no message");
+        }
+    }
+
+    #[test]
+    fn wrapped_errors_are_given_as_sources() {
+        use std::error::Error;
+
+        assert!(LightFixesError::Io(io_error()).source().is_some());
+        assert!(LightFixesError::NoMasters.source().is_none());
+        assert!(
+            LightFixesError::InvalidPattern {
+                pattern: "(".into(),
+                table: "excluded_ids",
+                source: Box::new(regex::Regex::new("(").unwrap_err()),
+            }
+            .source()
+            .is_some()
+        );
+    }
+}
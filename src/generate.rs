@@ -0,0 +1,1983 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    mem::take as TakeAndSwitch,
+};
+
+use palette::Hsv;
+use tes3::esp::{
+    AtmosphereData, Cell, CellFlags, EditorId, FixedString, Header, Light, LightFlags, MagicEffect,
+    ObjectFlags, Plugin, TES3Object,
+};
+
+use crate::{
+    CustomCellAmbient, CustomLightData, InstanceEdit, LightColorMult, LightConfig, LightFixesError,
+    LightPlacement, MAX_AUTHOR_LEN, MAX_DESCRIPTION_LEN, cell_key, config_fingerprint,
+    finalize_header, header_text, hsv_to_color, magic_effect_id, print_notification, process_light,
+    process_magic_effect, record_key, scale_fog_density,
+};
+
+/// What the master entry of a plugin needs, which a plugin loaded from memory can't tell by itself
+#[derive(Clone, Debug)]
+pub struct PluginMeta {
+    /// Name of the plugin as it appears in the load order, such as `Morrowind.esm`
+    pub file_name: String,
+    /// Size of the plugin file in bytes
+    pub size: u64,
+}
+
+/// The generated records, before they're put into one or more plugins
+#[derive(Debug)]
+pub struct Generation {
+    /// Every generated record, without a header
+    pub plugin: Plugin,
+    pub header: Header,
+    /// Lowercased name of the plugin each record of `plugin` came from, by its position
+    pub record_owners: Vec<String>,
+    /// Lowercased names of the masters of `header`, in load order
+    pub master_names: Vec<String>,
+    /// Ids of lights disabled by a light override
+    pub disabled_ids: Vec<String>,
+    /// One line for each reference deleted, replaced or modified by an ambient override
+    pub instance_log: Vec<String>,
+}
+
+/// The processed parts of a light which `copy_from` overrides clone onto other lights
+#[derive(Clone, Copy)]
+struct CopiedLightData {
+    color: [u8; 4],
+    radius: u32,
+    time: i32,
+    flags: LightFlags,
+}
+
+/// Whether processing changed any part of a light which the generated plugin would override
+fn light_changed(original: &Light, processed: &Light) -> bool {
+    original.data.color != processed.data.color
+        || original.data.radius != processed.data.radius
+        || original.data.time != processed.data.time
+        || original.data.flags != processed.data.flags
+        || original.data.weight != processed.data.weight
+        || original.data.value != processed.data.value
+        || original.sound != processed.sound
+        || original.mesh != processed.mesh
+        || original.icon != processed.icon
+}
+
+/// Merges the atmosphere of every cell across the load order, field by field.
+/// Each field comes from the last plugin to change it from the cell's first definition,
+/// so that mods changing different fields of the same cell don't drop each other's changes.
+/// `plugins` is in load order
+fn merged_atmospheres<P>(plugins: &[(Plugin, P)]) -> HashMap<String, AtmosphereData> {
+    let mut atmospheres: HashMap<String, (AtmosphereData, AtmosphereData)> = HashMap::new();
+
+    for cell in plugins
+        .iter()
+        .flat_map(|(plugin, _)| plugin.objects_of_type::<Cell>())
+    {
+        let Some(atmo) = &cell.atmosphere_data else {
+            continue;
+        };
+
+        let (base, merged) = atmospheres
+            .entry(cell_key(cell))
+            .or_insert_with(|| (atmo.clone(), atmo.clone()));
+
+        if atmo.ambient_color != base.ambient_color {
+            merged.ambient_color = atmo.ambient_color;
+        }
+
+        if atmo.sunlight_color != base.sunlight_color {
+            merged.sunlight_color = atmo.sunlight_color;
+        }
+
+        if atmo.fog_color != base.fog_color {
+            merged.fog_color = atmo.fog_color;
+        }
+
+        if atmo.fog_density != base.fog_density {
+            merged.fog_density = atmo.fog_density;
+        }
+    }
+
+    atmospheres
+        .into_iter()
+        .map(|(cell_id, (_, merged))| (cell_id, merged))
+        .collect()
+}
+
+/// Replaces a cell's atmosphere with its merged one, giving back whether anything changed
+fn merge_atmosphere(atmo: &mut AtmosphereData, merged: &AtmosphereData) -> bool {
+    let changed = atmo.ambient_color != merged.ambient_color
+        || atmo.sunlight_color != merged.sunlight_color
+        || atmo.fog_color != merged.fog_color
+        || atmo.fog_density != merged.fog_density;
+
+    *atmo = merged.clone();
+    changed
+}
+
+/// Points the references of a cell carried over from a plugin at the masters of the generated plugin.
+/// `source_names` holds the lowercased name of that plugin followed by those of its own masters,
+/// so that it's indexed by the master index of a reference.
+/// References from plugins which aren't masters of the generated plugin can't be addressed, and are dropped
+pub fn remap_references(cell: &mut Cell, source_names: &[String], master_names: &[String]) {
+    let cell_id = cell.editor_id_ascii_lowercase().into_owned();
+
+    cell.references = TakeAndSwitch(&mut cell.references)
+        .into_values()
+        .filter_map(|mut reference| {
+            let master_index = source_names
+                .get(reference.mast_index as usize)
+                .and_then(|source| master_names.iter().position(|name| name == source));
+
+            let Some(master_index) = master_index else {
+                eprintln!(
+                    "[ WARNING ]: Dropping reference {} ({}, {}) of cell {cell_id}, as the plugin it comes from isn't loaded!",
+                    reference.id, reference.mast_index, reference.refr_index
+                );
+                return None;
+            };
+
+            // Master indices start at 1, as 0 is the generated plugin itself
+            reference.mast_index = master_index as u32 + 1;
+
+            Some(((reference.mast_index, reference.refr_index), reference))
+        })
+        .collect();
+}
+
+/// Gathers which kinds of cells every light in the load order is placed in.
+/// Only ids of light records are kept, so the map stays small no matter how many references there are
+fn light_placements<P>(plugins: &[(Plugin, P)]) -> HashMap<String, LightPlacement> {
+    let light_ids: HashSet<String> = plugins
+        .iter()
+        .flat_map(|(plugin, _)| plugin.objects_of_type::<Light>())
+        .map(|light| light.editor_id_ascii_lowercase().into_owned())
+        .collect();
+
+    let mut placements: HashMap<String, LightPlacement> = HashMap::new();
+
+    for cell in plugins
+        .iter()
+        .flat_map(|(plugin, _)| plugin.objects_of_type::<Cell>())
+    {
+        let is_interior = cell.data.flags.contains(CellFlags::IS_INTERIOR);
+
+        for reference in cell.references.values() {
+            let id = reference.id.to_ascii_lowercase();
+
+            if !light_ids.contains(&id) {
+                continue;
+            }
+
+            let placement = placements.entry(id).or_default();
+
+            match is_interior {
+                true => placement.interior = true,
+                false => placement.exterior = true,
+            }
+        }
+    }
+
+    placements
+}
+
+/// Removes each light, cell or magic effect which a later definition in the same plugin replaces,
+/// as the engine only uses the last one. Every duplicate is warned about, since it's a mistake in the plugin
+fn drop_duplicate_records(plugin: &mut Plugin, plugin_name: &str) {
+    let mut seen_keys: HashSet<(&'static str, String)> = HashSet::new();
+    let mut keep = vec![true; plugin.objects.len()];
+
+    for (index, object) in plugin.objects.iter().enumerate().rev() {
+        let Some((kind, id)) = record_key(object) else {
+            continue;
+        };
+
+        if seen_keys.contains(&(kind, id.clone())) {
+            eprintln!(
+                "[ WARNING ]: {plugin_name} defines {kind} {id} more than once! Only its last definition is used."
+            );
+            keep[index] = false;
+        } else {
+            seen_keys.insert((kind, id));
+        }
+    }
+
+    let mut keep = keep.into_iter();
+    plugin.objects.retain(|_| keep.next().unwrap_or(true));
+}
+
+/// Runs every record of `plugins`, given in load order, through `light_config`, as lightfixes does with openmw.cfg.
+/// The winning definition of each record is processed, and those worth overriding are kept along with
+/// the masters they need
+pub fn generate(
+    mut plugins: Vec<(Plugin, PluginMeta)>,
+    light_config: &LightConfig,
+) -> Result<Generation, LightFixesError> {
+    let mut generated_plugin = Plugin::new();
+    // Cells and lights may share ids, so each type keeps its own load order winners
+    let mut used_cell_ids: HashSet<String> = HashSet::new();
+    let mut used_light_ids: HashSet<String> = HashSet::new();
+    let mut used_effect_ids: HashSet<String> = HashSet::new();
+
+    // Configs built in memory never went through LightConfig::get, so their patterns are still there to hash
+    let fingerprint = match light_config.fingerprint.is_empty() {
+        true => config_fingerprint(light_config),
+        false => light_config.fingerprint.clone(),
+    };
+    let fingerprint_tag = format!(" [cfg:{fingerprint}]");
+
+    let mut header = Header {
+        version: 1.3,
+        author: FixedString(header_text(
+            "author",
+            &light_config.plugin_author,
+            MAX_AUTHOR_LEN,
+        )),
+        description: FixedString(format!(
+            "{}{fingerprint_tag}",
+            header_text(
+                "description",
+                &light_config.plugin_description,
+                MAX_DESCRIPTION_LEN - fingerprint_tag.len(),
+            )
+        )),
+        file_type: light_config.output_file_type.file_type(),
+        flags: ObjectFlags::default(),
+        num_objects: 0,
+        masters: Vec::new(),
+    };
+
+    let load_order: Vec<String> = plugins
+        .iter()
+        .map(|(_, plugin_meta)| plugin_meta.file_name.clone())
+        .collect();
+
+    // Otherwise, the first of several definitions would be the one kept, unlike in the engine
+    for (plugin, plugin_meta) in &mut plugins {
+        drop_duplicate_records(plugin, &plugin_meta.file_name);
+    }
+
+    // References are gathered before the cell loop below clears them
+    let light_placements = (light_config.only_referenced || light_config.has_placement_overrides())
+        .then(|| light_placements(&plugins));
+
+    // Every plugin's version of a cell is needed, so this happens before the cell loop keeps only the winners
+    let merged_atmospheres = light_config
+        .merge_cell_atmosphere
+        .then(|| merged_atmospheres(&plugins));
+
+    // Lights which copy from others are held back until every light they copy from has been processed
+    let copy_source_ids: HashSet<String> = light_config
+        .light_regexes
+        .iter()
+        .filter_map(|(_, _, light_data)| light_data.copy_from.as_ref())
+        .map(|id| id.to_ascii_lowercase())
+        .collect();
+    let mut copy_sources: HashMap<String, CopiedLightData> = HashMap::new();
+    let mut pending_copies: Vec<(Light, Cow<CustomLightData>, String)> = Vec::new();
+    let mut disabled_ids: Vec<String> = Vec::new();
+    let mut instance_log: Vec<String> = Vec::new();
+
+    // Cells keeping references are held back until the masters of the generated plugin are known,
+    // along with the lowercased names of their plugin and its masters, by the master index of each reference
+    let mut cells_with_references: Vec<(Cell, Vec<String>)> = Vec::new();
+    let mut required_masters: HashSet<String> = HashSet::new();
+
+    let mut used_objects = 0;
+    // Lowercased name of the plugin each record of the generated plugin came from, by its position
+    let mut record_owners: Vec<String> = Vec::new();
+    // Plugins are visited from the end of the load order, so the first definition of a record seen wins
+    for (mut plugin, plugin_meta) in plugins.into_iter().rev() {
+        let plugin_name = plugin_meta.file_name.to_ascii_lowercase();
+
+        let source_names: Vec<String> = std::iter::once(plugin_name.clone())
+            .chain(
+                plugin
+                    .objects_of_type::<Header>()
+                    .flat_map(|header| &header.masters)
+                    .map(|(name, _)| name.to_ascii_lowercase()),
+            )
+            .collect();
+
+        // Disable sunlight color for true interiors
+        // Only do this for `classic` mode
+        // Exterior cells are only visited when ambient overrides address them by grid
+        for cell in plugin.objects_of_type_mut::<Cell>().filter(|cell| {
+            cell.atmosphere_data.is_some()
+                && (cell.data.flags.contains(CellFlags::IS_INTERIOR)
+                    || !light_config.exterior_ambients.is_empty())
+        }) {
+            let is_interior = cell.data.flags.contains(CellFlags::IS_INTERIOR);
+            let (grid_x, grid_y) = cell.data.grid;
+            let cell_id = cell_key(cell);
+
+            // The winning cell keeps the same cell from earlier plugins out, whether or not anything about it changes
+            if !used_cell_ids.insert(cell_id.clone()) {
+                continue;
+            };
+
+            // Quasi-exteriors are lit by the sky, so interior lighting changes would do nothing or look wrong
+            if is_interior
+                && cell.data.flags.contains(CellFlags::BEHAVES_LIKE_EXTERIOR)
+                && !light_config.process_quasi_exteriors
+            {
+                if light_config.debug {
+                    print_notification(&format!(
+                        "[ DEBUG ]: Skipping cell {cell_id} from {plugin_name}, as it behaves like an exterior"
+                    ));
+                }
+
+                continue;
+            }
+
+            if light_config.is_excluded_id(&cell_id, &plugin_name) {
+                continue;
+            }
+
+            match cell.atmosphere_data {
+                Some(ref mut atmo) => {
+                    // Otherwise, the water height of the winning cell is carried over as it is
+                    if light_config.clear_water_height {
+                        cell.water_height = None
+                    }
+
+                    let mut replaced = false;
+
+                    if let Some(merged) = merged_atmospheres
+                        .as_ref()
+                        .and_then(|merged_atmospheres| merged_atmospheres.get(&cell_id))
+                    {
+                        replaced |= merge_atmosphere(atmo, merged);
+                    }
+
+                    // Cells are only emitted for their sunlight if scaling actually changed it
+                    let interior_sunlight_mult =
+                        light_config.effective_interior_sunlight_mult(&cell_id);
+
+                    if is_interior && interior_sunlight_mult != 1.0 {
+                        let scaled = LightColorMult {
+                            value: interior_sunlight_mult,
+                            ..Default::default()
+                        }
+                        .apply(
+                            atmo.sunlight_color,
+                            light_config.linear_color,
+                            light_config.hue_overflow,
+                        );
+
+                        replaced |= scaled != atmo.sunlight_color;
+                        atmo.sunlight_color = scaled;
+                    }
+
+                    // Ambient overrides setting a fixed density are applied afterward, and win
+                    if is_interior && light_config.fog_density_mult != 1.0 {
+                        let scaled =
+                            scale_fog_density(atmo.fog_density, light_config.fog_density_mult);
+
+                        replaced |= scaled != atmo.fog_density;
+                        atmo.fog_density = scaled;
+                    }
+
+                    let ambient_overrides: Vec<&CustomCellAmbient> = match is_interior {
+                        true => light_config
+                            .ambient_regexes
+                            .iter()
+                            .filter(|(pattern, _)| pattern.is_match(&cell_id))
+                            .map(|(_, replacement_data)| replacement_data)
+                            .collect(),
+                        false => light_config
+                            .exterior_ambients
+                            .iter()
+                            .filter(|(grid, _)| grid.contains(grid_x, grid_y))
+                            .map(|(_, replacement_data)| replacement_data)
+                            .collect(),
+                    };
+
+                    for replacement_data in ambient_overrides {
+                        if let Some(ambient) = &replacement_data.ambient {
+                            let hsv: Hsv = Hsv::from_components((
+                                palette::RgbHue::from_degrees(ambient.hue as f32),
+                                ambient.saturation,
+                                ambient.value,
+                            ));
+
+                            atmo.ambient_color = hsv_to_color(hsv, light_config.linear_color);
+                            replaced = true;
+                        } else if let Some(ambient_mult) = &replacement_data.ambient_mult {
+                            let scaled = ambient_mult.apply(
+                                atmo.ambient_color,
+                                light_config.linear_color,
+                                light_config.hue_overflow,
+                            );
+
+                            replaced |= scaled != atmo.ambient_color;
+                            atmo.ambient_color = scaled;
+                        }
+
+                        if let Some(fog) = &replacement_data.fog {
+                            let hsv: Hsv = Hsv::from_components((
+                                palette::RgbHue::from_degrees(fog.hue as f32),
+                                fog.saturation,
+                                fog.value,
+                            ));
+
+                            atmo.fog_color = hsv_to_color(hsv, light_config.linear_color);
+                            replaced = true;
+                        } else if let Some(fog_mult) = &replacement_data.fog_mult {
+                            let scaled = fog_mult.apply(
+                                atmo.fog_color,
+                                light_config.linear_color,
+                                light_config.hue_overflow,
+                            );
+
+                            replaced |= scaled != atmo.fog_color;
+                            atmo.fog_color = scaled;
+                        }
+
+                        if let Some(sunlight) = &replacement_data.sunlight {
+                            let hsv: Hsv = Hsv::from_components((
+                                palette::RgbHue::from_degrees(sunlight.hue as f32),
+                                sunlight.saturation,
+                                sunlight.value,
+                            ));
+
+                            atmo.sunlight_color = hsv_to_color(hsv, light_config.linear_color);
+                            replaced = true;
+                        } else if let Some(sunlight_mult) = &replacement_data.sunlight_mult {
+                            let scaled = sunlight_mult.apply(
+                                atmo.sunlight_color,
+                                light_config.linear_color,
+                                light_config.hue_overflow,
+                            );
+
+                            replaced |= scaled != atmo.sunlight_color;
+                            atmo.sunlight_color = scaled;
+                        }
+
+                        if let Some(density) = &replacement_data.fog_density {
+                            atmo.fog_density = density.to_owned();
+                            replaced = true;
+                        } else if let Some(density_mult) = replacement_data.fog_density_mult {
+                            let scaled = scale_fog_density(atmo.fog_density, density_mult);
+
+                            replaced |= scaled != atmo.fog_density;
+                            atmo.fog_density = scaled;
+                        }
+                    }
+
+                    // Each reference is only touched by the first override which deletes or replaces it,
+                    // and the first which moves or scales it, all matched against its original object id
+                    let mut touched_references: HashSet<(u32, u32)> = HashSet::new();
+
+                    let has_instance_edits = ambient_overrides
+                        .iter()
+                        .any(|replacement_data| replacement_data.has_instance_edits());
+
+                    // References are visited by their key, so the log comes out the same between runs
+                    let mut reference_keys: Vec<(u32, u32)> = cell
+                        .references
+                        .keys()
+                        .copied()
+                        .filter(|_| has_instance_edits)
+                        .collect();
+                    reference_keys.sort_unstable();
+
+                    for key in &reference_keys {
+                        let Some(reference) = cell.references.get_mut(key) else {
+                            continue;
+                        };
+
+                        let reference_id = reference.id.to_ascii_lowercase();
+
+                        let edit = ambient_overrides.iter().find_map(|replacement_data| {
+                            replacement_data.instance_edit(&reference_id)
+                        });
+
+                        let instance_override =
+                            ambient_overrides.iter().find_map(|replacement_data| {
+                                replacement_data.instance_override(&reference_id)
+                            });
+
+                        match edit {
+                            // Deleted references aren't worth moving or scaling
+                            Some(InstanceEdit::Delete) => {
+                                reference.deleted = Some(0);
+                                instance_log.push(format!(
+                                    "[ DELETED ]: {reference_id} ({}, {}) in {cell_id}",
+                                    key.0, key.1
+                                ));
+                                touched_references.insert(*key);
+                                continue;
+                            }
+                            Some(InstanceEdit::Replace(new_id)) => {
+                                instance_log.push(format!(
+                                    "[ REPLACED ]: {reference_id} ({}, {}) in {cell_id} with {new_id}",
+                                    key.0, key.1
+                                ));
+                                reference.id = new_id.to_owned();
+                                touched_references.insert(*key);
+                            }
+                            None => {}
+                        }
+
+                        if let Some(instance_override) = instance_override {
+                            instance_override.apply_to(reference);
+                            instance_log.push(format!(
+                                "[ MODIFIED ]: {reference_id} ({}, {}) in {cell_id} with {instance_override}",
+                                key.0, key.1
+                            ));
+                            touched_references.insert(*key);
+                        }
+                    }
+
+                    // The engine merges untouched references from the original plugins
+                    if light_config.clear_references {
+                        cell.references
+                            .retain(|key, _| touched_references.contains(key));
+                    }
+
+                    replaced |= !touched_references.is_empty();
+
+                    if replaced {
+                        let cell = TakeAndSwitch(cell);
+
+                        if cell.references.is_empty() {
+                            generated_plugin.objects.push(cell.into());
+                        } else {
+                            required_masters.extend(
+                                cell.references
+                                    .values()
+                                    .filter_map(|reference| {
+                                        source_names.get(reference.mast_index as usize)
+                                    })
+                                    .cloned(),
+                            );
+
+                            cells_with_references.push((cell, source_names.clone()));
+                        }
+
+                        used_objects += 1;
+                    }
+                }
+                None => {}
+            }
+        }
+
+        // Magic effects are only loaded when asked for, so this finds nothing otherwise
+        for effect in plugin.objects_of_type_mut::<MagicEffect>() {
+            let effect_id = magic_effect_id(effect);
+
+            if !used_effect_ids.insert(effect_id.clone())
+                || light_config.is_excluded_id(&effect_id, &plugin_name)
+            {
+                continue;
+            }
+
+            if process_magic_effect(light_config, effect) {
+                generated_plugin.objects.push(TakeAndSwitch(effect).into());
+                used_objects += 1;
+            }
+        }
+
+        plugin
+            .into_objects_of_type::<Light>()
+            .filter_map(|light| {
+                let light_id = light.editor_id_ascii_lowercase().into_owned();
+
+                if used_light_ids.contains(&light_id) {
+                    return None;
+                }
+
+                if light_config.only_referenced
+                    && let Some(light_placements) = &light_placements
+                    && !light_placements.contains_key(&light_id)
+                    && !copy_source_ids.contains(&light_id)
+                {
+                    if light_config.debug {
+                        print_notification(&format!(
+                            "[ DEBUG ]: Skipping light {light_id} from {plugin_name}, as no cell places it"
+                        ));
+                    }
+
+                    return None;
+                }
+
+                // Excluded records still win over the same record from earlier plugins
+                let is_excluded = light_config.is_excluded_id(&light_id, &plugin_name);
+                used_light_ids.insert(light_id);
+
+                (!is_excluded).then_some(light)
+            })
+            .for_each(|mut light| {
+                let light_id = light.editor_id_ascii_lowercase().into_owned();
+
+                let placement = light_placements
+                    .as_ref()
+                    .and_then(|light_placements| light_placements.get(&light_id).copied());
+
+                // Overrides are matched against the light as it was before processing
+                let replacement_light_data =
+                    light_config.light_override(&light, &plugin_name, placement);
+
+                // Black, zero-radius lights cast no light, so they're only worth keeping when asked for by id
+                if light_config.skip_zero_radius
+                    && light.data.radius == 0
+                    && light.data.color[..3] == [0, 0, 0]
+                    && replacement_light_data.is_none()
+                    && !copy_source_ids.contains(&light_id)
+                    && !light_config.is_sound_stripped(&light_id)
+                {
+                    if light_config.debug {
+                        print_notification(&format!("[ DEBUG ]: Skipping light {light_id} from {plugin_name}, as its radius is zero, its color is black, and no light override matches it"));
+                    }
+
+                    return;
+                }
+
+                let original_light = light_config.skip_unchanged.then(|| light.clone());
+                let should_emit =
+                    process_light(light_config, &mut light, replacement_light_data.as_deref());
+
+                if replacement_light_data
+                    .as_deref()
+                    .is_some_and(CustomLightData::is_disabled)
+                {
+                    disabled_ids.push(light_id.clone());
+                }
+
+                if copy_source_ids.contains(&light_id) {
+                    copy_sources.insert(
+                        light_id.clone(),
+                        CopiedLightData {
+                            color: light.data.color,
+                            radius: light.data.radius,
+                            time: light.data.time,
+                            flags: light.data.flags,
+                        },
+                    );
+                }
+
+                // Copies are always emitted, so their plugin is counted as a master right away
+                if let Some(light_data) = replacement_light_data
+                    && light_data.copy_from.is_some()
+                {
+                    pending_copies.push((light, light_data, plugin_name.clone()));
+                    used_objects += 1;
+                    return;
+                }
+
+                if !should_emit {
+                    return;
+                }
+
+                if let Some(original_light) = original_light
+                    && !light_changed(&original_light, &light)
+                {
+                    return;
+                }
+
+                generated_plugin.objects.push(light.into());
+                used_objects += 1;
+            });
+
+        // Plugins whose references are carried over are masters, even if none of their records are
+        if used_objects > 0 || required_masters.contains(&plugin_name) {
+            header
+                .masters
+                .push((plugin_meta.file_name.clone(), plugin_meta.size));
+
+            header.num_objects += TakeAndSwitch(&mut used_objects);
+        }
+
+        record_owners.resize(generated_plugin.objects.len(), plugin_name);
+    }
+
+    // Plugins are visited from the end of the load order, so masters are put back into load order
+    header.masters.sort_by_key(|(name, _)| {
+        load_order
+            .iter()
+            .position(|file_name| file_name.eq_ignore_ascii_case(name))
+            .unwrap_or(usize::MAX)
+    });
+
+    let master_names: Vec<String> = header
+        .masters
+        .iter()
+        .map(|(name, _)| name.to_ascii_lowercase())
+        .collect();
+
+    for (mut cell, source_names) in cells_with_references {
+        remap_references(&mut cell, &source_names, &master_names);
+        generated_plugin.objects.push(cell.into());
+        record_owners.push(source_names[0].clone());
+    }
+
+    // A light may copy from one which copies from another in turn, so copies are made once their source has been.
+    // Whatever is still waiting after a pass where nothing could be copied is waiting on itself
+    let mut uncopied_ids: HashSet<String> = pending_copies
+        .iter()
+        .map(|(light, _, _)| light.editor_id_ascii_lowercase().into_owned())
+        .collect();
+
+    while !pending_copies.is_empty() {
+        let pending_count = pending_copies.len();
+        let mut waiting_copies = Vec::new();
+
+        for (mut light, light_data, owner) in TakeAndSwitch(&mut pending_copies) {
+            let light_id = light.editor_id_ascii_lowercase().into_owned();
+
+            let Some(source_id) = &light_data.copy_from else {
+                continue;
+            };
+
+            let source_key = source_id.to_ascii_lowercase();
+            if source_key != light_id && uncopied_ids.contains(&source_key) {
+                waiting_copies.push((light, light_data, owner));
+                continue;
+            }
+
+            let Some(source) = copy_sources.get(&source_key) else {
+                return Err(LightFixesError::MissingCopySource {
+                    light_id,
+                    source_id: source_id.clone(),
+                });
+            };
+
+            light.data.color = source.color;
+            light.data.radius = source.radius;
+            light.data.time = source.time;
+            light.data.flags = source.flags;
+
+            // Flags set explicitly in the same override still win
+            if let Some(flag) = &light_data.flag {
+                flag.apply_to(
+                    &mut light.data.flags,
+                    light_data.replace_flags.unwrap_or(false),
+                );
+            }
+
+            // Lights copying from this one get the copy, rather than what this one was before it
+            if copy_source_ids.contains(&light_id) {
+                copy_sources.insert(
+                    light_id.clone(),
+                    CopiedLightData {
+                        color: light.data.color,
+                        radius: light.data.radius,
+                        time: light.data.time,
+                        flags: light.data.flags,
+                    },
+                );
+            }
+
+            uncopied_ids.remove(&light_id);
+            generated_plugin.objects.push(light.into());
+            record_owners.push(owner);
+        }
+
+        if waiting_copies.len() == pending_count {
+            let mut light_ids: Vec<String> = uncopied_ids.into_iter().collect();
+            light_ids.sort();
+            return Err(LightFixesError::CopyCycle(light_ids));
+        }
+
+        pending_copies = waiting_copies;
+    }
+
+    if light_config.debug {
+        dbg!(&header);
+    }
+
+    if header.masters.is_empty() {
+        return Err(LightFixesError::NoMasters);
+    }
+
+    Ok(Generation {
+        plugin: generated_plugin,
+        header,
+        record_owners,
+        master_names,
+        disabled_ids,
+        instance_log,
+    })
+}
+
+/// Generates the lightfixes plugin for `plugins`, given in load order, as a single plugin with its header
+pub fn generate_lightfixes(
+    plugins: Vec<(Plugin, PluginMeta)>,
+    light_config: &LightConfig,
+) -> Result<Plugin, LightFixesError> {
+    let Generation {
+        plugin: mut generated_plugin,
+        header,
+        ..
+    } = generate(plugins, light_config)?;
+
+    generated_plugin.objects.push(TES3Object::Header(header));
+    generated_plugin.sort_objects();
+    finalize_header(&mut generated_plugin);
+
+    Ok(generated_plugin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{interior_cell, light, light_override, neutral_config, plugin};
+
+    fn generated_light<'a>(generation: &'a Generation, light_id: &str) -> &'a Light {
+        generation
+            .plugin
+            .objects_of_type::<Light>()
+            .find(|light| light.id == light_id)
+            .unwrap()
+    }
+
+    #[test]
+    fn chained_copies_resolve_in_order() {
+        let light_config = LightConfig {
+            standard_radius: 2.0,
+            light_regexes: vec![
+                light_override("^fire_b$", "copy_from=fire_a"),
+                light_override("^fire_c$", "copy_from=fire_b"),
+            ],
+            ..neutral_config()
+        };
+
+        let lights = vec![
+            light("fire_c", [255, 255, 255], 32),
+            light("fire_b", [0, 0, 255], 64),
+            light("fire_a", [255, 160, 64], 128),
+        ];
+        let generation = generate(vec![plugin("Fires.esp", lights)], &light_config).unwrap();
+
+        let source = generated_light(&generation, "fire_a");
+        for light_id in ["fire_b", "fire_c"] {
+            let copy = generated_light(&generation, light_id);
+
+            assert_eq!(copy.data.color, source.data.color, "{light_id}");
+            assert_eq!(copy.data.radius, 256, "{light_id}");
+        }
+    }
+
+    #[test]
+    fn copy_loops_are_rejected() {
+        let light_config = LightConfig {
+            light_regexes: vec![
+                light_override("^fire_a$", "copy_from=fire_b"),
+                light_override("^fire_b$", "copy_from=fire_a"),
+            ],
+            ..neutral_config()
+        };
+
+        let lights = vec![
+            light("fire_a", [255, 160, 64], 128),
+            light("fire_b", [255, 160, 64], 128),
+        ];
+
+        match generate(vec![plugin("Fires.esp", lights)], &light_config) {
+            Err(LightFixesError::CopyCycle(light_ids)) => {
+                assert_eq!(light_ids, ["fire_a", "fire_b"]);
+            }
+            other => panic!("expected a copy_from loop, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn excluded_copy_sources_are_missing() {
+        let light_config = LightConfig {
+            light_regexes: vec![light_override("^fire_b$", "copy_from=fire_a")],
+            excluded_id_regexes: vec![(None, regex::Regex::new("^fire_a$").unwrap())],
+            ..neutral_config()
+        };
+
+        let lights = vec![
+            light("fire_a", [255, 160, 64], 128),
+            light("fire_b", [255, 160, 64], 128),
+        ];
+
+        assert!(matches!(
+            generate(vec![plugin("Fires.esp", lights)], &light_config),
+            Err(LightFixesError::MissingCopySource { .. })
+        ));
+    }
+
+    #[test]
+    fn jittered_plugins_are_identical_between_runs() {
+        let light_config = LightConfig {
+            hue_jitter: Some(20.0),
+            value_jitter: Some(0.1),
+            radius_jitter: Some(32.0),
+            ..neutral_config()
+        };
+
+        let plugin_bytes = || {
+            let lights = (0..50)
+                .map(|index| light(&format!("torch_{index}"), [255, 160, 64], 128))
+                .collect();
+
+            let mut generated_plugin =
+                generate_lightfixes(vec![plugin("Torches.esp", lights)], &light_config).unwrap();
+
+            let mut bytes = Vec::new();
+            crate::save_plugin_to_writer(&mut bytes, &mut generated_plugin).unwrap();
+            bytes
+        };
+
+        assert_eq!(plugin_bytes(), plugin_bytes());
+    }
+
+    fn generated_cell<'a>(generation: &'a Generation, cell_name: &str) -> &'a Cell {
+        generation
+            .plugin
+            .objects_of_type::<Cell>()
+            .find(|cell| cell.name == cell_name)
+            .unwrap()
+    }
+
+    #[test]
+    fn ambient_multipliers_scale_existing_colors() {
+        let light_config = LightConfig {
+            ambient_regexes: vec![(
+                regex::Regex::new("^vivec, arena pit$").unwrap(),
+                "ambient_mult=value=0.5;fog_mult=saturation=0"
+                    .parse()
+                    .unwrap(),
+            )],
+            ..neutral_config()
+        };
+
+        let cells = vec![interior_cell("Vivec, Arena Pit", [200, 100, 50], 1.0)];
+        let generation = generate(vec![plugin("Vivec.esp", cells)], &light_config).unwrap();
+
+        let atmo = generated_cell(&generation, "Vivec, Arena Pit")
+            .atmosphere_data
+            .as_ref()
+            .unwrap();
+
+        assert_eq!(atmo.ambient_color, [100, 50, 25, 0]);
+        assert_eq!(atmo.fog_color, [200, 200, 200, 0]);
+        assert_eq!(atmo.sunlight_color, [200, 100, 50, 0]);
+    }
+
+    fn generated_sunlight(light_config: &LightConfig) -> Option<[u8; 4]> {
+        let records: Vec<TES3Object> = vec![
+            interior_cell("Sadrith Mora, Tel Naga", [200, 100, 50], 0.5).into(),
+            light("torch", [255, 160, 64], 128).into(),
+        ];
+        let generation = generate(vec![plugin("Telvanni.esp", records)], light_config).unwrap();
+
+        generation
+            .plugin
+            .objects_of_type::<Cell>()
+            .next()
+            .map(|cell| cell.atmosphere_data.as_ref().unwrap().sunlight_color)
+    }
+
+    #[test]
+    fn interior_sunlight_is_scaled_only_when_it_changes() {
+        assert_eq!(generated_sunlight(&neutral_config()), None);
+
+        let light_config = LightConfig {
+            interior_sunlight_mult: 0.3,
+            ..neutral_config()
+        };
+        assert_eq!(generated_sunlight(&light_config), Some([60, 30, 15, 0]));
+    }
+
+    #[test]
+    fn disable_interior_sun_is_a_zero_multiplier() {
+        let disabled = LightConfig {
+            disable_interior_sun: true,
+            ..neutral_config()
+        };
+        let zeroed = LightConfig {
+            interior_sunlight_mult: 0.0,
+            ..neutral_config()
+        };
+
+        assert_eq!(generated_sunlight(&disabled), Some([0, 0, 0, 0]));
+        assert_eq!(generated_sunlight(&disabled), generated_sunlight(&zeroed));
+    }
+
+    #[test]
+    fn fog_density_mult_scales_cells_proportionally() {
+        let light_config = LightConfig {
+            ambient_regexes: vec![(
+                regex::Regex::new("^ald-ruhn").unwrap(),
+                "fog_density_mult=1.5".parse().unwrap(),
+            )],
+            ..neutral_config()
+        };
+
+        let cells = vec![
+            interior_cell("Ald-ruhn, Guild of Mages", [60, 60, 60], 0.2),
+            interior_cell("Ald-ruhn, Temple", [60, 60, 60], 0.4),
+        ];
+        let generation = generate(vec![plugin("Ald-ruhn.esp", cells)], &light_config).unwrap();
+
+        let density = |cell_name| {
+            generated_cell(&generation, cell_name)
+                .atmosphere_data
+                .as_ref()
+                .unwrap()
+                .fog_density
+        };
+
+        assert!((density("Ald-ruhn, Guild of Mages") - 0.3).abs() < 1e-6);
+        assert!((density("Ald-ruhn, Temple") - 0.6).abs() < 1e-6);
+    }
+
+    /// Ambient color of the generated cell, when a plugin earlier in the load order holds another copy of the winner
+    fn generated_over_earlier_copy(light_config: &LightConfig, winner: Cell) -> Option<[u8; 4]> {
+        let earlier_copy = interior_cell(&winner.name, [120, 120, 120], 0.5);
+        let records: Vec<TES3Object> =
+            vec![winner.into(), light("torch", [255, 160, 64], 128).into()];
+        let plugins = vec![
+            plugin("Old.esp", [earlier_copy]),
+            plugin("New.esp", records),
+        ];
+
+        generate(plugins, light_config)
+            .unwrap()
+            .plugin
+            .objects_of_type::<Cell>()
+            .next()
+            .map(|cell| cell.atmosphere_data.as_ref().unwrap().ambient_color)
+    }
+
+    #[test]
+    fn winning_cells_scaling_to_themselves_keep_earlier_copies_out() {
+        let light_config = LightConfig {
+            ambient_regexes: vec![(
+                regex::Regex::new("^balmora").unwrap(),
+                "ambient_mult=value=0.5;sunlight_mult=value=0.5;fog_mult=value=0.5"
+                    .parse()
+                    .unwrap(),
+            )],
+            ..neutral_config()
+        };
+
+        // Black scales to black, so neither copy is generated
+        let winner = interior_cell("Balmora, Temple", [0, 0, 0], 0.5);
+        assert_eq!(generated_over_earlier_copy(&light_config, winner), None);
+
+        let winner = interior_cell("Balmora, Temple", [80, 80, 80], 0.5);
+        assert_eq!(
+            generated_over_earlier_copy(&light_config, winner),
+            Some([40, 40, 40, 0])
+        );
+    }
+
+    #[test]
+    fn black_sunlight_keeps_earlier_copies_out() {
+        let light_config = LightConfig {
+            interior_sunlight_mult: 0.5,
+            ..neutral_config()
+        };
+
+        let winner = interior_cell("Balmora, Temple", [0, 0, 0], 0.5);
+        assert_eq!(generated_over_earlier_copy(&light_config, winner), None);
+    }
+
+    #[test]
+    fn fogless_winning_cells_keep_earlier_copies_out() {
+        let light_config = LightConfig {
+            fog_density_mult: 2.0,
+            ..neutral_config()
+        };
+
+        let winner = interior_cell("Balmora, Temple", [120, 120, 120], 0.0);
+        assert_eq!(generated_over_earlier_copy(&light_config, winner), None);
+    }
+
+    #[test]
+    fn water_height_is_kept_unless_cleared() {
+        let water_height = |light_config: LightConfig| {
+            let light_config = LightConfig {
+                ambient_regexes: vec![(
+                    regex::Regex::new("^vivec, canal$").unwrap(),
+                    "ambient_mult=value=0.5".parse().unwrap(),
+                )],
+                ..light_config
+            };
+
+            let mut canal = interior_cell("Vivec, Canal", [120, 120, 120], 0.5);
+            canal.water_height = Some(-128.0);
+
+            let generation = generate(vec![plugin("Vivec.esp", [canal])], &light_config).unwrap();
+            generated_cell(&generation, "Vivec, Canal").water_height
+        };
+
+        assert_eq!(water_height(neutral_config()), Some(-128.0));
+
+        let light_config = LightConfig {
+            clear_water_height: true,
+            ..neutral_config()
+        };
+        assert_eq!(water_height(light_config), None);
+    }
+
+    /// A cell placing the given objects, each as its own reference from the plugin defining the cell
+    fn cell_with_references(name: &str, object_ids: &[&str]) -> Cell {
+        let mut cell = interior_cell(name, [120, 120, 120], 0.5);
+
+        for (refr_index, object_id) in (1..).zip(object_ids) {
+            cell.references.insert(
+                (0, refr_index),
+                tes3::esp::Reference {
+                    mast_index: 0,
+                    refr_index,
+                    id: object_id.to_string(),
+                    ..Default::default()
+                },
+            );
+        }
+
+        cell
+    }
+
+    #[test]
+    fn references_are_cleared_unless_asked_to_keep_them() {
+        let references = |clear_references| {
+            let light_config = LightConfig {
+                clear_references,
+                ambient_regexes: vec![(
+                    regex::Regex::new("^balmora, council club$").unwrap(),
+                    "ambient_mult=value=0.5".parse().unwrap(),
+                )],
+                ..neutral_config()
+            };
+
+            let club = cell_with_references(
+                "Balmora, Council Club",
+                &[
+                    "light_com_candle_06",
+                    "furn_de_table_01",
+                    "misc_com_bottle_01",
+                ],
+            );
+            let generation = generate(vec![plugin("Balmora.esp", [club])], &light_config).unwrap();
+
+            let mut object_ids: Vec<String> = generated_cell(&generation, "Balmora, Council Club")
+                .references
+                .values()
+                .map(|reference| reference.id.clone())
+                .collect();
+            object_ids.sort();
+            object_ids
+        };
+
+        assert!(references(true).is_empty());
+        assert_eq!(
+            references(false),
+            [
+                "furn_de_table_01",
+                "light_com_candle_06",
+                "misc_com_bottle_01"
+            ]
+        );
+    }
+
+    #[test]
+    fn quasi_exteriors_are_untouched_by_default() {
+        let quasi_exterior_cells = |process_quasi_exteriors| {
+            let light_config = LightConfig {
+                process_quasi_exteriors,
+                interior_sunlight_mult: 0.5,
+                ambient_regexes: vec![(
+                    regex::Regex::new("^mournhold, plaza brindisi dorom$").unwrap(),
+                    "ambient_mult=value=0.5".parse().unwrap(),
+                )],
+                ..neutral_config()
+            };
+
+            let mut plaza = interior_cell("Mournhold, Plaza Brindisi Dorom", [120, 120, 120], 0.5);
+            plaza.data.flags |= CellFlags::BEHAVES_LIKE_EXTERIOR;
+
+            let records: Vec<TES3Object> =
+                vec![plaza.into(), light("torch", [255, 160, 64], 128).into()];
+            let generation =
+                generate(vec![plugin("Mournhold.esp", records)], &light_config).unwrap();
+
+            generation.plugin.objects_of_type::<Cell>().count()
+        };
+
+        assert_eq!(quasi_exterior_cells(false), 0);
+        assert_eq!(quasi_exterior_cells(true), 1);
+    }
+
+    #[test]
+    fn cells_and_lights_sharing_an_id_are_both_processed() {
+        let light_config = LightConfig {
+            ambient_regexes: vec![(
+                regex::Regex::new("^dupe_test$").unwrap(),
+                "ambient_mult=value=0.5".parse().unwrap(),
+            )],
+            ..neutral_config()
+        };
+
+        let records: Vec<TES3Object> = vec![
+            interior_cell("dupe_test", [120, 120, 120], 0.5).into(),
+            light("dupe_test", [255, 160, 64], 128).into(),
+        ];
+        let generation = generate(vec![plugin("Dupes.esp", records)], &light_config).unwrap();
+
+        assert_eq!(generation.plugin.objects_of_type::<Cell>().count(), 1);
+        assert_eq!(generated_light(&generation, "dupe_test").data.radius, 128);
+    }
+
+    #[test]
+    fn atmospheres_are_merged_field_by_field() {
+        let light_config = LightConfig {
+            merge_cell_atmosphere: true,
+            ..neutral_config()
+        };
+
+        let base = interior_cell("Caldera, Mages Guild", [100, 100, 100], 0.5);
+
+        let mut foggier = base.clone();
+        foggier.atmosphere_data.as_mut().unwrap().fog_color = [20, 40, 60, 0];
+        foggier.atmosphere_data.as_mut().unwrap().fog_density = 0.9;
+
+        let mut brighter = base.clone();
+        brighter.atmosphere_data.as_mut().unwrap().ambient_color = [180, 160, 140, 0];
+
+        let plugins = vec![
+            plugin("Morrowind.esm", [base]),
+            plugin("Fog.esp", [foggier]),
+            plugin("Ambient.esp", [brighter]),
+        ];
+        let generation = generate(plugins, &light_config).unwrap();
+
+        let atmo = generated_cell(&generation, "Caldera, Mages Guild")
+            .atmosphere_data
+            .as_ref()
+            .unwrap();
+
+        assert_eq!(atmo.ambient_color, [180, 160, 140, 0]);
+        assert_eq!(atmo.fog_color, [20, 40, 60, 0]);
+        assert_eq!(atmo.fog_density, 0.9);
+        assert_eq!(atmo.sunlight_color, [100, 100, 100, 0]);
+    }
+
+    /// A load order where several plugins edit the same lights and cells
+    fn overlapping_load_order() -> Vec<(Plugin, PluginMeta)> {
+        let base: Vec<TES3Object> = vec![
+            light("torch", [255, 160, 64], 128).into(),
+            light("candle", [255, 200, 120], 64).into(),
+            interior_cell("Balmora, Guild of Mages", [80, 80, 120], 0.4).into(),
+            interior_cell("Vivec, Arena Pit", [120, 80, 40], 0.6).into(),
+        ];
+        let addon: Vec<TES3Object> = vec![
+            light("torch", [255, 120, 32], 192).into(),
+            light("lantern", [200, 220, 255], 256).into(),
+            interior_cell("Balmora, Guild of Mages", [60, 60, 160], 0.5).into(),
+        ];
+        let patch: Vec<TES3Object> = vec![
+            light("candle", [240, 180, 100], 96).into(),
+            interior_cell("Ald-ruhn, Temple", [90, 90, 90], 0.3).into(),
+        ];
+
+        vec![
+            plugin("Base.esm", base),
+            plugin("Addon.esp", addon),
+            plugin("Patch.esp", patch),
+        ]
+    }
+
+    #[test]
+    fn repeated_runs_write_identical_bytes() {
+        let light_config = LightConfig {
+            hue_jitter: Some(20.0),
+            radius_jitter: Some(32.0),
+            ..neutral_config()
+        };
+
+        let saved_bytes = || {
+            let mut generated_plugin =
+                generate_lightfixes(overlapping_load_order(), &light_config).unwrap();
+
+            let mut bytes = Vec::new();
+            crate::save_plugin_to_writer(&mut bytes, &mut generated_plugin).unwrap();
+            bytes
+        };
+
+        let first_run = saved_bytes();
+        assert!(!first_run.is_empty());
+
+        for _ in 0..4 {
+            assert_eq!(saved_bytes(), first_run);
+        }
+    }
+
+    #[test]
+    fn masters_follow_load_order() {
+        let load_order: Vec<(Plugin, PluginMeta)> = [
+            ("Morrowind.esm", "torch", 1_000),
+            ("Tribunal.esm", "candle", 200),
+            ("Lanterns.esp", "lantern", 30),
+        ]
+        .into_iter()
+        .map(|(file_name, light_id, size)| {
+            let (plugin, plugin_meta) = plugin(file_name, [light(light_id, [255, 160, 64], 128)]);
+            (
+                plugin,
+                PluginMeta {
+                    size,
+                    ..plugin_meta
+                },
+            )
+        })
+        .collect();
+
+        let generation = generate(load_order, &neutral_config()).unwrap();
+
+        assert_eq!(
+            generation.header.masters,
+            [
+                ("Morrowind.esm".to_string(), 1_000),
+                ("Tribunal.esm".to_string(), 200),
+                ("Lanterns.esp".to_string(), 30),
+            ]
+        );
+        assert_eq!(
+            generation.master_names,
+            ["morrowind.esm", "tribunal.esm", "lanterns.esp"]
+        );
+    }
+
+    #[test]
+    fn masters_skip_plugins_without_records() {
+        let load_order = vec![
+            plugin("Morrowind.esm", [light("torch", [255, 160, 64], 128)]),
+            plugin("Empty.esp", Vec::<TES3Object>::new()),
+            plugin("Lanterns.esp", [light("lantern", [200, 220, 255], 256)]),
+        ];
+
+        let generation = generate(load_order, &neutral_config()).unwrap();
+
+        assert_eq!(generation.master_names, ["morrowind.esm", "lanterns.esp"]);
+    }
+
+    #[test]
+    fn generated_plugin_starts_with_its_header() {
+        let generated_plugin =
+            generate_lightfixes(overlapping_load_order(), &neutral_config()).unwrap();
+
+        let TES3Object::Header(header) = &generated_plugin.objects[0] else {
+            panic!("The generated plugin doesn't start with its header");
+        };
+
+        assert_eq!(
+            header.num_objects as usize,
+            generated_plugin.objects.len() - 1
+        );
+        assert!(
+            header
+                .description
+                .0
+                .contains(&format!("[cfg:{}]", config_fingerprint(&neutral_config())))
+        );
+        assert_eq!(
+            generated_plugin.objects_of_type::<Header>().count(),
+            1,
+            "The generated plugin has more than one header"
+        );
+    }
+
+    #[test]
+    fn generated_plugin_holds_the_winning_records() {
+        let light_config = LightConfig {
+            standard_radius: 2.0,
+            colored_radius: 2.0,
+            ..neutral_config()
+        };
+        let generated_plugin =
+            generate_lightfixes(overlapping_load_order(), &light_config).unwrap();
+
+        let radius = |light_id: &str| {
+            let mut matching = generated_plugin
+                .objects_of_type::<Light>()
+                .filter(|light| light.id == light_id);
+            let radius = matching.next().unwrap().data.radius;
+            assert!(matching.next().is_none(), "{light_id} was generated twice");
+            radius
+        };
+
+        // Later plugins win, as they do in the engine
+        assert_eq!(radius("torch"), 384);
+        assert_eq!(radius("candle"), 192);
+        assert_eq!(radius("lantern"), 512);
+
+        let cell_names: Vec<&str> = generated_plugin
+            .objects_of_type::<Cell>()
+            .map(|cell| cell.name.as_str())
+            .collect();
+        let unique_names: HashSet<&str> = cell_names.iter().copied().collect();
+        assert_eq!(cell_names.len(), unique_names.len());
+    }
+
+    #[test]
+    fn plugins_without_lights_or_cells_generate_nothing() {
+        let plugins = vec![plugin("Empty.esp", Vec::<TES3Object>::new())];
+
+        assert!(matches!(
+            generate_lightfixes(plugins, &neutral_config()),
+            Err(LightFixesError::NoMasters)
+        ));
+    }
+
+    #[test]
+    fn last_duplicate_within_a_plugin_wins() {
+        let light_config = LightConfig {
+            fog_density_mult: 2.0,
+            ..neutral_config()
+        };
+
+        let records: Vec<TES3Object> = vec![
+            light("torch", [255, 160, 64], 64).into(),
+            interior_cell("Vivec, Arena Pit", [120, 80, 40], 0.1).into(),
+            light("Torch", [255, 160, 64], 256).into(),
+            interior_cell("vivec, arena pit", [120, 80, 40], 0.3).into(),
+        ];
+        let generation = generate(vec![plugin("Broken.esp", records)], &light_config).unwrap();
+
+        let torches: Vec<&Light> = generation
+            .plugin
+            .objects_of_type::<Light>()
+            .filter(|light| light.id.eq_ignore_ascii_case("torch"))
+            .collect();
+        assert_eq!(torches.len(), 1);
+        assert_eq!(torches[0].data.radius, 256);
+
+        let pits: Vec<&Cell> = generation
+            .plugin
+            .objects_of_type::<Cell>()
+            .filter(|cell| cell.name.eq_ignore_ascii_case("vivec, arena pit"))
+            .collect();
+        assert_eq!(pits.len(), 1);
+        assert!((pits[0].atmosphere_data.as_ref().unwrap().fog_density - 0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn duplicates_are_dropped_before_their_last_definition() {
+        let (mut broken, _) = plugin(
+            "Broken.esp",
+            [
+                light("torch", [255, 0, 0], 64),
+                light("candle", [255, 200, 120], 64),
+                light("torch", [0, 255, 0], 128),
+                light("torch", [0, 0, 255], 256),
+            ],
+        );
+
+        drop_duplicate_records(&mut broken, "Broken.esp");
+
+        let kept: Vec<(&str, u32)> = broken
+            .objects_of_type::<Light>()
+            .map(|light| (light.id.as_str(), light.data.radius))
+            .collect();
+        assert_eq!(kept, [("candle", 64), ("torch", 256)]);
+    }
+
+    #[test]
+    fn disabled_lights_are_recorded() {
+        let light_config = LightConfig {
+            light_regexes: vec![light_override("^deco_", "disabled=true")],
+            ..neutral_config()
+        };
+
+        let lights = vec![
+            light("deco_candle", [255, 200, 120], 64),
+            light("torch", [255, 160, 64], 128),
+        ];
+        let generation = generate(vec![plugin("Decor.esp", lights)], &light_config).unwrap();
+
+        assert_eq!(generation.disabled_ids, ["deco_candle"]);
+        assert_eq!(generated_light(&generation, "deco_candle").data.radius, 0);
+        assert_eq!(generated_light(&generation, "torch").data.radius, 128);
+    }
+
+    #[test]
+    fn skip_unchanged_leaves_out_untouched_lights() {
+        let light_config = LightConfig {
+            skip_unchanged: true,
+            light_regexes: vec![light_override("^torch$", "radius=256")],
+            ..neutral_config()
+        };
+
+        let lights = vec![
+            light("torch", [255, 255, 255], 128),
+            light("candle", [255, 255, 255], 64),
+        ];
+        let generation = generate(vec![plugin("Fires.esp", lights)], &light_config).unwrap();
+
+        let light_ids: Vec<&str> = generation
+            .plugin
+            .objects_of_type::<Light>()
+            .map(|light| light.id.as_str())
+            .collect();
+        assert_eq!(light_ids, ["torch"]);
+        assert_eq!(generation.header.num_objects, 1);
+    }
+
+    #[test]
+    fn skip_unchanged_drops_plugins_left_without_records() {
+        let light_config = LightConfig {
+            skip_unchanged: true,
+            ..neutral_config()
+        };
+
+        let plugins = vec![
+            plugin("Fires.esp", [light("torch", [255, 255, 255], 128)]),
+            plugin("Lanterns.esp", [light("lantern", [255, 255, 255], 256)]),
+        ];
+
+        assert!(matches!(
+            generate(plugins, &light_config),
+            Err(LightFixesError::NoMasters)
+        ));
+    }
+
+    /// Ids of the lights generated from `lights` with `light_config`
+    fn generated_light_ids(light_config: &LightConfig, mut lights: Vec<Light>) -> Vec<String> {
+        // Keeps a master around when every other light is skipped
+        lights.push(light("torch", [255, 160, 64], 128));
+
+        generate(vec![plugin("Lights.esp", lights)], light_config)
+            .unwrap()
+            .plugin
+            .objects_of_type::<Light>()
+            .map(|light| light.id.clone())
+            .filter(|light_id| light_id != "torch")
+            .collect()
+    }
+
+    #[test]
+    fn only_black_zero_radius_lights_are_skipped() {
+        let light_config = LightConfig {
+            skip_zero_radius: true,
+            ..neutral_config()
+        };
+
+        let lights = vec![
+            light("black_zero", [0, 0, 0], 0),
+            light("orange_zero", [255, 160, 64], 0),
+            light("black_lit", [0, 0, 0], 64),
+        ];
+
+        assert_eq!(
+            generated_light_ids(&light_config, lights),
+            ["orange_zero", "black_lit"]
+        );
+    }
+
+    #[test]
+    fn black_zero_radius_lights_are_kept_when_asked_for() {
+        let skipping = LightConfig {
+            skip_zero_radius: true,
+            ..neutral_config()
+        };
+        let overridden = LightConfig {
+            skip_zero_radius: true,
+            light_regexes: vec![light_override("^black_zero$", "radius=64")],
+            ..neutral_config()
+        };
+        let keeping = LightConfig {
+            skip_zero_radius: false,
+            ..neutral_config()
+        };
+
+        let lights = || vec![light("black_zero", [0, 0, 0], 0)];
+
+        assert!(generated_light_ids(&skipping, lights()).is_empty());
+        assert_eq!(generated_light_ids(&overridden, lights()), ["black_zero"]);
+        assert_eq!(generated_light_ids(&keeping, lights()), ["black_zero"]);
+    }
+
+    #[test]
+    fn only_referenced_skips_lights_no_cell_places() {
+        let light_config = LightConfig {
+            only_referenced: true,
+            ..neutral_config()
+        };
+
+        let records: Vec<TES3Object> = vec![
+            light("torch", [255, 160, 64], 128).into(),
+            light("candle", [255, 200, 120], 64).into(),
+            cell_with_references("Balmora, Guild of Mages", &["Torch", "misc_com_bucket_01"])
+                .into(),
+        ];
+        let generation = generate(vec![plugin("Balmora.esp", records)], &light_config).unwrap();
+
+        let light_ids: Vec<&str> = generation
+            .plugin
+            .objects_of_type::<Light>()
+            .map(|light| light.id.as_str())
+            .collect();
+        assert_eq!(light_ids, ["torch"]);
+    }
+
+    #[test]
+    fn only_referenced_sees_references_from_other_plugins() {
+        let light_config = LightConfig {
+            only_referenced: true,
+            ..neutral_config()
+        };
+
+        let plugins = vec![
+            plugin("Lights.esp", [light("lantern", [200, 220, 255], 256)]),
+            plugin(
+                "Placements.esp",
+                [cell_with_references("Vivec, Arena Pit", &["lantern"])],
+            ),
+        ];
+        let generation = generate(plugins, &light_config).unwrap();
+
+        assert!(
+            generation
+                .plugin
+                .objects_of_type::<Light>()
+                .any(|light| light.id == "lantern")
+        );
+    }
+
+    /// An exterior cell at a grid position, placing each of `object_ids` once
+    fn exterior_with_references(grid: (i32, i32), object_ids: &[&str]) -> Cell {
+        let mut cell = cell_with_references("", object_ids);
+        cell.data.flags.remove(CellFlags::IS_INTERIOR);
+        cell.data.grid = grid;
+        cell.atmosphere_data = None;
+        cell
+    }
+
+    #[test]
+    fn placement_scoped_overrides_follow_where_lights_are_placed() {
+        let generated_radii = |light_data: &str| {
+            let light_config = LightConfig {
+                light_regexes: vec![light_override(".*", light_data)],
+                ..neutral_config()
+            };
+
+            let records: Vec<TES3Object> = vec![
+                light("lantern", [255, 160, 64], 128).into(),
+                light("torch", [255, 160, 64], 128).into(),
+                light("candle", [255, 160, 64], 128).into(),
+                light("unplaced", [255, 160, 64], 128).into(),
+                cell_with_references("Balmora, Guild of Mages", &["lantern", "candle"]).into(),
+                exterior_with_references((-3, -2), &["torch", "candle"]).into(),
+            ];
+            let generation = generate(vec![plugin("Balmora.esp", records)], &light_config).unwrap();
+
+            ["lantern", "torch", "candle", "unplaced"]
+                .map(|light_id| generated_light(&generation, light_id).data.radius)
+        };
+
+        // Lights placed in both kinds of cells, or in none, match neither
+        assert_eq!(
+            generated_radii("interior_only=true,radius=512"),
+            [512, 128, 128, 128]
+        );
+        assert_eq!(
+            generated_radii("exterior_only=true,radius=512"),
+            [128, 512, 128, 128]
+        );
+    }
+
+    #[test]
+    fn placement_scopes_are_exclusive() {
+        assert!(
+            "interior_only=true,exterior_only=true"
+                .parse::<CustomLightData>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn exterior_ambients_apply_by_grid() {
+        let exterior = |grid| {
+            let mut cell = interior_cell("", [120, 120, 120], 0.5);
+            cell.data.flags.remove(CellFlags::IS_INTERIOR);
+            cell.data.grid = grid;
+            cell
+        };
+
+        let light_config = LightConfig {
+            exterior_ambients: vec![(
+                crate::ExteriorGrid::from_key("ext:2..3,-9")
+                    .unwrap()
+                    .unwrap(),
+                "fog_density=0.9".parse().unwrap(),
+            )],
+            ..neutral_config()
+        };
+
+        let records: Vec<TES3Object> = vec![
+            exterior((2, -9)).into(),
+            exterior((3, -9)).into(),
+            exterior((4, -9)).into(),
+            interior_cell("ext:2,-9", [120, 120, 120], 0.5).into(),
+            light("torch", [255, 160, 64], 128).into(),
+        ];
+        let generation = generate(vec![plugin("Vvardenfell.esp", records)], &light_config).unwrap();
+
+        let fog_densities: Vec<((i32, i32), f32)> = generation
+            .plugin
+            .objects_of_type::<Cell>()
+            .filter(|cell| !cell.data.flags.contains(CellFlags::IS_INTERIOR))
+            .map(|cell| {
+                (
+                    cell.data.grid,
+                    cell.atmosphere_data.as_ref().unwrap().fog_density,
+                )
+            })
+            .collect();
+        assert_eq!(fog_densities, [((2, -9), 0.9), ((3, -9), 0.9)]);
+
+        assert!(
+            !generation
+                .plugin
+                .objects_of_type::<Cell>()
+                .any(|cell| cell.name == "ext:2,-9")
+        );
+    }
+
+    #[test]
+    fn global_fog_density_mult_scales_interiors_only() {
+        let light_config = LightConfig {
+            fog_density_mult: 2.0,
+            ambient_regexes: vec![(
+                regex::Regex::new("^vivec").unwrap(),
+                "fog_density=0.1".parse().unwrap(),
+            )],
+            ..neutral_config()
+        };
+
+        let mut exterior = interior_cell("", [120, 120, 120], 0.3);
+        exterior.data.flags.remove(CellFlags::IS_INTERIOR);
+        exterior.data.grid = (2, -9);
+
+        let records: Vec<TES3Object> = vec![
+            interior_cell("Balmora, Guild of Mages", [120, 120, 120], 0.3).into(),
+            interior_cell("Ald-ruhn, Temple", [120, 120, 120], 0.8).into(),
+            interior_cell("Vivec, Arena Pit", [120, 120, 120], 0.3).into(),
+            exterior.into(),
+            light("torch", [255, 160, 64], 128).into(),
+        ];
+        let generation = generate(vec![plugin("Cells.esp", records)], &light_config).unwrap();
+
+        let density = |cell_name| {
+            generated_cell(&generation, cell_name)
+                .atmosphere_data
+                .as_ref()
+                .unwrap()
+                .fog_density
+        };
+
+        assert!((density("Balmora, Guild of Mages") - 0.6).abs() < 1e-6);
+        // Densities stay within what the engine accepts
+        assert_eq!(density("Ald-ruhn, Temple"), 1.0);
+        // Fixed densities from ambient overrides win over the multiplier
+        assert!((density("Vivec, Arena Pit") - 0.1).abs() < 1e-6);
+
+        assert!(
+            !generation
+                .plugin
+                .objects_of_type::<Cell>()
+                .any(|cell| !cell.data.flags.contains(CellFlags::IS_INTERIOR))
+        );
+    }
+
+    #[test]
+    fn fog_density_is_clamped_into_range() {
+        assert_eq!(scale_fog_density(0.4, 0.5), 0.2);
+        assert_eq!(scale_fog_density(0.8, 2.0), 1.0);
+        assert_eq!(scale_fog_density(0.8, -1.0), 0.0);
+        assert_eq!(scale_fog_density(0.8, f32::INFINITY), 0.8);
+    }
+
+    #[test]
+    fn instance_edits_delete_and_replace_matching_references() {
+        let light_config = LightConfig {
+            ambient_regexes: vec![(
+                regex::Regex::new("^balmora, temple$").unwrap(),
+                "instance_deletions=^light_com_candle_0[0-9]$;instance_replacements=^furn_.*=>furn_de_table_02"
+                    .parse()
+                    .unwrap(),
+            )],
+            ..neutral_config()
+        };
+
+        let temple = cell_with_references(
+            "Balmora, Temple",
+            &[
+                "Light_Com_Candle_06",
+                "furn_de_table_01",
+                "misc_com_bottle_01",
+            ],
+        );
+        let generation = generate(vec![plugin("Balmora.esp", [temple])], &light_config).unwrap();
+        let cell = generated_cell(&generation, "Balmora, Temple");
+
+        // Untouched references are still cleared, while edited ones keep their original indices
+        let mut references: Vec<((u32, u32), String, Option<u32>)> = cell
+            .references
+            .iter()
+            .map(|(key, reference)| (*key, reference.id.clone(), reference.deleted))
+            .collect();
+        references.sort();
+        assert_eq!(
+            references,
+            [
+                ((0, 1), "Light_Com_Candle_06".to_string(), Some(0)),
+                ((0, 2), "furn_de_table_02".to_string(), None),
+            ]
+        );
+
+        assert_eq!(
+            generation.instance_log,
+            [
+                "[ DELETED ]: light_com_candle_06 (0, 1) in balmora, temple",
+                "[ REPLACED ]: furn_de_table_01 (0, 2) in balmora, temple with furn_de_table_02",
+            ]
+        );
+    }
+
+    #[test]
+    fn instance_deletions_win_over_replacements() {
+        let ambient: CustomCellAmbient =
+            "instance_replacements=^torch=>torch_02;instance_deletions=^torch"
+                .parse()
+                .unwrap();
+
+        assert!(matches!(
+            ambient.instance_edit("torch_01"),
+            Some(InstanceEdit::Delete)
+        ));
+        assert!(ambient.instance_edit("lantern").is_none());
+        assert!(
+            "instance_replacements=^torch"
+                .parse::<CustomCellAmbient>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn instance_overrides_scale_and_move_every_matching_reference() {
+        let light_config = LightConfig {
+            ambient_regexes: vec![(
+                regex::Regex::new("^balmora, temple$").unwrap(),
+                "instance_overrides=^light_com_lantern=>scale=0.5,offset=0 0 -16;instance_deletions=_03$"
+                    .parse()
+                    .unwrap(),
+            )],
+            ..neutral_config()
+        };
+
+        let mut temple = cell_with_references(
+            "Balmora, Temple",
+            &[
+                "light_com_lantern_02",
+                "light_com_lantern_02",
+                "light_com_lantern_03",
+                "light_com_candle_06",
+            ],
+        );
+        // Already scaled references are multiplied, and kept within what the engine accepts
+        temple.references.get_mut(&(0, 2)).unwrap().scale = Some(0.8);
+
+        let generation = generate(vec![plugin("Balmora.esp", [temple])], &light_config).unwrap();
+        let cell = generated_cell(&generation, "Balmora, Temple");
+
+        let mut references: Vec<((u32, u32), Option<f32>, [f32; 3], Option<u32>)> = cell
+            .references
+            .iter()
+            .map(|(key, reference)| {
+                (
+                    *key,
+                    reference.scale,
+                    reference.translation,
+                    reference.deleted,
+                )
+            })
+            .collect();
+        references.sort_by_key(|(key, ..)| *key);
+        assert_eq!(
+            references,
+            [
+                ((0, 1), Some(0.5), [0.0, 0.0, -16.0], None),
+                ((0, 2), Some(0.5), [0.0, 0.0, -16.0], None),
+                ((0, 3), None, [0.0, 0.0, 0.0], Some(0)),
+            ]
+        );
+
+        // Deleted references aren't also logged as modified
+        assert_eq!(
+            generation.instance_log,
+            [
+                "[ MODIFIED ]: light_com_lantern_02 (0, 1) in balmora, temple with scale=0.5,offset=0 0 -16",
+                "[ MODIFIED ]: light_com_lantern_02 (0, 2) in balmora, temple with scale=0.5,offset=0 0 -16",
+                "[ DELETED ]: light_com_lantern_03 (0, 3) in balmora, temple",
+            ]
+        );
+    }
+
+    #[test]
+    fn instance_overrides_need_a_whole_offset() {
+        assert!(
+            "instance_overrides=^torch=>offset=0 16"
+                .parse::<CustomCellAmbient>()
+                .is_err()
+        );
+        assert!(
+            "instance_overrides=^torch=>scale=big"
+                .parse::<CustomCellAmbient>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn magic_effects_are_processed_once_when_asked_for() {
+        let effect = |color| {
+            let mut effect = MagicEffect::default();
+            effect.data.color = color;
+            effect
+        };
+
+        let generated_colors = |light_config: &LightConfig| {
+            let records: Vec<TES3Object> = vec![
+                light("torch", [255, 160, 64], 128).into(),
+                effect([255, 0, 0]).into(),
+                effect([0, 0, 255]).into(),
+            ];
+            let generation = generate(vec![plugin("Spells.esp", records)], light_config).unwrap();
+
+            generation
+                .plugin
+                .objects_of_type::<MagicEffect>()
+                .map(|effect| effect.data.color)
+                .collect::<Vec<_>>()
+        };
+
+        let light_config = LightConfig {
+            process_magic_effects: true,
+            colored_saturation: 0.0,
+            ..neutral_config()
+        };
+
+        // Both records share an effect id, so only the first is kept
+        assert_eq!(generated_colors(&light_config), [[255, 255, 255]]);
+
+        let effect_id = magic_effect_id(&MagicEffect::default());
+        let excluding = LightConfig {
+            excluded_id_regexes: vec![(
+                None,
+                regex::Regex::new(&format!("^{effect_id}$")).unwrap(),
+            )],
+            ..light_config
+        };
+        assert!(generated_colors(&excluding).is_empty());
+    }
+}
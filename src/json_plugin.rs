@@ -0,0 +1,122 @@
+use std::{
+    fs::{File, read},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use tes3::esp::{Plugin, TES3Object};
+
+/// Whether a plugin was converted to JSON by tes3conv, going by its extension
+pub fn is_json_plugin(plugin_path: &Path) -> bool {
+    plugin_path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+/// Loads a plugin converted to JSON by tes3conv, which is a list of its records with the header first
+pub fn load_json_plugin(plugin_path: &Path) -> io::Result<Plugin> {
+    let objects: Vec<TES3Object> =
+        serde_json::from_slice(&read(plugin_path)?).map_err(io::Error::from)?;
+
+    let mut plugin = Plugin::new();
+    plugin.objects = objects;
+    Ok(plugin)
+}
+
+/// Writes a plugin as JSON in the same form as tes3conv, so that tes3conv can turn it back into a plugin
+pub fn save_json_plugin(plugin: &Plugin, json_path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(json_path)?);
+    serde_json::to_writer_pretty(&mut writer, &plugin.objects).map_err(io::Error::from)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use tes3::esp::{Cell, Light};
+
+    use super::*;
+    use crate::{
+        generate_lightfixes,
+        test_fixtures::{interior_cell, light, neutral_config, plugin, temp_dir},
+    };
+
+    fn generated_plugin() -> Plugin {
+        let records: Vec<TES3Object> = vec![
+            light("torch", [255, 160, 64], 128).into(),
+            light("blue_lantern", [64, 128, 255], 256).into(),
+            interior_cell("Balmora, Guild of Mages", [80, 80, 120], 0.4).into(),
+        ];
+
+        let light_config = crate::LightConfig {
+            fog_density_mult: 2.0,
+            ..neutral_config()
+        };
+
+        generate_lightfixes(vec![plugin("Balmora.esp", records)], &light_config).unwrap()
+    }
+
+    #[test]
+    fn json_plugins_round_trip() {
+        let mut original = generated_plugin();
+        let json_path = temp_dir("json-round-trip").join("S3LightFixes.json");
+
+        save_json_plugin(&original, &json_path).unwrap();
+        let mut loaded = load_json_plugin(&json_path).unwrap();
+
+        let lights = |plugin: &Plugin| -> Vec<(String, [u8; 4], u32, u32)> {
+            plugin
+                .objects_of_type::<Light>()
+                .map(|light| {
+                    (
+                        light.id.clone(),
+                        light.data.color,
+                        light.data.radius,
+                        light.data.flags.bits(),
+                    )
+                })
+                .collect()
+        };
+        assert_eq!(lights(&loaded), lights(&original));
+
+        let atmospheres = |plugin: &Plugin| -> Vec<(String, [u8; 4], f32)> {
+            plugin
+                .objects_of_type::<Cell>()
+                .map(|cell| {
+                    let atmosphere = cell.atmosphere_data.as_ref().unwrap();
+                    (
+                        cell.name.clone(),
+                        atmosphere.ambient_color,
+                        atmosphere.fog_density,
+                    )
+                })
+                .collect()
+        };
+        assert_eq!(atmospheres(&loaded), atmospheres(&original));
+
+        assert_eq!(loaded.save_bytes().unwrap(), original.save_bytes().unwrap());
+    }
+
+    #[test]
+    fn json_plugins_are_a_list_of_records_with_the_header_first() {
+        let json_path = temp_dir("json-shape").join("S3LightFixes.json");
+        save_json_plugin(&generated_plugin(), &json_path).unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(&read(&json_path).unwrap()).unwrap();
+        let records = json.as_array().unwrap();
+
+        assert_eq!(records[0]["type"], "Header");
+        assert!(
+            records
+                .iter()
+                .any(|record| record["type"] == "Light" && record["id"] == "torch")
+        );
+    }
+
+    #[test]
+    fn json_plugins_are_known_by_their_extension() {
+        assert!(is_json_plugin(Path::new("Patch.json")));
+        assert!(is_json_plugin(Path::new("Patch.JSON")));
+        assert!(!is_json_plugin(Path::new("Patch.esp")));
+        assert!(!is_json_plugin(Path::new("json")));
+    }
+}
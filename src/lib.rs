@@ -1,11 +1,13 @@
 use std::{
     env::current_dir,
-    fs::{create_dir_all, metadata},
-    io,
+    fs::{File, OpenOptions, create_dir_all, metadata, remove_file, rename},
+    io::{self, Read, Write},
     path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
 };
 
 pub use openmw_config::OpenMWConfiguration;
+use serde::{Deserialize, Serialize};
 pub use tes3::esp::Plugin;
 
 pub mod default;
@@ -14,49 +16,298 @@ pub mod light_args;
 pub use light_args::LightArgs;
 
 mod light_config;
-pub use light_config::LightConfig;
+pub use light_config::{LightConfig, config_fingerprint};
 
 mod light_override;
-pub use light_override::{CustomCellAmbient, CustomLightData};
+pub use light_override::{
+    CustomCellAmbient, CustomLightData, ExteriorGrid, InstanceEdit, InstanceOverride,
+    LightColorMult, LightPlacement, TintColor, TypedLightColor,
+};
+
+mod error;
+pub use error::{ConfigPathError, LightFixesError};
+
+mod generate;
+pub use generate::{Generation, PluginMeta, generate, generate_lightfixes, remap_references};
+
+mod cfg_fragment;
+pub use cfg_fragment::{FRAGMENT_NAME, fragment_path, revert_fragment, write_fragment};
+
+mod morrowind_ini;
+pub use morrowind_ini::{MorrowindIni, is_morrowind_ini};
+
+mod json_plugin;
+pub use json_plugin::{is_json_plugin, load_json_plugin, save_json_plugin};
+
+mod verify;
+pub use verify::{VerifyError, verify_plugin};
+
+mod plugin_metadata;
+pub use plugin_metadata::{METADATA_EXTENSION, METADATA_VERSION, MasterMetadata, PluginMetadata};
+
+mod plugin_diff;
+pub use plugin_diff::{PluginDiff, cell_key, magic_effect_id, record_key};
+
+mod run_lock;
+pub use run_lock::{LOCK_NAME, RunLock, release_run_lock};
+
+mod run_cache;
+pub use run_cache::{CACHE_NAME, ContentFileStamp, RunCache};
+
+mod run;
+pub use run::{NoHooks, RunHooks, RunOutput, RunSummary, run, run_with};
+
+mod light_process;
+pub use light_process::{
+    FlickerMode, HueOverflow, LightCategory, NegativeLightMode, ProcessedLight, apply_model,
+    apply_sound, clean_animation_flags, color_to_hsv, fnv1a, hsv_to_color, jitter, light_to_hsv,
+    multiply_hue, offset_hue, perceived_brightness, process_light, process_magic_effect,
+    real_minutes_to_duration, scale_duration, scale_fog_density, scale_gold_value, scale_radius,
+    scale_radius_curved, scale_weight, simulate_light, simulate_light_in, simulate_light_with,
+    tint_hsv, value_normalization_factor,
+};
+
+#[cfg(test)]
+mod test_fixtures;
 
 pub const DEFAULT_CONFIG_NAME: &str = "lightconfig.toml";
 pub const LOG_NAME: &str = "lightconfig.log";
+/// Name of the record of weather fallbacks changed by `adjust_weather_fallbacks`, kept next to the user openmw.cfg
+pub const WEATHER_FALLBACKS_NAME: &str = "lightconfig.weather.json";
 pub const PLUGIN_NAME: &str = "S3LightFixes.omwaddon";
+pub const ESM_PLUGIN_NAME: &str = "S3LightFixes.esm";
+pub const VANILLA_PLUGIN_NAME: &str = "S3LightFixes.esp";
+/// Suffixes of the plugins written by `split_output`, holding lights and magic effects, or cells
+pub const LIGHTS_SUFFIX: &str = "Lights";
+pub const CELLS_SUFFIX: &str = "Cells";
+/// Directory next to the user openmw.cfg which `--output-as-data-dir` saves the plugin into
+pub const OUTPUT_DATA_DIR_NAME: &str = "s3lightfixes-output";
+/// Every name the generated plugin may be written under, apart from the parts of a split plugin
+pub const PLUGIN_NAMES: [&str; 3] = [PLUGIN_NAME, ESM_PLUGIN_NAME, VANILLA_PLUGIN_NAME];
 
-pub fn get_config_path(args: &mut LightArgs) -> PathBuf {
-    if let Some(path) = &args.openmw_cfg {
-        let absolute_path = if path.is_relative() {
-            path.canonicalize().unwrap()
-        } else {
-            path.to_owned()
-        };
+/// What happens when the generated plugin already exists
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverwritePolicy {
+    /// Always replace it
+    #[default]
+    Always,
+    /// Stop with an error instead
+    Never,
+    /// Only replace it if the new plugin is different, leaving openmw.cfg alone otherwise
+    IfChanged,
+}
+
+/// Which engine the generated plugin is written for
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// OpenMW, which the plugin may be enabled in automatically
+    #[default]
+    Openmw,
+    /// The original engine, which can't load .omwaddon files.
+    /// OpenMW-only plugins are left out of the load order, and openmw.cfg is never changed
+    Vanilla,
+}
+
+/// Whether the generated plugin is a regular addon, or flagged as a master so that it sorts before addons
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFileType {
+    /// A regular addon, written as S3LightFixes.omwaddon, or S3LightFixes.esp for the original engine
+    #[default]
+    Esp,
+    /// A master, written as S3LightFixes.esm
+    Esm,
+}
 
-        if absolute_path.is_dir() && absolute_path.join("openmw.cfg").is_file() {
-            return absolute_path;
-        } else if absolute_path.is_file() {
-            return absolute_path;
+impl OutputFileType {
+    /// File name of the generated plugin
+    pub fn plugin_name(self, format: OutputFormat) -> &'static str {
+        match (self, format) {
+            (OutputFileType::Esp, OutputFormat::Openmw) => PLUGIN_NAME,
+            (OutputFileType::Esp, OutputFormat::Vanilla) => VANILLA_PLUGIN_NAME,
+            (OutputFileType::Esm, _) => ESM_PLUGIN_NAME,
         }
+    }
+
+    pub fn file_type(self) -> tes3::esp::types::FileType {
+        match self {
+            OutputFileType::Esp => tes3::esp::types::FileType::Esp,
+            OutputFileType::Esm => tes3::esp::types::FileType::Esm,
+        }
+    }
+}
+
+/// Environment variable which OpenMW and its launchers use to point at the directory of a non-default openmw.cfg
+pub const OPENMW_CONFIG_VAR: &str = "OPENMW_CONFIG";
+
+/// Checks an openmw.cfg path given by the user, which may be the file itself or the directory holding it
+fn validated_config_path(path: &Path, source: &'static str) -> Result<PathBuf, ConfigPathError> {
+    let absolute_path = if path.is_relative() {
+        path.canonicalize()
+            .map_err(|_| ConfigPathError::DoesNotExist {
+                path: path.to_owned(),
+                source,
+            })?
+    } else {
+        path.to_owned()
+    };
+
+    if absolute_path.is_dir() {
+        match absolute_path.join("openmw.cfg").is_file() {
+            true => Ok(absolute_path),
+            false => Err(ConfigPathError::NoConfigInDirectory {
+                path: absolute_path,
+                source,
+            }),
+        }
+    } else if absolute_path.is_file() {
+        Ok(absolute_path)
+    } else {
+        Err(ConfigPathError::DoesNotExist {
+            path: absolute_path,
+            source,
+        })
+    }
+}
+
+/// Finds the openmw.cfg to use, from the first of: `--openmw-cfg`, the `OPENMW_CONFIG` environment variable,
+/// the current working directory, and the default location for the platform
+pub fn get_config_path(args: &mut LightArgs) -> Result<PathBuf, LightFixesError> {
+    let debug = args.debug || std::env::var("S3L_DEBUG").is_ok();
 
-        panic!("This shit should never ever happen!");
+    let (config_path, source) = if let Some(path) = &args.openmw_cfg {
+        (validated_config_path(path, "--openmw-cfg")?, "--openmw-cfg")
+    } else if let Some(path) = std::env::var_os(OPENMW_CONFIG_VAR) {
+        (
+            validated_config_path(Path::new(&path), OPENMW_CONFIG_VAR)?,
+            OPENMW_CONFIG_VAR,
+        )
     } else {
-        let cwd_cfg = current_dir()
-            .expect("Failed to get current directory")
-            .join("openmw.cfg");
+        let cwd = current_dir().map_err(ConfigPathError::NoWorkingDirectory)?;
+        let cwd_cfg = cwd.join("openmw.cfg");
+        let default_path = openmw_config::default_config_path();
 
         if cwd_cfg.is_file() {
-            return cwd_cfg;
+            (cwd_cfg, "the current working directory")
+        } else if let Some(path) = remembered_config_path(&cwd) {
+            (
+                validated_config_path(&path, "lightconfig.toml")?,
+                "openmw_cfg in lightconfig.toml",
+            )
+        } else if default_path.is_file() || default_path.join("openmw.cfg").is_file() {
+            (default_path, "the default location")
+        } else {
+            return Err(ConfigPathError::NoConfigFound { default_path }.into());
         }
+    };
+
+    if debug {
+        print_notification(&format!(
+            "[ DEBUG ]: Using openmw.cfg at {}, from {source}",
+            config_path.display()
+        ));
     }
 
-    openmw_config::default_config_path()
+    Ok(config_path)
+}
+
+/// The `openmw_cfg` of the lightconfig.toml in dir, which remembers the openmw.cfg picked when lightfixes was last run from there
+pub fn remembered_config_path(dir: &Path) -> Option<PathBuf> {
+    let contents = std::fs::read_to_string(dir.join(DEFAULT_CONFIG_NAME)).ok()?;
+    let table: toml::Table = toml::from_str(&contents).ok()?;
+
+    table
+        .get("openmw_cfg")
+        .and_then(toml::Value::as_str)
+        .map(PathBuf::from)
+}
+
+/// Sets `openmw_cfg` in the lightconfig.toml in dir, creating it if needed, so that later runs from there use the same openmw.cfg
+pub fn remember_config_path(dir: &Path, config_path: &Path) -> Result<(), LightFixesError> {
+    let lightconfig_path = dir.join(DEFAULT_CONFIG_NAME);
+
+    let mut table: toml::Table = match std::fs::read_to_string(&lightconfig_path) {
+        Ok(contents) => {
+            toml::from_str(&contents).map_err(|source| LightFixesError::ConfigParse {
+                path: lightconfig_path.clone(),
+                source,
+            })?
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => toml::Table::new(),
+        Err(err) => return Err(err.into()),
+    };
+
+    table.insert(
+        "openmw_cfg".to_string(),
+        toml::Value::String(config_path.to_string_lossy().into_owned()),
+    );
+
+    std::fs::write(
+        lightconfig_path,
+        toml::to_string_pretty(&table).map_err(LightFixesError::ConfigSerialize)?,
+    )?;
+
+    Ok(())
+}
+
+/// Lowercased file name of a plugin, which plugin patterns are matched against
+pub fn plugin_file_name(plugin_path: &Path) -> String {
+    plugin_path
+        .file_name()
+        .and_then(|name| name.to_ascii_lowercase().into_string().ok())
+        .unwrap_or_default()
+}
+
+/// Name of one part of a generated plugin split into several, as in `S3LightFixes-2.omwaddon` or `S3LightFixes-Cells.omwaddon`
+pub fn suffixed_plugin_name(plugin_name: &str, suffix: impl std::fmt::Display) -> String {
+    match plugin_name.rsplit_once('.') {
+        Some((stem, extension)) => format!("{stem}-{suffix}.{extension}"),
+        None => format!("{plugin_name}-{suffix}"),
+    }
+}
+
+/// Whether a file name is one the generated plugin may be written under,
+/// including the parts of a plugin split by masters or by record type
+pub fn is_generated_plugin_name(file_name: &str) -> bool {
+    let file_name = file_name.to_ascii_lowercase();
+
+    PLUGIN_NAMES.iter().any(|plugin_name| {
+        let plugin_name = plugin_name.to_ascii_lowercase();
+
+        if file_name == plugin_name {
+            return true;
+        }
+
+        let Some((stem, extension)) = plugin_name.rsplit_once('.') else {
+            return false;
+        };
+
+        file_name
+            .strip_prefix(stem)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .and_then(|rest| rest.strip_suffix(extension))
+            .and_then(|rest| rest.strip_suffix('.'))
+            .is_some_and(|suffix| {
+                suffix.split('-').all(|part| {
+                    part.eq_ignore_ascii_case(LIGHTS_SUFFIX)
+                        || part.eq_ignore_ascii_case(CELLS_SUFFIX)
+                        || (!part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit()))
+                })
+            })
+    })
 }
 
 pub fn is_fixable_plugin(plug_path: &Path) -> bool {
     // If path doesn't exist
     if metadata(plug_path).is_err() {
         return false;
-    // If path is the lightfixes plugin
-    } else if plug_path.to_string_lossy().contains(PLUGIN_NAME) {
+    // If path is the lightfixes plugin, under any of its names
+    } else if plug_path
+        .file_name()
+        .is_some_and(|name| is_generated_plugin_name(&name.to_string_lossy()))
+    {
         return false;
     } else {
         // Don't match extensionless files
@@ -64,17 +315,159 @@ pub fn is_fixable_plugin(plug_path: &Path) -> bool {
         match plug_path.extension() {
             None => return false,
             Some(ext) => match ext.to_ascii_lowercase().to_str().unwrap_or_default() {
-                "esp" | "esm" | "omwaddon" | "omwgame" => return true,
+                // Plugins converted by tes3conv are loaded as well
+                "esp" | "esm" | "omwaddon" | "omwgame" | "json" => return true,
                 _ => return false,
             },
         }
     }
 }
 
+/// Reads the author and description of a plugin straight from the start of its file, without parsing any records.
+/// The header record comes first, and its `HEDR` subrecord holds a version, a file type,
+/// then the author and description as null-padded strings of 32 and 256 bytes
+fn read_header_text(plugin_path: &Path) -> Option<(String, String)> {
+    const AUTHOR_START: usize = 32;
+    const DESCRIPTION_START: usize = AUTHOR_START + 32;
+    const HEADER_END: usize = DESCRIPTION_START + 256;
+
+    let mut header = [0; HEADER_END];
+    File::open(plugin_path).ok()?.read_exact(&mut header).ok()?;
+
+    if &header[0..4] != b"TES3" || &header[16..20] != b"HEDR" {
+        return None;
+    }
+
+    let text = |bytes: &[u8]| {
+        let end = bytes
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(bytes.len());
+        String::from_utf8_lossy(&bytes[..end]).into_owned()
+    };
+
+    Some((
+        text(&header[AUTHOR_START..DESCRIPTION_START]),
+        text(&header[DESCRIPTION_START..HEADER_END]),
+    ))
+}
+
+/// Whether the header of a plugin says lightfixes generated it, whatever it's called now.
+/// Plugins carrying a config fingerprint are always recognized, and older ones by the default author and description
+pub fn is_generated_plugin(plugin_path: &Path) -> bool {
+    let Some((author, description)) = read_header_text(plugin_path) else {
+        return false;
+    };
+
+    let has_fingerprint = description
+        .trim_end()
+        .strip_suffix(']')
+        .and_then(|rest| rest.rsplit_once(" [cfg:"))
+        .is_some_and(|(_, fingerprint)| {
+            fingerprint.len() == 8 && fingerprint.bytes().all(|byte| byte.is_ascii_hexdigit())
+        });
+
+    has_fingerprint
+        || (author == default::plugin_author()
+            && description.starts_with("Plugin generated by s3-lightfixes"))
+}
+
+/// Whether notifications which aren't shown as dialogs are printed to stderr, keeping stdout free for a streamed plugin
+static NOTIFICATIONS_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+pub fn set_notifications_to_stderr(to_stderr: bool) {
+    NOTIFICATIONS_TO_STDERR.store(to_stderr, Ordering::Relaxed);
+}
+
+/// Version of the `--porcelain` format, which goes up whenever a record is changed or removed
+pub const PORCELAIN_VERSION: u32 = 1;
+
+/// Whether `--porcelain` records are printed to stdout, which leaves everything else to stderr
+static PORCELAIN: AtomicBool = AtomicBool::new(false);
+
+/// Switches to the `--porcelain` format, printing its version as the first record
+pub fn set_porcelain(porcelain: bool) {
+    PORCELAIN.store(porcelain, Ordering::Relaxed);
+
+    if porcelain {
+        set_notifications_to_stderr(true);
+        porcelain_record("porcelain_version", PORCELAIN_VERSION);
+    }
+}
+
+pub fn is_porcelain() -> bool {
+    PORCELAIN.load(Ordering::Relaxed)
+}
+
+/// Prints a `key<TAB>value` record in porcelain mode, and nothing otherwise
+pub fn porcelain_record(key: &str, value: impl std::fmt::Display) {
+    if !is_porcelain() {
+        return;
+    }
+
+    println!("{}", porcelain_line(key, value));
+}
+
+/// A `key<TAB>value` record. Tabs and line breaks in the value are replaced by spaces,
+/// so that each record is exactly one line
+fn porcelain_line(key: &str, value: impl std::fmt::Display) -> String {
+    let value = value.to_string().replace(['\t', '\n', '\r'], " ");
+    format!("{key}\t{value}")
+}
+
+/// Shows why lightfixes can't go on, and exits with the given code.
+/// In porcelain mode, this is given as the `status` and `reason` records as well
+pub fn exit_with_error(title: &str, message: &str, no_notifications: bool, code: i32) -> ! {
+    notification_box(title, message, no_notifications);
+    porcelain_record("status", "error");
+    porcelain_record("reason", message);
+    exit(code)
+}
+
+/// Releases the run lock, which `std::process::exit` would leave behind as it skips destructors, then exits
+pub fn exit(code: i32) -> ! {
+    release_run_lock();
+    std::process::exit(code)
+}
+
+/// Prints a message which would otherwise be shown as a dialog, or which goes along with them, such as debug output.
+/// It goes to stderr whenever stdout carries the plugin or porcelain records
+pub fn print_notification(message: &str) {
+    match NOTIFICATIONS_TO_STDERR.load(Ordering::Relaxed) {
+        true => eprintln!("{}", message),
+        false => println!("{}", message),
+    }
+}
+
+/// Environment variables set by CI services, which never have anyone to click through a dialog
+const CI_VARS: [&str; 5] = ["CI", "GITHUB_ACTIONS", "GITLAB_CI", "BUILDKITE", "TF_BUILD"];
+
+/// Why dialogs can't be shown, if they can't, such as under a cron job, an SSH session, or a tool capturing the output.
+/// Dialogs would otherwise block forever, or fail for lack of a display
+pub fn non_interactive_reason() -> Option<&'static str> {
+    if CI_VARS
+        .iter()
+        .any(|ci_var| std::env::var_os(ci_var).is_some())
+    {
+        return Some("a CI environment was detected");
+    }
+
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "android")))]
+    if std::env::var_os("DISPLAY").is_none() && std::env::var_os("WAYLAND_DISPLAY").is_none() {
+        return Some("neither DISPLAY nor WAYLAND_DISPLAY is set");
+    }
+
+    if !std::io::IsTerminal::is_terminal(&io::stdout()) {
+        return Some("stdout isn't a terminal");
+    }
+
+    None
+}
+
 /// Displays a notification taking title and message as argument
 pub fn notification_box(title: &str, message: &str, no_notifications: bool) {
     #[cfg(target_os = "android")]
-    println!("{}", message);
+    print_notification(message);
 
     #[cfg(not(target_os = "android"))]
     if !no_notifications {
@@ -84,17 +477,105 @@ pub fn notification_box(title: &str, message: &str, no_notifications: bool) {
             .alert()
             .show();
     } else {
-        println!("{}", message);
+        print_notification(message);
+    }
+}
+
+/// Asks where openmw.cfg is through a native file picker, explaining why in its title.
+/// None means the picker was cancelled, or that there was nobody to ask, as on android or when notifications are disabled
+pub fn pick_config_path(reason: &str, no_notifications: bool) -> Option<PathBuf> {
+    #[cfg(target_os = "android")]
+    let picked = {
+        let _ = (reason, no_notifications);
+        None
+    };
+
+    #[cfg(not(target_os = "android"))]
+    let picked = match no_notifications {
+        true => None,
+        false => native_dialog::DialogBuilder::file()
+            .set_title(format!("{reason} Please locate your openmw.cfg"))
+            .add_filter("OpenMW configuration", &["cfg"])
+            .open_single_file()
+            .show()
+            .ok()
+            .flatten(),
+    };
+
+    picked
+}
+
+/// Asks a yes or no question through a native dialog, giving back the answer.
+/// None means there was nobody to ask, as on android or when notifications are disabled, or that the dialog failed
+pub fn confirmation_box(title: &str, message: &str, no_notifications: bool) -> Option<bool> {
+    #[cfg(target_os = "android")]
+    let answer = {
+        let _ = (title, message, no_notifications);
+        None
+    };
+
+    #[cfg(not(target_os = "android"))]
+    let answer = match no_notifications {
+        true => None,
+        false => native_dialog::DialogBuilder::message()
+            .set_title(title)
+            .set_text(message)
+            .confirm()
+            .show()
+            .ok(),
+    };
+
+    answer
+}
+
+/// Sets `num_objects` of the plugin's header to the number of records it actually holds, not counting the header itself.
+/// Returns false if the header had a different count, or if the plugin has no header at all
+pub fn finalize_header(plugin: &mut Plugin) -> bool {
+    let record_count = plugin
+        .objects
+        .iter()
+        .filter(|object| !matches!(object, tes3::esp::TES3Object::Header(_)))
+        .count() as u32;
+
+    let Some(header) = plugin.objects_of_type_mut::<tes3::esp::Header>().next() else {
+        return false;
+    };
+
+    let was_correct = header.num_objects == record_count;
+    header.num_objects = record_count;
+    was_correct
+}
+
+/// What `save_plugin` did with the generated plugin
+#[derive(Debug)]
+pub enum SaveOutcome {
+    /// The plugin was written to this path
+    Written(PathBuf),
+    /// The plugin at this path already held the same bytes, and was left alone
+    UpToDate(PathBuf),
+}
+
+impl SaveOutcome {
+    pub fn path(&self) -> &Path {
+        match self {
+            SaveOutcome::Written(path) | SaveOutcome::UpToDate(path) => path,
+        }
     }
 }
 
-pub fn save_plugin(output_dir: &PathBuf, generated_plugin: &mut Plugin) -> io::Result<()> {
-    let mut plugin_path = output_dir.join(PLUGIN_NAME);
+/// Writes the generated plugin, following `overwrite_policy` when one already exists
+/// and backing up the previous plugin according to `keep_backups`
+pub fn save_plugin(
+    output_dir: &PathBuf,
+    plugin_name: &str,
+    generated_plugin: &mut Plugin,
+    light_config: &LightConfig,
+) -> Result<SaveOutcome, LightFixesError> {
+    let mut plugin_path = output_dir.join(plugin_name);
 
     match metadata(output_dir) {
         Ok(metadata) if !metadata.is_dir() => {
-            let cwd =
-                current_dir().expect("CRITICAL FAILURE: FAILED TO READ CURRENT WORKING DIRECTORY!");
+            let cwd = current_dir().map_err(LightFixesError::NoWorkingDirectory)?;
 
             eprintln!(
                 "WARNING: Couldn't use {} as an output directory, as it isn't a directory. Using the current working directory, {}, instead!",
@@ -102,20 +583,900 @@ pub fn save_plugin(output_dir: &PathBuf, generated_plugin: &mut Plugin) -> io::R
                 cwd.display()
             );
 
-            plugin_path = cwd.join(PLUGIN_NAME);
+            plugin_path = cwd.join(plugin_name);
         }
         Ok(_) => {}
         Err(err) if err.kind() == io::ErrorKind::NotFound => {
-            create_dir_all(output_dir)?;
+            create_dir_all(output_dir).map_err(|source| LightFixesError::OutputDir {
+                path: output_dir.to_owned(),
+                source: Some(source),
+            })?;
+        }
+        Err(source) => {
+            return Err(LightFixesError::OutputDir {
+                path: output_dir.to_owned(),
+                source: Some(source),
+            });
         }
-        Err(err) => return Err(err),
     }
 
-    generated_plugin.save_path(plugin_path)?;
+    if light_config.overwrite_policy == OverwritePolicy::Never && plugin_path.exists() {
+        return Err(LightFixesError::PluginExists(plugin_path));
+    }
+
+    // A plugin cut off halfway through would crash the engine, so the old one is only replaced once the new one is complete
+    let tmp_path = plugin_path.with_file_name(format!("{plugin_name}.tmp"));
+
+    if let Err(source) = save_synced(generated_plugin, &tmp_path) {
+        let _ = remove_file(&tmp_path);
+        return Err(LightFixesError::PluginSave {
+            path: plugin_path,
+            source,
+        });
+    }
+
+    let unchanged = plugin_path.is_file() && same_contents(&tmp_path, &plugin_path);
+
+    if unchanged && light_config.overwrite_policy == OverwritePolicy::IfChanged {
+        let _ = remove_file(&tmp_path);
+        return Ok(SaveOutcome::UpToDate(plugin_path));
+    }
+
+    // Plugins which wouldn't change aren't worth a backup
+    if light_config.keep_backups > 0
+        && plugin_path.is_file()
+        && !unchanged
+        && let Err(err) = rotate_backups(&plugin_path, light_config.keep_backups)
+    {
+        eprintln!(
+            "[ WARNING ]: Couldn't back up {}: {err}",
+            plugin_path.display()
+        );
+    }
+
+    // Both files are in the same directory, so this is atomic. On Windows, it also replaces the old plugin
+    if let Err(source) = rename(&tmp_path, &plugin_path) {
+        let _ = remove_file(&tmp_path);
+        return Err(LightFixesError::PluginSave {
+            path: plugin_path,
+            source,
+        });
+    }
+
+    Ok(SaveOutcome::Written(plugin_path))
+}
+
+/// Whether two files hold the same bytes, which they don't if either can't be read
+fn same_contents(left: &Path, right: &Path) -> bool {
+    match (std::fs::read(left), std::fs::read(right)) {
+        (Ok(left), Ok(right)) => left == right,
+        _ => false,
+    }
+}
+
+/// Path of a backup of the plugin, where 0 is the newest
+fn backup_path(plugin_path: &Path, index: u8) -> PathBuf {
+    let mut backup_name = plugin_path.file_name().unwrap_or_default().to_os_string();
+
+    match index {
+        0 => backup_name.push(".bak"),
+        index => backup_name.push(format!(".bak.{index}")),
+    }
 
+    plugin_path.with_file_name(backup_name)
+}
+
+/// Shifts every backup of the plugin one place older, dropping the oldest, and copies the plugin into the newest.
+/// The plugin is copied rather than moved, so it's left alone if replacing it fails
+fn rotate_backups(plugin_path: &Path, keep_backups: u8) -> io::Result<()> {
+    let oldest_path = backup_path(plugin_path, keep_backups - 1);
+    if oldest_path.is_file() {
+        remove_file(oldest_path)?;
+    }
+
+    for index in (0..keep_backups - 1).rev() {
+        let backup = backup_path(plugin_path, index);
+        if backup.is_file() {
+            rename(backup, backup_path(plugin_path, index + 1))?;
+        }
+    }
+
+    std::fs::copy(plugin_path, backup_path(plugin_path, 0))?;
     Ok(())
 }
 
-pub fn to_io_error<E: std::fmt::Display>(err: E) -> std::io::Error {
-    std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string())
+/// Serializes the plugin in memory and writes it out whole, such as to stdout
+pub fn save_plugin_to_writer(writer: &mut impl Write, plugin: &mut Plugin) -> io::Result<()> {
+    writer.write_all(&plugin.save_bytes()?)?;
+    writer.flush()
+}
+
+/// Writes a plugin and flushes it to disk, so that it's complete before anything is renamed over it
+fn save_synced(plugin: &mut Plugin, path: &Path) -> io::Result<()> {
+    plugin.save_path(path)?;
+    OpenOptions::new().write(true).open(path)?.sync_all()
+}
+
+/// Longest author the plugin header can hold, leaving room for its null terminator
+pub const MAX_AUTHOR_LEN: usize = 31;
+/// Longest description the plugin header can hold, leaving room for its null terminator
+pub const MAX_DESCRIPTION_LEN: usize = 255;
+
+/// Today's date in UTC, as `YYYY-MM-DD`
+fn current_date() -> String {
+    let days = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() / 86_400)
+        .unwrap_or_default() as i64;
+
+    // Converts days since the epoch into a proleptic Gregorian date, in eras of 400 years
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Fills `{version}` and `{date}` into text written into the plugin header.
+/// Text longer than `max_len` bytes is cut at the last character which fits, with a warning.
+/// `{date}` is the one input which isn't part of the load order or the light config,
+/// so plugins using it are only byte-identical between runs on the same day
+pub fn header_text(field: &str, template: &str, max_len: usize) -> String {
+    let mut text = template
+        .replace("{version}", env!("CARGO_PKG_VERSION"))
+        .replace("{date}", &current_date());
+
+    if text.len() > max_len {
+        let mut end = max_len;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        eprintln!(
+            "[ WARNING ]: The plugin {field} `{text}` is longer than {max_len} bytes, and was cut to `{}`",
+            &text[..end]
+        );
+
+        text.truncate(end);
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tes3::esp::{Header, types::FileType};
+
+    use crate::test_fixtures::{light, neutral_config, plugin, temp_dir};
+
+    /// Generates a plugin from a single light, writes it with `output_file_type`, and reads its header back
+    fn saved_file_type(test_name: &str, output_file_type: OutputFileType) -> (PathBuf, FileType) {
+        let light_config = LightConfig {
+            output_file_type,
+            ..neutral_config()
+        };
+
+        let plugins = vec![plugin("Fires.esp", [light("fire_a", [255, 160, 64], 128)])];
+        let mut generated_plugin = generate_lightfixes(plugins, &light_config).unwrap();
+
+        let plugin_name = output_file_type.plugin_name(light_config.output_format);
+        let outcome = save_plugin(
+            &temp_dir(test_name),
+            plugin_name,
+            &mut generated_plugin,
+            &light_config,
+        )
+        .unwrap();
+
+        let saved_plugin = Plugin::from_path(outcome.path()).unwrap();
+        let header = saved_plugin.objects_of_type::<Header>().next().unwrap();
+
+        (outcome.path().to_owned(), header.file_type)
+    }
+
+    #[test]
+    fn saved_plugin_keeps_its_file_type() {
+        let (esp_path, esp_type) = saved_file_type("file-type-esp", OutputFileType::Esp);
+        assert!(esp_path.ends_with(PLUGIN_NAME));
+        assert_eq!(esp_type, FileType::Esp);
+
+        let (esm_path, esm_type) = saved_file_type("file-type-esm", OutputFileType::Esm);
+        assert!(esm_path.ends_with(ESM_PLUGIN_NAME));
+        assert_eq!(esm_type, FileType::Esm);
+    }
+
+    #[test]
+    fn generated_plugins_are_not_fixable() {
+        let (esp_path, _) = saved_file_type("fixable-esp", OutputFileType::Esp);
+        let (esm_path, _) = saved_file_type("fixable-esm", OutputFileType::Esm);
+
+        assert!(!is_fixable_plugin(&esp_path));
+        assert!(!is_fixable_plugin(&esm_path));
+
+        let other_master = esm_path.with_file_name("Morrowind.esm");
+        std::fs::copy(&esm_path, &other_master).unwrap();
+        assert!(is_fixable_plugin(&other_master));
+    }
+
+    #[test]
+    fn header_text_fills_tokens() {
+        assert_eq!(
+            header_text("author", "S3 v{version}", MAX_AUTHOR_LEN),
+            format!("S3 v{}", env!("CARGO_PKG_VERSION"))
+        );
+
+        let date = header_text("description", "{date}", MAX_DESCRIPTION_LEN);
+        let date_parts: Vec<&str> = date.split('-').collect();
+        assert_eq!(date_parts.len(), 3);
+        assert_eq!(
+            date_parts.iter().map(|part| part.len()).collect::<Vec<_>>(),
+            [4, 2, 2]
+        );
+    }
+
+    #[test]
+    fn header_text_keeps_text_which_fits() {
+        let author = "a".repeat(MAX_AUTHOR_LEN);
+        assert_eq!(header_text("author", &author, MAX_AUTHOR_LEN), author);
+    }
+
+    #[test]
+    fn header_text_truncates_long_text() {
+        let author = "a".repeat(MAX_AUTHOR_LEN + 10);
+        assert_eq!(
+            header_text("author", &author, MAX_AUTHOR_LEN),
+            "a".repeat(MAX_AUTHOR_LEN)
+        );
+
+        let description = "b".repeat(MAX_DESCRIPTION_LEN * 2);
+        assert_eq!(
+            header_text("description", &description, MAX_DESCRIPTION_LEN).len(),
+            MAX_DESCRIPTION_LEN
+        );
+    }
+
+    #[test]
+    fn header_text_truncates_at_char_boundaries() {
+        // Each of these takes two bytes, so the last one would straddle the limit
+        let author = "é".repeat(MAX_AUTHOR_LEN);
+        let truncated = header_text("author", &author, MAX_AUTHOR_LEN);
+
+        assert_eq!(truncated, "é".repeat(MAX_AUTHOR_LEN / 2));
+        assert!(truncated.len() <= MAX_AUTHOR_LEN);
+    }
+
+    #[test]
+    fn header_text_truncates_after_filling_tokens() {
+        let template = format!("{}{{version}}", "c".repeat(MAX_AUTHOR_LEN - 2));
+        let author = header_text("author", &template, MAX_AUTHOR_LEN);
+
+        assert_eq!(author.len(), MAX_AUTHOR_LEN);
+        assert!(author.starts_with(&"c".repeat(MAX_AUTHOR_LEN - 2)));
+        assert!(!author.contains('{'));
+    }
+
+    /// A generated plugin holding a single torch of the given radius
+    fn generated_torch(light_config: &LightConfig, radius: u32) -> Plugin {
+        let plugins = vec![plugin(
+            "Fires.esp",
+            [light("torch", [255, 160, 64], radius)],
+        )];
+        generate_lightfixes(plugins, light_config).unwrap()
+    }
+
+    #[test]
+    fn failed_save_keeps_previous_plugin() {
+        let light_config = neutral_config();
+        let output_dir = temp_dir("failed-save");
+
+        let outcome = save_plugin(
+            &output_dir,
+            PLUGIN_NAME,
+            &mut generated_torch(&light_config, 128),
+            &light_config,
+        )
+        .unwrap();
+        let previous_bytes = std::fs::read(outcome.path()).unwrap();
+
+        // Nothing can be written over a directory, so the temporary plugin can't be saved
+        let tmp_path = output_dir.join(format!("{PLUGIN_NAME}.tmp"));
+        create_dir_all(tmp_path.join("blocker")).unwrap();
+
+        let result = save_plugin(
+            &output_dir,
+            PLUGIN_NAME,
+            &mut generated_torch(&light_config, 256),
+            &light_config,
+        );
+
+        assert!(matches!(result, Err(LightFixesError::PluginSave { .. })));
+        assert_eq!(std::fs::read(outcome.path()).unwrap(), previous_bytes);
+    }
+
+    #[test]
+    fn successful_save_leaves_no_temporary_file() {
+        let light_config = neutral_config();
+        let output_dir = temp_dir("successful-save");
+
+        for radius in [128, 256] {
+            save_plugin(
+                &output_dir,
+                PLUGIN_NAME,
+                &mut generated_torch(&light_config, radius),
+                &light_config,
+            )
+            .unwrap();
+        }
+
+        assert!(output_dir.join(PLUGIN_NAME).is_file());
+        assert!(!output_dir.join(format!("{PLUGIN_NAME}.tmp")).exists());
+    }
+
+    /// Saves a torch of each radius in turn, returning the bytes written each time
+    fn save_torches(
+        light_config: &LightConfig,
+        output_dir: &PathBuf,
+        radii: &[u32],
+    ) -> Vec<Vec<u8>> {
+        radii
+            .iter()
+            .map(|&radius| {
+                let outcome = save_plugin(
+                    output_dir,
+                    PLUGIN_NAME,
+                    &mut generated_torch(light_config, radius),
+                    light_config,
+                )
+                .unwrap();
+
+                std::fs::read(outcome.path()).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn backups_rotate_previous_plugins() {
+        let light_config = LightConfig {
+            keep_backups: 2,
+            ..neutral_config()
+        };
+        let output_dir = temp_dir("rotate-backups");
+        let plugin_path = output_dir.join(PLUGIN_NAME);
+
+        let saved = save_torches(&light_config, &output_dir, &[64, 128, 256]);
+
+        assert_eq!(
+            std::fs::read(backup_path(&plugin_path, 0)).unwrap(),
+            saved[1]
+        );
+        assert_eq!(
+            std::fs::read(backup_path(&plugin_path, 1)).unwrap(),
+            saved[0]
+        );
+        assert!(!backup_path(&plugin_path, 2).exists());
+    }
+
+    #[test]
+    fn identical_plugins_are_not_backed_up() {
+        let light_config = neutral_config();
+        let output_dir = temp_dir("identical-backup");
+        let plugin_path = output_dir.join(PLUGIN_NAME);
+
+        save_torches(&light_config, &output_dir, &[128, 128]);
+        assert!(!backup_path(&plugin_path, 0).exists());
+
+        let saved = save_torches(&light_config, &output_dir, &[256]);
+        assert_ne!(
+            std::fs::read(backup_path(&plugin_path, 0)).unwrap(),
+            saved[0]
+        );
+    }
+
+    #[test]
+    fn disabled_backups_are_not_written() {
+        let light_config = LightConfig {
+            keep_backups: 0,
+            ..neutral_config()
+        };
+        let output_dir = temp_dir("no-backups");
+
+        save_torches(&light_config, &output_dir, &[64, 128]);
+
+        assert!(!backup_path(&output_dir.join(PLUGIN_NAME), 0).exists());
+    }
+
+    #[test]
+    fn backups_are_not_fixable() {
+        let light_config = neutral_config();
+        let output_dir = temp_dir("fixable-backups");
+        let plugin_path = output_dir.join(PLUGIN_NAME);
+
+        save_torches(&light_config, &output_dir, &[64, 128]);
+
+        let backup = backup_path(&plugin_path, 0);
+        assert!(backup.is_file());
+        assert!(!is_fixable_plugin(&backup));
+
+        let rotated_backup = backup_path(&plugin_path, 1);
+        std::fs::copy(&backup, &rotated_backup).unwrap();
+        assert!(!is_fixable_plugin(&rotated_backup));
+    }
+
+    #[test]
+    fn generated_header_counts_its_records() {
+        let light_config = neutral_config();
+        let plugins = vec![
+            plugin(
+                "Fires.esm",
+                [
+                    light("torch", [255, 160, 64], 128),
+                    light("candle", [255, 200, 120], 64),
+                ],
+            ),
+            plugin("Lanterns.esp", [light("lantern", [200, 220, 255], 256)]),
+        ];
+        let mut generated_plugin = generate_lightfixes(plugins, &light_config).unwrap();
+
+        let outcome = save_plugin(
+            &temp_dir("header-count"),
+            PLUGIN_NAME,
+            &mut generated_plugin,
+            &light_config,
+        )
+        .unwrap();
+
+        let mut saved_plugin = Plugin::from_path(outcome.path()).unwrap();
+        let header = saved_plugin.objects_of_type::<Header>().next().unwrap();
+
+        assert_eq!(header.num_objects, 3);
+        assert_eq!(saved_plugin.objects.len(), 4);
+        assert!(finalize_header(&mut saved_plugin));
+    }
+
+    #[test]
+    fn finalize_header_fixes_wrong_counts() {
+        let mut plugin = Plugin::new();
+        plugin.objects.push(
+            Header {
+                num_objects: 7,
+                ..Default::default()
+            }
+            .into(),
+        );
+        plugin
+            .objects
+            .push(light("torch", [255, 160, 64], 128).into());
+
+        assert!(!finalize_header(&mut plugin));
+        assert_eq!(
+            plugin
+                .objects_of_type::<Header>()
+                .next()
+                .unwrap()
+                .num_objects,
+            1
+        );
+        assert!(finalize_header(&mut plugin));
+    }
+
+    #[test]
+    fn finalize_header_needs_a_header() {
+        let mut plugin = Plugin::new();
+        plugin
+            .objects
+            .push(light("torch", [255, 160, 64], 128).into());
+
+        assert!(!finalize_header(&mut plugin));
+    }
+
+    #[test]
+    fn written_bytes_parse_back() {
+        let light_config = neutral_config();
+        let mut generated_plugin = generated_torch(&light_config, 128);
+
+        let mut bytes = Vec::new();
+        save_plugin_to_writer(&mut bytes, &mut generated_plugin).unwrap();
+
+        let mut parsed_plugin = Plugin::new();
+        parsed_plugin.load_bytes(&bytes).unwrap();
+
+        let header = parsed_plugin.objects_of_type::<Header>().next().unwrap();
+        assert_eq!(header.num_objects, 1);
+
+        let torch = parsed_plugin
+            .objects_of_type::<tes3::esp::Light>()
+            .next()
+            .unwrap();
+        assert_eq!(torch.id, "torch");
+    }
+
+    #[test]
+    fn written_bytes_match_the_saved_plugin() {
+        let light_config = neutral_config();
+
+        let mut bytes = Vec::new();
+        save_plugin_to_writer(&mut bytes, &mut generated_torch(&light_config, 128)).unwrap();
+
+        let outcome = save_plugin(
+            &temp_dir("writer-bytes"),
+            PLUGIN_NAME,
+            &mut generated_torch(&light_config, 128),
+            &light_config,
+        )
+        .unwrap();
+
+        assert_eq!(std::fs::read(outcome.path()).unwrap(), bytes);
+    }
+
+    /// Held by tests which change or depend on the environment, which every test shares
+    static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    /// Arguments pointing at openmw.cfg with `--openmw-cfg`, if given a path
+    fn config_args(openmw_cfg: Option<&Path>) -> LightArgs {
+        let openmw_cfg = openmw_cfg.map(|path| path.display().to_string());
+
+        LightArgs::parse_from(
+            std::iter::once("s3lightfixes").chain(
+                openmw_cfg
+                    .iter()
+                    .flat_map(|path| ["--openmw-cfg", path.as_str()]),
+            ),
+        )
+    }
+
+    /// A directory holding an empty openmw.cfg
+    fn config_dir(test_name: &str) -> PathBuf {
+        let dir = temp_dir(test_name);
+        std::fs::write(dir.join("openmw.cfg"), "").unwrap();
+        dir
+    }
+
+    #[test]
+    fn config_path_resolution_order() {
+        let _env_lock = ENV_LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        let cli_dir = config_dir("config-path-cli");
+        let env_dir = config_dir("config-path-env");
+        let empty_dir = temp_dir("config-path-empty");
+
+        // SAFETY: Every test depending on OPENMW_CONFIG holds ENV_LOCK, and the rest always pass --openmw-cfg
+        unsafe { std::env::set_var(OPENMW_CONFIG_VAR, &env_dir) };
+
+        let from_env = get_config_path(&mut config_args(None));
+        let from_cli = get_config_path(&mut config_args(Some(&cli_dir)));
+
+        // SAFETY: As above
+        unsafe { std::env::set_var(OPENMW_CONFIG_VAR, &empty_dir) };
+
+        let from_broken_env = get_config_path(&mut config_args(None));
+        let from_cli_over_broken_env = get_config_path(&mut config_args(Some(&cli_dir)));
+
+        // SAFETY: As above
+        unsafe { std::env::remove_var(OPENMW_CONFIG_VAR) };
+
+        assert_eq!(from_env.unwrap(), env_dir);
+        assert_eq!(from_cli.unwrap(), cli_dir);
+        assert!(matches!(
+            from_broken_env,
+            Err(LightFixesError::ConfigNotFound(ConfigPathError::NoConfigInDirectory { source, .. }))
+                if source == OPENMW_CONFIG_VAR
+        ));
+        assert_eq!(from_cli_over_broken_env.unwrap(), cli_dir);
+    }
+
+    #[test]
+    fn config_path_accepts_the_file_itself() {
+        let dir = config_dir("config-path-file");
+        let cfg_path = dir.join("openmw.cfg");
+
+        assert_eq!(
+            get_config_path(&mut config_args(Some(&cfg_path))).unwrap(),
+            cfg_path
+        );
+    }
+
+    #[test]
+    fn config_dir_without_a_config_is_an_error() {
+        let dir = temp_dir("config-path-no-cfg");
+
+        let Err(LightFixesError::ConfigNotFound(err)) =
+            get_config_path(&mut config_args(Some(&dir)))
+        else {
+            panic!("A directory without openmw.cfg was accepted");
+        };
+
+        assert!(matches!(
+            &err,
+            ConfigPathError::NoConfigInDirectory { path, source: "--openmw-cfg" } if *path == dir
+        ));
+        assert!(err.to_string().contains("contains no openmw.cfg"));
+    }
+
+    #[test]
+    fn missing_config_path_is_an_error() {
+        let missing_path = temp_dir("config-path-missing").join("typo");
+
+        let Err(LightFixesError::ConfigNotFound(err)) =
+            get_config_path(&mut config_args(Some(&missing_path)))
+        else {
+            panic!("A path which doesn't exist was accepted");
+        };
+
+        assert!(matches!(
+            &err,
+            ConfigPathError::DoesNotExist { path, .. } if *path == missing_path
+        ));
+        assert!(err.to_string().contains("does not exist"));
+    }
+
+    #[test]
+    fn missing_relative_config_path_is_an_error() {
+        let relative_path = Path::new("no-such-directory-for-lightfixes");
+
+        assert!(matches!(
+            get_config_path(&mut config_args(Some(relative_path))),
+            Err(LightFixesError::ConfigNotFound(ConfigPathError::DoesNotExist { path, .. }))
+                if path == relative_path
+        ));
+    }
+
+    #[test]
+    fn no_config_anywhere_is_an_error() {
+        let _env_lock = ENV_LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        // Only meaningful where nothing points at an openmw.cfg already
+        let default_path = openmw_config::default_config_path();
+        if std::env::var_os(OPENMW_CONFIG_VAR).is_some()
+            || Path::new("openmw.cfg").is_file()
+            || default_path.is_file()
+            || default_path.join("openmw.cfg").is_file()
+            || remembered_config_path(&current_dir().unwrap()).is_some()
+        {
+            return;
+        }
+
+        let result = get_config_path(&mut config_args(None));
+
+        assert!(matches!(
+            result,
+            Err(LightFixesError::ConfigNotFound(
+                ConfigPathError::NoConfigFound { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn ci_environments_are_non_interactive() {
+        let _env_lock = ENV_LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        let previous_ci = std::env::var_os("CI");
+
+        // SAFETY: Every test depending on CI holds ENV_LOCK, and it's put back before the lock is released
+        unsafe { std::env::set_var("CI", "true") };
+        let reason = non_interactive_reason();
+
+        // SAFETY: As above
+        unsafe {
+            match previous_ci {
+                Some(previous_ci) => std::env::set_var("CI", previous_ci),
+                None => std::env::remove_var("CI"),
+            }
+        }
+
+        assert_eq!(reason, Some("a CI environment was detected"));
+    }
+
+    #[test]
+    fn dialog_flags_are_parsed() {
+        let args = LightArgs::parse_from(["s3lightfixes", "--force-dialogs"]);
+        assert!(args.force_dialogs && !args.no_notifications);
+
+        let args = LightArgs::parse_from(["s3lightfixes", "-n"]);
+        assert!(args.no_notifications && !args.force_dialogs);
+    }
+
+    #[test]
+    fn vanilla_format_names_addons_as_esps() {
+        assert_eq!(
+            OutputFileType::Esp.plugin_name(OutputFormat::Openmw),
+            PLUGIN_NAME
+        );
+        assert_eq!(
+            OutputFileType::Esp.plugin_name(OutputFormat::Vanilla),
+            VANILLA_PLUGIN_NAME
+        );
+        assert_eq!(
+            OutputFileType::Esm.plugin_name(OutputFormat::Vanilla),
+            ESM_PLUGIN_NAME
+        );
+    }
+
+    #[test]
+    fn numbered_parts_are_generated_plugins() {
+        assert_eq!(
+            suffixed_plugin_name(PLUGIN_NAME, 2),
+            "S3LightFixes-2.omwaddon"
+        );
+
+        assert!(is_generated_plugin_name("S3LightFixes-2.omwaddon"));
+        assert!(is_generated_plugin_name("s3lightfixes-12.esm"));
+        assert!(!is_generated_plugin_name("S3LightFixes-.omwaddon"));
+        assert!(!is_generated_plugin_name("S3LightFixes-Extra.omwaddon"));
+        assert!(!is_generated_plugin_name("S3LightFixes-2.esp.bak"));
+    }
+
+    #[test]
+    fn never_policy_refuses_to_replace_a_plugin() {
+        let light_config = LightConfig {
+            overwrite_policy: OverwritePolicy::Never,
+            ..neutral_config()
+        };
+        let output_dir = temp_dir("overwrite-never");
+
+        let [previous_bytes] = save_torches(&light_config, &output_dir, &[128])
+            .try_into()
+            .unwrap();
+
+        let result = save_plugin(
+            &output_dir,
+            PLUGIN_NAME,
+            &mut generated_torch(&light_config, 256),
+            &light_config,
+        );
+        assert!(matches!(result, Err(LightFixesError::PluginExists(_))));
+        assert_eq!(
+            std::fs::read(output_dir.join(PLUGIN_NAME)).unwrap(),
+            previous_bytes
+        );
+    }
+
+    #[test]
+    fn if_changed_policy_skips_identical_plugins() {
+        let save = |light_config: &LightConfig, output_dir: &PathBuf, radius| {
+            save_plugin(
+                output_dir,
+                PLUGIN_NAME,
+                &mut generated_torch(light_config, radius),
+                light_config,
+            )
+            .unwrap()
+        };
+
+        let if_changed = LightConfig {
+            overwrite_policy: OverwritePolicy::IfChanged,
+            ..neutral_config()
+        };
+        let output_dir = temp_dir("overwrite-if-changed");
+
+        assert!(matches!(
+            save(&if_changed, &output_dir, 128),
+            SaveOutcome::Written(_)
+        ));
+        assert!(matches!(
+            save(&if_changed, &output_dir, 128),
+            SaveOutcome::UpToDate(_)
+        ));
+        assert!(matches!(
+            save(&if_changed, &output_dir, 256),
+            SaveOutcome::Written(_)
+        ));
+
+        // Identical plugins are still written over by default
+        assert!(matches!(
+            save(&neutral_config(), &output_dir, 256),
+            SaveOutcome::Written(_)
+        ));
+    }
+
+    #[test]
+    fn generated_plugins_are_recognized_by_their_header() {
+        let light_config = neutral_config();
+        let output_dir = temp_dir("generated-header");
+
+        // Renamed by hand, so only the header gives it away
+        let outcome = save_plugin(
+            &output_dir,
+            "Old Fixes.esp",
+            &mut generated_torch(&light_config, 128),
+            &light_config,
+        )
+        .unwrap();
+        assert!(is_fixable_plugin(outcome.path()));
+        assert!(is_generated_plugin(outcome.path()));
+
+        let (plain_plugin, plain_meta) = plugin("Fires.esp", [light("torch", [255, 160, 64], 128)]);
+        crate::test_fixtures::write_plugin(&output_dir, plain_plugin, &plain_meta);
+        assert!(!is_generated_plugin(&output_dir.join("Fires.esp")));
+
+        // Plugins from before the fingerprint are known by the default author and description
+        let mut legacy_plugin = Plugin::new();
+        legacy_plugin.objects.push(
+            Header {
+                author: tes3::esp::FixedString(default::plugin_author()),
+                description: tes3::esp::FixedString(
+                    "Plugin generated by s3-lightfixes v0.4.0".to_string(),
+                ),
+                ..Default::default()
+            }
+            .into(),
+        );
+        let legacy_path = output_dir.join("Legacy.esp");
+        legacy_plugin.save_path(&legacy_path).unwrap();
+        assert!(is_generated_plugin(&legacy_path));
+
+        assert!(!is_generated_plugin(&output_dir.join("Missing.esp")));
+    }
+
+    #[test]
+    fn generated_names_are_matched_exactly() {
+        let output_dir = temp_dir("generated-names");
+
+        for file_name in ["S3LightFixes.omwaddon.esp", "Not_S3LightFixes.omwaddon"] {
+            let plugin_path = output_dir.join(file_name);
+            std::fs::write(&plugin_path, b"").unwrap();
+            assert!(is_fixable_plugin(&plugin_path), "{file_name}");
+        }
+    }
+
+    #[test]
+    fn remembered_config_paths_keep_the_rest_of_lightconfig() {
+        let dir = temp_dir("remember-config");
+        std::fs::write(dir.join(DEFAULT_CONFIG_NAME), "standard_value = 0.5\n").unwrap();
+
+        remember_config_path(&dir, Path::new("games/openmw")).unwrap();
+
+        let table: toml::Table =
+            toml::from_str(&std::fs::read_to_string(dir.join(DEFAULT_CONFIG_NAME)).unwrap())
+                .unwrap();
+        assert_eq!(table["openmw_cfg"].as_str(), Some("games/openmw"));
+        assert_eq!(table["standard_value"].as_float(), Some(0.5));
+
+        std::fs::write(dir.join(DEFAULT_CONFIG_NAME), "standard_value = ").unwrap();
+        assert!(matches!(
+            remember_config_path(&dir, Path::new("games/openmw")),
+            Err(LightFixesError::ConfigParse { .. })
+        ));
+    }
+
+    #[test]
+    fn porcelain_records_are_single_lines() {
+        assert_eq!(
+            porcelain_line("lights_modified", 1234),
+            "lights_modified\t1234"
+        );
+        assert_eq!(
+            porcelain_line("warning", "Fires.esp\tfailed:\r\nbad header"),
+            "warning\tFires.esp failed:  bad header"
+        );
+        assert_eq!(
+            porcelain_line("porcelain_version", PORCELAIN_VERSION),
+            format!("porcelain_version\t{PORCELAIN_VERSION}")
+        );
+    }
+
+    #[test]
+    fn porcelain_is_off_by_default() {
+        assert!(!is_porcelain());
+        assert!(!LightArgs::parse_from(["s3lightfixes"]).porcelain);
+        assert!(LightArgs::parse_from(["s3lightfixes", "--porcelain"]).porcelain);
+    }
 }
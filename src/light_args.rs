@@ -11,6 +11,7 @@ use crate::default;
 )]
 pub struct LightArgs {
     /// Path to openmw.cfg
+    /// Otherwise, the directory in the OPENMW_CONFIG environment variable is used, then the current working directory.
     /// By default, uses the system paths defined by:
     /// https://openmw.readthedocs.io/en/latest/reference/modding/paths.html
     /// Can be the literal path to an openmw.cfg file (including not literally being called openmw.cfg)
@@ -18,6 +19,13 @@ pub struct LightArgs {
     #[arg(short = 'c', long = "openmw-cfg")]
     pub openmw_cfg: Option<PathBuf>,
 
+    /// Path to the Morrowind.ini of an installation without openmw.cfg, such as one using MGE XE.
+    /// Its `[Game Files]` are processed in order, from the Data Files directory next to it,
+    /// and the plugin is saved into Data Files unless --output says otherwise.
+    /// The plugin isn't enabled, as Morrowind.ini is left alone. Used as well when --openmw-cfg ends in .ini
+    #[arg(long = "morrowind-ini", conflicts_with = "openmw_cfg")]
+    pub morrowind_ini: Option<PathBuf>,
+
     /// Enables classic mode using vtastek shaders.
     /// ONLY for openmw 0.47. Relevant shaders can be found in the OpenMW discord:
     /// https://discord.com/channels/260439894298460160/718892786157617163/966468825321177148
@@ -27,27 +35,149 @@ pub struct LightArgs {
     /// Output directory.
     /// The plugin may be saved to any location, but its name will always be `S3Lightfixes.omwaddon`.
     /// Accepts relative and absolute terms.
-    #[arg(short = 'o', long = "output")]
+    /// `-` writes the plugin to stdout instead, without enabling it or cleaning up old copies, and prints notifications to stderr.
+    #[arg(short = 'o', long = "output", conflicts_with = "output_as_data_dir")]
     pub output: Option<PathBuf>,
 
+    /// Save the plugin into its own `s3lightfixes-output` directory next to the user openmw.cfg,
+    /// which is added to openmw.cfg as the last data directory, along with the plugin itself.
+    #[arg(long = "output-as-data-dir")]
+    pub output_as_data_dir: bool,
+
+    /// Undo --output-as-data-dir, removing its data directory and the generated plugins from openmw.cfg,
+    /// and deleting the directory if nothing else is left in it.
+    #[arg(long = "revert", conflicts_with = "output_as_data_dir")]
+    pub revert: bool,
+
+    /// Enable the plugin by writing `content=` lines, and the `data=` line of --output-as-data-dir, into this cfg file,
+    /// instead of into openmw.cfg, for those who manage openmw.cfg with another tool.
+    /// May be a directory, in which case its openmw.cfg is used, as `config=` only reads files of that name.
+    /// Lines already in it aren't added twice, and --revert takes them back out of it.
+    #[arg(long = "enable-via-fragment")]
+    pub enable_via_fragment: Option<PathBuf>,
+
+    /// Generate the plugin even if nothing changed since the last run.
+    /// Otherwise, lightfixes exits early when the content files, their sizes and modification times, the light config,
+    /// and its own version all match what lightconfig.cache.json recorded, and the plugin is still there.
+    #[arg(long = "force")]
+    pub force: bool,
+
     /// Whether to save a text form of the generated plugin.
     /// Extremely verbose!
     /// You probably don't want to enable this unless asked specifically to do so.
     #[arg(short = 'l', long = "write-log")]
     pub write_log: bool,
 
+    /// Don't copy the previous plugin to S3LightFixes.omwaddon.bak before replacing it.
+    /// Otherwise, as many copies as `keep_backups` in lightConfig.toml are kept, rotating through `.bak.1`, `.bak.2`, and so on.
+    #[arg(long = "no-backup")]
+    pub no_backup: bool,
+
+    /// Write S3LightFixes.omwaddon.meta.json next to the plugin, holding the version of lightfixes, a hash of the light config,
+    /// the openmw.cfg used, the masters of the plugin, and how many lights and cells it changes.
+    /// Modlist tools can use it to tell when the plugin is out of date.
+    #[arg(long = "write-metadata")]
+    pub write_metadata: bool,
+
+    /// Read the plugin back after writing it, checking that it parses, that its masters exist with the sizes it expects,
+    /// and that its header counts its records correctly. Exits with an error otherwise.
+    #[arg(long = "verify")]
+    pub verify: bool,
+
+    /// Also write the generated plugin as JSON, in the form tes3conv uses, to this path.
+    /// When the plugin is split, or the path is a directory, each part is written next to it as `<plugin name>.json`.
+    #[arg(long = "emit-json")]
+    pub emit_json: Option<PathBuf>,
+
+    /// Before replacing the previous plugin, a summary of what changed since it is printed and logged.
+    /// This lists each light, cell and magic effect which changed, was added or was removed as well.
+    #[arg(long = "diff-details")]
+    pub diff_details: bool,
+
+    /// Content files of openmw.cfg which can't be found in any data directory are warned about, and left out.
+    /// This makes them an error instead, so nothing is generated from an incomplete load order.
+    #[arg(long = "strict")]
+    pub strict: bool,
+
+    /// Print the path each content file is loaded from, along with any copies of it in earlier data directories
+    /// which that one shadows. This is always done with --debug, and goes into the log when it's written.
+    #[arg(long = "vfs-report")]
+    pub vfs_report: bool,
+
+    /// Print how each content file of openmw.cfg resolves: whether it was found, its path and size,
+    /// whether it's a plugin lightfixes can load, and which excluded_plugins pattern matches it, if any.
+    /// Nothing is generated.
+    #[arg(long = "vfs-debug")]
+    pub vfs_debug: bool,
+
+    /// Extra data directory to find plugins in, after those of openmw.cfg, so its files win over theirs.
+    /// May be used more than once. openmw.cfg isn't changed.
+    #[arg(long = "data-dir")]
+    pub data_dirs: Vec<PathBuf>,
+
+    /// Extra plugin to process after the content files of openmw.cfg, found in the data directories like them.
+    /// May be used more than once. openmw.cfg isn't changed, so the plugin isn't enabled either.
+    #[arg(long = "content")]
+    pub content: Vec<String>,
+
+    /// Plugins registered with groundcover= in openmw.cfg are skipped, as they're usually full of broken references.
+    /// This processes them like any other plugin instead.
+    #[arg(long = "process-groundcover")]
+    pub process_groundcover: bool,
+
+    /// Generated plugins found in data directories other than the output one are reported, as they'd be loaded twice.
+    /// This deletes them as well, such as copies left behind by an earlier run with a different --output.
+    /// Without it, interactive runs ask whether to delete them.
+    #[arg(long = "clean-stale")]
+    pub clean_stale: bool,
+
+    /// What happens when the generated plugin already exists.
+    /// `always` replaces it, `never` stops with an error,
+    /// and `if-changed` leaves it and openmw.cfg alone when the new plugin would be identical.
+    #[arg(long = "overwrite-policy")]
+    pub overwrite_policy: Option<crate::OverwritePolicy>,
+
     /// Whether to automatically enable the output plugin in openmw.cfg.
-    /// Disabled by default.
+    /// Disabled by default, in which case a dialog asks whether to enable it, unless notifications are disabled.
     /// Typically lightfixes is ran under momw-configurator, making this param
     /// unnecessary for many users.
     #[arg(short = 'e', long = "auto-enable")]
     pub auto_enable: bool,
 
+    /// When enabling the plugin, leave it where it already is in openmw.cfg.
+    /// Otherwise, it's moved after any plugin enabled since, which would override the lights it fixes.
+    #[arg(long = "no-reorder")]
+    pub no_reorder: bool,
+
     /// If used, print to stdout instead of using native GUI dialogs.
     /// Not available on android.
     #[arg(short = 'n', long = "no-notifications")]
     pub no_notifications: bool,
 
+    /// Show native GUI dialogs even when lightfixes doesn't seem to be running interactively.
+    /// Otherwise, notifications are printed when there's no display, stdout isn't a terminal, or a CI environment is detected.
+    /// --no-notifications still takes precedence.
+    #[arg(long = "force-dialogs")]
+    pub force_dialogs: bool,
+
+    /// Print stable, line-oriented `key<TAB>value` records to stdout for tools which run lightfixes, instead of dialogs.
+    ///
+    /// Everything else is printed to stderr, apart from --debug output, which isn't covered by the format.
+    /// Records are only changed or removed along with `porcelain_version`, and new keys may appear at any time.
+    /// Tabs and line breaks in values are replaced by spaces. The keys are:
+    ///   porcelain_version  the version of this format, always the first record
+    ///   output             path of a plugin which was written, or was already up to date. Repeated for each part of a split plugin
+    ///   lights_modified    how many lights the plugin of the preceding `output` record holds
+    ///   cells_modified     how many cells the plugin of the preceding `output` record holds
+    ///   missing            a content file which couldn't be found in any data directory
+    ///   stale              a generated plugin found in a data directory other than the output one
+    ///   warning            a problem which didn't stop lightfixes, such as a plugin which failed to load
+    ///   up_to_date         `true` when nothing needed to be written, `false` otherwise
+    ///   status             `ok`, or `error` when lightfixes exits with a nonzero code. Only `reason` may follow it
+    ///   reason             why lightfixes failed, following `status error`
+    #[arg(long = "porcelain", verbatim_doc_comment)]
+    pub porcelain: bool,
+
     /// Output debugging information during lightfixes generation
     /// Primarily displays output related to the openmw.cfg being used for generation
     #[arg(short = 'd', long = "debug")]
@@ -62,10 +192,69 @@ pub struct LightArgs {
     #[arg(short = 'f', long = "no-flicker")]
     pub disable_flickering: Option<bool>,
 
+    /// What to do with flickering lights during lightfixes generation.
+    /// `keep` leaves them alone, `remove` stops them flickering,
+    /// `to_pulse` turns flicker into pulse of the same speed, and `to_pulse_slow` turns all flicker into slow pulse.
+    /// Takes precedence over --no-flicker.
+    #[arg(long = "flicker-mode")]
+    pub flicker_mode: Option<crate::FlickerMode>,
+
     /// Whether to disable pulsing lights during lightfixes generation
     #[arg(short = 'p', long = "no-pulse")]
     pub disable_pulse: Option<bool>,
 
+    /// Only fix light flags (flicker, pulse, and override flags) and negative lights,
+    /// leaving colors, radii, and durations exactly as mods shipped them.
+    /// Lights whose flags don't change are left out of the plugin.
+    #[arg(long = "flags-only")]
+    pub flags_only: bool,
+
+    /// Leave lights out of the plugin when processing didn't change them,
+    /// so that plugins which contribute nothing aren't made into masters.
+    #[arg(long = "skip-unchanged")]
+    pub skip_unchanged: bool,
+
+    /// Only process lights which are placed in at least one cell of the load order.
+    /// Lights which only appear through leveled lists, containers, or scripts can't be detected, and are skipped.
+    #[arg(long = "only-referenced")]
+    pub only_referenced: bool,
+
+    /// Clear the water height of every cell written into the plugin, as older versions always did.
+    /// By default, the water height of the cell being overridden is kept.
+    #[arg(long = "clear-water-height")]
+    pub clear_water_height: bool,
+
+    /// Process interiors which behave like exteriors, such as Mournhold's plazas.
+    /// These take their lighting from the sky, so interior sunlight and ambient overrides have little effect on them.
+    #[arg(long = "process-quasi-exteriors")]
+    pub process_quasi_exteriors: bool,
+
+    /// Merge the atmosphere of cells changed by several plugins field by field.
+    /// Each of ambient, sunlight, fog, and fog density comes from the last plugin to change it from the cell's first definition,
+    /// instead of every field coming from the last plugin to change the cell.
+    #[arg(long = "merge-cell-atmosphere")]
+    pub merge_cell_atmosphere: bool,
+
+    /// Scale the glow color of magic effects by the colored light multipliers, or --magic-saturation and --magic-value.
+    /// Without this, spell effects look blinding next to dimmed lights.
+    #[arg(long = "process-magic-effects")]
+    pub process_magic_effects: bool,
+
+    /// Scale the `Weather_*_Ambient_*` color fallbacks by --weather-hue, --weather-saturation, and --weather-value,
+    /// writing them into the user openmw.cfg. The original values are recorded in the log.
+    #[arg(long = "adjust-weather-fallbacks")]
+    pub adjust_weather_fallbacks: bool,
+
+    /// Whether to leave lights whose original radius is zero and whose color is black out of the plugin, unless a light override matches them.
+    /// Such lights cast no light, and are usually sound emitters or script markers.
+    #[arg(long = "skip-zero-radius")]
+    pub skip_zero_radius: Option<bool>,
+
+    /// Whether to clear the references of cells written into the plugin, leaving the engine to merge them from the original plugins.
+    /// When false, each cell's references are kept as the winning plugin placed them, making the plugin much larger.
+    #[arg(long = "clear-references")]
+    pub clear_references: Option<bool>,
+
     #[arg(
         long = "standard-hue",
         help = &format!("For lights in the orange range, multiply their HSV hue by this value.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.\nThis argument has no short form due to a conflict with -h.", default::standard_hue())
@@ -93,6 +282,13 @@ pub struct LightArgs {
     )]
     pub standard_radius: Option<f32>,
 
+    #[arg(
+        long = "standard-radius-add",
+        allow_hyphen_values = true,
+        help = &format!("For lights in the orange range, add this value to their radius after it has been multiplied. The result will not drop below zero.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::standard_radius_add())
+    )]
+    pub standard_radius_add: Option<i32>,
+
     #[arg(
         short = 'H',
         long = "colored-hue",
@@ -121,17 +317,286 @@ pub struct LightArgs {
     )]
     pub colored_radius: Option<f32>,
 
+    #[arg(
+        long = "colored-radius-add",
+        allow_hyphen_values = true,
+        help = &format!("For lights that are red, purple, blue, green, or yellow, add this value to their radius after it has been multiplied. The result will not drop below zero.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::colored_radius_add())
+    )]
+    pub colored_radius_add: Option<i32>,
+
+    #[arg(
+        long = "radius-exponent",
+        help = &format!("Raises the radius of every light to this power around --radius-curve-base before it is multiplied. Below 1.0, small lights grow more than large ones. Fixed radii from light overrides are unaffected.\nIf this argument is not used, the value will be derived from lightConfig.toml, or radii are multiplied directly.")
+    )]
+    pub radius_exponent: Option<f32>,
+
+    #[arg(
+        long = "radius-curve-base",
+        help = &format!("The radius which --radius-exponent pivots around, which the curve leaves unchanged.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::radius_curve_base())
+    )]
+    pub radius_curve_base: Option<f32>,
+
     #[arg(
         short = 'M',
         long = "duration-mult",
-        help = &format!("Multiplies the duration of all carryable lights.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::duration_mult())
+        help = &format!("Deprecated: multiplies the duration of all carryable lights, setting both --standard-duration-mult and --colored-duration-mult.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::duration_mult())
     )]
     pub duration_mult: Option<f32>,
 
+    #[arg(
+        long = "standard-duration-mult",
+        help = &format!("Multiplies the duration of carryable non-colored lights.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the value of --duration-mult.")
+    )]
+    pub standard_duration_mult: Option<f32>,
+
+    #[arg(
+        long = "colored-duration-mult",
+        help = &format!("Multiplies the duration of carryable colored lights.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the value of --duration-mult.")
+    )]
+    pub colored_duration_mult: Option<f32>,
+
+    #[arg(
+        long = "magic-saturation",
+        help = &format!("Multiplies the saturation of magic effect colors, when --process-magic-effects is used.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the value of --colored-saturation.")
+    )]
+    pub magic_saturation: Option<f32>,
+
+    #[arg(
+        long = "magic-value",
+        help = &format!("Multiplies the value of magic effect colors, when --process-magic-effects is used.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the value of --colored-value.")
+    )]
+    pub magic_value: Option<f32>,
+
+    #[arg(
+        long = "target-real-minutes",
+        help = &format!("Real-time minutes every finite carryable light should burn for, taking the game's timescale into account. Replaces the duration multipliers.\nIf this argument is not used, the value will be derived from lightConfig.toml, or the duration multipliers are used.")
+    )]
+    pub target_real_minutes: Option<f32>,
+
+    #[arg(
+        long = "timescale",
+        help = &format!("The game's timescale, used by --target-real-minutes. At Morrowind's own timescale, light durations are in real seconds.\nIf this argument is not used, the value will be derived from lightConfig.toml, then fallback=General_Timescale in openmw.cfg, or use the default value of {}.", default::timescale())
+    )]
+    pub timescale: Option<f32>,
+
+    #[arg(
+        long = "carryable-radius-mult",
+        help = &format!("Multiplies the radius of carryable lights, on top of the standard or colored radius multiplier.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::carryable_radius_mult())
+    )]
+    pub carryable_radius_mult: Option<f32>,
+
+    #[arg(
+        long = "carryable-value-mult",
+        help = &format!("Multiplies the value of carryable lights, on top of the standard or colored value multiplier.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::carryable_value_mult())
+    )]
+    pub carryable_value_mult: Option<f32>,
+
+    #[arg(
+        long = "carryable-duration-mult",
+        help = &format!("Multiplies the duration of carryable lights, on top of the standard or colored duration multiplier.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::carryable_duration_mult())
+    )]
+    pub carryable_duration_mult: Option<f32>,
+
+    #[arg(
+        long = "weight-mult",
+        help = &format!("Multiplies the weight of all carryable lights.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::weight_mult())
+    )]
+    pub weight_mult: Option<f32>,
+
+    #[arg(
+        long = "gold-value-mult",
+        help = &format!("Multiplies the gold value of all carryable lights.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::gold_value_mult())
+    )]
+    pub gold_value_mult: Option<f32>,
+
+    #[arg(
+        long = "standard-normalize-value",
+        help = &format!("For lights in the orange range, scale their HSV value so their perceived brightness lands near this target (0.0 - 1.0). Replaces the standard value multiplier when used.\nIf this argument is not used, the value will be derived from lightConfig.toml, or the standard value multiplier is used.")
+    )]
+    pub standard_normalize_value: Option<f32>,
+
+    #[arg(
+        long = "colored-normalize-value",
+        help = &format!("For lights that are red, purple, blue, green, or yellow, scale their HSV value so their perceived brightness lands near this target (0.0 - 1.0). Replaces the colored value multiplier when used.\nIf this argument is not used, the value will be derived from lightConfig.toml, or the colored value multiplier is used.")
+    )]
+    pub colored_normalize_value: Option<f32>,
+
+    #[arg(
+        long = "normalize-max-adjustment",
+        help = &format!("Largest factor by which brightness normalization may brighten or dim a light.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::normalize_max_adjustment())
+    )]
+    pub normalize_max_adjustment: Option<f32>,
+
+    #[arg(
+        long = "standard-tint",
+        help = &format!("For lights in the orange range, a hex color such as #ff9944 to blend them toward after all other color math.\nIf this argument is not used, the value will be derived from lightConfig.toml, or no tint is applied.")
+    )]
+    pub standard_tint: Option<crate::TintColor>,
+
+    #[arg(
+        long = "standard-tint-strength",
+        help = &format!("How far lights in the orange range are blended toward the standard tint (0.0 - 1.0).\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::tint_strength())
+    )]
+    pub standard_tint_strength: Option<f32>,
+
+    #[arg(
+        long = "colored-tint",
+        help = &format!("For lights that are red, purple, blue, green, or yellow, a hex color such as #ff9944 to blend them toward after all other color math.\nIf this argument is not used, the value will be derived from lightConfig.toml, or no tint is applied.")
+    )]
+    pub colored_tint: Option<crate::TintColor>,
+
+    #[arg(
+        long = "colored-tint-strength",
+        help = &format!("How far colored lights are blended toward the colored tint (0.0 - 1.0).\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::tint_strength())
+    )]
+    pub colored_tint_strength: Option<f32>,
+
+    /// Performs color math on linear light instead of encoded sRGB values.
+    /// Halving a light's value then halves its perceived brightness,
+    /// but colors will differ from older versions of lightfixes.
+    #[arg(long = "linear-color")]
+    pub linear_color: bool,
+
+    /// How lights with the NEGATIVE flag are handled.
+    /// `zero` turns them into black, zero-radius lights, as older versions of lightfixes always did.
+    /// `keep` leaves them alone apart from flicker and pulse removal.
+    /// `remove_flag` removes the flag and processes them like any other light.
+    #[arg(long = "negative-light-mode")]
+    pub negative_light_mode: Option<crate::NegativeLightMode>,
+
+    /// How hues multiplied or jittered past either end of the color wheel are handled.
+    /// `wrap` continues around the wheel and matches older versions of lightfixes,
+    /// while `clamp` stops at 360 degrees so warm lights never turn green.
+    #[arg(long = "hue-overflow")]
+    pub hue_overflow: Option<crate::HueOverflow>,
+
+    /// Whether the generated plugin is a regular addon, written as S3LightFixes.omwaddon,
+    /// or flagged as a master written as S3LightFixes.esm, so that load order tools sort it before addons.
+    #[arg(long = "output-file-type")]
+    pub output_file_type: Option<crate::OutputFileType>,
+
+    /// Which engine the generated plugin is written for.
+    /// `vanilla` writes S3LightFixes.esp for the original engine, leaves .omwaddon and .omwgame plugins out of the load order,
+    /// and never changes openmw.cfg, so --auto-enable and --adjust-weather-fallbacks do nothing.
+    #[arg(long = "format")]
+    pub output_format: Option<crate::OutputFormat>,
+
+    #[arg(
+        long = "max-masters",
+        help = &format!("Most masters the generated plugin may have before a warning is shown.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::max_masters())
+    )]
+    pub max_masters: Option<usize>,
+
+    /// When the generated plugin would have more masters than --max-masters,
+    /// split it into S3LightFixes-1.omwaddon, S3LightFixes-2.omwaddon, and so on, each with its own share of the masters and their records.
+    #[arg(long = "split-masters")]
+    pub split_masters: bool,
+
+    /// Write lights and magic effects into S3LightFixes-Lights.omwaddon, and cells into S3LightFixes-Cells.omwaddon,
+    /// each with only the masters its own records come from, so that either can be disabled without the other.
+    #[arg(long = "split-output")]
+    pub split_output: bool,
+
+    #[arg(
+        long = "plugin-author",
+        help = &format!("Author written into the header of the generated plugin, cut to {} bytes along with a hash of the light config. `{{version}}` and `{{date}}` are replaced by the version of lightfixes and today's date. `{{date}}` keeps the plugin from being byte-identical between days.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", crate::MAX_AUTHOR_LEN, default::plugin_author())
+    )]
+    pub plugin_author: Option<String>,
+
+    #[arg(
+        long = "plugin-description",
+        help = &format!("Description written into the header of the generated plugin, cut to {} bytes along with a hash of the light config. `{{version}}` and `{{date}}` are replaced by the version of lightfixes and today's date. `{{date}}` keeps the plugin from being byte-identical between days.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", crate::MAX_DESCRIPTION_LEN, default::plugin_description())
+    )]
+    pub plugin_description: Option<String>,
+
+    #[arg(
+        long = "min-duration",
+        help = &format!("Minimum duration of carryable lights, enforced after all duration multipliers and overrides. Lights which burn forever are exempt.\nIf this argument is not used, the value will be derived from lightConfig.toml, or no minimum is enforced.")
+    )]
+    pub min_duration: Option<i32>,
+
+    #[arg(
+        long = "hue-jitter",
+        help = &format!("Largest number of degrees each light's hue may be randomly shifted by. The shift is derived from the light's id, so repeated runs produce identical plugins.\nIf this argument is not used, the value will be derived from lightConfig.toml, or no jitter is applied.")
+    )]
+    pub hue_jitter: Option<f32>,
+
+    #[arg(
+        long = "value-jitter",
+        help = &format!("Largest amount each light's HSV value may be randomly shifted by. The shift is derived from the light's id, so repeated runs produce identical plugins.\nIf this argument is not used, the value will be derived from lightConfig.toml, or no jitter is applied.")
+    )]
+    pub value_jitter: Option<f32>,
+
+    #[arg(
+        long = "radius-jitter",
+        help = &format!("Largest number of units each light's radius may be randomly shifted by. The shift is derived from the light's id, so repeated runs produce identical plugins.\nIf this argument is not used, the value will be derived from lightConfig.toml, or no jitter is applied.")
+    )]
+    pub radius_jitter: Option<f32>,
+
+    #[arg(
+        long = "interior-sunlight-mult",
+        help = &format!("Multiplies the brightness of sunlight in every interior cell. 0.0 is equivalent to --classic's disabling of interior sunlight, which takes precedence.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::interior_sunlight_mult())
+    )]
+    pub interior_sunlight_mult: Option<f32>,
+
+    #[arg(
+        long = "fog-density-mult",
+        help = &format!("Multiplies the fog density of every interior cell, keeping it within 0.0 - 1.0.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::fog_density_mult())
+    )]
+    pub fog_density_mult: Option<f32>,
+
+    #[arg(
+        long = "weather-hue",
+        help = &format!("Multiplies the HSV hue of weather ambient colors, when --adjust-weather-fallbacks is used.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::weather_mult())
+    )]
+    pub weather_hue: Option<f32>,
+
+    #[arg(
+        long = "weather-saturation",
+        help = &format!("Multiplies the HSV saturation of weather ambient colors, when --adjust-weather-fallbacks is used.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::weather_mult())
+    )]
+    pub weather_saturation: Option<f32>,
+
+    #[arg(
+        long = "weather-value",
+        help = &format!("Multiplies the HSV value of weather ambient colors, when --adjust-weather-fallbacks is used.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::weather_mult())
+    )]
+    pub weather_value: Option<f32>,
+
+    #[arg(
+        long = "min-value",
+        help = &format!("Minimum HSV value of all processed lights, enforced after all value multipliers so that dim lights do not become black. Negative lights are exempt.\nIf this argument is not used, the value will be derived from lightConfig.toml or use the default value of {}.", default::min_value())
+    )]
+    pub min_value: Option<f32>,
+
+    #[arg(
+        long = "min-saturation",
+        help = &format!("Minimum HSV saturation of all processed lights, enforced after all multipliers and overrides. Must be between 0.0 and 1.0.\nIf this argument is not used, the value will be derived from lightConfig.toml, or no minimum is enforced.")
+    )]
+    pub min_saturation: Option<f32>,
+
+    #[arg(
+        long = "max-saturation",
+        help = &format!("Maximum HSV saturation of all processed lights, enforced after all multipliers and overrides. Must be between 0.0 and 1.0.\nIf this argument is not used, the value will be derived from lightConfig.toml, or no maximum is enforced.")
+    )]
+    pub max_saturation: Option<f32>,
+
+    #[arg(
+        long = "min-radius",
+        help = &format!("Minimum radius of all processed lights, enforced after all multipliers and overrides. Negative lights are exempt.\nIf this argument is not used, the value will be derived from lightConfig.toml, or no minimum is enforced.")
+    )]
+    pub min_radius: Option<u32>,
+
+    #[arg(
+        long = "max-radius",
+        help = &format!("Maximum radius of all processed lights, enforced after all multipliers and overrides.\nIf this argument is not used, the value will be derived from lightConfig.toml, or no maximum is enforced.")
+    )]
+    pub max_radius: Option<u32>,
+
     #[arg(
         short = 'x',
         long = "excluded-ids",
-        help = &format!("List of Regex patterns of light recordIds to exclude. This setting is *merged* onto values defined by lightconfig.toml.\nIf this argument is not used, the value will be derived from lightConfig.toml."),
+        help = &format!("List of Regex patterns of light recordIds to exclude. Patterns may be limited to one plugin as `plugin.esp::id_pattern`. This setting is *merged* onto values defined by lightconfig.toml.\nIf this argument is not used, the value will be derived from lightConfig.toml."),
         value_delimiter = ',',
     )]
     pub excluded_ids: Vec<String>,
@@ -144,6 +609,41 @@ pub struct LightArgs {
     )]
     pub excluded_plugins: Vec<String>,
 
+    #[arg(
+        long = "force-off-by-default-ids",
+        help = &format!("List of Regex patterns of light recordIds which will be off until a script turns them on. This setting is *merged* onto values defined by lightconfig.toml.\nIf this argument is not used, the value will be derived from lightConfig.toml."),
+        value_delimiter = ',',
+    )]
+    pub force_off_by_default_ids: Vec<String>,
+
+    #[arg(
+        long = "strip-sounds-ids",
+        help = &format!("List of Regex patterns of light recordIds whose looping sounds will be removed. This setting is *merged* onto values defined by lightconfig.toml.\nIf this argument is not used, the value will be derived from lightConfig.toml."),
+        value_delimiter = ',',
+    )]
+    pub strip_sounds_ids: Vec<String>,
+
+    #[arg(
+        long = "disable-interior-sun-cells",
+        help = &format!("List of Regex patterns of cell ids which --classic disables interior sunlight in. When empty, it's disabled in every interior. This setting is *merged* onto values defined by lightconfig.toml.\nIf this argument is not used, the value will be derived from lightConfig.toml."),
+        value_delimiter = ',',
+    )]
+    pub disable_interior_sun_cells: Vec<String>,
+
+    #[arg(
+        long = "preserve-ids",
+        help = &format!("List of Regex patterns of record ids to carry over unchanged from the existing plugin instead of regenerating them, so hand edits survive. Lights and interior cells are matched by id, exterior cells as ext:x,y and magic effects by name. This setting is *merged* onto values defined by lightconfig.toml.\nIf this argument is not used, the value will be derived from lightConfig.toml."),
+        value_delimiter = ',',
+    )]
+    pub preserve_ids: Vec<String>,
+
+    #[arg(
+        long = "ignore-data-dir",
+        help = &format!("List of Regex patterns of data directory paths to leave out of the VFS, along with every plugin in them. This setting is *merged* onto values defined by lightconfig.toml.\nIf this argument is not used, the value will be derived from lightConfig.toml."),
+        value_delimiter = ',',
+    )]
+    pub ignored_data_dirs: Vec<String>,
+
     #[arg(
         long = "light",
         value_parser = crate::light_override::parse_light_override,
@@ -151,13 +651,34 @@ pub struct LightArgs {
         help = &format!(
      "Colon-separated list of regexes to light values.
      May be specified multiple times instead of as a separated list.
-     Light values are specified as *either* fixed HSV values or as multipliers of existing ones.
+     Light values are specified as *either* fixed HSV values, multipliers of existing ones, or offsets added to them.
      EG:
      --light \"Torch_001=radius=255,hue=240,duration=1200,flag=FLICKERSLOW\" --light \"Torch_002=radius_mult=2.0,hue_mult=1.3,duration_mult=5.0,flag=NONE\"
      OR
      --light \"Torch_001=radius=255,hue=240,duration=1200,flag=FLICKERSLOW:Torch_002=radius_mult=2.0,hue_mult=1.3,duration_mult=5.0,flag=NONE\"
      Hue is a range from 0-360 and saturation/value are normalized floats (0.0 - 1.0). Radius and duration are u32 (can be very big).
-     `flag` may be: NONE, FLICKER, FLICKERSLOW, PULSE, PULSESLOW
+     `radius_add` adds to the original radius without dropping below zero.
+     `tint` blends the processed color toward a hex color by `tint_strength` (0.0 - 1.0, default 0.5), EG: `tint=#ff9944,tint_strength=0.4`. It may not be combined with fixed hue, saturation, or value.
+     `target_real_minutes` sets the duration so that the light burns for that many real minutes at the configured timescale. It may not be combined with `duration` or `duration_mult`.
+     `weight`/`weight_mult` and `gold_value`/`gold_value_mult` set or multiply the weight and gold value of carryable lights.
+     `hue_shift` adds degrees to the original hue, wrapping around the color wheel. `saturation_add` and `value_add` are added to the original values and clamped to 0.0 - 1.0.
+     `flag` may be: NONE, FLICKER, FLICKERSLOW, PULSE, PULSESLOW, OFFDEFAULT, DYNAMIC, CANCARRY, FIRE
+     Several flags may be combined with `|` or `+`, EG: `flag=FLICKERSLOW|OFFDEFAULT`.
+     FLICKER, FLICKERSLOW, PULSE, PULSESLOW and NONE replace the light's animation, while other flags are added to it.
+     Prefix a flag with `+` or `-` to add it to or remove it from the light's original flags, EG: `flag=+FIRE|-DYNAMIC`.
+     `match_hue_min` and `match_hue_max` only apply the override to lights whose original hue falls in that range, EG: `.*=match_hue_min=180,match_hue_max=260,saturation_mult=0.5`. Ranges like 350-20 wrap around through 0.
+     `match_radius_min` and `match_radius_max` likewise only apply the override to lights whose original radius falls in that range, EG: `.*=match_radius_max=95,radius=128`.
+     `match_flags` only applies the override to lights whose original flags include all of those listed, and none prefixed with `-`, EG: `.*=match_flags=FLICKER|-CANCARRY,value_mult=1.2`. NONE only matches lights which don't flicker or pulse.
+     `match_name` only applies the override to lights whose display name matches the pattern, regardless of case, EG: `.*=match_name=^candle$,radius=96`.
+     When several overrides match a light, the one with the highest `priority` (default 0) wins, with ties going to whichever was declared first.
+     `accumulate=true` makes an override only contribute the fields it sets, leaving the next matching override to fill in the rest.
+     `interior_only=true` and `exterior_only=true` only apply the override to lights placed exclusively in interiors or exteriors. Lights placed in both are skipped.
+     `plugin` only applies the override to lights coming from plugins whose lowercased file name matches the pattern, EG: `torch_.*=plugin=tamriel_data\\.esm,radius_mult=1.2`.
+     `sound` sets the sound record the light plays, or `sound=none` makes it silent.
+     `disabled=true` zeroes the light's radius and color and clears its flags. It may not be combined with any other field.
+     `copy_from` copies the processed color, radius, duration, and flags of another light, EG: `copy_from=light_pitfire00`. It may not be combined with hue, saturation, value, radius, or duration fields.
+     `mesh` and `icon` replace the light's model and inventory icon, relative to the meshes and icons folders.
+     `replace_flags=true` replaces every flag of the light instead. Override flags win over --no-flicker and --no-pulse.
      Fixed values are mutually exclusive with multipliers for each value and setting both will cause an error."),
     )]
     pub light_overrides: Vec<(String, crate::CustomLightData)>,
@@ -170,19 +691,72 @@ pub struct LightArgs {
             "
             Colon-separated list of cell id regexes, to the corresponding ambient data.
             `sunlight`, `ambient`, `fog`, and `fog_density` are available parameters.
-            Values are provided as fixed HSV values, no multipliers.
+            Values are provided as fixed HSV values, or as multipliers of the cell's existing colors using `ambient_mult`, `sunlight_mult`, and `fog_mult`.
+            Multipliers default to 1.0 for any of hue, saturation, and value left out, EG: `ambient_mult=value=0.5`.
+            `fog_density_mult` likewise multiplies the cell's existing fog density, and may not be combined with `fog_density`.
+            A fixed color and a multiplier of the same color may not be combined.
+            `instance_deletions` deletes the cell's references whose object id matches a regex, and `instance_replacements=pattern=>new_id` swaps their object id instead.
+            `instance_overrides=pattern=>scale=0.8,offset=0 0 -16` multiplies the scale of matching references and moves them by an offset.
+            All three may be given several times, EG: `instance_deletions=light_com_candle_0[1-3];instance_replacements=light_de_lantern_.*=>light_de_lantern_05`.
             Hue is a range from 0-360 and saturation/value are normalized floats (0.0 - 1.0).
             Each field of cell ambient data is separated by a semicolon, as below:
             --ambient \"caius cosades\' house=sunlight=hue=360,saturation=1.0,value=1.0;ambient=hue=24,saturation=0.25,value=0.69\"
+            Exterior cells are addressed by their grid coordinates using `grid`, in which case the regex only names the override:
+            --ambient \"balmora=grid=-3,-2;ambient=hue=30,saturation=0.2,value=0.3\"
             "
         )
     )]
     pub ambient_overrides: Vec<(String, crate::CustomCellAmbient)>,
 
+    #[arg(
+        long = "cell-sunlight",
+        value_parser = crate::light_override::parse_cell_color,
+        value_delimiter = ':',
+        help = &format!(
+            "
+            Colon-separated list of cell id regexes, to the sunlight color they're given.
+            A shortcut for --ambient which only sets `sunlight`, and replaces just that field of an existing override for the same regex.
+            Colors are a hue,saturation,value triple, or written out as in --ambient:
+            --cell-sunlight \"balmora, guild of mages=0,0,0:caius cosades\' house=hue=360,saturation=1.0,value=1.0\"
+            "
+        )
+    )]
+    pub cell_sunlight: Vec<(String, crate::TypedLightColor)>,
+
+    #[arg(
+        long = "cell-ambient",
+        value_parser = crate::light_override::parse_cell_color,
+        value_delimiter = ':',
+        help = &format!(
+            "
+            Colon-separated list of cell id regexes, to the ambient color they're given.
+            A shortcut for --ambient which only sets `ambient`, and replaces just that field of an existing override for the same regex.
+            Colors are a hue,saturation,value triple, or written out as in --ambient:
+            --cell-ambient \"balmora, guild of mages=24,0.25,0.69\"
+            "
+        )
+    )]
+    pub cell_ambient: Vec<(String, crate::TypedLightColor)>,
+
     #[arg(
         short = 'U',
         long,
         help = &format!("Force-saves the light config on this run. Note that this parameter does not merge into lightConfig.toml like others, and must be manually set there.")
     )]
     pub update_light_config: bool,
+
+    /// When another run of lightfixes is writing the plugin, wait up to this many seconds for it to finish.
+    /// Otherwise, lightfixes exits straight away with an error.
+    #[arg(long = "wait-lock")]
+    pub wait_lock: Option<u64>,
+
+    /// Apply the profile of this name from the `[profiles]` of lightconfig.toml.
+    /// Otherwise, the profile named after the path of the openmw.cfg being used is applied, if there is one.
+    #[arg(long = "profile")]
+    pub profile: Option<String>,
+
+    /// Print the light config lightfixes would use, after applying its profile and these arguments, and exit.
+    /// The profile which was applied, if any, is given in a comment first.
+    #[arg(long = "print-config")]
+    pub print_config: bool,
 }
@@ -1,9 +1,10 @@
 use std::{
+    borrow::Cow,
     fmt,
     fs::{File, read_dir, read_to_string},
     io::{self, Write},
     marker::PhantomData,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use ordered_hash_map::OrderedHashMap;
@@ -11,9 +12,12 @@ use serde::{
     Deserialize, Deserializer, Serialize, Serializer,
     de::{MapAccess, Visitor},
 };
+use tes3::esp::EditorId;
 
 use crate::{
-    CustomCellAmbient, CustomLightData, DEFAULT_CONFIG_NAME, default, notification_box, to_io_error,
+    CustomCellAmbient, CustomLightData, DEFAULT_CONFIG_NAME, ExteriorGrid, FlickerMode,
+    HueOverflow, LightFixesError, LightPlacement, NegativeLightMode, OutputFileType, OutputFormat,
+    OverwritePolicy, TintColor, color_to_hsv, default, fnv1a, plugin_file_name, print_notification,
 };
 
 pub fn deserialize_ordered_hash_map<'de, D, K, V>(
@@ -87,21 +91,150 @@ pub struct LightConfig {
     /// This parameter is DANGEROUS
     /// It's only meant to be used with vtastek's experimental shaders for openmw 0.47
     /// <https://discord.com/channels/260439894298460160/718892786157617163/966468825321177148>
+    /// Equivalent to an `interior_sunlight_mult` of 0.0, which it takes precedence over
     #[serde(default)]
     pub disable_interior_sun: bool,
 
+    /// Multiplies the HSV value of the sunlight color of every interior cell
+    #[serde(default = "default::interior_sunlight_mult")]
+    pub interior_sunlight_mult: f32,
+
+    /// Multiplies the fog density of every interior cell
+    #[serde(default = "default::fog_density_mult")]
+    pub fog_density_mult: f32,
+
+    /// Scale the `Weather_*_Ambient_*` color fallbacks of openmw.cfg, writing them into the user openmw.cfg
+    #[serde(default)]
+    pub adjust_weather_fallbacks: bool,
+
+    /// Multiplies the HSV hue of weather ambient colors, when `adjust_weather_fallbacks` is set
+    #[serde(default = "default::weather_mult")]
+    pub weather_hue: f32,
+
+    /// Multiplies the HSV saturation of weather ambient colors, when `adjust_weather_fallbacks` is set
+    #[serde(default = "default::weather_mult")]
+    pub weather_saturation: f32,
+
+    /// Multiplies the HSV value of weather ambient colors, when `adjust_weather_fallbacks` is set
+    #[serde(default = "default::weather_mult")]
+    pub weather_value: f32,
+
+    /// Deprecated in favor of `flicker_mode`, which takes precedence when set
+    /// true is equivalent to `remove`, and false to `keep`
     #[serde(default = "default::disable_flicker")]
     pub disable_flickering: bool,
 
+    #[serde(default)]
+    pub flicker_mode: Option<FlickerMode>,
+
     #[serde(default = "default::disable_pulse")]
     pub disable_pulse: bool,
 
+    /// Only fix light flags and negative lights, leaving colors, radii, and durations alone
+    /// Lights whose flags don't change are left out of the plugin entirely
+    #[serde(default)]
+    pub flags_only: bool,
+
+    /// Leave lights out of the plugin when processing didn't change them,
+    /// so that plugins which contribute nothing aren't made into masters
+    #[serde(default)]
+    pub skip_unchanged: bool,
+
+    /// Only process lights which are placed in at least one cell of the load order
+    /// Lights which only appear through leveled lists, containers, or scripts can't be detected, and are skipped
+    #[serde(default)]
+    pub only_referenced: bool,
+
+    /// Clear the water height of every cell written into the plugin, as older versions always did
+    #[serde(default)]
+    pub clear_water_height: bool,
+
+    /// Process interiors which behave like exteriors, even though they take their lighting from the sky
+    #[serde(default)]
+    pub process_quasi_exteriors: bool,
+
+    /// Merge the atmosphere of cells changed by several plugins field by field, instead of only using the last plugin's
+    #[serde(default)]
+    pub merge_cell_atmosphere: bool,
+
+    /// Process plugins registered as groundcover in openmw.cfg, which are skipped otherwise
+    #[serde(default)]
+    pub process_groundcover: bool,
+
+    /// Scale the glow color of magic effects, so spells aren't blinding next to dimmed lights
+    #[serde(default)]
+    pub process_magic_effects: bool,
+
+    /// Multiplies the saturation of magic effect colors, instead of `colored_saturation`
+    #[serde(default)]
+    pub magic_saturation: Option<f32>,
+
+    /// Multiplies the value of magic effect colors, instead of `colored_value`
+    #[serde(default)]
+    pub magic_value: Option<f32>,
+
+    /// Leave lights whose original radius is zero and whose color is black out of the plugin, unless a light override matches them
+    #[serde(default = "default::skip_zero_radius")]
+    pub skip_zero_radius: bool,
+
+    /// Clear the references of every cell written into the plugin, leaving the engine to merge them from the original plugins
+    /// When false, the winning cell's references are carried over as they are, which makes the plugin much larger
+    #[serde(default = "default::clear_references")]
+    pub clear_references: bool,
+
     #[serde(default = "default::save_log")]
     pub save_log: bool,
 
+    /// What happens when the generated plugin already exists
+    #[serde(default)]
+    pub overwrite_policy: OverwritePolicy,
+
+    /// Read the plugin back after writing it, and stop with an error if it's broken
+    #[serde(default)]
+    pub verify: bool,
+
+    /// List every record which changed since the previous plugin, rather than only counting them
+    #[serde(default)]
+    pub diff_details: bool,
+
+    /// Print which data directory each content file is loaded from, and which copies of it that shadows.
+    /// This is always done in debug mode
+    #[serde(default)]
+    pub vfs_report: bool,
+
+    /// Stop with an error when content files of openmw.cfg can't be found, instead of only warning about them
+    #[serde(default)]
+    pub strict: bool,
+
+    /// Delete generated plugins found in data directories other than the output one, instead of only reporting them
+    #[serde(default)]
+    pub clean_stale: bool,
+
+    /// Also write the generated plugin as tes3conv JSON to this path
+    #[serde(default)]
+    pub emit_json: Option<PathBuf>,
+
+    /// openmw.cfg to use when lightfixes is run from the directory holding this lightconfig.toml, which has none of its own.
+    /// Written when an openmw.cfg picked through the file dialog is remembered
+    #[serde(default)]
+    pub openmw_cfg: Option<PathBuf>,
+
+    /// Write a `.meta.json` file next to the plugin, describing how it was generated
+    #[serde(default = "default::write_metadata")]
+    pub write_metadata: bool,
+
+    /// How many copies of the previous plugin are kept, as `.bak`, `.bak.1`, `.bak.2`, and so on
+    /// 0 disables backups
+    #[serde(default = "default::keep_backups")]
+    pub keep_backups: u8,
+
     #[serde(default = "default::auto_enable")]
     pub auto_enable: bool,
 
+    /// Leave an already enabled plugin where it is in the load order, instead of moving it to the end when enabling it
+    #[serde(default)]
+    pub no_reorder: bool,
+
     #[serde(default)]
     pub no_notifications: bool,
 
@@ -120,6 +253,9 @@ pub struct LightConfig {
     #[serde(default = "default::standard_radius")]
     pub standard_radius: f32,
 
+    #[serde(default = "default::standard_radius_add")]
+    pub standard_radius_add: i32,
+
     #[serde(default = "default::colored_hue")]
     pub colored_hue: f32,
 
@@ -132,15 +268,199 @@ pub struct LightConfig {
     #[serde(default = "default::colored_radius")]
     pub colored_radius: f32,
 
+    #[serde(default = "default::colored_radius_add")]
+    pub colored_radius_add: i32,
+
+    /// When set, radii are raised to this power around `radius_curve_base` before being multiplied
+    /// Below 1.0, small lights grow more than large ones
+    #[serde(default)]
+    pub radius_exponent: Option<f32>,
+
+    #[serde(default = "default::radius_curve_base")]
+    pub radius_curve_base: f32,
+
+    /// Deprecated in favor of `standard_duration_mult` and `colored_duration_mult`
+    /// Used for whichever of them isn't set
     #[serde(default = "default::duration_mult")]
     pub duration_mult: f32,
 
+    #[serde(default)]
+    pub standard_duration_mult: Option<f32>,
+
+    #[serde(default)]
+    pub colored_duration_mult: Option<f32>,
+
+    /// Real-time minutes every finite carryable light should burn for
+    /// When set, this replaces the duration multipliers
+    #[serde(default)]
+    pub target_real_minutes: Option<f32>,
+
+    /// The game's timescale, used by `target_real_minutes`.
+    /// When unset, `fallback=General_Timescale` from openmw.cfg is used, or Morrowind's own timescale without one
+    #[serde(default)]
+    pub timescale: Option<f32>,
+
+    /// Applied on top of the category radius multiplier for carryable lights only
+    #[serde(default = "default::carryable_radius_mult")]
+    pub carryable_radius_mult: f32,
+
+    /// Applied on top of the category value multiplier for carryable lights only
+    #[serde(default = "default::carryable_value_mult")]
+    pub carryable_value_mult: f32,
+
+    /// Applied on top of the category duration multiplier for carryable lights only
+    #[serde(default = "default::carryable_duration_mult")]
+    pub carryable_duration_mult: f32,
+
+    /// Multiplies the weight of all carryable lights
+    #[serde(default = "default::weight_mult")]
+    pub weight_mult: f32,
+
+    /// Multiplies the gold value of all carryable lights
+    #[serde(default = "default::gold_value_mult")]
+    pub gold_value_mult: f32,
+
+    /// Target perceived brightness for non-colored lights
+    /// When set, this replaces `standard_value` as the value multiplier, which may then not be changed from its default
+    #[serde(default)]
+    pub standard_normalize_value: Option<f32>,
+
+    /// Target perceived brightness for colored lights
+    /// When set, this replaces `colored_value` as the value multiplier, which may then not be changed from its default
+    #[serde(default)]
+    pub colored_normalize_value: Option<f32>,
+
+    /// Largest factor by which brightness normalization may brighten or dim a light
+    #[serde(default = "default::normalize_max_adjustment")]
+    pub normalize_max_adjustment: f32,
+
+    /// Color non-colored lights are blended toward, after all other color math
+    #[serde(default)]
+    pub standard_tint: Option<TintColor>,
+
+    /// How far non-colored lights are blended toward `standard_tint`, from 0.0 to 1.0
+    #[serde(default = "default::tint_strength")]
+    pub standard_tint_strength: f32,
+
+    /// Color colored lights are blended toward, after all other color math
+    #[serde(default)]
+    pub colored_tint: Option<TintColor>,
+
+    /// How far colored lights are blended toward `colored_tint`, from 0.0 to 1.0
+    #[serde(default = "default::tint_strength")]
+    pub colored_tint_strength: f32,
+
+    /// Performs color math on linear light instead of encoded sRGB values,
+    /// so that scaling a light's value scales its perceived brightness proportionally
+    #[serde(default)]
+    pub linear_color: bool,
+
+    /// How lights carrying the NEGATIVE flag are handled
+    #[serde(default)]
+    pub negative_light_mode: NegativeLightMode,
+
+    /// Whether multiplied or jittered hues wrap around the color wheel or stop at its end
+    #[serde(default)]
+    pub hue_overflow: HueOverflow,
+
+    /// Whether the generated plugin is a regular addon, or flagged as a master which sorts before addons
+    #[serde(default)]
+    pub output_file_type: OutputFileType,
+
+    /// Which engine the generated plugin is written for
+    #[serde(default)]
+    pub output_format: OutputFormat,
+
+    /// Most masters the generated plugin may have before a warning is shown
+    #[serde(default = "default::max_masters")]
+    pub max_masters: usize,
+
+    /// Split the generated plugin into numbered parts of at most `max_masters` masters each, when it would have more
+    #[serde(default)]
+    pub split_masters: bool,
+
+    /// Write lights and magic effects into S3LightFixes-Lights.omwaddon, and cells into S3LightFixes-Cells.omwaddon,
+    /// so that either can be disabled without the other
+    #[serde(default)]
+    pub split_output: bool,
+
+    /// Author written into the plugin header, cut to 31 bytes
+    /// `{version}` and `{date}` are replaced by the version of lightfixes and today's date
+    #[serde(default = "default::plugin_author")]
+    pub plugin_author: String,
+
+    /// Description written into the plugin header, cut to 255 bytes
+    /// `{version}` and `{date}` are replaced by the version of lightfixes and today's date.
+    /// `{date}` makes the plugin differ from day to day, so it's no longer byte-identical between runs
+    #[serde(default = "default::plugin_description")]
+    pub plugin_description: String,
+
+    /// Lower bound for the duration of carryable lights
+    /// Lights which burn forever (duration <= 0) are exempt
+    #[serde(default)]
+    pub min_duration: Option<i32>,
+
+    /// Largest number of degrees each light's hue may be randomly shifted by
+    #[serde(default)]
+    pub hue_jitter: Option<f32>,
+
+    /// Largest amount each light's HSV value may be randomly shifted by
+    #[serde(default)]
+    pub value_jitter: Option<f32>,
+
+    /// Largest number of units each light's radius may be randomly shifted by
+    #[serde(default)]
+    pub radius_jitter: Option<f32>,
+
+    /// Lower bound for the HSV value of all processed lights
+    #[serde(default = "default::min_value")]
+    pub min_value: f32,
+
+    /// Lower bound for the HSV saturation of all processed lights
+    #[serde(default)]
+    pub min_saturation: Option<f32>,
+
+    /// Upper bound for the HSV saturation of all processed lights
+    #[serde(default)]
+    pub max_saturation: Option<f32>,
+
+    /// Lower bound for the radius of all processed lights
+    /// Lights nullified due to being negative are exempt
+    #[serde(default)]
+    pub min_radius: Option<u32>,
+
+    /// Upper bound for the radius of all processed lights
+    #[serde(default)]
+    pub max_radius: Option<u32>,
+
     #[serde(default = "default::excluded_plugins")]
     pub excluded_plugins: Vec<String>,
 
+    /// Patterns may be scoped to a plugin as `plugin.esp::id_pattern`
     #[serde(default)]
     pub excluded_ids: Vec<String>,
 
+    /// Lights whose ids match these patterns will be off until a script turns them on
+    #[serde(default)]
+    pub force_off_by_default_ids: Vec<String>,
+
+    /// Lights whose ids match these patterns will have their looping sound removed
+    #[serde(default)]
+    pub strip_sounds_ids: Vec<String>,
+
+    /// When not empty, `disable_interior_sun` only applies to cells whose ids match these patterns
+    #[serde(default)]
+    pub disable_interior_sun_cells: Vec<String>,
+
+    /// Data directories whose paths match these patterns are left out of the VFS, along with every plugin in them
+    #[serde(default)]
+    pub ignored_data_dirs: Vec<String>,
+
+    /// Records whose ids match these patterns are carried over unchanged from the existing plugin,
+    /// so hand edits made to it survive regeneration
+    #[serde(default)]
+    pub preserve_ids: Vec<String>,
+
     #[serde(
         default,
         serialize_with = "serialize_ordered_hash_map",
@@ -160,20 +480,101 @@ pub struct LightConfig {
     #[serde(default)]
     pub save_config: bool,
 
+    /// Id patterns, each optionally limited to plugins matching the first pattern
     #[serde(skip)]
-    pub excluded_id_regexes: Vec<regex::Regex>,
+    pub excluded_id_regexes: Vec<(Option<regex::Regex>, regex::Regex)>,
     #[serde(skip)]
     pub excluded_plugin_regexes: Vec<regex::Regex>,
     #[serde(skip)]
-    pub light_regexes: Vec<(regex::Regex, CustomLightData)>,
+    pub force_off_by_default_regexes: Vec<regex::Regex>,
+    #[serde(skip)]
+    pub strip_sounds_regexes: Vec<regex::Regex>,
+    #[serde(skip)]
+    pub disable_interior_sun_regexes: Vec<regex::Regex>,
+    #[serde(skip)]
+    pub preserve_id_regexes: Vec<regex::Regex>,
+    #[serde(skip)]
+    pub ignored_data_dir_regexes: Vec<regex::Regex>,
+    /// Id patterns of light overrides, each optionally limited to plugins matching the second pattern
+    #[serde(skip)]
+    pub light_regexes: Vec<(regex::Regex, Option<regex::Regex>, CustomLightData)>,
     #[serde(skip)]
     pub ambient_regexes: Vec<(regex::Regex, CustomCellAmbient)>,
+    /// Ambient overrides addressing exterior cells by `ext:` keys or `grid`, which never match interiors
+    #[serde(skip)]
+    pub exterior_ambients: Vec<(ExteriorGrid, CustomCellAmbient)>,
+    /// `config_fingerprint` of the config, taken before its patterns were compiled
+    #[serde(skip)]
+    pub fingerprint: String,
+
+    /// Sets of fields which replace the top-level ones when their name is given with --profile,
+    /// or when it's the path of the openmw.cfg being used
+    #[serde(default, skip_serializing_if = "toml::Table::is_empty")]
+    pub profiles: toml::Table,
+
+    /// Name of the profile which was applied, if any
+    #[serde(skip)]
+    pub applied_profile: Option<String>,
+}
+
+/// The form of an openmw.cfg path which profiles are matched by, leaving out trailing separators,
+/// and openmw.cfg itself, so that a profile may name either the file or its directory.
+/// Windows paths aren't case sensitive, so they're lowercased there
+fn profile_path_key(path: &str) -> String {
+    let mut key = path.trim().to_string();
+
+    if cfg!(windows) {
+        key = key.replace('\\', "/").to_lowercase();
+    }
+
+    if key.to_ascii_lowercase().ends_with("/openmw.cfg") {
+        key.truncate(key.len() - "/openmw.cfg".len());
+    }
+
+    while key.len() > 1 && key.ends_with('/') {
+        key.pop();
+    }
+
+    key
+}
+
+/// The `fallback=General_Timescale` entry of openmw.cfg, if it has a usable one
+fn openmw_timescale(openmw_config: &openmw_config::OpenMWConfiguration) -> Option<f32> {
+    openmw_config
+        .game_settings()
+        .find(|setting| setting.key().eq_ignore_ascii_case("General_Timescale"))
+        .and_then(|setting| setting.value().trim().parse::<f32>().ok())
+        .filter(|timescale| timescale.is_finite() && *timescale > 0.0)
+}
+
+fn invalid_pattern(
+    pattern: String,
+    table: &'static str,
+    source: impl std::error::Error + Send + Sync + 'static,
+) -> LightFixesError {
+    LightFixesError::InvalidPattern {
+        pattern,
+        table,
+        source: Box::new(source),
+    }
+}
+
+/// Plugin names are matched regardless of case
+fn compile_plugin_pattern(pattern: &str) -> Result<regex::Regex, regex::Error> {
+    regex::RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+}
+
+/// Compiles a pattern of one of the tables of lightconfig.toml, or the args which extend it
+fn compile_pattern(pattern: String, table: &'static str) -> Result<regex::Regex, LightFixesError> {
+    regex::Regex::new(&pattern).map_err(|source| invalid_pattern(pattern, table, source))
 }
 
 /// Primarily exists to provide default implementations
 /// for field values
 impl LightConfig {
-    fn find(root_path: &PathBuf) -> Result<PathBuf, io::Error> {
+    fn find(root_path: &Path) -> Result<PathBuf, io::Error> {
         read_dir(root_path)?
             .filter_map(|entry| entry.ok())
             .find(|entry| entry.file_name().eq_ignore_ascii_case(DEFAULT_CONFIG_NAME))
@@ -195,37 +596,223 @@ impl LightConfig {
         }
     }
 
-    /// Gives back the lightconfig adjacent to openmw.cfg when called
+    /// Checks settings which depend on one another for consistency
+    fn validate(&self) -> Result<(), String> {
+        if self.max_masters == 0 {
+            return Err("max_masters must be at least 1".into());
+        }
+
+        if let (Some(min_radius), Some(max_radius)) = (self.min_radius, self.max_radius)
+            && min_radius > max_radius
+        {
+            return Err(format!(
+                "min_radius ({min_radius}) may not be greater than max_radius ({max_radius})"
+            ));
+        }
+
+        for (name, strength) in [
+            ("standard_tint_strength", self.standard_tint_strength),
+            ("colored_tint_strength", self.colored_tint_strength),
+        ] {
+            if !(0.0..=1.0).contains(&strength) {
+                return Err(format!("{name} ({strength}) must be between 0.0 and 1.0"));
+            }
+        }
+
+        for (name, target) in [
+            ("standard_normalize_value", self.standard_normalize_value),
+            ("colored_normalize_value", self.colored_normalize_value),
+        ] {
+            if let Some(target) = target
+                && !(0.0..=1.0).contains(&target)
+            {
+                return Err(format!("{name} ({target}) must be between 0.0 and 1.0"));
+            }
+        }
+
+        // Normalization replaces the value multiplier of its category, so setting both would silently drop one
+        for (normalize_name, target, value_name, value, default_value) in [
+            (
+                "standard_normalize_value",
+                self.standard_normalize_value,
+                "standard_value",
+                self.standard_value,
+                default::standard_value(),
+            ),
+            (
+                "colored_normalize_value",
+                self.colored_normalize_value,
+                "colored_value",
+                self.colored_value,
+                default::colored_value(),
+            ),
+        ] {
+            if target.is_some() && value != default_value {
+                return Err(format!(
+                    "{normalize_name} and {value_name} ({value}) can't be used together, as normalization replaces the value multiplier"
+                ));
+            }
+        }
+
+        for (name, mult) in [
+            ("interior_sunlight_mult", self.interior_sunlight_mult),
+            ("fog_density_mult", self.fog_density_mult),
+            ("weather_hue", self.weather_hue),
+            ("weather_saturation", self.weather_saturation),
+            ("weather_value", self.weather_value),
+            ("weight_mult", self.weight_mult),
+            ("gold_value_mult", self.gold_value_mult),
+        ] {
+            if mult.is_nan() || mult < 0.0 {
+                return Err(format!("{name} ({mult}) may not be negative"));
+            }
+        }
+
+        for (name, jitter) in [
+            ("hue_jitter", self.hue_jitter),
+            ("value_jitter", self.value_jitter),
+            ("radius_jitter", self.radius_jitter),
+        ] {
+            if let Some(jitter) = jitter
+                && (jitter.is_nan() || jitter < 0.0)
+            {
+                return Err(format!("{name} ({jitter}) may not be negative"));
+            }
+        }
+
+        if let Some(exponent) = self.radius_exponent
+            && !(exponent.is_finite() && exponent > 0.0)
+        {
+            return Err(format!(
+                "radius_exponent ({exponent}) must be greater than 0.0"
+            ));
+        }
+
+        if !(self.radius_curve_base.is_finite() && self.radius_curve_base > 0.0) {
+            return Err(format!(
+                "radius_curve_base ({}) must be greater than 0.0",
+                self.radius_curve_base
+            ));
+        }
+
+        if let Some(timescale) = self.timescale
+            && !(timescale.is_finite() && timescale > 0.0)
+        {
+            return Err(format!("timescale ({timescale}) must be greater than 0.0"));
+        }
+
+        if let Some(real_minutes) = self.target_real_minutes
+            && !(real_minutes.is_finite() && real_minutes > 0.0)
+        {
+            return Err(format!(
+                "target_real_minutes ({real_minutes}) must be greater than 0.0"
+            ));
+        }
+
+        if self.normalize_max_adjustment < 1.0 {
+            return Err(format!(
+                "normalize_max_adjustment ({}) may not be less than 1.0",
+                self.normalize_max_adjustment
+            ));
+        }
+
+        for (name, saturation) in [
+            ("min_saturation", self.min_saturation),
+            ("max_saturation", self.max_saturation),
+        ] {
+            if let Some(saturation) = saturation
+                && !(0.0..=1.0).contains(&saturation)
+            {
+                return Err(format!("{name} ({saturation}) must be between 0.0 and 1.0"));
+            }
+        }
+
+        if let (Some(min_saturation), Some(max_saturation)) =
+            (self.min_saturation, self.max_saturation)
+            && min_saturation > max_saturation
+        {
+            return Err(format!(
+                "min_saturation ({min_saturation}) may not be greater than max_saturation ({max_saturation})"
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The profile named by --profile, or otherwise the first whose name is the path of openmw_cfg
+    fn matching_profile(
+        config_table: &toml::Table,
+        profile_name: Option<&str>,
+        openmw_cfg: &Path,
+    ) -> Option<(String, toml::Table)> {
+        let profiles = config_table.get("profiles")?.as_table()?;
+        let config_key = profile_path_key(&openmw_cfg.to_string_lossy());
+
+        profiles
+            .iter()
+            .find(|(name, _)| match profile_name {
+                Some(profile_name) => name.as_str() == profile_name,
+                None => profile_path_key(name) == config_key,
+            })
+            .and_then(|(name, profile)| Some((name.clone(), profile.as_table()?.clone())))
+    }
+
+    /// Gives back the lightconfig in user_config_path, which is the directory of the user openmw.cfg, when called
     /// use_classic dictates whether or not a fixed radius of 2.0 will be used on orange-y lights
     /// and whether or not to disable interior sunlight
     /// the latter field is not de/serializable and can only be used via the --classic argument
+    /// A profile matching --profile or openmw_cfg is applied before the CLI args.
+    /// Settings lightconfig.toml leaves unset may be read from openmw_config, which Morrowind.ini doesn't have.
+    /// With --print-config, it's given back as soon as it's merged, without compiling its patterns
     pub fn get(
         mut light_args: crate::LightArgs,
-        openmw_config: &openmw_config::OpenMWConfiguration,
-    ) -> Result<LightConfig, io::Error> {
+        user_config_path: &Path,
+        data_local: Option<PathBuf>,
+        openmw_cfg: &Path,
+        openmw_config: Option<&openmw_config::OpenMWConfiguration>,
+    ) -> Result<LightConfig, LightFixesError> {
         let mut write_config = false;
+        let mut applied_profile = None;
+        // Top-level values the profile replaced, which are put back when the config is saved
+        let mut replaced_values: Vec<(String, Option<toml::Value>)> = Vec::new();
 
-        let user_config_path = openmw_config.user_config_path();
+        let mut light_config: LightConfig = if let Ok(config_path) = Self::find(user_config_path) {
+            let config_contents = read_to_string(&config_path)?;
 
-        let mut light_config: LightConfig = if let Ok(config_path) = Self::find(&user_config_path) {
-            let config_contents = read_to_string(config_path)?;
+            let parsed = toml::from_str::<toml::Table>(&config_contents).and_then(|mut table| {
+                if let Some((name, profile)) =
+                    Self::matching_profile(&table, light_args.profile.as_deref(), openmw_cfg)
+                {
+                    for (key, value) in profile {
+                        replaced_values.push((key.clone(), table.insert(key, value)));
+                    }
 
-            match toml::from_str(&config_contents) {
-                Ok(config) => config,
-                Err(e) => {
-                    notification_box(
-                        "Failed to read light config!",
-                        &format!("Lightconfig.toml couldn't be read: {e}"),
-                        light_args.no_notifications,
-                    );
-                    std::process::exit(256);
+                    applied_profile = Some(name);
                 }
-            }
+
+                toml::Value::Table(table).try_into::<LightConfig>()
+            });
+
+            parsed.map_err(|source| LightFixesError::ConfigParse {
+                path: config_path,
+                source,
+            })?
         } else {
             write_config = true;
             LightConfig::default()
         };
 
+        if let Some(profile_name) = &light_args.profile
+            && applied_profile.is_none()
+        {
+            return Err(LightFixesError::UnknownProfile(profile_name.clone()));
+        }
+
+        light_config.applied_profile = applied_profile;
+
+        // The deprecated --duration-mult applies to both categories, so hold onto it before it's merged
+        let duration_mult_arg = light_args.duration_mult;
+
         // Replace any values provided as CLI args in the config
         // use_classic will always override the standard_radius and disable_interior_sun
         Self::overwrite_if_some([
@@ -259,6 +846,68 @@ impl LightConfig {
                 &mut light_config.duration_mult,
                 &mut light_args.duration_mult,
             ),
+            (
+                &mut light_config.carryable_radius_mult,
+                &mut light_args.carryable_radius_mult,
+            ),
+            (
+                &mut light_config.carryable_value_mult,
+                &mut light_args.carryable_value_mult,
+            ),
+            (
+                &mut light_config.carryable_duration_mult,
+                &mut light_args.carryable_duration_mult,
+            ),
+            (&mut light_config.weight_mult, &mut light_args.weight_mult),
+            (
+                &mut light_config.gold_value_mult,
+                &mut light_args.gold_value_mult,
+            ),
+            (&mut light_config.min_value, &mut light_args.min_value),
+            (
+                &mut light_config.interior_sunlight_mult,
+                &mut light_args.interior_sunlight_mult,
+            ),
+            (
+                &mut light_config.fog_density_mult,
+                &mut light_args.fog_density_mult,
+            ),
+            (&mut light_config.weather_hue, &mut light_args.weather_hue),
+            (
+                &mut light_config.weather_saturation,
+                &mut light_args.weather_saturation,
+            ),
+            (
+                &mut light_config.weather_value,
+                &mut light_args.weather_value,
+            ),
+            (
+                &mut light_config.radius_curve_base,
+                &mut light_args.radius_curve_base,
+            ),
+            (
+                &mut light_config.normalize_max_adjustment,
+                &mut light_args.normalize_max_adjustment,
+            ),
+            (
+                &mut light_config.standard_tint_strength,
+                &mut light_args.standard_tint_strength,
+            ),
+            (
+                &mut light_config.colored_tint_strength,
+                &mut light_args.colored_tint_strength,
+            ),
+        ]);
+
+        Self::overwrite_if_some([
+            (
+                &mut light_config.standard_radius_add,
+                &mut light_args.standard_radius_add,
+            ),
+            (
+                &mut light_config.colored_radius_add,
+                &mut light_args.colored_radius_add,
+            ),
         ]);
 
         Self::overwrite_if_some([
@@ -266,6 +915,14 @@ impl LightConfig {
                 &mut light_config.disable_pulse,
                 &mut light_args.disable_pulse,
             ),
+            (
+                &mut light_config.skip_zero_radius,
+                &mut light_args.skip_zero_radius,
+            ),
+            (
+                &mut light_config.clear_references,
+                &mut light_args.clear_references,
+            ),
             (
                 &mut light_config.disable_flickering,
                 &mut light_args.disable_flickering,
@@ -279,31 +936,316 @@ impl LightConfig {
                 },
             ),
             (
-                &mut light_config.auto_enable,
-                &mut if light_args.auto_enable {
-                    Some(light_args.auto_enable)
+                &mut light_config.verify,
+                &mut if light_args.verify {
+                    Some(light_args.verify)
                 } else {
                     None
                 },
             ),
             (
-                &mut light_config.no_notifications,
-                &mut if light_args.no_notifications {
-                    Some(light_args.no_notifications)
+                &mut light_config.clean_stale,
+                &mut if light_args.clean_stale {
+                    Some(light_args.clean_stale)
                 } else {
                     None
                 },
             ),
             (
-                &mut light_config.debug,
-                &mut if light_args.debug {
-                    Some(light_args.debug)
+                &mut light_config.process_groundcover,
+                &mut if light_args.process_groundcover {
+                    Some(light_args.process_groundcover)
                 } else {
                     None
                 },
             ),
-        ]);
-
+            (
+                &mut light_config.vfs_report,
+                &mut if light_args.vfs_report {
+                    Some(light_args.vfs_report)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.strict,
+                &mut if light_args.strict {
+                    Some(light_args.strict)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.diff_details,
+                &mut if light_args.diff_details {
+                    Some(light_args.diff_details)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.write_metadata,
+                &mut if light_args.write_metadata {
+                    Some(light_args.write_metadata)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.auto_enable,
+                &mut if light_args.auto_enable {
+                    Some(light_args.auto_enable)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.no_reorder,
+                &mut if light_args.no_reorder {
+                    Some(light_args.no_reorder)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.no_notifications,
+                &mut if light_args.no_notifications {
+                    Some(light_args.no_notifications)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.debug,
+                &mut if light_args.debug {
+                    Some(light_args.debug)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.flags_only,
+                &mut if light_args.flags_only {
+                    Some(light_args.flags_only)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.skip_unchanged,
+                &mut if light_args.skip_unchanged {
+                    Some(light_args.skip_unchanged)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.only_referenced,
+                &mut if light_args.only_referenced {
+                    Some(light_args.only_referenced)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.merge_cell_atmosphere,
+                &mut if light_args.merge_cell_atmosphere {
+                    Some(light_args.merge_cell_atmosphere)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.process_quasi_exteriors,
+                &mut if light_args.process_quasi_exteriors {
+                    Some(light_args.process_quasi_exteriors)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.split_output,
+                &mut if light_args.split_output {
+                    Some(light_args.split_output)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.split_masters,
+                &mut if light_args.split_masters {
+                    Some(light_args.split_masters)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.adjust_weather_fallbacks,
+                &mut if light_args.adjust_weather_fallbacks {
+                    Some(light_args.adjust_weather_fallbacks)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.process_magic_effects,
+                &mut if light_args.process_magic_effects {
+                    Some(light_args.process_magic_effects)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.clear_water_height,
+                &mut if light_args.clear_water_height {
+                    Some(light_args.clear_water_height)
+                } else {
+                    None
+                },
+            ),
+            (
+                &mut light_config.linear_color,
+                &mut if light_args.linear_color {
+                    Some(light_args.linear_color)
+                } else {
+                    None
+                },
+            ),
+        ]);
+
+        if light_args.standard_normalize_value.is_some() {
+            light_config.standard_normalize_value = light_args.standard_normalize_value;
+        }
+
+        if light_args.colored_normalize_value.is_some() {
+            light_config.colored_normalize_value = light_args.colored_normalize_value;
+        }
+
+        if light_args.standard_tint.is_some() {
+            light_config.standard_tint = light_args.standard_tint;
+        }
+
+        if light_args.colored_tint.is_some() {
+            light_config.colored_tint = light_args.colored_tint;
+        }
+
+        if light_args.flicker_mode.is_some() {
+            light_config.flicker_mode = light_args.flicker_mode;
+        }
+
+        if let Some(negative_light_mode) = light_args.negative_light_mode {
+            light_config.negative_light_mode = negative_light_mode;
+        }
+
+        if let Some(hue_overflow) = light_args.hue_overflow {
+            light_config.hue_overflow = hue_overflow;
+        }
+
+        if let Some(output_file_type) = light_args.output_file_type {
+            light_config.output_file_type = output_file_type;
+        }
+
+        if let Some(output_format) = light_args.output_format {
+            light_config.output_format = output_format;
+        }
+
+        if let Some(overwrite_policy) = light_args.overwrite_policy {
+            light_config.overwrite_policy = overwrite_policy;
+        }
+
+        if light_args.no_backup {
+            light_config.keep_backups = 0;
+        }
+
+        if let Some(max_masters) = light_args.max_masters {
+            light_config.max_masters = max_masters;
+        }
+
+        if let Some(plugin_author) = light_args.plugin_author.take() {
+            light_config.plugin_author = plugin_author;
+        }
+
+        if let Some(plugin_description) = light_args.plugin_description.take() {
+            light_config.plugin_description = plugin_description;
+        }
+
+        if let Some(emit_json) = light_args.emit_json.take() {
+            light_config.emit_json = Some(emit_json);
+        }
+
+        if let Some(duration_mult) = duration_mult_arg {
+            light_config.standard_duration_mult = Some(duration_mult);
+            light_config.colored_duration_mult = Some(duration_mult);
+        }
+
+        if light_args.standard_duration_mult.is_some() {
+            light_config.standard_duration_mult = light_args.standard_duration_mult;
+        }
+
+        if light_args.colored_duration_mult.is_some() {
+            light_config.colored_duration_mult = light_args.colored_duration_mult;
+        }
+
+        if light_args.magic_saturation.is_some() {
+            light_config.magic_saturation = light_args.magic_saturation;
+        }
+
+        if light_args.magic_value.is_some() {
+            light_config.magic_value = light_args.magic_value;
+        }
+
+        // Resolve both duration multipliers so that they're written back under their new names
+        light_config
+            .standard_duration_mult
+            .get_or_insert(light_config.duration_mult);
+        light_config
+            .colored_duration_mult
+            .get_or_insert(light_config.duration_mult);
+
+        if light_args.target_real_minutes.is_some() {
+            light_config.target_real_minutes = light_args.target_real_minutes;
+        }
+
+        if light_args.min_duration.is_some() {
+            light_config.min_duration = light_args.min_duration;
+        }
+
+        if light_args.min_saturation.is_some() {
+            light_config.min_saturation = light_args.min_saturation;
+        }
+
+        if light_args.max_saturation.is_some() {
+            light_config.max_saturation = light_args.max_saturation;
+        }
+
+        if light_args.min_radius.is_some() {
+            light_config.min_radius = light_args.min_radius;
+        }
+
+        if light_args.max_radius.is_some() {
+            light_config.max_radius = light_args.max_radius;
+        }
+
+        if light_args.radius_exponent.is_some() {
+            light_config.radius_exponent = light_args.radius_exponent;
+        }
+
+        if light_args.timescale.is_some() {
+            light_config.timescale = light_args.timescale;
+        }
+
+        if light_args.hue_jitter.is_some() {
+            light_config.hue_jitter = light_args.hue_jitter;
+        }
+
+        if light_args.value_jitter.is_some() {
+            light_config.value_jitter = light_args.value_jitter;
+        }
+
+        if light_args.radius_jitter.is_some() {
+            light_config.radius_jitter = light_args.radius_jitter;
+        }
+
         light_config.no_notifications |= std::env::var("S3L_NO_NOTIFICATIONS").is_ok();
         light_config.debug |= std::env::var("S3L_DEBUG").is_ok();
 
@@ -313,21 +1255,17 @@ impl LightConfig {
             if out_dir.is_dir() {
                 light_config.output_dir = Some(out_dir);
             } else {
-                notification_box(
-                    "Can't find output location!",
-                    &format!(
-                        "WARNING: The requested output path {out_dir:?} does not exist! Terminating."
-                    ),
-                    light_config.no_notifications,
-                );
-                std::process::exit(1)
+                return Err(LightFixesError::OutputDir {
+                    path: out_dir,
+                    source: None,
+                });
             }
         // Otherwise, if there is neither an output directory specified by the config nor the CLI, use the default location,
         // Being data-local, if defined by the current openmw.cfg, or the current working directory
         } else if let None = light_config.output_dir {
-            light_config.output_dir = Some(match openmw_config.data_local() {
-                Some(path) => path.parsed().to_owned(),
-                None => std::env::current_dir().expect("Failed to get workdir!"),
+            light_config.output_dir = Some(match data_local {
+                Some(path) => path,
+                None => std::env::current_dir().map_err(LightFixesError::NoWorkingDirectory)?,
             });
         };
 
@@ -339,6 +1277,26 @@ impl LightConfig {
             .excluded_plugins
             .extend(std::mem::take(&mut light_args.excluded_plugins));
 
+        light_config
+            .force_off_by_default_ids
+            .extend(std::mem::take(&mut light_args.force_off_by_default_ids));
+
+        light_config
+            .strip_sounds_ids
+            .extend(std::mem::take(&mut light_args.strip_sounds_ids));
+
+        light_config
+            .disable_interior_sun_cells
+            .extend(std::mem::take(&mut light_args.disable_interior_sun_cells));
+
+        light_config
+            .preserve_ids
+            .extend(std::mem::take(&mut light_args.preserve_ids));
+
+        light_config
+            .ignored_data_dirs
+            .extend(std::mem::take(&mut light_args.ignored_data_dirs));
+
         light_config
             .light_overrides
             .extend(std::mem::take(&mut light_args.light_overrides));
@@ -347,6 +1305,29 @@ impl LightConfig {
             .ambient_overrides
             .extend(std::mem::take(&mut light_args.ambient_overrides));
 
+        // --cell-sunlight and --cell-ambient only replace their own color of an existing override
+        for (id, sunlight) in std::mem::take(&mut light_args.cell_sunlight) {
+            if let Some(cell_ambient) = light_config.ambient_overrides.get_mut(&id) {
+                cell_ambient.sunlight = Some(sunlight);
+                cell_ambient.sunlight_mult = None;
+            } else {
+                let mut cell_ambient = CustomCellAmbient::default();
+                cell_ambient.sunlight = Some(sunlight);
+                light_config.ambient_overrides.insert(id, cell_ambient);
+            }
+        }
+
+        for (id, ambient) in std::mem::take(&mut light_args.cell_ambient) {
+            if let Some(cell_ambient) = light_config.ambient_overrides.get_mut(&id) {
+                cell_ambient.ambient = Some(ambient);
+                cell_ambient.ambient_mult = None;
+            } else {
+                let mut cell_ambient = CustomCellAmbient::default();
+                cell_ambient.ambient = Some(ambient);
+                light_config.ambient_overrides.insert(id, cell_ambient);
+            }
+        }
+
         // This parameter indicates whether the user requested
         // To use compatibility mode for vtastek's old 0.47 shaders
         // via startup arguments
@@ -356,104 +1337,307 @@ impl LightConfig {
             light_config.disable_interior_sun = true;
         }
 
+        light_config
+            .validate()
+            .map_err(LightFixesError::InvalidConfig)?;
+
         // If the configuration file didn't exist when we tried to find it, or the user specified to update
         // serialize it here
         if write_config || light_config.save_config || light_args.update_light_config {
-            let config_serialized = toml::to_string_pretty(&light_config).map_err(to_io_error)?;
+            let config_serialized = match replaced_values.is_empty() {
+                true => toml::to_string_pretty(&light_config)
+                    .map_err(LightFixesError::ConfigSerialize)?,
+                // The profile's values stay in the profile, rather than replacing the top-level ones for good
+                false => {
+                    let mut config_table = toml::Table::try_from(&light_config)
+                        .map_err(LightFixesError::ConfigSerialize)?;
+
+                    for (key, value) in replaced_values {
+                        match value {
+                            Some(value) => config_table.insert(key, value),
+                            None => config_table.remove(&key),
+                        };
+                    }
+
+                    toml::to_string_pretty(&config_table)
+                        .map_err(LightFixesError::ConfigSerialize)?
+                }
+            };
 
             let config_path = user_config_path.join(DEFAULT_CONFIG_NAME);
             let mut config_file = File::create(config_path)?;
             write!(config_file, "{}", config_serialized)?;
         }
 
+        // Only filled in after saving, so that lightconfig.toml keeps following openmw.cfg
+        if light_config.timescale.is_none() {
+            light_config.timescale = openmw_config.and_then(openmw_timescale);
+        }
+
+        // Printed as it's merged, before its patterns are compiled and taken out of it
+        if light_args.print_config {
+            return Ok(light_config);
+        }
+
+        light_config.fingerprint = config_fingerprint(&light_config);
+
         // Consume the original values *after* reserializing the config
-        std::mem::take(&mut light_config.excluded_ids)
-            .into_iter()
-            .for_each(|id| {
-                match regex::Regex::new(&id) {
-                    Ok(pattern) => light_config.excluded_id_regexes.push(pattern),
-                    Err(error) => {
-                        notification_box(
-                            "Invalid excluded id regex!",
-                            &format!("Couldn't compile excluded id regex: {id}: {error}"),
-                            light_config.no_notifications,
-                        );
-                    }
-                };
-            });
+        for id in std::mem::take(&mut light_config.excluded_ids) {
+            let (plugin_pattern, id_pattern) = match id.split_once("::") {
+                Some((plugin, id_pattern)) => (Some(plugin), id_pattern),
+                None => (None, id.as_str()),
+            };
 
-        std::mem::take(&mut light_config.excluded_plugins)
-            .into_iter()
-            .for_each(|id| {
-                match regex::Regex::new(&id) {
-                    Ok(pattern) => light_config.excluded_plugin_regexes.push(pattern),
-                    Err(error) => {
-                        notification_box(
-                            "Invalid excluded plugin regex!",
-                            &format!("Couldn't compile excluded plugin regex: {id}: {error}"),
-                            light_config.no_notifications,
-                        );
-                    }
-                };
-            });
+            let compiled_plugin = plugin_pattern.map(compile_plugin_pattern).transpose();
 
-        std::mem::take(&mut light_config.light_overrides)
-            .into_iter()
-            .for_each(|(id, light_data)| {
-                match regex::Regex::new(&id) {
-                    Ok(pattern) => light_config.light_regexes.push((pattern, light_data)),
-                    Err(error) => {
-                        notification_box(
-                            "Invalid light override!",
-                            &format!("Couldn't compile light override regex: {id}: {error}"),
-                            light_config.no_notifications,
-                        );
-                    }
-                };
-            });
+            match compiled_plugin.and_then(|plugin| Ok((plugin, regex::Regex::new(id_pattern)?))) {
+                Ok(patterns) => light_config.excluded_id_regexes.push(patterns),
+                Err(source) => return Err(invalid_pattern(id, "excluded_ids", source)),
+            };
+        }
 
-        std::mem::take(&mut light_config.ambient_overrides)
-            .into_iter()
-            .for_each(|(id, light_data)| {
-                match regex::Regex::new(&id) {
-                    Ok(pattern) => light_config.ambient_regexes.push((pattern, light_data)),
-                    Err(error) => {
-                        notification_box(
-                            "Invalid ambient override!",
-                            &format!("Couldn't compile ambient override regex: {id}: {error}"),
-                            light_config.no_notifications,
-                        );
-                    }
-                };
-            });
+        for id in std::mem::take(&mut light_config.excluded_plugins) {
+            let pattern = compile_pattern(id, "excluded_plugins")?;
+            light_config.excluded_plugin_regexes.push(pattern);
+        }
+
+        for id in std::mem::take(&mut light_config.force_off_by_default_ids) {
+            let pattern = compile_pattern(id, "force_off_by_default_ids")?;
+            light_config.force_off_by_default_regexes.push(pattern);
+        }
+
+        for id in std::mem::take(&mut light_config.strip_sounds_ids) {
+            let pattern = compile_pattern(id, "strip_sounds_ids")?;
+            light_config.strip_sounds_regexes.push(pattern);
+        }
+
+        for id in std::mem::take(&mut light_config.disable_interior_sun_cells) {
+            let pattern = compile_pattern(id, "disable_interior_sun_cells")?;
+            light_config.disable_interior_sun_regexes.push(pattern);
+        }
+
+        for id in std::mem::take(&mut light_config.preserve_ids) {
+            let pattern = compile_pattern(id, "preserve_ids")?;
+            light_config.preserve_id_regexes.push(pattern);
+        }
+
+        for dir in std::mem::take(&mut light_config.ignored_data_dirs) {
+            let pattern = compile_pattern(dir, "ignored_data_dirs")?;
+            light_config.ignored_data_dir_regexes.push(pattern);
+        }
+
+        for (id, light_data) in std::mem::take(&mut light_config.light_overrides) {
+            let compiled_plugin = light_data
+                .plugin
+                .as_deref()
+                .map(compile_plugin_pattern)
+                .transpose();
+
+            match compiled_plugin.and_then(|plugin| Ok((regex::Regex::new(&id)?, plugin))) {
+                Ok((pattern, plugin)) => light_config
+                    .light_regexes
+                    .push((pattern, plugin, light_data)),
+                Err(source) => return Err(invalid_pattern(id, "light_overrides", source)),
+            };
+        }
+
+        // Higher priorities are checked first, and the sort is stable so ties keep their declaration order
+        light_config
+            .light_regexes
+            .sort_by_key(|(_, _, light_data)| std::cmp::Reverse(light_data.priority()));
+
+        for (id, light_data) in std::mem::take(&mut light_config.ambient_overrides) {
+            let exterior_grid = match light_data.grid {
+                Some([x, y]) => Some(Ok(ExteriorGrid::cell(x, y))),
+                None => ExteriorGrid::from_key(&id),
+            };
+
+            match exterior_grid {
+                Some(Ok(grid)) => light_config.exterior_ambients.push((grid, light_data)),
+                Some(Err(source)) => {
+                    return Err(invalid_pattern(id, "ambient_overrides", source));
+                }
+                None => {
+                    let pattern = compile_pattern(id, "ambient_overrides")?;
+                    light_config.ambient_regexes.push((pattern, light_data));
+                }
+            };
+        }
 
         Ok(light_config)
     }
 
+    /// The timescale `target_real_minutes` is measured at
+    pub fn effective_timescale(&self) -> f32 {
+        self.timescale.unwrap_or_else(default::timescale)
+    }
+
+    /// Resolves `interior_sunlight_mult` for a cell, which `disable_interior_sun` forces to 0.0
+    /// in every interior, or only those matching `disable_interior_sun_cells` when any are given
+    pub fn effective_interior_sunlight_mult(&self, cell_id: &str) -> f32 {
+        let sun_disabled = self.disable_interior_sun
+            && (self.disable_interior_sun_regexes.is_empty()
+                || self
+                    .disable_interior_sun_regexes
+                    .iter()
+                    .any(|pattern| pattern.is_match(cell_id)));
+
+        match sun_disabled {
+            true => 0.0,
+            false => self.interior_sunlight_mult,
+        }
+    }
+
+    /// Resolves `flicker_mode`, falling back to the deprecated `disable_flickering`
+    pub fn effective_flicker_mode(&self) -> FlickerMode {
+        self.flicker_mode.unwrap_or(match self.disable_flickering {
+            true => FlickerMode::Remove,
+            false => FlickerMode::Keep,
+        })
+    }
+
+    pub fn is_ignored_data_dir(&self, dir: &std::path::Path) -> bool {
+        let dir = dir.to_string_lossy();
+
+        self.ignored_data_dir_regexes
+            .iter()
+            .any(|pattern| pattern.is_match(&dir))
+    }
+
     pub fn is_excluded_plugin(&self, plugin_path: &std::path::Path) -> bool {
-        let file_name = match plugin_path.file_name() {
-            None => return false,
-            Some(name) => name.to_ascii_lowercase().into_string().unwrap_or_default(),
-        };
+        self.excluding_plugin_pattern(plugin_path).is_some()
+    }
 
-        for pattern in &self.excluded_plugin_regexes {
-            if pattern.is_match(&file_name) {
-                return true;
+    /// The first `excluded_plugins` pattern matching the name of a plugin, if any
+    pub fn excluding_plugin_pattern(&self, plugin_path: &std::path::Path) -> Option<&regex::Regex> {
+        if plugin_path.file_name().is_none() {
+            return None;
+        }
+
+        let file_name = plugin_file_name(plugin_path);
+
+        self.excluded_plugin_regexes
+            .iter()
+            .find(|pattern| pattern.is_match(&file_name))
+    }
+
+    /// Gives back the highest priority light override whose pattern matches the id of a light,
+    /// whose plugin pattern (if any) matches the plugin it came from,
+    /// and whose matchers all accept the light as it is before processing.
+    /// Overrides with `accumulate` set only contribute the fields they set,
+    /// leaving lower priority matches to fill in the rest.
+    pub fn light_override(
+        &self,
+        light: &tes3::esp::Light,
+        plugin_name: &str,
+        placement: Option<LightPlacement>,
+    ) -> Option<Cow<'_, CustomLightData>> {
+        self.matched_override(light, plugin_name, placement)
+            .map(|(_, light_data)| light_data)
+    }
+
+    /// Like [`Self::light_override`], but also gives back the key of the highest priority override which matched
+    pub fn matched_override(
+        &self,
+        light: &tes3::esp::Light,
+        plugin_name: &str,
+        placement: Option<LightPlacement>,
+    ) -> Option<(&str, Cow<'_, CustomLightData>)> {
+        let record_id = light.editor_id_ascii_lowercase();
+        let hue = color_to_hsv(light.data.color, self.linear_color)
+            .hue
+            .into_positive_degrees();
+
+        let mut matches = self
+            .light_regexes
+            .iter()
+            .filter(|(pattern, plugin_pattern, light_data)| {
+                pattern.is_match(&record_id)
+                    && plugin_pattern
+                        .as_ref()
+                        .is_none_or(|plugin_pattern| plugin_pattern.is_match(plugin_name))
+                    && light_data.matches_hue(hue)
+                    && light_data.matches_radius(light.data.radius)
+                    && light_data.matches_flags(light.data.flags)
+                    && light_data.matches_name(&light.name)
+                    && self.matches_placement(&record_id, light_data, placement)
+            })
+            .map(|(pattern, _, light_data)| (pattern.as_str(), light_data));
+
+        let (key, first) = matches.next()?;
+
+        if !first.accumulates() {
+            return Some((key, Cow::Borrowed(first)));
+        }
+
+        let mut merged = first.clone();
+
+        for (_, light_data) in matches {
+            merged.fill_from(light_data);
+
+            if !light_data.accumulates() {
+                break;
             }
         }
 
-        false
+        Some((key, Cow::Owned(merged)))
+    }
+
+    /// Whether any light override is limited to interiors or exteriors,
+    /// which requires knowing where every light is placed
+    pub fn has_placement_overrides(&self) -> bool {
+        self.light_regexes
+            .iter()
+            .any(|(_, _, light_data)| light_data.is_placement_scoped())
     }
 
-    pub fn is_excluded_id(&self, record_id: &str) -> bool {
-        for pattern in &self.excluded_id_regexes {
-            if pattern.is_match(record_id) {
+    /// Lights placed in both interiors and exteriors are never guessed at, but noted while debugging
+    fn matches_placement(
+        &self,
+        record_id: &str,
+        light_data: &CustomLightData,
+        placement: Option<LightPlacement>,
+    ) -> bool {
+        let matches = light_data.matches_placement(placement);
+
+        if !matches
+            && self.debug
+            && placement.is_some_and(|placement| placement.interior && placement.exterior)
+        {
+            print_notification(&format!(
+                "[ DEBUG ]: Skipping an interior_only or exterior_only override for {record_id}, as it is placed in both interiors and exteriors"
+            ));
+        }
+
+        matches
+    }
+
+    /// Whether a record id is excluded, either everywhere or in the plugin it came from
+    pub fn is_excluded_id(&self, record_id: &str, plugin_name: &str) -> bool {
+        for (plugin_pattern, pattern) in &self.excluded_id_regexes {
+            let in_plugin = plugin_pattern
+                .as_ref()
+                .is_none_or(|plugin_pattern| plugin_pattern.is_match(plugin_name));
+
+            if in_plugin && pattern.is_match(record_id) {
                 return true;
             };
         }
 
         false
     }
+
+    pub fn is_forced_off_by_default(&self, record_id: &str) -> bool {
+        self.force_off_by_default_regexes
+            .iter()
+            .any(|pattern| pattern.is_match(record_id))
+    }
+
+    pub fn is_sound_stripped(&self, record_id: &str) -> bool {
+        self.strip_sounds_regexes
+            .iter()
+            .any(|pattern| pattern.is_match(record_id))
+    }
 }
 
 impl Default for LightConfig {
@@ -464,27 +1648,585 @@ impl Default for LightConfig {
             no_notifications: false,
             output_dir: None,
             disable_interior_sun: false,
+            interior_sunlight_mult: default::interior_sunlight_mult(),
+            fog_density_mult: default::fog_density_mult(),
             disable_flickering: default::disable_flicker(),
+            flicker_mode: None,
             disable_pulse: default::disable_pulse(),
+            flags_only: false,
+            skip_unchanged: false,
+            only_referenced: false,
+            clear_water_height: false,
+            process_quasi_exteriors: false,
+            merge_cell_atmosphere: false,
+            process_magic_effects: false,
+            adjust_weather_fallbacks: false,
+            weather_hue: default::weather_mult(),
+            weather_saturation: default::weather_mult(),
+            weather_value: default::weather_mult(),
+            magic_saturation: None,
+            magic_value: None,
+            emit_json: None,
+            openmw_cfg: None,
+            skip_zero_radius: default::skip_zero_radius(),
+            clear_references: default::clear_references(),
             save_log: default::save_log(),
+            keep_backups: default::keep_backups(),
+            overwrite_policy: OverwritePolicy::default(),
+            write_metadata: default::write_metadata(),
+            verify: false,
+            diff_details: false,
+            strict: false,
+            vfs_report: false,
+            process_groundcover: false,
+            clean_stale: false,
             auto_enable: default::auto_enable(),
+            no_reorder: false,
             standard_hue: default::standard_hue(),
             standard_saturation: default::standard_saturation(),
             standard_value: default::standard_value(),
             standard_radius: default::standard_radius(),
+            standard_radius_add: default::standard_radius_add(),
             colored_hue: default::colored_hue(),
             colored_saturation: default::colored_saturation(),
             colored_value: default::colored_value(),
             colored_radius: default::colored_radius(),
+            colored_radius_add: default::colored_radius_add(),
+            radius_exponent: None,
+            radius_curve_base: default::radius_curve_base(),
             duration_mult: default::duration_mult(),
+            standard_duration_mult: None,
+            colored_duration_mult: None,
+            target_real_minutes: None,
+            timescale: None,
+            carryable_radius_mult: default::carryable_radius_mult(),
+            carryable_value_mult: default::carryable_value_mult(),
+            carryable_duration_mult: default::carryable_duration_mult(),
+            weight_mult: default::weight_mult(),
+            gold_value_mult: default::gold_value_mult(),
+            standard_normalize_value: None,
+            colored_normalize_value: None,
+            normalize_max_adjustment: default::normalize_max_adjustment(),
+            standard_tint: None,
+            standard_tint_strength: default::tint_strength(),
+            colored_tint: None,
+            colored_tint_strength: default::tint_strength(),
+            linear_color: false,
+            negative_light_mode: NegativeLightMode::default(),
+            hue_overflow: HueOverflow::default(),
+            output_file_type: OutputFileType::default(),
+            output_format: OutputFormat::default(),
+            max_masters: default::max_masters(),
+            split_masters: false,
+            split_output: false,
+            plugin_author: default::plugin_author(),
+            plugin_description: default::plugin_description(),
+            min_duration: None,
+            min_value: default::min_value(),
+            min_saturation: None,
+            max_saturation: None,
+            min_radius: None,
+            max_radius: None,
+            hue_jitter: None,
+            value_jitter: None,
+            radius_jitter: None,
             excluded_ids: Vec::new(),
             excluded_plugins: default::excluded_plugins(),
+            force_off_by_default_ids: Vec::new(),
+            force_off_by_default_regexes: Vec::new(),
+            strip_sounds_ids: Vec::new(),
+            strip_sounds_regexes: Vec::new(),
+            disable_interior_sun_cells: Vec::new(),
+            disable_interior_sun_regexes: Vec::new(),
+            preserve_ids: Vec::new(),
+            preserve_id_regexes: Vec::new(),
+            ignored_data_dirs: Vec::new(),
+            ignored_data_dir_regexes: Vec::new(),
             excluded_id_regexes: Vec::new(),
             excluded_plugin_regexes: Vec::new(),
             light_regexes: Vec::new(),
             light_overrides: OrderedHashMap::new(),
             ambient_overrides: OrderedHashMap::new(),
             ambient_regexes: Vec::new(),
+            exterior_ambients: Vec::new(),
+            fingerprint: String::new(),
+            profiles: toml::Table::new(),
+            applied_profile: None,
+        }
+    }
+}
+
+/// Short, stable hash of a light config, which the generated plugin carries in its description so that
+/// support tools can tell which config made it. The config is hashed as a TOML table, whose keys are always sorted,
+/// so the order they're written in lightconfig.toml doesn't matter. `LightConfig::get` takes it before
+/// compiling the config's patterns, which empties the lists they came from
+pub fn config_fingerprint(light_config: &LightConfig) -> String {
+    let canonical = toml::Value::try_from(light_config)
+        .map(|value| value.to_string())
+        .unwrap_or_default();
+
+    format!("{:08x}", fnv1a(canonical.into_bytes()) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tes3::esp::LightFlags;
+
+    use crate::test_fixtures::{light, light_override, load_config};
+
+    fn matched_key(light_config: &LightConfig, light: &tes3::esp::Light) -> Option<String> {
+        light_config
+            .matched_override(light, "", None)
+            .map(|(key, _)| key.to_owned())
+    }
+
+    #[test]
+    fn min_radius_may_not_exceed_max_radius() {
+        let light_config = LightConfig {
+            min_radius: Some(512),
+            max_radius: Some(256),
+            ..Default::default()
+        };
+        assert!(light_config.validate().is_err());
+
+        let light_config = LightConfig {
+            min_radius: Some(256),
+            max_radius: Some(256),
+            ..Default::default()
+        };
+        assert!(light_config.validate().is_ok());
+    }
+
+    #[test]
+    fn saturation_clamps_are_validated() {
+        for (min_saturation, max_saturation) in [
+            (Some(0.8), Some(0.2)),
+            (Some(-0.1), None),
+            (None, Some(1.5)),
+        ] {
+            let light_config = LightConfig {
+                min_saturation,
+                max_saturation,
+                ..Default::default()
+            };
+            assert!(
+                light_config.validate().is_err(),
+                "{min_saturation:?} - {max_saturation:?} should have been rejected"
+            );
         }
+
+        let light_config = LightConfig {
+            min_saturation: Some(0.2),
+            max_saturation: Some(0.8),
+            ..Default::default()
+        };
+        assert!(light_config.validate().is_ok());
+    }
+
+    #[test]
+    fn normalize_value_excludes_the_value_multiplier() {
+        let light_config = LightConfig {
+            colored_normalize_value: Some(0.6),
+            colored_value: 0.5,
+            ..Default::default()
+        };
+        assert!(light_config.validate().is_err());
+
+        let light_config = LightConfig {
+            colored_normalize_value: Some(0.6),
+            ..Default::default()
+        };
+        assert!(light_config.validate().is_ok());
+    }
+
+    #[test]
+    fn radius_ranges_dont_shadow_later_overrides() {
+        let light_config = LightConfig {
+            light_regexes: vec![
+                light_override("^candle", "match_radius_max=95,radius=128"),
+                light_override(".*", "radius_mult=2"),
+            ],
+            ..Default::default()
+        };
+
+        let small_candle = light("candle_small", [255, 160, 64], 64);
+        let large_candle = light("candle_large", [255, 160, 64], 200);
+
+        assert_eq!(
+            matched_key(&light_config, &small_candle).as_deref(),
+            Some("^candle")
+        );
+        assert_eq!(
+            matched_key(&light_config, &large_candle).as_deref(),
+            Some(".*")
+        );
+    }
+
+    #[test]
+    fn matchers_must_all_hold() {
+        let light_config = LightConfig {
+            light_regexes: vec![light_override(
+                "^candle",
+                "match_radius_max=95,match_hue_min=180,match_hue_max=260,saturation_mult=0.5",
+            )],
+            ..Default::default()
+        };
+
+        let orange_candle = light("candle_orange", [255, 160, 64], 64);
+        let blue_candle = light("candle_blue", [0, 0, 255], 64);
+        let large_blue_candle = light("candle_blue_large", [0, 0, 255], 200);
+
+        assert!(matched_key(&light_config, &orange_candle).is_none());
+        assert!(matched_key(&light_config, &blue_candle).is_some());
+        assert!(matched_key(&light_config, &large_blue_candle).is_none());
+    }
+
+    #[test]
+    fn flicker_rules_skip_pulsing_lights() {
+        let light_config = LightConfig {
+            light_regexes: vec![light_override(".*", "match_flags=FLICKER,value_mult=1.5")],
+            ..Default::default()
+        };
+
+        let mut flickering_light = light("flickering_light", [255, 160, 64], 128);
+        flickering_light.data.flags = LightFlags::FLICKER | LightFlags::CAN_CARRY;
+
+        let mut pulsing_light = light("pulsing_light", [255, 160, 64], 128);
+        pulsing_light.data.flags = LightFlags::PULSE;
+
+        assert!(matched_key(&light_config, &flickering_light).is_some());
+        assert!(matched_key(&light_config, &pulsing_light).is_none());
+    }
+
+    #[test]
+    fn flag_rules_see_flags_from_before_flicker_removal() {
+        let light_config = LightConfig {
+            disable_flickering: true,
+            light_regexes: vec![light_override(".*", "match_flags=FLICKER,radius=300")],
+            ..Default::default()
+        };
+
+        let mut flickering_light = light("flickering_light", [255, 160, 64], 128);
+        flickering_light.data.flags = LightFlags::FLICKER;
+
+        let processed = crate::simulate_light(&light_config, &flickering_light);
+        assert_eq!(processed.radius, 300);
+        assert!(!processed.flags.contains(LightFlags::FLICKER));
+    }
+
+    #[test]
+    fn higher_priorities_win_and_ties_keep_their_order() {
+        let overrides = r#"
+            [light_overrides."^torch"]
+            radius = 100
+
+            [light_overrides."^torch_01"]
+            radius = 300
+        "#;
+
+        let light_config = load_config("override-order", overrides, &[]);
+        let torch = light("torch_01", [255, 160, 64], 128);
+        assert_eq!(
+            matched_key(&light_config, &torch).as_deref(),
+            Some("^torch")
+        );
+
+        let prioritized = format!("{overrides}priority = 10\n");
+        let light_config = load_config("override-priority", &prioritized, &[]);
+        assert_eq!(
+            matched_key(&light_config, &torch).as_deref(),
+            Some("^torch_01")
+        );
+
+        let other_torch = light("torch_02", [255, 160, 64], 128);
+        assert_eq!(
+            matched_key(&light_config, &other_torch).as_deref(),
+            Some("^torch")
+        );
+    }
+
+    #[test]
+    fn accumulating_overrides_are_filled_in_by_later_ones() {
+        let broad_rule = light_override(".*", "radius=100,value_mult=0.5");
+        let torch = light("torch_01", [255, 160, 64], 128);
+
+        let light_config = LightConfig {
+            light_regexes: vec![light_override("^torch", "radius=300"), broad_rule.clone()],
+            ..Default::default()
+        };
+        let (_, light_data) = light_config.matched_override(&torch, "", None).unwrap();
+        assert_eq!(light_data.radius, Some(300));
+        assert_eq!(light_data.value_mult, None);
+
+        let light_config = LightConfig {
+            light_regexes: vec![
+                light_override("^torch", "radius=300,accumulate=true"),
+                broad_rule,
+            ],
+            ..Default::default()
+        };
+        let (key, light_data) = light_config.matched_override(&torch, "", None).unwrap();
+        assert_eq!(key, "^torch");
+        assert_eq!(light_data.radius, Some(300));
+        assert_eq!(light_data.value_mult, Some(0.5));
+    }
+
+    #[test]
+    fn interior_sun_is_disabled_only_in_matching_cells() {
+        let light_config = load_config(
+            "interior-sun-cells",
+            "disable_interior_sun = true\ndisable_interior_sun_cells = [\"^vivec\"]\n",
+            &[],
+        );
+
+        assert_eq!(
+            light_config.effective_interior_sunlight_mult("vivec, arena"),
+            0.0
+        );
+        assert_eq!(
+            light_config.effective_interior_sunlight_mult("sadrith mora, wolverine hall"),
+            1.0
+        );
+
+        let light_config = load_config("interior-sun-all", "disable_interior_sun = true\n", &[]);
+        assert_eq!(
+            light_config.effective_interior_sunlight_mult("sadrith mora, wolverine hall"),
+            0.0
+        );
+    }
+
+    #[test]
+    fn cli_cell_colors_reach_ambient_regexes() {
+        let light_config = load_config(
+            "cli-cell-colors",
+            "",
+            &[
+                "--cell-sunlight",
+                "balmora, guild of mages=0,0,0",
+                "--cell-ambient",
+                "balmora, guild of mages=hue=30,saturation=0.5,value=0.25",
+                "--cell-ambient",
+                "ald-ruhn, temple=200,0.2,0.4",
+            ],
+        );
+
+        let ambient_override = |cell_id: &str| {
+            light_config
+                .ambient_regexes
+                .iter()
+                .find(|(pattern, _)| pattern.is_match(cell_id))
+                .map(|(_, cell_ambient)| cell_ambient)
+                .unwrap()
+        };
+
+        let guild = ambient_override("balmora, guild of mages");
+        assert_eq!(guild.sunlight.as_ref().unwrap().value, 0.0);
+        assert_eq!(guild.ambient.as_ref().unwrap().hue, 30);
+        assert!(guild.fog.is_none() && guild.fog_density.is_none());
+
+        let temple = ambient_override("ald-ruhn, temple");
+        assert_eq!(temple.ambient.as_ref().unwrap().hue, 200);
+        assert!(temple.sunlight.is_none());
+    }
+
+    #[test]
+    fn fingerprint_is_stable() {
+        let light_config = LightConfig {
+            standard_radius: 1.5,
+            ..Default::default()
+        };
+        let fingerprint = config_fingerprint(&light_config);
+
+        assert_eq!(fingerprint.len(), 8);
+        assert!(fingerprint.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert_eq!(config_fingerprint(&light_config), fingerprint);
+    }
+
+    #[test]
+    fn fingerprint_ignores_key_order() {
+        let first = load_config(
+            "fingerprint-order-a",
+            "standard_radius = 1.5\ncolored_value = 0.8\n",
+            &[],
+        );
+        let second = load_config(
+            "fingerprint-order-b",
+            "colored_value = 0.8\nstandard_radius = 1.5\n",
+            &[],
+        );
+
+        assert!(!first.fingerprint.is_empty());
+        assert_eq!(first.fingerprint, second.fingerprint);
+    }
+
+    #[test]
+    fn fingerprint_changes_with_a_multiplier() {
+        let fingerprint = |standard_radius| {
+            config_fingerprint(&LightConfig {
+                standard_radius,
+                ..Default::default()
+            })
+        };
+
+        assert_ne!(fingerprint(1.5), fingerprint(1.6));
+
+        let from_file = load_config("fingerprint-file", "standard_radius = 1.5\n", &[]);
+        let from_cli = load_config("fingerprint-cli", "standard_radius = 1.5\n", &["-r", "1.6"]);
+        assert_ne!(from_file.fingerprint, from_cli.fingerprint);
+    }
+
+    #[test]
+    fn overrides_and_exclusions_may_be_scoped_to_a_plugin() {
+        let light_config = load_config(
+            "plugin-scoped",
+            r#"
+excluded_ids = ["Fires\\.esp::^torch$", "^candle$"]
+
+[light_overrides."^lantern"]
+plugin = "Lanterns\\.esp"
+radius = 512
+"#,
+            &[],
+        );
+
+        assert!(light_config.is_excluded_id("torch", "fires.esp"));
+        assert!(!light_config.is_excluded_id("torch", "lanterns.esp"));
+        assert!(light_config.is_excluded_id("candle", "fires.esp"));
+        assert!(light_config.is_excluded_id("candle", "lanterns.esp"));
+
+        let lantern = light("lantern", [255, 160, 64], 128);
+        assert!(
+            light_config
+                .matched_override(&lantern, "lanterns.esp", None)
+                .is_some()
+        );
+        assert!(
+            light_config
+                .matched_override(&lantern, "fires.esp", None)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn plugin_patterns_keep_uppercase_escapes() {
+        let light_config = load_config(
+            "plugin-pattern-escapes",
+            r#"
+excluded_ids = ["^Torches\\W\\D+\\.esp::^torch$"]
+
+[light_overrides."^lantern"]
+plugin = "^Lanterns\\W\\D+\\.esp"
+radius = 512
+"#,
+            &[],
+        );
+
+        assert!(light_config.is_excluded_id("torch", "torches - lit.esp"));
+        assert!(!light_config.is_excluded_id("torch", "torches 2.esp"));
+
+        let lantern = light("lantern", [255, 160, 64], 128);
+        assert!(
+            light_config
+                .matched_override(&lantern, "lanterns - lit.esp", None)
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn excluding_plugin_pattern_names_the_first_match() {
+        let light_config = LightConfig {
+            excluded_plugin_regexes: ["^fires", r"\.esp$"]
+                .into_iter()
+                .map(|pattern| regex::Regex::new(pattern).unwrap())
+                .collect(),
+            ..crate::test_fixtures::neutral_config()
+        };
+
+        let pattern = |plugin_path: &str| {
+            light_config
+                .excluding_plugin_pattern(std::path::Path::new(plugin_path))
+                .map(|pattern| pattern.as_str().to_owned())
+        };
+
+        assert_eq!(pattern("data/Fires.esp").as_deref(), Some("^fires"));
+        assert_eq!(pattern("data/Lanterns.esp").as_deref(), Some(r"\.esp$"));
+        assert_eq!(pattern("data/Lanterns.omwaddon"), None);
+        assert_eq!(pattern(".."), None);
+        assert!(light_config.is_excluded_plugin(std::path::Path::new("Fires.omwaddon")));
+    }
+
+    #[test]
+    fn profile_paths_ignore_trailing_separators_and_the_file_name() {
+        assert_eq!(profile_path_key("/games/openmw/"), "/games/openmw");
+        assert_eq!(
+            profile_path_key(" /games/openmw/openmw.cfg "),
+            "/games/openmw"
+        );
+        assert_eq!(
+            profile_path_key("/games/openmw//OpenMW.cfg"),
+            "/games/openmw"
+        );
+        assert_eq!(profile_path_key("/"), "/");
+    }
+
+    /// Loads a lightconfig.toml holding `contents` for the openmw.cfg at `openmw_cfg`
+    fn profiled_config(
+        contents: &str,
+        args: &[&str],
+        openmw_cfg: &str,
+    ) -> Result<LightConfig, LightFixesError> {
+        let dir = crate::test_fixtures::temp_dir("profiles");
+        std::fs::write(dir.join(crate::DEFAULT_CONFIG_NAME), contents).unwrap();
+
+        LightConfig::get(
+            crate::LightArgs::parse_from(
+                std::iter::once("s3lightfixes").chain(args.iter().copied()),
+            ),
+            &dir,
+            Some(dir.clone()),
+            Path::new(openmw_cfg),
+            None,
+        )
+    }
+
+    #[test]
+    fn profiles_apply_by_name_or_openmw_cfg_path() {
+        let contents = r#"
+            standard_radius = 1.5
+            standard_value = 0.8
+
+            [profiles.dim]
+            standard_value = 0.5
+
+            [profiles."/games/openmw"]
+            standard_radius = 3.0
+        "#;
+
+        let by_path = profiled_config(contents, &[], "/games/openmw/openmw.cfg").unwrap();
+        assert_eq!(by_path.applied_profile.as_deref(), Some("/games/openmw"));
+        assert_eq!(by_path.standard_radius, 3.0);
+        assert_eq!(by_path.standard_value, 0.8);
+
+        // Naming a profile wins over the path, and arguments still win over the profile
+        let by_name = profiled_config(
+            contents,
+            &["--profile", "dim", "--standard-radius", "2.0"],
+            "/games/openmw/openmw.cfg",
+        )
+        .unwrap();
+        assert_eq!(by_name.applied_profile.as_deref(), Some("dim"));
+        assert_eq!(by_name.standard_radius, 2.0);
+        assert_eq!(by_name.standard_value, 0.5);
+
+        let unmatched = profiled_config(contents, &[], "/games/other/openmw.cfg").unwrap();
+        assert_eq!(unmatched.applied_profile, None);
+        assert_eq!(unmatched.standard_radius, 1.5);
+
+        assert!(matches!(
+            profiled_config(contents, &["--profile", "bright"], "/games/openmw/openmw.cfg"),
+            Err(LightFixesError::UnknownProfile(profile_name)) if profile_name == "bright"
+        ));
     }
 }
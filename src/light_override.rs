@@ -1,4 +1,4 @@
-use std::{fmt, str::FromStr};
+use std::{fmt, ops::RangeInclusive, str::FromStr};
 
 use serde::{Deserialize, Serialize};
 
@@ -10,6 +10,11 @@ pub enum ParseLightError {
     BadNumber(&'static str, String),
     MissingPrefix,
     UnknownVariant(String),
+    BadBool(&'static str, String),
+    EmptyFlags,
+    BadPath(&'static str, String),
+    BadRegex(&'static str, String),
+    BadColor(&'static str, String),
 }
 
 impl std::fmt::Display for ParseLightError {
@@ -24,15 +29,47 @@ impl std::fmt::Display for ParseLightError {
             UnknownField(k) => write!(f, "Unknown field: `{k}`"),
             BadNumber(field, e) => write!(f, "Invalid number for `{field}`: {e}"),
             MissingPrefix => write!(f, "Missing type prefix (e.g., `Fixed:` or `Mult:`)"),
-            UnknownVariant(v) => {
-                write!(f, "Unknown light type: `{v}` (expected `Fixed` or `Mult`)")
-            }
+            UnknownVariant(v) => write!(
+                f,
+                "Unknown light flag: `{v}` (expected NONE, FLICKER, FLICKERSLOW, PULSE, PULSESLOW, OFFDEFAULT, DYNAMIC, CANCARRY, or FIRE)"
+            ),
+            BadBool(field, e) => write!(f, "Invalid boolean for `{field}`: {e}"),
+            EmptyFlags => write!(f, "Expected at least one light flag"),
+            BadRegex(field, e) => write!(f, "Invalid regex for `{field}`: {e}"),
+            BadColor(field, color) => write!(
+                f,
+                "Invalid color for `{field}`: `{color}` (expected a hex color, EG: `#ff9944`)"
+            ),
+            BadPath(field, path) => write!(
+                f,
+                "Invalid path for `{field}`: `{path}` (expected a relative path, EG: `l\\light_com_lantern_01.nif`)"
+            ),
         }
     }
 }
 
 impl std::error::Error for ParseLightError {}
 
+/// Mesh and icon paths are relative to the meshes and icons folders of the VFS,
+/// so absolute paths, drive letters, and empty paths are rejected
+fn validate_vfs_path(field: &'static str, path: &str) -> Result<(), ParseLightError> {
+    let is_absolute = path.starts_with(['/', '\\']) || path.chars().nth(1) == Some(':');
+
+    if path.trim().is_empty() || is_absolute {
+        return Err(ParseLightError::BadPath(field, path.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Display names are matched regardless of case
+fn compile_name_pattern(pattern: &str) -> Result<regex::Regex, ParseLightError> {
+    regex::RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| ParseLightError::BadRegex("match_name", e.to_string()))
+}
+
 fn parse_pairs<F>(s: &str, mut set: F) -> Result<(), ParseLightError>
 where
     F: FnMut(&str, &str) -> Result<(), ParseLightError>,
@@ -52,56 +89,82 @@ impl FromStr for CustomLightData {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut data = CustomLightData::default();
 
+        // Fixed values, multipliers, and offsets of the same channel may not be combined
+        macro_rules! check_exclusive {
+            ($field:literal, $($other:ident),+) => {
+                $(
+                    if data.$other.is_some() {
+                        return Err(ParseLightError::ExclusiveFields(stringify!($other), $field));
+                    }
+                )+
+            };
+        }
+
         parse_pairs(s, |k, v| {
             match k {
                 "radius_mult" => {
-                    if let Some(_) = data.radius {
-                        return Err(ParseLightError::ExclusiveFields("radius", "radius_mult"));
-                    }
+                    check_exclusive!("radius_mult", radius, radius_add);
 
                     data.radius_mult = Some(v.parse().map_err(|e: std::num::ParseFloatError| {
                         ParseLightError::BadNumber("radius", e.to_string())
                     })?)
                 }
                 "hue_mult" => {
-                    if let Some(_) = data.hue {
-                        return Err(ParseLightError::ExclusiveFields("hue", "hue_mult"));
-                    }
+                    check_exclusive!("hue_mult", hue, hue_shift);
 
                     data.hue_mult = Some(v.parse().map_err(|e: std::num::ParseFloatError| {
                         ParseLightError::BadNumber("hue", e.to_string())
                     })?)
                 }
+                "hue_shift" => {
+                    check_exclusive!("hue_shift", hue, hue_mult);
+
+                    data.hue_shift = Some(v.parse().map_err(|e: std::num::ParseFloatError| {
+                        ParseLightError::BadNumber("hue_shift", e.to_string())
+                    })?)
+                }
                 "saturation_mult" => {
-                    if let Some(_) = data.saturation {
-                        return Err(ParseLightError::ExclusiveFields(
-                            "saturation",
-                            "saturation_mult",
-                        ));
-                    }
+                    check_exclusive!("saturation_mult", saturation, saturation_add);
 
                     data.saturation_mult =
                         Some(v.parse().map_err(|e: std::num::ParseFloatError| {
                             ParseLightError::BadNumber("saturation", e.to_string())
                         })?)
                 }
+                "saturation_add" => {
+                    check_exclusive!("saturation_add", saturation, saturation_mult);
+
+                    data.saturation_add =
+                        Some(v.parse().map_err(|e: std::num::ParseFloatError| {
+                            ParseLightError::BadNumber("saturation_add", e.to_string())
+                        })?)
+                }
                 "value_mult" => {
-                    if let Some(_) = data.value {
-                        return Err(ParseLightError::ExclusiveFields("value", "value_mult"));
-                    }
+                    check_exclusive!("value_mult", value, value_add);
 
                     data.value_mult = Some(v.parse().map_err(|e: std::num::ParseFloatError| {
                         ParseLightError::BadNumber("value_mult", e.to_string())
                     })?)
                 }
+                "value_add" => {
+                    check_exclusive!("value_add", value, value_mult);
+
+                    data.value_add = Some(v.parse().map_err(|e: std::num::ParseFloatError| {
+                        ParseLightError::BadNumber("value_add", e.to_string())
+                    })?)
+                }
+
+                "target_real_minutes" => {
+                    check_exclusive!("target_real_minutes", duration, duration_mult);
+
+                    data.target_real_minutes =
+                        Some(v.parse().map_err(|e: std::num::ParseFloatError| {
+                            ParseLightError::BadNumber("target_real_minutes", e.to_string())
+                        })?)
+                }
 
                 "duration_mult" => {
-                    if let Some(_) = data.duration {
-                        return Err(ParseLightError::ExclusiveFields(
-                            "duration",
-                            "duration_mult",
-                        ));
-                    }
+                    check_exclusive!("duration_mult", duration, target_real_minutes);
 
                     data.duration_mult =
                         Some(v.parse().map_err(|e: std::num::ParseFloatError| {
@@ -110,31 +173,62 @@ impl FromStr for CustomLightData {
                 }
 
                 "duration" => {
-                    if let Some(_) = data.duration_mult {
-                        return Err(ParseLightError::ExclusiveFields(
-                            "duration_mult",
-                            "duration",
-                        ));
-                    }
+                    check_exclusive!("duration", duration_mult, target_real_minutes);
 
                     data.duration = Some(v.parse().map_err(|e: std::num::ParseFloatError| {
                         ParseLightError::BadNumber("duration", e.to_string())
                     })?)
                 }
 
+                "weight_mult" => {
+                    check_exclusive!("weight_mult", weight);
+
+                    data.weight_mult = Some(v.parse().map_err(|e: std::num::ParseFloatError| {
+                        ParseLightError::BadNumber("weight_mult", e.to_string())
+                    })?)
+                }
+
+                "weight" => {
+                    check_exclusive!("weight", weight_mult);
+
+                    data.weight = Some(v.parse().map_err(|e: std::num::ParseFloatError| {
+                        ParseLightError::BadNumber("weight", e.to_string())
+                    })?)
+                }
+
+                "gold_value_mult" => {
+                    check_exclusive!("gold_value_mult", gold_value);
+
+                    data.gold_value_mult =
+                        Some(v.parse().map_err(|e: std::num::ParseFloatError| {
+                            ParseLightError::BadNumber("gold_value_mult", e.to_string())
+                        })?)
+                }
+
+                "gold_value" => {
+                    check_exclusive!("gold_value", gold_value_mult);
+
+                    data.gold_value = Some(v.parse().map_err(|e: std::num::ParseIntError| {
+                        ParseLightError::BadNumber("gold_value", e.to_string())
+                    })?)
+                }
+
                 "radius" => {
-                    if let Some(_) = data.radius_mult {
-                        return Err(ParseLightError::ExclusiveFields("radius_mult", "radius"));
-                    }
+                    check_exclusive!("radius", radius_mult, radius_add);
 
                     data.radius = Some(v.parse().map_err(|e: std::num::ParseIntError| {
                         ParseLightError::BadNumber("radius", e.to_string())
                     })?)
                 }
+                "radius_add" => {
+                    check_exclusive!("radius_add", radius, radius_mult);
+
+                    data.radius_add = Some(v.parse().map_err(|e: std::num::ParseIntError| {
+                        ParseLightError::BadNumber("radius_add", e.to_string())
+                    })?)
+                }
                 "hue" => {
-                    if let Some(_) = data.hue_mult {
-                        return Err(ParseLightError::ExclusiveFields("hue_mult", "hue"));
-                    }
+                    check_exclusive!("hue", hue_mult, hue_shift, tint);
 
                     let parsed: u32 = v.parse().map_err(|e: std::num::ParseIntError| {
                         ParseLightError::BadNumber("hue", e.to_string())
@@ -143,12 +237,7 @@ impl FromStr for CustomLightData {
                     data.hue = Some(parsed.clamp(0, 360))
                 }
                 "saturation" => {
-                    if let Some(_) = data.saturation_mult {
-                        return Err(ParseLightError::ExclusiveFields(
-                            "saturation_mult",
-                            "saturation",
-                        ));
-                    }
+                    check_exclusive!("saturation", saturation_mult, saturation_add, tint);
 
                     let parsed: f32 = v.parse().map_err(|e: std::num::ParseFloatError| {
                         ParseLightError::BadNumber("saturation", e.to_string())
@@ -157,9 +246,7 @@ impl FromStr for CustomLightData {
                     data.saturation = Some(parsed.clamp(0.0, 1.0))
                 }
                 "value" => {
-                    if let Some(_) = data.value_mult {
-                        return Err(ParseLightError::ExclusiveFields("value_mult", "value"));
-                    }
+                    check_exclusive!("value", value_mult, value_add, tint);
 
                     let parsed: f32 = v.parse().map_err(|e: std::num::ParseFloatError| {
                         ParseLightError::BadNumber("value", e.to_string())
@@ -167,15 +254,119 @@ impl FromStr for CustomLightData {
 
                     data.value = Some(parsed.clamp(0.0, 1.0))
                 }
+                "tint" => {
+                    check_exclusive!("tint", hue, saturation, value);
+
+                    data.tint = Some(v.parse()?)
+                }
+                "tint_strength" => {
+                    let parsed: f32 = v.parse().map_err(|e: std::num::ParseFloatError| {
+                        ParseLightError::BadNumber("tint_strength", e.to_string())
+                    })?;
+
+                    data.tint_strength = Some(parsed.clamp(0.0, 1.0))
+                }
                 "flag" => {
-                    let parsed: LightFlag = v.parse()?;
+                    let parsed: LightFlagOverride = v.parse()?;
                     data.flag = Some(parsed);
                 }
+                "match_flags" => {
+                    let parsed: LightFlagOverride = v.parse()?;
+                    data.match_flags = Some(parsed);
+                }
+                "match_hue_min" => {
+                    data.match_hue_min =
+                        Some(v.parse().map_err(|e: std::num::ParseFloatError| {
+                            ParseLightError::BadNumber("match_hue_min", e.to_string())
+                        })?)
+                }
+                "match_hue_max" => {
+                    data.match_hue_max =
+                        Some(v.parse().map_err(|e: std::num::ParseFloatError| {
+                            ParseLightError::BadNumber("match_hue_max", e.to_string())
+                        })?)
+                }
+                "match_radius_min" => {
+                    data.match_radius_min =
+                        Some(v.parse().map_err(|e: std::num::ParseIntError| {
+                            ParseLightError::BadNumber("match_radius_min", e.to_string())
+                        })?)
+                }
+                "match_radius_max" => {
+                    data.match_radius_max =
+                        Some(v.parse().map_err(|e: std::num::ParseIntError| {
+                            ParseLightError::BadNumber("match_radius_max", e.to_string())
+                        })?)
+                }
+                "plugin" => data.plugin = Some(v.to_owned()),
+                "interior_only" => {
+                    check_exclusive!("interior_only", exterior_only);
+
+                    data.interior_only =
+                        Some(v.parse().map_err(|e: std::str::ParseBoolError| {
+                            ParseLightError::BadBool("interior_only", e.to_string())
+                        })?)
+                }
+                "exterior_only" => {
+                    check_exclusive!("exterior_only", interior_only);
+
+                    data.exterior_only =
+                        Some(v.parse().map_err(|e: std::str::ParseBoolError| {
+                            ParseLightError::BadBool("exterior_only", e.to_string())
+                        })?)
+                }
+                "priority" => {
+                    data.priority = Some(v.parse().map_err(|e: std::num::ParseIntError| {
+                        ParseLightError::BadNumber("priority", e.to_string())
+                    })?)
+                }
+                "accumulate" => {
+                    data.accumulate = Some(v.parse().map_err(|e: std::str::ParseBoolError| {
+                        ParseLightError::BadBool("accumulate", e.to_string())
+                    })?)
+                }
+                "match_name" => {
+                    data.match_name_regex = Some(compile_name_pattern(v)?);
+                    data.match_name = Some(v.to_owned());
+                }
+                "sound" => data.sound = Some(v.to_owned()),
+                "copy_from" => data.copy_from = Some(v.to_owned()),
+                "mesh" => {
+                    validate_vfs_path("mesh", v)?;
+                    data.mesh = Some(v.to_owned());
+                }
+                "icon" => {
+                    validate_vfs_path("icon", v)?;
+                    data.icon = Some(v.to_owned());
+                }
+                "disabled" => {
+                    data.disabled = Some(v.parse().map_err(|e: std::str::ParseBoolError| {
+                        ParseLightError::BadBool("disabled", e.to_string())
+                    })?)
+                }
+                "replace_flags" => {
+                    data.replace_flags =
+                        Some(v.parse().map_err(|e: std::str::ParseBoolError| {
+                            ParseLightError::BadBool("replace_flags", e.to_string())
+                        })?)
+                }
                 _ => return Err(ParseLightError::UnknownField(k.to_owned())),
             }
             Ok(())
         })?;
 
+        if data.copy_from.is_some()
+            && let Some(field) = data.copied_field()
+        {
+            return Err(ParseLightError::ExclusiveFields(field, "copy_from"));
+        }
+
+        if data.is_disabled()
+            && let Some(field) = data.overridden_field()
+        {
+            return Err(ParseLightError::ExclusiveFields(field, "disabled"));
+        }
+
         Ok(data)
     }
 }
@@ -198,19 +389,68 @@ pub fn parse_ambient_override(s: &str) -> Result<(String, CustomCellAmbient), Pa
     Ok((id.to_string(), parsed_setting))
 }
 
+/// Parses a cell id regex to a single color, for `--cell-sunlight` and `--cell-ambient`.
+/// The color is either a `hue,saturation,value` triple or the keyed form used everywhere else.
+pub fn parse_cell_color(s: &str) -> Result<(String, TypedLightColor), ParseAmbientError> {
+    let (id, color) = s
+        .split_once('=')
+        .ok_or_else(|| ParseAmbientError::BadPair(s.to_string()))?;
+
+    let color = match color.split(',').collect::<Vec<_>>()[..] {
+        [hue, saturation, value] if !color.contains('=') => {
+            format!("hue={hue},saturation={saturation},value={value}")
+        }
+        _ => color.to_string(),
+    };
+
+    let parsed_color: TypedLightColor = color
+        .parse()
+        .map_err(|e| ParseAmbientError::BadColor(id.to_string(), Box::new(e)))?;
+
+    Ok((id.to_string(), parsed_color))
+}
+
 #[derive(Deserialize)]
 struct RawCustomLightData {
     hue: Option<u32>,
     hue_mult: Option<f32>,
+    hue_shift: Option<f32>,
     saturation: Option<f32>,
     saturation_mult: Option<f32>,
+    saturation_add: Option<f32>,
     value: Option<f32>,
     value_mult: Option<f32>,
+    value_add: Option<f32>,
+    tint: Option<TintColor>,
+    tint_strength: Option<f32>,
     radius: Option<u32>,
     radius_mult: Option<f32>,
+    radius_add: Option<i32>,
     duration: Option<f32>,
     duration_mult: Option<f32>,
-    flag: Option<LightFlag>,
+    target_real_minutes: Option<f32>,
+    weight: Option<f32>,
+    weight_mult: Option<f32>,
+    gold_value: Option<u32>,
+    gold_value_mult: Option<f32>,
+    flag: Option<LightFlagOverride>,
+    replace_flags: Option<bool>,
+    sound: Option<String>,
+    mesh: Option<String>,
+    icon: Option<String>,
+    copy_from: Option<String>,
+    disabled: Option<bool>,
+    match_hue_min: Option<f32>,
+    match_hue_max: Option<f32>,
+    match_radius_min: Option<u32>,
+    match_radius_max: Option<u32>,
+    match_flags: Option<LightFlagOverride>,
+    plugin: Option<String>,
+    match_name: Option<String>,
+    priority: Option<i32>,
+    accumulate: Option<bool>,
+    interior_only: Option<bool>,
+    exterior_only: Option<bool>,
 }
 
 impl<'de> serde::Deserialize<'de> for CustomLightData {
@@ -222,57 +462,406 @@ impl<'de> serde::Deserialize<'de> for CustomLightData {
 
         // Check exclusivity
         macro_rules! check_exclusive {
-            ($field:ident, $mult:ident) => {
-                if raw.$field.is_some() && raw.$mult.is_some() {
+            ($($field:ident),+) => {
+                let set_fields: Vec<&str> = [$((stringify!($field), raw.$field.is_some())),+]
+                    .into_iter()
+                    .filter_map(|(name, is_set)| is_set.then_some(name))
+                    .collect();
+
+                if set_fields.len() > 1 {
                     return Err(serde::de::Error::custom(format!(
                         "Fields `{}` and `{}` are mutually exclusive",
-                        stringify!($field),
-                        stringify!($mult)
+                        set_fields[0], set_fields[1]
                     )));
                 }
             };
         }
 
-        check_exclusive!(hue, hue_mult);
-        check_exclusive!(saturation, saturation_mult);
-        check_exclusive!(value, value_mult);
-        check_exclusive!(radius, radius_mult);
-        check_exclusive!(duration, duration_mult);
+        check_exclusive!(hue, hue_mult, hue_shift);
+        check_exclusive!(saturation, saturation_mult, saturation_add);
+        check_exclusive!(value, value_mult, value_add);
+        check_exclusive!(hue, tint);
+        check_exclusive!(saturation, tint);
+        check_exclusive!(value, tint);
+        check_exclusive!(radius, radius_mult, radius_add);
+        check_exclusive!(duration, duration_mult, target_real_minutes);
+        check_exclusive!(weight, weight_mult);
+        check_exclusive!(gold_value, gold_value_mult);
+        check_exclusive!(interior_only, exterior_only);
 
-        Ok(CustomLightData {
+        for (field, path) in [("mesh", &raw.mesh), ("icon", &raw.icon)] {
+            if let Some(path) = path {
+                validate_vfs_path(field, path).map_err(serde::de::Error::custom)?;
+            }
+        }
+
+        let data = CustomLightData {
             hue: raw.hue.map(|h| h.clamp(0, 360)),
             hue_mult: raw.hue_mult,
+            hue_shift: raw.hue_shift,
             saturation: raw.saturation.map(|s| s.clamp(0.0, 1.0)),
             saturation_mult: raw.saturation_mult,
+            saturation_add: raw.saturation_add,
             value: raw.value.map(|v| v.clamp(0.0, 1.0)),
             value_mult: raw.value_mult,
+            value_add: raw.value_add,
+            tint: raw.tint,
+            tint_strength: raw.tint_strength.map(|s| s.clamp(0.0, 1.0)),
             radius: raw.radius,
             radius_mult: raw.radius_mult,
+            radius_add: raw.radius_add,
             duration: raw.duration,
             duration_mult: raw.duration_mult,
+            target_real_minutes: raw.target_real_minutes,
+            weight: raw.weight,
+            weight_mult: raw.weight_mult,
+            gold_value: raw.gold_value,
+            gold_value_mult: raw.gold_value_mult,
             flag: raw.flag,
-        })
+            replace_flags: raw.replace_flags,
+            sound: raw.sound,
+            mesh: raw.mesh,
+            icon: raw.icon,
+            copy_from: raw.copy_from,
+            disabled: raw.disabled,
+            match_hue_min: raw.match_hue_min,
+            match_hue_max: raw.match_hue_max,
+            match_radius_min: raw.match_radius_min,
+            match_radius_max: raw.match_radius_max,
+            match_flags: raw.match_flags,
+            plugin: raw.plugin,
+            match_name_regex: raw
+                .match_name
+                .as_deref()
+                .map(compile_name_pattern)
+                .transpose()
+                .map_err(serde::de::Error::custom)?,
+            match_name: raw.match_name,
+            priority: raw.priority,
+            accumulate: raw.accumulate,
+            interior_only: raw.interior_only,
+            exterior_only: raw.exterior_only,
+        };
+
+        if data.copy_from.is_some()
+            && let Some(field) = data.copied_field()
+        {
+            return Err(serde::de::Error::custom(ParseLightError::ExclusiveFields(
+                field,
+                "copy_from",
+            )));
+        }
+
+        if data.is_disabled()
+            && let Some(field) = data.overridden_field()
+        {
+            return Err(serde::de::Error::custom(ParseLightError::ExclusiveFields(
+                field, "disabled",
+            )));
+        }
+
+        Ok(data)
     }
 }
 
+/// The kinds of cells a light is placed in, gathered from cell references across the load order
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LightPlacement {
+    pub interior: bool,
+    pub exterior: bool,
+}
+
 #[derive(Clone, Debug, Default, Serialize)]
 pub struct CustomLightData {
     pub hue: Option<u32>,
     pub hue_mult: Option<f32>,
+    /// Degrees added to the original hue, wrapping around the color wheel
+    pub hue_shift: Option<f32>,
     pub saturation: Option<f32>,
     pub saturation_mult: Option<f32>,
+    /// Added to the original saturation, with the result clamped to 0.0 - 1.0
+    pub saturation_add: Option<f32>,
     pub value: Option<f32>,
     pub value_mult: Option<f32>,
+    /// Added to the original value, with the result clamped to 0.0 - 1.0
+    pub value_add: Option<f32>,
+    /// Color the processed light is blended toward in RGB space, after all other color math
+    pub tint: Option<TintColor>,
+    /// How far the light is blended toward `tint`, from 0.0 to 1.0. Defaults to 0.5
+    pub tint_strength: Option<f32>,
     pub radius: Option<u32>,
     pub radius_mult: Option<f32>,
+    /// Added to the original radius, which will not drop below zero
+    pub radius_add: Option<i32>,
     pub duration: Option<f32>,
     pub duration_mult: Option<f32>,
-    pub flag: Option<LightFlag>,
+    /// Real-time minutes the light should burn for, at the configured timescale
+    pub target_real_minutes: Option<f32>,
+    pub weight: Option<f32>,
+    pub weight_mult: Option<f32>,
+    /// Gold value of the light, named so as not to be confused with its brightness
+    pub gold_value: Option<u32>,
+    pub gold_value_mult: Option<f32>,
+    pub flag: Option<LightFlagOverride>,
+    /// When set, `flag` replaces every flag of the light instead of only its animation
+    pub replace_flags: Option<bool>,
+    /// Sound record id the light plays, or `none` to make it silent
+    pub sound: Option<String>,
+    /// Mesh path, relative to the meshes folder
+    pub mesh: Option<String>,
+    /// Inventory icon path, relative to the icons folder
+    pub icon: Option<String>,
+    /// Id of another light whose processed color, radius, duration, and flags are copied onto this one
+    pub copy_from: Option<String>,
+    /// Zeroes the radius and color of the light and clears all of its flags
+    pub disabled: Option<bool>,
+    /// The override only applies to lights whose original hue is at least this many degrees
+    pub match_hue_min: Option<f32>,
+    /// The override only applies to lights whose original hue is at most this many degrees
+    /// If this is less than `match_hue_min`, the range wraps around through 0
+    pub match_hue_max: Option<f32>,
+    /// The override only applies to lights whose original radius is at least this large
+    pub match_radius_min: Option<u32>,
+    /// The override only applies to lights whose original radius is at most this large
+    pub match_radius_max: Option<u32>,
+    /// The override only applies to lights whose original flags include all of these,
+    /// and none of those prefixed with `-`
+    pub match_flags: Option<LightFlagOverride>,
+    /// Pattern matched against the lowercased file name of the plugin a light comes from
+    pub plugin: Option<String>,
+    /// Pattern matched case-insensitively against the display name of a light
+    pub match_name: Option<String>,
+    #[serde(skip)]
+    match_name_regex: Option<regex::Regex>,
+    /// Overrides with a higher priority are checked first. Defaults to 0
+    pub priority: Option<i32>,
+    /// When set, this override only contributes the fields it sets,
+    /// and the next matching override fills in the rest
+    pub accumulate: Option<bool>,
+    /// The override only applies to lights which are placed in interiors, and never in exteriors
+    pub interior_only: Option<bool>,
+    /// The override only applies to lights which are placed in exteriors, and never in interiors
+    pub exterior_only: Option<bool>,
+}
+
+impl CustomLightData {
+    pub fn is_disabled(&self) -> bool {
+        self.disabled.unwrap_or(false)
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority.unwrap_or(0)
+    }
+
+    pub fn accumulates(&self) -> bool {
+        self.accumulate.unwrap_or(false)
+    }
+
+    /// Fills every group of fields this override leaves unset from a lower priority one.
+    /// Mutually exclusive fields are filled together, so that only one of them is ever set
+    pub fn fill_from(&mut self, other: &CustomLightData) {
+        macro_rules! fill_group {
+            ($($field:ident),+) => {
+                if $(self.$field.is_none())&&+ {
+                    $(self.$field = other.$field;)+
+                }
+            };
+        }
+
+        macro_rules! fill_owned {
+            ($field:ident) => {
+                if self.$field.is_none() {
+                    self.$field.clone_from(&other.$field);
+                }
+            };
+        }
+
+        fill_group!(hue, hue_mult, hue_shift);
+        fill_group!(saturation, saturation_mult, saturation_add);
+        fill_group!(value, value_mult, value_add);
+        fill_group!(tint, tint_strength);
+        fill_group!(radius, radius_mult, radius_add);
+        fill_group!(duration, duration_mult, target_real_minutes);
+        fill_group!(weight, weight_mult);
+        fill_group!(gold_value, gold_value_mult);
+        fill_group!(disabled);
+
+        if self.flag.is_none() {
+            self.flag.clone_from(&other.flag);
+            self.replace_flags = other.replace_flags;
+        }
+
+        fill_owned!(sound);
+        fill_owned!(mesh);
+        fill_owned!(icon);
+        fill_owned!(copy_from);
+    }
+
+    /// Whether a hue, in degrees, falls within `match_hue_min` and `match_hue_max`
+    pub fn matches_hue(&self, hue: f32) -> bool {
+        match (self.match_hue_min, self.match_hue_max) {
+            (None, None) => true,
+            (Some(min), None) => hue >= min,
+            (None, Some(max)) => hue <= max,
+            (Some(min), Some(max)) if min <= max => (min..=max).contains(&hue),
+            (Some(min), Some(max)) => hue >= min || hue <= max,
+        }
+    }
+
+    pub fn is_placement_scoped(&self) -> bool {
+        self.interior_only.unwrap_or(false) || self.exterior_only.unwrap_or(false)
+    }
+
+    /// Whether a light's placement satisfies `interior_only` or `exterior_only`.
+    /// Lights which aren't placed anywhere, or are placed in both kinds of cells, never do
+    pub fn matches_placement(&self, placement: Option<LightPlacement>) -> bool {
+        if !self.is_placement_scoped() {
+            return true;
+        }
+
+        let Some(placement) = placement else {
+            return false;
+        };
+
+        if placement.interior == placement.exterior {
+            return false;
+        }
+
+        placement.interior == self.interior_only.unwrap_or(false)
+    }
+
+    /// Whether a light's display name matches `match_name`
+    pub fn matches_name(&self, name: &str) -> bool {
+        self.match_name_regex
+            .as_ref()
+            .is_none_or(|pattern| pattern.is_match(name))
+    }
+
+    /// Whether a light's original flags satisfy `match_flags`
+    pub fn matches_flags(&self, flags: LightFlags) -> bool {
+        self.match_flags
+            .as_ref()
+            .is_none_or(|match_flags| match_flags.matches(flags))
+    }
+
+    /// Whether a radius falls within `match_radius_min` and `match_radius_max`
+    pub fn matches_radius(&self, radius: u32) -> bool {
+        self.match_radius_min.is_none_or(|min| radius >= min)
+            && self.match_radius_max.is_none_or(|max| radius <= max)
+    }
+
+    /// Gives back the first field set alongside `disabled`, which would make it meaningless
+    fn overridden_field(&self) -> Option<&'static str> {
+        self.copied_field().or_else(|| {
+            [
+                ("weight", self.weight.is_some()),
+                ("weight_mult", self.weight_mult.is_some()),
+                ("gold_value", self.gold_value.is_some()),
+                ("gold_value_mult", self.gold_value_mult.is_some()),
+                ("flag", self.flag.is_some()),
+                ("replace_flags", self.replace_flags.is_some()),
+                ("sound", self.sound.is_some()),
+                ("mesh", self.mesh.is_some()),
+                ("icon", self.icon.is_some()),
+                ("copy_from", self.copy_from.is_some()),
+            ]
+            .into_iter()
+            .find_map(|(name, is_set)| is_set.then_some(name))
+        })
+    }
+
+    /// Gives back the first field set alongside `copy_from` which it would overwrite
+    fn copied_field(&self) -> Option<&'static str> {
+        [
+            ("hue", self.hue.is_some()),
+            ("hue_mult", self.hue_mult.is_some()),
+            ("hue_shift", self.hue_shift.is_some()),
+            ("saturation", self.saturation.is_some()),
+            ("saturation_mult", self.saturation_mult.is_some()),
+            ("saturation_add", self.saturation_add.is_some()),
+            ("value", self.value.is_some()),
+            ("value_mult", self.value_mult.is_some()),
+            ("value_add", self.value_add.is_some()),
+            ("tint", self.tint.is_some()),
+            ("tint_strength", self.tint_strength.is_some()),
+            ("radius", self.radius.is_some()),
+            ("radius_mult", self.radius_mult.is_some()),
+            ("radius_add", self.radius_add.is_some()),
+            ("duration", self.duration.is_some()),
+            ("duration_mult", self.duration_mult.is_some()),
+            ("target_real_minutes", self.target_real_minutes.is_some()),
+        ]
+        .into_iter()
+        .find_map(|(name, is_set)| is_set.then_some(name))
+    }
+}
+
+/// A color lights are blended toward, stored as 8-bit sRGB.
+/// Written as a hex color like `#ff9944`, or in TOML as a table of hue, saturation, and value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TintColor(pub [u8; 4]);
+
+impl FromStr for TintColor {
+    type Err = ParseLightError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.trim().strip_prefix('#').unwrap_or(s.trim());
+
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(ParseLightError::BadColor("tint", s.to_string()));
+        }
+
+        let channel = |index: usize| {
+            u8::from_str_radix(&hex[index..index + 2], 16)
+                .map_err(|_| ParseLightError::BadColor("tint", s.to_string()))
+        };
+
+        Ok(TintColor([channel(0)?, channel(2)?, channel(4)?, 0]))
+    }
+}
+
+impl fmt::Display for TintColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [red, green, blue, _] = self.0;
+        write!(f, "#{red:02x}{green:02x}{blue:02x}")
+    }
+}
+
+impl Serialize for TintColor {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Tints may be written in TOML as a hex string or as an HSV table
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawTintColor {
+    Hex(String),
+    Hsv(TypedLightColor),
+}
+
+impl<'de> serde::Deserialize<'de> for TintColor {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        match RawTintColor::deserialize(deserializer)? {
+            RawTintColor::Hex(hex) => hex.parse().map_err(serde::de::Error::custom),
+            RawTintColor::Hsv(color) => Ok(TintColor(crate::hsv_to_color(
+                palette::Hsv::new(color.hue as f32, color.saturation, color.value),
+                false,
+            ))),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, Serialize)]
 /// Struct used to store color replacements for cells.
-/// No fields are optional, unlike light record replacements. Nor are multipliers supported.
+/// No fields are optional, unlike light record replacements. Multipliers are written as a [`LightColorMult`] instead.
 pub struct TypedLightColor {
     pub hue: u32,
     pub saturation: f32,
@@ -367,57 +956,494 @@ impl FromStr for TypedLightColor {
     }
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct CustomCellAmbient {
-    pub ambient: Option<TypedLightColor>,
-    pub sunlight: Option<TypedLightColor>,
-    pub fog: Option<TypedLightColor>,
-    pub fog_density: Option<f32>,
-}
-
-#[derive(Debug)]
-pub enum ParseAmbientError {
-    BadPair(String),
-    UnknownField(String),
-    BadColor(String, Box<dyn std::error::Error + Send + Sync>),
+/// Multipliers for a cell's existing color, scaled the same way light colors are.
+/// Unlike [`TypedLightColor`], every field is optional and defaults to 1.0
+#[derive(Clone, Debug, Serialize)]
+pub struct LightColorMult {
+    pub hue: f32,
+    pub saturation: f32,
+    pub value: f32,
 }
 
-impl fmt::Display for ParseAmbientError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use ParseAmbientError::*;
-        match self {
-            BadPair(pair) => write!(f, "Expected key=value pair, got: `{pair}`"),
-            UnknownField(field) => write!(f, "Unknown field: `{field}`"),
-            BadColor(field, err) => write!(f, "Invalid color for `{field}`: {err}"),
+impl Default for LightColorMult {
+    fn default() -> Self {
+        LightColorMult {
+            hue: 1.0,
+            saturation: 1.0,
+            value: 1.0,
         }
     }
 }
 
-impl std::error::Error for ParseAmbientError {}
+#[derive(Deserialize)]
+struct RawLightColorMult {
+    hue: Option<f32>,
+    saturation: Option<f32>,
+    value: Option<f32>,
+}
 
-impl FromStr for CustomCellAmbient {
-    type Err = ParseAmbientError;
+impl<'de> serde::Deserialize<'de> for LightColorMult {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let raw = RawLightColorMult::deserialize(deserializer)?;
+
+        Ok(LightColorMult {
+            hue: raw.hue.unwrap_or(1.0),
+            saturation: raw.saturation.unwrap_or(1.0),
+            value: raw.value.unwrap_or(1.0),
+        })
+    }
+}
+
+impl FromStr for LightColorMult {
+    type Err = ParseTypedColorError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut ambient = None;
-        let mut sunlight = None;
-        let mut fog = None;
-        let mut fog_density = None;
+        let mut mult = LightColorMult::default();
 
-        for pair in s.split(';').filter(|p| !p.trim().is_empty()) {
-            let (key, value) = pair
+        for pair in s.split(',').filter(|p| !p.trim().is_empty()) {
+            let (k, v) = pair
                 .split_once('=')
-                .ok_or_else(|| ParseAmbientError::BadPair(pair.to_string()))?;
+                .ok_or_else(|| ParseTypedColorError::BadPair(pair.to_string()))?;
 
-            match key.trim() {
-                "ambient" => {
-                    let parsed = value
-                        .parse()
-                        .map_err(|e| ParseAmbientError::BadColor("ambient".into(), Box::new(e)))?;
-                    ambient = Some(parsed);
-                }
-                "sunlight" => {
-                    let parsed = value
+            let (field, target) = match k.trim() {
+                "hue" => ("hue", &mut mult.hue),
+                "saturation" => ("saturation", &mut mult.saturation),
+                "value" => ("value", &mut mult.value),
+                other => return Err(ParseTypedColorError::UnknownField(other.to_string())),
+            };
+
+            *target = v.trim().parse().map_err(|e: std::num::ParseFloatError| {
+                ParseTypedColorError::BadNumber(field, e.to_string())
+            })?;
+        }
+
+        Ok(mult)
+    }
+}
+
+impl LightColorMult {
+    /// Scales an 8-bit color in HSV, with saturation and value kept within 0.0 - 1.0
+    pub fn apply(&self, color: [u8; 4], linear: bool, overflow: crate::HueOverflow) -> [u8; 4] {
+        let mut hsv = crate::color_to_hsv(color, linear);
+
+        hsv.hue = crate::multiply_hue(hsv.hue, self.hue, overflow);
+        hsv.saturation = (hsv.saturation * self.saturation).clamp(0.0, 1.0);
+        hsv.value = (hsv.value * self.value).clamp(0.0, 1.0);
+
+        crate::hsv_to_color(hsv, linear)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CustomCellAmbient {
+    pub ambient: Option<TypedLightColor>,
+    /// Multiplies the cell's existing ambient color, instead of replacing it
+    pub ambient_mult: Option<LightColorMult>,
+    pub sunlight: Option<TypedLightColor>,
+    /// Multiplies the cell's existing sunlight color, instead of replacing it
+    pub sunlight_mult: Option<LightColorMult>,
+    pub fog: Option<TypedLightColor>,
+    /// Multiplies the cell's existing fog color, instead of replacing it
+    pub fog_mult: Option<LightColorMult>,
+    pub fog_density: Option<f32>,
+    /// Multiplies the cell's existing fog density, instead of replacing it
+    pub fog_density_mult: Option<f32>,
+    /// Grid coordinates of the exterior cell this override applies to, instead of matching cell ids
+    pub grid: Option<[i32; 2]>,
+    /// Patterns matched against the lowercased object ids of the cell's references, which are deleted
+    pub instance_deletions: Vec<String>,
+    /// Patterns matched against the lowercased object ids of the cell's references, to the object id they're swapped to
+    #[serde(serialize_with = "serialize_pairs")]
+    pub instance_replacements: Vec<(String, String)>,
+    #[serde(skip)]
+    instance_deletion_regexes: Vec<regex::Regex>,
+    /// Patterns matched against the lowercased object ids of the cell's references, to how they're moved or scaled
+    #[serde(serialize_with = "serialize_pairs")]
+    pub instance_overrides: Vec<(String, InstanceOverride)>,
+    #[serde(skip)]
+    instance_replacement_regexes: Vec<(regex::Regex, String)>,
+    #[serde(skip)]
+    instance_override_regexes: Vec<(regex::Regex, InstanceOverride)>,
+}
+
+/// Changes made to individual references of a cell, instead of every instance of a record
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct InstanceOverride {
+    /// Multiplies the reference's existing scale, keeping it within the 0.5 - 2.0 the engine accepts
+    pub scale: Option<f32>,
+    /// Added to the reference's position
+    pub offset: Option<[f32; 3]>,
+}
+
+impl InstanceOverride {
+    pub fn apply_to(&self, reference: &mut tes3::esp::Reference) {
+        if let Some(scale) = self.scale {
+            let scaled = reference.scale.unwrap_or(1.0) * scale;
+
+            if scaled.is_finite() {
+                reference.scale = Some(scaled.clamp(0.5, 2.0));
+            }
+        }
+
+        if let Some(offset) = self.offset {
+            for (position, offset) in reference.translation.iter_mut().zip(offset) {
+                *position += offset;
+            }
+        }
+    }
+}
+
+impl fmt::Display for InstanceOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut entries = Vec::new();
+
+        if let Some(scale) = self.scale {
+            entries.push(format!("scale={scale}"));
+        }
+
+        if let Some([x, y, z]) = self.offset {
+            entries.push(format!("offset={x} {y} {z}"));
+        }
+
+        write!(f, "{}", entries.join(","))
+    }
+}
+
+impl FromStr for InstanceOverride {
+    type Err = ParseAmbientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut instance_override = InstanceOverride::default();
+
+        for pair in s.split(',').filter(|p| !p.trim().is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| ParseAmbientError::BadPair(pair.to_string()))?;
+
+            match key.trim() {
+                "scale" => {
+                    let parsed: f32 = value
+                        .trim()
+                        .parse()
+                        .map_err(|e| ParseAmbientError::BadColor("scale".into(), Box::new(e)))?;
+                    instance_override.scale = Some(parsed);
+                }
+                // Written as three numbers separated by spaces, EG: `offset=0 0 -16`
+                "offset" => {
+                    let parsed = value
+                        .split_whitespace()
+                        .map(str::parse)
+                        .collect::<Result<Vec<f32>, _>>()
+                        .map_err(|e| ParseAmbientError::BadColor("offset".into(), Box::new(e)))?;
+
+                    let offset: [f32; 3] = parsed
+                        .try_into()
+                        .map_err(|_| ParseAmbientError::BadPair(pair.to_string()))?;
+
+                    instance_override.offset = Some(offset);
+                }
+                other => return Err(ParseAmbientError::UnknownField(other.to_string())),
+            }
+        }
+
+        Ok(instance_override)
+    }
+}
+
+/// What an ambient override does to one of its cell's references
+#[derive(Debug)]
+pub enum InstanceEdit<'a> {
+    Delete,
+    Replace(&'a str),
+}
+
+impl CustomCellAmbient {
+    /// Whether this override deletes or replaces any references
+    pub fn has_instance_edits(&self) -> bool {
+        !self.instance_deletion_regexes.is_empty()
+            || !self.instance_replacement_regexes.is_empty()
+            || !self.instance_override_regexes.is_empty()
+    }
+
+    /// Gives back how a reference of the given lowercased object id is moved or scaled.
+    /// The first matching pattern wins over later ones
+    pub fn instance_override(&self, reference_id: &str) -> Option<&InstanceOverride> {
+        self.instance_override_regexes
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(reference_id))
+            .map(|(_, instance_override)| instance_override)
+    }
+
+    /// Gives back what happens to a reference of the given lowercased object id.
+    /// Deletions win over replacements, and the first matching replacement wins over later ones
+    pub fn instance_edit(&self, reference_id: &str) -> Option<InstanceEdit<'_>> {
+        if self
+            .instance_deletion_regexes
+            .iter()
+            .any(|pattern| pattern.is_match(reference_id))
+        {
+            return Some(InstanceEdit::Delete);
+        }
+
+        self.instance_replacement_regexes
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(reference_id))
+            .map(|(_, new_id)| InstanceEdit::Replace(new_id))
+    }
+}
+
+fn compile_instance_pattern(
+    field: &'static str,
+    pattern: &str,
+) -> Result<regex::Regex, ParseAmbientError> {
+    regex::Regex::new(pattern).map_err(|e| ParseAmbientError::BadRegex(field, e.to_string()))
+}
+
+/// Instance replacements and overrides are written in TOML as tables, whose order decides which pattern wins
+fn serialize_pairs<S, V>(pairs: &[(String, V)], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    V: Serialize,
+{
+    use serde::ser::SerializeMap;
+
+    let mut ser_map = serializer.serialize_map(Some(pairs.len()))?;
+    for (k, v) in pairs {
+        ser_map.serialize_entry(k, v)?;
+    }
+    ser_map.end()
+}
+
+#[derive(Deserialize)]
+struct RawCustomCellAmbient {
+    ambient: Option<TypedLightColor>,
+    ambient_mult: Option<LightColorMult>,
+    sunlight: Option<TypedLightColor>,
+    sunlight_mult: Option<LightColorMult>,
+    fog: Option<TypedLightColor>,
+    fog_mult: Option<LightColorMult>,
+    fog_density: Option<f32>,
+    fog_density_mult: Option<f32>,
+    grid: Option<[i32; 2]>,
+    #[serde(default)]
+    instance_deletions: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "crate::light_config::deserialize_ordered_hash_map"
+    )]
+    instance_replacements: ordered_hash_map::OrderedHashMap<String, String>,
+    #[serde(
+        default,
+        deserialize_with = "crate::light_config::deserialize_ordered_hash_map"
+    )]
+    instance_overrides: ordered_hash_map::OrderedHashMap<String, InstanceOverride>,
+}
+
+impl<'de> serde::Deserialize<'de> for CustomCellAmbient {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let raw = RawCustomCellAmbient::deserialize(deserializer)?;
+
+        for (field, fixed, mult) in [
+            ("ambient", raw.ambient.is_some(), raw.ambient_mult.is_some()),
+            (
+                "sunlight",
+                raw.sunlight.is_some(),
+                raw.sunlight_mult.is_some(),
+            ),
+            ("fog", raw.fog.is_some(), raw.fog_mult.is_some()),
+            (
+                "fog_density",
+                raw.fog_density.is_some(),
+                raw.fog_density_mult.is_some(),
+            ),
+        ] {
+            if fixed && mult {
+                return Err(serde::de::Error::custom(format!(
+                    "Fields `{field}` and `{field}_mult` are mutually exclusive"
+                )));
+            }
+        }
+
+        let instance_deletion_regexes = raw
+            .instance_deletions
+            .iter()
+            .map(|pattern| compile_instance_pattern("instance_deletions", pattern))
+            .collect::<Result<_, _>>()
+            .map_err(serde::de::Error::custom)?;
+
+        let instance_replacements: Vec<(String, String)> =
+            raw.instance_replacements.into_iter().collect();
+
+        let instance_replacement_regexes = instance_replacements
+            .iter()
+            .map(|(pattern, new_id)| {
+                Ok((
+                    compile_instance_pattern("instance_replacements", pattern)?,
+                    new_id.to_owned(),
+                ))
+            })
+            .collect::<Result<_, _>>()
+            .map_err(serde::de::Error::custom)?;
+
+        let instance_overrides: Vec<(String, InstanceOverride)> =
+            raw.instance_overrides.into_iter().collect();
+
+        let instance_override_regexes = instance_overrides
+            .iter()
+            .map(|(pattern, instance_override)| {
+                Ok((
+                    compile_instance_pattern("instance_overrides", pattern)?,
+                    instance_override.clone(),
+                ))
+            })
+            .collect::<Result<_, _>>()
+            .map_err(serde::de::Error::custom)?;
+
+        Ok(CustomCellAmbient {
+            ambient: raw.ambient,
+            ambient_mult: raw.ambient_mult,
+            sunlight: raw.sunlight,
+            sunlight_mult: raw.sunlight_mult,
+            fog: raw.fog,
+            fog_mult: raw.fog_mult,
+            fog_density: raw.fog_density,
+            fog_density_mult: raw.fog_density_mult,
+            grid: raw.grid,
+            instance_deletions: raw.instance_deletions,
+            instance_replacements,
+            instance_overrides,
+            instance_deletion_regexes,
+            instance_replacement_regexes,
+            instance_override_regexes,
+        })
+    }
+}
+
+/// Exterior cells an ambient override applies to, by their grid coordinates
+#[derive(Clone, Debug)]
+pub struct ExteriorGrid {
+    pub x: RangeInclusive<i32>,
+    pub y: RangeInclusive<i32>,
+}
+
+impl ExteriorGrid {
+    /// Prefix marking ambient override keys which address exterior cells
+    pub const PREFIX: &str = "ext:";
+
+    pub fn cell(x: i32, y: i32) -> Self {
+        ExteriorGrid { x: x..=x, y: y..=y }
+    }
+
+    /// Whether an exterior cell's grid coordinates fall within this grid
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.x.contains(&x) && self.y.contains(&y)
+    }
+
+    /// Parses an ambient override key like `ext:2,-9`, or `ext:2..5,-9..-6` for a block of cells.
+    /// Gives back `None` for keys which aren't prefixed with `ext:`, and are cell id patterns instead
+    pub fn from_key(key: &str) -> Option<Result<Self, ParseAmbientError>> {
+        let coordinates = key.trim().strip_prefix(Self::PREFIX)?;
+
+        let axis = |axis: &str| -> Result<RangeInclusive<i32>, ParseAmbientError> {
+            let bad_grid = || ParseAmbientError::BadGrid(key.to_string());
+
+            let (start, end) = axis.split_once("..").unwrap_or((axis, axis));
+            let start: i32 = start.trim().parse().map_err(|_| bad_grid())?;
+            let end: i32 = end.trim().parse().map_err(|_| bad_grid())?;
+
+            if start > end {
+                return Err(bad_grid());
+            }
+
+            Ok(start..=end)
+        };
+
+        Some(
+            coordinates
+                .split_once(',')
+                .ok_or_else(|| ParseAmbientError::BadGrid(key.to_string()))
+                .and_then(|(x, y)| {
+                    Ok(ExteriorGrid {
+                        x: axis(x)?,
+                        y: axis(y)?,
+                    })
+                }),
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseAmbientError {
+    BadPair(String),
+    UnknownField(String),
+    BadColor(String, Box<dyn std::error::Error + Send + Sync>),
+    BadGrid(String),
+    BadRegex(&'static str, String),
+    ExclusiveFields(&'static str, &'static str),
+}
+
+impl fmt::Display for ParseAmbientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ParseAmbientError::*;
+        match self {
+            BadPair(pair) => write!(f, "Expected key=value pair, got: `{pair}`"),
+            UnknownField(field) => write!(f, "Unknown field: `{field}`"),
+            BadColor(field, err) => write!(f, "Invalid color for `{field}`: {err}"),
+            ExclusiveFields(existing_field, bad_field) => write!(
+                f,
+                "Key {existing_field} is mutually exclusive with {bad_field}"
+            ),
+            BadRegex(field, e) => write!(f, "Invalid regex for `{field}`: {e}"),
+            BadGrid(grid) => write!(
+                f,
+                "Invalid exterior grid: `{grid}` (expected coordinates or ranges of them, EG: `2,-9` or `2..5,-9..-6`)"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseAmbientError {}
+
+impl FromStr for CustomCellAmbient {
+    type Err = ParseAmbientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ambient = None;
+        let mut ambient_mult = None;
+        let mut sunlight = None;
+        let mut sunlight_mult = None;
+        let mut fog = None;
+        let mut fog_mult = None;
+        let mut fog_density = None;
+        let mut fog_density_mult = None;
+        let mut grid = None;
+        let mut instance_deletions = Vec::new();
+        let mut instance_deletion_regexes = Vec::new();
+        let mut instance_replacements = Vec::new();
+        let mut instance_replacement_regexes = Vec::new();
+        let mut instance_overrides = Vec::new();
+        let mut instance_override_regexes = Vec::new();
+
+        for pair in s.split(';').filter(|p| !p.trim().is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| ParseAmbientError::BadPair(pair.to_string()))?;
+
+            match key.trim() {
+                "ambient" => {
+                    let parsed = value
+                        .parse()
+                        .map_err(|e| ParseAmbientError::BadColor("ambient".into(), Box::new(e)))?;
+                    ambient = Some(parsed);
+                }
+                "sunlight" => {
+                    let parsed = value
                         .parse()
                         .map_err(|e| ParseAmbientError::BadColor("sunlight".into(), Box::new(e)))?;
                     sunlight = Some(parsed);
@@ -428,21 +1454,122 @@ impl FromStr for CustomCellAmbient {
                         .map_err(|e| ParseAmbientError::BadColor("fog".into(), Box::new(e)))?;
                     fog = Some(parsed);
                 }
+                "ambient_mult" => {
+                    let parsed = value.parse().map_err(|e| {
+                        ParseAmbientError::BadColor("ambient_mult".into(), Box::new(e))
+                    })?;
+                    ambient_mult = Some(parsed);
+                }
+                "sunlight_mult" => {
+                    let parsed = value.parse().map_err(|e| {
+                        ParseAmbientError::BadColor("sunlight_mult".into(), Box::new(e))
+                    })?;
+                    sunlight_mult = Some(parsed);
+                }
+                "fog_mult" => {
+                    let parsed = value
+                        .parse()
+                        .map_err(|e| ParseAmbientError::BadColor("fog_mult".into(), Box::new(e)))?;
+                    fog_mult = Some(parsed);
+                }
                 "fog_density" => {
                     let parsed: f32 = value.parse().map_err(|e| {
                         ParseAmbientError::BadColor("fog_density".into(), Box::new(e))
                     })?;
                     fog_density = Some(parsed);
                 }
+                "fog_density_mult" => {
+                    let parsed: f32 = value.parse().map_err(|e| {
+                        ParseAmbientError::BadColor("fog_density_mult".into(), Box::new(e))
+                    })?;
+                    fog_density_mult = Some(parsed);
+                }
+                "grid" => {
+                    let bad_grid = || ParseAmbientError::BadGrid(value.to_string());
+
+                    let (x, y) = value.split_once(',').ok_or_else(bad_grid)?;
+                    let x: i32 = x.trim().parse().map_err(|_| bad_grid())?;
+                    let y: i32 = y.trim().parse().map_err(|_| bad_grid())?;
+
+                    grid = Some([x, y]);
+                }
+                // May be given several times, adding a pattern each time
+                "instance_deletions" => {
+                    instance_deletion_regexes
+                        .push(compile_instance_pattern("instance_deletions", value)?);
+                    instance_deletions.push(value.to_owned());
+                }
+                // Written as `pattern=>new_id`, and may likewise be given several times
+                "instance_replacements" => {
+                    let (pattern, new_id) = value
+                        .rsplit_once("=>")
+                        .ok_or_else(|| ParseAmbientError::BadPair(value.to_string()))?;
+
+                    instance_replacement_regexes.push((
+                        compile_instance_pattern("instance_replacements", pattern)?,
+                        new_id.trim().to_owned(),
+                    ));
+                    instance_replacements.push((pattern.to_owned(), new_id.trim().to_owned()));
+                }
+                // Written as `pattern=>scale=0.8,offset=0 0 -16`, and may likewise be given several times
+                "instance_overrides" => {
+                    let (pattern, instance_override) = value
+                        .split_once("=>")
+                        .ok_or_else(|| ParseAmbientError::BadPair(value.to_string()))?;
+                    let instance_override: InstanceOverride = instance_override.parse()?;
+
+                    instance_override_regexes.push((
+                        compile_instance_pattern("instance_overrides", pattern)?,
+                        instance_override.clone(),
+                    ));
+                    instance_overrides.push((pattern.to_owned(), instance_override));
+                }
                 other => return Err(ParseAmbientError::UnknownField(other.to_string())),
             }
         }
 
+        for (field, fixed, mult_field, mult) in [
+            (
+                "ambient",
+                ambient.is_some(),
+                "ambient_mult",
+                ambient_mult.is_some(),
+            ),
+            (
+                "sunlight",
+                sunlight.is_some(),
+                "sunlight_mult",
+                sunlight_mult.is_some(),
+            ),
+            ("fog", fog.is_some(), "fog_mult", fog_mult.is_some()),
+            (
+                "fog_density",
+                fog_density.is_some(),
+                "fog_density_mult",
+                fog_density_mult.is_some(),
+            ),
+        ] {
+            if fixed && mult {
+                return Err(ParseAmbientError::ExclusiveFields(field, mult_field));
+            }
+        }
+
         Ok(CustomCellAmbient {
             ambient,
+            ambient_mult,
             sunlight,
+            sunlight_mult,
             fog,
+            fog_mult,
             fog_density,
+            fog_density_mult,
+            grid,
+            instance_deletions,
+            instance_replacements,
+            instance_overrides,
+            instance_deletion_regexes,
+            instance_replacement_regexes,
+            instance_override_regexes,
         })
     }
 }
@@ -453,6 +1580,10 @@ pub enum LightFlag {
     FLICKER,
     PULSE,
     PULSESLOW,
+    OFFDEFAULT,
+    DYNAMIC,
+    CANCARRY,
+    FIRE,
     #[default]
     NONE,
 }
@@ -465,9 +1596,18 @@ impl LightFlag {
             Self::FLICKERSLOW => LightFlags::FLICKER_SLOW,
             Self::PULSE => LightFlags::PULSE,
             Self::PULSESLOW => LightFlags::PULSE_SLOW,
+            Self::OFFDEFAULT => LightFlags::OFF_BY_DEFAULT,
+            Self::DYNAMIC => LightFlags::DYNAMIC,
+            Self::CANCARRY => LightFlags::CAN_CARRY,
+            Self::FIRE => LightFlags::FIRE,
             Self::NONE => LightFlags::empty(),
         }
     }
+
+    /// Flags deciding how a light animates, of which a light should only use one
+    pub fn animation_flags() -> LightFlags {
+        LightFlags::FLICKER | LightFlags::FLICKER_SLOW | LightFlags::PULSE | LightFlags::PULSE_SLOW
+    }
 }
 
 impl FromStr for LightFlag {
@@ -479,8 +1619,650 @@ impl FromStr for LightFlag {
             "flickerslow" => Ok(LightFlag::FLICKERSLOW),
             "pulse" => Ok(LightFlag::PULSE),
             "pulseslow" => Ok(LightFlag::PULSESLOW),
+            "offdefault" => Ok(LightFlag::OFFDEFAULT),
+            "dynamic" => Ok(LightFlag::DYNAMIC),
+            "cancarry" => Ok(LightFlag::CANCARRY),
+            "fire" => Ok(LightFlag::FIRE),
             "none" => Ok(LightFlag::NONE),
             _ => Err(ParseLightError::UnknownVariant(s.to_string())),
         }
     }
 }
+
+/// Any number of flags set by a light override, written like `FLICKERSLOW|OFFDEFAULT|-FIRE`.
+/// Entries may be separated by `|` or `+`.
+/// Entries prefixed with `+` or `-` are added to or removed from the light's original flags,
+/// while the rest are applied together as described in [`LightFlagOverride::apply_to`].
+#[derive(Clone, Debug, Default)]
+pub struct LightFlagOverride {
+    pub flags: Vec<LightFlag>,
+    pub added: Vec<LightFlag>,
+    pub removed: Vec<LightFlag>,
+}
+
+impl LightFlagOverride {
+    fn combine(flags: &[LightFlag]) -> LightFlags {
+        flags.iter().fold(LightFlags::empty(), |combined, flag| {
+            combined | flag.to_esp_flag()
+        })
+    }
+
+    /// Applies the override onto a light's existing flags.
+    /// With `replace`, the unprefixed flags replace every original flag.
+    /// Otherwise, unprefixed animation flags (or NONE) replace the light's animation
+    /// and any other unprefixed flags are added.
+    /// Prefixed flags are added or removed afterward in either case.
+    pub fn apply_to(&self, flags: &mut LightFlags, replace: bool) {
+        let combined = Self::combine(&self.flags);
+
+        if replace {
+            *flags = combined;
+        } else {
+            let sets_animation = self.flags.iter().any(|flag| {
+                matches!(
+                    flag,
+                    LightFlag::FLICKER
+                        | LightFlag::FLICKERSLOW
+                        | LightFlag::PULSE
+                        | LightFlag::PULSESLOW
+                        | LightFlag::NONE
+                )
+            });
+
+            if sets_animation {
+                flags.remove(LightFlag::animation_flags());
+            }
+
+            flags.insert(combined);
+        }
+
+        flags.insert(Self::combine(&self.added));
+        flags.remove(Self::combine(&self.removed));
+    }
+
+    /// Whether a light's flags include every unprefixed or `+` flag, and no `-` flag.
+    /// NONE only matches lights which don't animate.
+    pub fn matches(&self, flags: LightFlags) -> bool {
+        let required = Self::combine(&self.flags) | Self::combine(&self.added);
+        let wants_static = self
+            .flags
+            .iter()
+            .chain(&self.added)
+            .any(|flag| matches!(flag, LightFlag::NONE));
+
+        if wants_static && flags.intersects(LightFlag::animation_flags()) {
+            return false;
+        }
+
+        flags.contains(required) && !flags.intersects(Self::combine(&self.removed))
+    }
+}
+
+impl FromStr for LightFlagOverride {
+    type Err = ParseLightError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut flag_override = LightFlagOverride::default();
+
+        for entry in s.split('|').map(str::trim).filter(|e| !e.is_empty()) {
+            let (target, names) = if let Some(names) = entry.strip_prefix('+') {
+                (&mut flag_override.added, names)
+            } else if let Some(names) = entry.strip_prefix('-') {
+                (&mut flag_override.removed, names)
+            } else {
+                (&mut flag_override.flags, entry)
+            };
+
+            for name in names.split('+').map(str::trim).filter(|n| !n.is_empty()) {
+                target.push(name.parse()?);
+            }
+        }
+
+        if flag_override.flags.is_empty()
+            && flag_override.added.is_empty()
+            && flag_override.removed.is_empty()
+        {
+            return Err(ParseLightError::EmptyFlags);
+        }
+
+        Ok(flag_override)
+    }
+}
+
+impl fmt::Display for LightFlagOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries: Vec<String> = self
+            .flags
+            .iter()
+            .map(|flag| format!("{flag:?}"))
+            .chain(self.added.iter().map(|flag| format!("+{flag:?}")))
+            .chain(self.removed.iter().map(|flag| format!("-{flag:?}")))
+            .collect();
+
+        write!(f, "{}", entries.join("|"))
+    }
+}
+
+impl Serialize for LightFlagOverride {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Flags may be written in TOML as a single string or as an array of strings
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawLightFlagOverride {
+    Single(String),
+    Many(Vec<String>),
+}
+
+impl<'de> serde::Deserialize<'de> for LightFlagOverride {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let flags = match RawLightFlagOverride::deserialize(deserializer)? {
+            RawLightFlagOverride::Single(flags) => flags,
+            RawLightFlagOverride::Many(flags) => flags.join("|"),
+        };
+
+        flags.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_are_parsed() {
+        let data: CustomLightData = "hue_shift=-20,saturation_add=0.1,value_add=-0.25"
+            .parse()
+            .unwrap();
+
+        assert_eq!(data.hue_shift, Some(-20.0));
+        assert_eq!(data.saturation_add, Some(0.1));
+        assert_eq!(data.value_add, Some(-0.25));
+    }
+
+    #[test]
+    fn offsets_exclude_fixed_values_and_multipliers() {
+        for override_str in [
+            "hue=10,hue_shift=5",
+            "hue_shift=5,hue_mult=2",
+            "saturation_add=0.1,saturation=0.5",
+            "saturation_mult=2,saturation_add=0.1",
+            "value=0.5,value_add=0.1",
+            "value_add=0.1,value_mult=2",
+        ] {
+            assert!(
+                matches!(
+                    override_str.parse::<CustomLightData>(),
+                    Err(ParseLightError::ExclusiveFields(..))
+                ),
+                "{override_str} should have been rejected"
+            );
+        }
+
+        for toml_str in [
+            "hue = 10\nhue_shift = 5.0",
+            "saturation_mult = 2.0\nsaturation_add = 0.1",
+            "value = 0.5\nvalue_add = 0.1",
+        ] {
+            assert!(
+                toml::from_str::<CustomLightData>(toml_str).is_err(),
+                "{toml_str} should have been rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn offsets_must_be_numbers() {
+        assert!(matches!(
+            "value_add=bright".parse::<CustomLightData>(),
+            Err(ParseLightError::BadNumber("value_add", _))
+        ));
+    }
+
+    #[test]
+    fn offsets_round_trip_through_toml() {
+        let data: CustomLightData = "hue_shift=15,saturation_add=-0.2,value_add=0.3"
+            .parse()
+            .unwrap();
+
+        let round_tripped: CustomLightData =
+            toml::from_str(&toml::to_string(&data).unwrap()).unwrap();
+
+        assert_eq!(round_tripped.hue_shift, data.hue_shift);
+        assert_eq!(round_tripped.saturation_add, data.saturation_add);
+        assert_eq!(round_tripped.value_add, data.value_add);
+    }
+
+    #[test]
+    fn radius_add_excludes_fixed_radius_and_multiplier() {
+        let data: CustomLightData = "radius_add=-16".parse().unwrap();
+        assert_eq!(data.radius_add, Some(-16));
+
+        for override_str in ["radius=10,radius_add=5", "radius_add=5,radius_mult=2"] {
+            assert!(
+                matches!(
+                    override_str.parse::<CustomLightData>(),
+                    Err(ParseLightError::ExclusiveFields(..))
+                ),
+                "{override_str} should have been rejected"
+            );
+        }
+
+        assert!(matches!(
+            "radius_add=1.5".parse::<CustomLightData>(),
+            Err(ParseLightError::BadNumber("radius_add", _))
+        ));
+    }
+
+    #[test]
+    fn off_by_default_is_parsed_in_any_case() {
+        for name in ["offdefault", "OFFDEFAULT", "OffDefault"] {
+            let flag: LightFlag = name.parse().unwrap();
+
+            assert!(matches!(flag, LightFlag::OFFDEFAULT));
+            assert_eq!(flag.to_esp_flag(), LightFlags::OFF_BY_DEFAULT);
+        }
+
+        assert!(matches!(
+            "offbydefault".parse::<LightFlag>(),
+            Err(ParseLightError::UnknownVariant(_))
+        ));
+    }
+
+    #[test]
+    fn off_by_default_round_trips_through_toml() {
+        let data: CustomLightData = "flag=offdefault".parse().unwrap();
+        let round_tripped: CustomLightData =
+            toml::from_str(&toml::to_string(&data).unwrap()).unwrap();
+
+        let mut flags = LightFlags::empty();
+        round_tripped
+            .flag
+            .unwrap()
+            .apply_to(&mut flags, round_tripped.replace_flags.unwrap_or(false));
+
+        assert_eq!(flags, LightFlags::OFF_BY_DEFAULT);
+    }
+
+    #[test]
+    fn combined_flags_replace_the_animation() {
+        let flag_override: LightFlagOverride = "FLICKERSLOW|OFFDEFAULT".parse().unwrap();
+
+        let mut flags = LightFlags::PULSE | LightFlags::FIRE;
+        flag_override.apply_to(&mut flags, false);
+        assert_eq!(
+            flags,
+            LightFlags::FLICKER_SLOW | LightFlags::OFF_BY_DEFAULT | LightFlags::FIRE
+        );
+
+        let mut flags = LightFlags::PULSE | LightFlags::FIRE;
+        flag_override.apply_to(&mut flags, true);
+        assert_eq!(flags, LightFlags::FLICKER_SLOW | LightFlags::OFF_BY_DEFAULT);
+    }
+
+    #[test]
+    fn prefixed_flags_are_relative_to_the_original() {
+        let flag_override: LightFlagOverride = "+FIRE|-DYNAMIC".parse().unwrap();
+
+        let mut flags = LightFlags::PULSE | LightFlags::DYNAMIC;
+        flag_override.apply_to(&mut flags, false);
+        assert_eq!(flags, LightFlags::PULSE | LightFlags::FIRE);
+
+        // Prefixed flags are applied after replacement too
+        let mut flags = LightFlags::PULSE | LightFlags::DYNAMIC;
+        flag_override.apply_to(&mut flags, true);
+        assert_eq!(flags, LightFlags::FIRE);
+    }
+
+    #[test]
+    fn flags_may_be_joined_with_plus_or_given_as_an_array() {
+        let joined: LightFlagOverride = "flickerslow+offdefault".parse().unwrap();
+        let data: CustomLightData =
+            toml::from_str(r#"flag = ["FLICKERSLOW", "OFFDEFAULT"]"#).unwrap();
+
+        for flag_override in [joined, data.flag.unwrap()] {
+            let mut flags = LightFlags::empty();
+            flag_override.apply_to(&mut flags, true);
+            assert_eq!(flags, LightFlags::FLICKER_SLOW | LightFlags::OFF_BY_DEFAULT);
+        }
+    }
+
+    #[test]
+    fn bad_flag_lists_are_rejected() {
+        assert!(matches!(
+            " | ".parse::<LightFlagOverride>(),
+            Err(ParseLightError::EmptyFlags)
+        ));
+        assert!(matches!(
+            "FLICKER|SPARKLE".parse::<LightFlagOverride>(),
+            Err(ParseLightError::UnknownVariant(_))
+        ));
+    }
+
+    #[test]
+    fn model_paths_must_be_relative() {
+        for override_str in [
+            r"mesh=C:\meshes\lantern.nif",
+            "mesh=/meshes/lantern.nif",
+            r"icon=\icons\lantern.dds",
+            "icon= ",
+        ] {
+            assert!(
+                matches!(
+                    override_str.parse::<CustomLightData>(),
+                    Err(ParseLightError::BadPath(..))
+                ),
+                "{override_str} should have been rejected"
+            );
+        }
+
+        assert!(toml::from_str::<CustomLightData>(r#"mesh = "/meshes/lantern.nif""#).is_err());
+    }
+
+    #[test]
+    fn model_paths_round_trip_through_toml() {
+        let data: CustomLightData = r"mesh=l\light_paper_lantern_01.nif,icon=l\paper_lantern.dds"
+            .parse()
+            .unwrap();
+        let round_tripped: CustomLightData =
+            toml::from_str(&toml::to_string(&data).unwrap()).unwrap();
+
+        assert_eq!(
+            round_tripped.mesh.as_deref(),
+            Some(r"l\light_paper_lantern_01.nif")
+        );
+        assert_eq!(round_tripped.icon.as_deref(), Some(r"l\paper_lantern.dds"));
+    }
+
+    #[test]
+    fn radius_ranges_are_parsed_and_inclusive() {
+        let data: CustomLightData = "match_radius_min=32,match_radius_max=95,radius=128"
+            .parse()
+            .unwrap();
+
+        assert!(data.matches_radius(32));
+        assert!(data.matches_radius(95));
+        assert!(!data.matches_radius(31));
+        assert!(!data.matches_radius(96));
+
+        let data: CustomLightData = toml::from_str("match_radius_max = 95").unwrap();
+        assert!(data.matches_radius(0));
+        assert!(!data.matches_radius(200));
+
+        assert!(matches!(
+            "match_radius_min=-1".parse::<CustomLightData>(),
+            Err(ParseLightError::BadNumber(..))
+        ));
+    }
+
+    #[test]
+    fn display_names_match_regardless_of_case() {
+        let data: CustomLightData = "match_name=^paper lantern".parse().unwrap();
+
+        assert!(data.matches_name("Paper Lantern"));
+        assert!(data.matches_name("PAPER LANTERN (Blue)"));
+        assert!(!data.matches_name("Candle"));
+
+        // Lights without a pattern match any name
+        assert!(CustomLightData::default().matches_name("Candle"));
+    }
+
+    #[test]
+    fn localized_display_names_are_matched() {
+        let data: CustomLightData = "match_name=свеча|bougie".parse().unwrap();
+
+        assert!(data.matches_name("Свеча"));
+        assert!(data.matches_name("Bougie éclairée"));
+        assert!(!data.matches_name("Fackel"));
+
+        let data: CustomLightData = toml::from_str(r#"match_name = "^ランタン""#).unwrap();
+        assert!(data.matches_name("ランタン（紙）"));
+    }
+
+    #[test]
+    fn bad_name_patterns_are_rejected() {
+        assert!(matches!(
+            "match_name=(candle".parse::<CustomLightData>(),
+            Err(ParseLightError::BadRegex("match_name", _))
+        ));
+        assert!(toml::from_str::<CustomLightData>(r#"match_name = "(candle""#).is_err());
+    }
+
+    #[test]
+    fn real_minutes_exclude_other_durations() {
+        for override_str in [
+            "duration=60,target_real_minutes=10",
+            "target_real_minutes=10,duration_mult=2",
+        ] {
+            assert!(
+                matches!(
+                    override_str.parse::<CustomLightData>(),
+                    Err(ParseLightError::ExclusiveFields(..))
+                ),
+                "{override_str} should have been rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn tints_are_parsed_from_hex_or_hsv() {
+        assert_eq!(
+            "#ff9944".parse::<TintColor>().unwrap(),
+            TintColor([255, 153, 68, 0])
+        );
+        assert_eq!(
+            "FF9944".parse::<TintColor>().unwrap(),
+            TintColor([255, 153, 68, 0])
+        );
+        assert_eq!(TintColor([255, 153, 68, 0]).to_string(), "#ff9944");
+
+        for bad_tint in ["#ff99", "#gg9944", "#ff99440", "#ff99é"] {
+            assert!(
+                matches!(
+                    bad_tint.parse::<TintColor>(),
+                    Err(ParseLightError::BadColor("tint", _))
+                ),
+                "{bad_tint} should have been rejected"
+            );
+        }
+
+        let data: CustomLightData =
+            toml::from_str("tint = { hue = 240, saturation = 1.0, value = 1.0 }").unwrap();
+        assert_eq!(data.tint, Some(TintColor([0, 0, 255, 0])));
+    }
+
+    #[test]
+    fn tints_exclude_fixed_color_channels() {
+        for override_str in [
+            "tint=#ff9944,hue=10",
+            "saturation=0.5,tint=#ff9944",
+            "value=0.5,tint=#ff9944",
+        ] {
+            assert!(
+                matches!(
+                    override_str.parse::<CustomLightData>(),
+                    Err(ParseLightError::ExclusiveFields(..))
+                ),
+                "{override_str} should have been rejected"
+            );
+        }
+
+        assert!(toml::from_str::<CustomLightData>("tint = \"#ff9944\"\nhue = 10").is_err());
+    }
+
+    #[test]
+    fn tint_strength_is_clamped() {
+        let data: CustomLightData = "tint=#ff9944,tint_strength=3".parse().unwrap();
+        assert_eq!(data.tint_strength, Some(1.0));
+
+        let round_tripped: CustomLightData =
+            toml::from_str(&toml::to_string(&data).unwrap()).unwrap();
+        assert_eq!(round_tripped.tint, data.tint);
+        assert_eq!(round_tripped.tint_strength, Some(1.0));
+    }
+
+    #[test]
+    fn ambient_multipliers_are_parsed() {
+        let cell_ambient: CustomCellAmbient =
+            "ambient_mult=value=0.5;fog_mult=hue=1.2,saturation=0.5"
+                .parse()
+                .unwrap();
+
+        let ambient_mult = cell_ambient.ambient_mult.unwrap();
+        assert_eq!(
+            (
+                ambient_mult.hue,
+                ambient_mult.saturation,
+                ambient_mult.value
+            ),
+            (1.0, 1.0, 0.5)
+        );
+
+        let fog_mult = cell_ambient.fog_mult.unwrap();
+        assert_eq!(
+            (fog_mult.hue, fog_mult.saturation, fog_mult.value),
+            (1.2, 0.5, 1.0)
+        );
+
+        assert!(matches!(
+            "ambient_mult=brightness=0.5".parse::<CustomCellAmbient>(),
+            Err(ParseAmbientError::BadColor(..))
+        ));
+    }
+
+    #[test]
+    fn ambient_multipliers_exclude_fixed_colors() {
+        assert!(matches!(
+            "sunlight=hue=30,saturation=0.5,value=0.5;sunlight_mult=value=0.5"
+                .parse::<CustomCellAmbient>(),
+            Err(ParseAmbientError::ExclusiveFields(
+                "sunlight",
+                "sunlight_mult"
+            ))
+        ));
+
+        let toml_str =
+            "fog = { hue = 30, saturation = 0.5, value = 0.5 }\nfog_mult = { value = 0.5 }";
+        assert!(toml::from_str::<CustomCellAmbient>(toml_str).is_err());
+    }
+
+    #[test]
+    fn color_multipliers_scale_in_hsv_and_clamp() {
+        let halved = LightColorMult {
+            value: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(
+            halved.apply([200, 100, 50, 0], false, crate::HueOverflow::Wrap),
+            [100, 50, 25, 0]
+        );
+
+        let tripled = LightColorMult {
+            value: 3.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            tripled.apply([200, 100, 50, 0], false, crate::HueOverflow::Wrap),
+            [255, 128, 64, 0]
+        );
+
+        // Unset fields leave their channel alone
+        let data: LightColorMult = toml::from_str("saturation = 0.0").unwrap();
+        assert_eq!((data.hue, data.value), (1.0, 1.0));
+    }
+
+    #[test]
+    fn fog_density_mult_excludes_fixed_density() {
+        let cell_ambient: CustomCellAmbient = "fog_density_mult=1.5".parse().unwrap();
+        assert_eq!(cell_ambient.fog_density_mult, Some(1.5));
+
+        assert!(matches!(
+            "fog_density=0.5;fog_density_mult=1.5".parse::<CustomCellAmbient>(),
+            Err(ParseAmbientError::ExclusiveFields(
+                "fog_density",
+                "fog_density_mult"
+            ))
+        ));
+        assert!(
+            toml::from_str::<CustomCellAmbient>("fog_density = 0.5\nfog_density_mult = 1.5")
+                .is_err()
+        );
+
+        let round_tripped: CustomCellAmbient =
+            toml::from_str(&toml::to_string(&cell_ambient).unwrap()).unwrap();
+        assert_eq!(round_tripped.fog_density_mult, Some(1.5));
+    }
+
+    #[test]
+    fn hue_ranges_are_inclusive() {
+        let light_data: CustomLightData = "match_hue_min=180,match_hue_max=260".parse().unwrap();
+
+        assert!(light_data.matches_hue(180.0));
+        assert!(light_data.matches_hue(240.0));
+        assert!(light_data.matches_hue(260.0));
+        assert!(!light_data.matches_hue(30.0));
+        assert!(!light_data.matches_hue(260.5));
+    }
+
+    #[test]
+    fn hue_ranges_wrap_through_zero() {
+        let reds: CustomLightData = "match_hue_min=330,match_hue_max=20".parse().unwrap();
+
+        assert!(reds.matches_hue(350.0));
+        assert!(reds.matches_hue(0.0));
+        assert!(reds.matches_hue(15.0));
+        assert!(!reds.matches_hue(120.0));
+        assert!(!reds.matches_hue(329.0));
+    }
+
+    #[test]
+    fn open_hue_ranges_are_bounded_on_one_side() {
+        let above: CustomLightData = "match_hue_min=200".parse().unwrap();
+        assert!(above.matches_hue(359.0) && !above.matches_hue(100.0));
+
+        let below: CustomLightData = "match_hue_max=60".parse().unwrap();
+        assert!(below.matches_hue(0.0) && !below.matches_hue(61.0));
+
+        assert!(CustomLightData::default().matches_hue(123.0));
+    }
+
+    #[test]
+    fn exterior_grids_are_parsed() {
+        let grid = ExteriorGrid::from_key("ext:2,-9").unwrap().unwrap();
+        assert!(grid.contains(2, -9));
+        assert!(!grid.contains(2, -8) && !grid.contains(3, -9));
+
+        let block = ExteriorGrid::from_key(" ext: 2..5 , -9..-6 ")
+            .unwrap()
+            .unwrap();
+        assert_eq!((block.x, block.y), (2..=5, -9..=-6));
+    }
+
+    #[test]
+    fn cell_id_patterns_are_not_grids() {
+        assert!(ExteriorGrid::from_key("balmora, guild of mages").is_none());
+        assert!(ExteriorGrid::from_key("^ext").is_none());
+    }
+
+    #[test]
+    fn bad_grids_are_rejected() {
+        for key in ["ext:2", "ext:a,b", "ext:5..2,0", "ext:1,2,3", "ext:"] {
+            assert!(
+                matches!(
+                    ExteriorGrid::from_key(key),
+                    Some(Err(ParseAmbientError::BadGrid(_)))
+                ),
+                "{key}"
+            );
+        }
+    }
+}
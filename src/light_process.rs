@@ -0,0 +1,1787 @@
+use palette::{FromColor, GetHue, Hsv, IntoColor, LinSrgb, RgbHue, SetHue, Srgb};
+use serde::{Deserialize, Serialize};
+use tes3::esp::{EditorId, Light, LightFlags, MagicEffect};
+
+use crate::{CustomLightData, LightConfig, LightPlacement};
+
+/// Decides what happens to a hue which is multiplied past either end of the color wheel
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum HueOverflow {
+    /// Continue around the color wheel, so 390 degrees becomes 30
+    #[default]
+    Wrap,
+    /// Stop at the end of the color wheel, so 390 degrees becomes 360
+    Clamp,
+}
+
+/// Decides what happens to flickering lights
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum FlickerMode {
+    /// Leave flickering lights alone
+    Keep,
+    /// Stop lights from flickering
+    Remove,
+    /// Turn flickering into pulsing, keeping the speed of the original flicker
+    ToPulse,
+    /// Turn all flickering into slow pulsing
+    ToPulseSlow,
+}
+
+/// Decides what happens to lights carrying the NEGATIVE flag
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum NegativeLightMode {
+    /// Remove the flag and turn the light into a black, zero-radius light
+    #[default]
+    Zero,
+    /// Leave the light exactly as it is, apart from flicker and pulse removal
+    Keep,
+    /// Remove the flag, then process the light like any other
+    RemoveFlag,
+}
+
+/// Multiplies a hue, normalizing the result into 0 - 360 degrees
+/// according to the requested overflow behavior
+pub fn multiply_hue(hue: RgbHue, mult: f32, overflow: HueOverflow) -> RgbHue {
+    let degrees = match overflow {
+        HueOverflow::Wrap => (hue.into_raw_degrees() * mult).rem_euclid(360.0),
+        HueOverflow::Clamp => (hue.into_positive_degrees() * mult).clamp(0.0, 360.0),
+    };
+
+    RgbHue::from_degrees(degrees)
+}
+
+/// Offsets a hue by some degrees, normalizing the result into 0 - 360 degrees
+/// according to the requested overflow behavior, like [`multiply_hue`]
+pub fn offset_hue(hue: RgbHue, offset: f32, overflow: HueOverflow) -> RgbHue {
+    let degrees = match overflow {
+        HueOverflow::Wrap => (hue.into_raw_degrees() + offset).rem_euclid(360.0),
+        HueOverflow::Clamp => (hue.into_positive_degrees() + offset).clamp(0.0, 360.0),
+    };
+
+    RgbHue::from_degrees(degrees)
+}
+
+/// Scales a light's radius by a multiplier, then adds an offset,
+/// rounding to the nearest unit and saturating into the range of a u32.
+/// If the result isn't a finite number, the original radius is kept.
+pub fn scale_radius(radius: u32, mult: f32, offset: i32) -> u32 {
+    let scaled = radius as f64 * mult as f64 + offset as f64;
+
+    if !scaled.is_finite() {
+        return radius;
+    }
+
+    scaled.round().clamp(0.0, u32::MAX as f64) as u32
+}
+
+/// Remaps a light's radius along a power curve around `base`, then scales it like [`scale_radius`].
+/// Exponents below 1.0 grow small lights more than large ones, while a radius of `base` is unaffected by the curve.
+/// If the result isn't a finite number, the original radius is kept.
+pub fn scale_radius_curved(radius: u32, base: f32, exponent: f32, mult: f32, offset: i32) -> u32 {
+    let curved = (radius as f64 / base as f64).powf(exponent as f64) * base as f64;
+    let scaled = curved * mult as f64 + offset as f64;
+
+    if !scaled.is_finite() {
+        return radius;
+    }
+
+    scaled.round().clamp(0.0, u32::MAX as f64) as u32
+}
+
+/// Gives back a pseudo-random offset between `-max` and `max`, seeded by a light's id and
+/// the channel being jittered, so that repeated runs produce identical plugins
+pub fn jitter(light_id: &str, channel: &str, max: f32) -> f32 {
+    let hash = fnv1a(light_id.bytes().chain([0]).chain(channel.bytes()));
+
+    // The top 53 bits fill an f64's mantissa exactly, giving an even spread over 0.0 - 1.0
+    let unit = (hash >> 11) as f64 / (1_u64 << 53) as f64;
+
+    ((unit * 2.0 - 1.0) * max as f64) as f32
+}
+
+/// FNV-1a, which unlike the std hasher is guaranteed to stay the same between Rust versions and platforms
+pub fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes
+        .into_iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Converts an 8-bit sRGB color from a plugin into HSV.
+/// When `linear` is set, the color is decoded into linear light first,
+/// so that scaling its value scales perceived brightness proportionally.
+pub fn color_to_hsv(color: [u8; 4], linear: bool) -> Hsv {
+    let mut rgb: Srgb = Srgb::new(color[0], color[1], color[2]).into_format();
+
+    if linear {
+        let linear_rgb: LinSrgb = rgb.into_linear();
+        rgb = Srgb::new(linear_rgb.red, linear_rgb.green, linear_rgb.blue);
+    }
+
+    Hsv::from_color(rgb)
+}
+
+/// Converts an HSV color back into the 8-bit sRGB format used by plugins.
+/// `linear` must match the value used to produce the HSV color.
+pub fn hsv_to_color(hsv: Hsv, linear: bool) -> [u8; 4] {
+    let mut rgb: Srgb = <Hsv as IntoColor<Srgb>>::into_color(hsv);
+
+    if linear {
+        rgb = Srgb::from_linear(LinSrgb::new(rgb.red, rgb.green, rgb.blue));
+    }
+
+    let rgb8_color: Srgb<u8> = rgb.into_format();
+    [rgb8_color.red, rgb8_color.green, rgb8_color.blue, 0]
+}
+
+/// Perceived brightness of a color from 0.0 to 1.0,
+/// being its relative luminance re-encoded with the sRGB transfer curve
+pub fn perceived_brightness(color: [u8; 4]) -> f32 {
+    let rgb: Srgb = Srgb::new(color[0], color[1], color[2]).into_format();
+    let linear_rgb: LinSrgb = rgb.into_linear();
+
+    let luminance = 0.2126 * linear_rgb.red + 0.7152 * linear_rgb.green + 0.0722 * linear_rgb.blue;
+
+    Srgb::from_linear(LinSrgb::new(luminance, luminance, luminance)).red
+}
+
+/// Finds the value multiplier which brings a color's perceived brightness near `target`.
+/// The multiplier is bounded to `1 / max_adjustment ..= max_adjustment`,
+/// so lights already close to the target are barely touched and extreme ones aren't flattened.
+pub fn value_normalization_factor(
+    color: [u8; 4],
+    target: f32,
+    max_adjustment: f32,
+    linear: bool,
+) -> f32 {
+    let brightness = perceived_brightness(color);
+
+    // Black lights can't be brightened by scaling their value
+    if brightness <= 0.0 {
+        return 1.0;
+    }
+
+    // Scaling linear light changes perceived brightness along the sRGB curve,
+    // whereas scaling encoded values changes it (roughly) proportionally
+    let factor = match linear {
+        true => (target / brightness).powf(2.2),
+        false => target / brightness,
+    };
+
+    factor.clamp(1.0 / max_adjustment, max_adjustment)
+}
+
+/// Blends an HSV color toward a tint in RGB space, by `strength` from 0.0 to 1.0.
+/// `linear` must match the value used to produce the HSV color, so both colors are mixed in the same space
+pub fn tint_hsv(hsv: Hsv, tint: [u8; 4], strength: f32, linear: bool) -> Hsv {
+    let strength = strength.clamp(0.0, 1.0);
+    let rgb: Srgb = hsv.into_color();
+    let target: Srgb = color_to_hsv(tint, linear).into_color();
+
+    let mix = |from: f32, to: f32| from + (to - from) * strength;
+
+    Hsv::from_color(Srgb::new(
+        mix(rgb.red, target.red),
+        mix(rgb.green, target.green),
+        mix(rgb.blue, target.blue),
+    ))
+}
+
+/// Given a LightData reference from an ESP light,
+/// returns the HSV version and whether it is colored or not (for the global modifier)
+/// Lights are always classified by their encoded sRGB hue,
+/// so the same lights count as colored whether or not `linear` is used
+pub fn light_to_hsv(light_data: &tes3::esp::LightData, linear: bool) -> (Hsv, bool) {
+    let hue_degrees = color_to_hsv(light_data.color, false)
+        .get_hue()
+        .into_positive_degrees();
+
+    (
+        color_to_hsv(light_data.color, linear),
+        hue_degrees > 64. || hue_degrees < 14.,
+    )
+}
+
+/// Gives back the duration a light needs to burn for `real_minutes` at the given timescale.
+/// The vanilla timescale of 30 is the reference, at which durations are left in real seconds.
+/// Like [`scale_duration`], lights which burn forever are left untouched and finite lights stay finite
+pub fn real_minutes_to_duration(time: i32, real_minutes: f32, timescale: f32) -> i32 {
+    if time <= 0 {
+        return time;
+    }
+
+    let duration = (real_minutes as f64 * 60.0 * timescale as f64 / 30.0).round();
+
+    if duration.is_nan() {
+        return time;
+    }
+
+    duration.clamp(1.0, i32::MAX as f64) as i32
+}
+
+/// Multiplies a light's duration, rounding to the nearest second
+/// Lights which burn forever (duration <= 0) are left untouched,
+/// and finite lights are kept finite by never scaling below one second
+pub fn scale_duration(time: i32, mult: f32) -> i32 {
+    if time <= 0 {
+        return time;
+    }
+
+    let scaled = (time as f64 * mult as f64).round();
+
+    if scaled.is_nan() {
+        return time;
+    }
+
+    scaled.clamp(1.0, i32::MAX as f64) as i32
+}
+
+/// Multiplies a light's weight, which never drops below zero
+/// If the result isn't a finite number, the original weight is kept
+pub fn scale_weight(weight: f32, mult: f32) -> f32 {
+    let scaled = weight * mult;
+
+    if !scaled.is_finite() {
+        return weight;
+    }
+
+    scaled.max(0.0)
+}
+
+/// Scales the glow color of a magic effect by the colored light multipliers,
+/// with `magic_saturation` and `magic_value` used instead when set
+/// Returns whether the color changed
+pub fn process_magic_effect(light_config: &LightConfig, effect: &mut MagicEffect) -> bool {
+    let original_color = effect.data.color;
+    let [red, green, blue] = original_color.map(|channel| channel.clamp(0, 255) as u8);
+
+    let mut effect_as_hsv = color_to_hsv([red, green, blue, 0], light_config.linear_color);
+
+    effect_as_hsv.set_hue(multiply_hue(
+        effect_as_hsv.hue,
+        light_config.colored_hue,
+        light_config.hue_overflow,
+    ));
+    effect_as_hsv.saturation = (effect_as_hsv.saturation
+        * light_config
+            .magic_saturation
+            .unwrap_or(light_config.colored_saturation))
+    .clamp(0.0, 1.0);
+    effect_as_hsv.value = (effect_as_hsv.value
+        * light_config
+            .magic_value
+            .unwrap_or(light_config.colored_value))
+    .clamp(0.0, 1.0);
+
+    let [red, green, blue, _] = hsv_to_color(effect_as_hsv, light_config.linear_color);
+    effect.data.color = [red, green, blue].map(Into::into);
+
+    effect.data.color != original_color
+}
+
+/// Multiplies a cell's fog density, keeping it within the 0.0 - 1.0 the engine accepts
+/// If the result isn't a finite number, the original density is kept
+pub fn scale_fog_density(density: f32, mult: f32) -> f32 {
+    let scaled = density * mult;
+
+    if !scaled.is_finite() {
+        return density;
+    }
+
+    scaled.clamp(0.0, 1.0)
+}
+
+/// Multiplies a light's gold value, rounding to the nearest coin
+/// and saturating into the range of a u32
+pub fn scale_gold_value(value: u32, mult: f32) -> u32 {
+    let scaled = (value as f64 * mult as f64).round();
+
+    if scaled.is_nan() {
+        return value;
+    }
+
+    scaled.clamp(0.0, u32::MAX as f64) as u32
+}
+
+/// Removes or converts flickering and pulsing on a light, as requested by the config
+/// Pulsing is removed first, so that flicker converted into pulse survives
+pub fn clean_animation_flags(light_config: &LightConfig, light: &mut Light) {
+    let flags = &mut light.data.flags;
+
+    if light_config.disable_pulse {
+        flags.remove(LightFlags::PULSE | LightFlags::PULSE_SLOW);
+    }
+
+    match light_config.effective_flicker_mode() {
+        FlickerMode::Keep => {}
+        FlickerMode::Remove => flags.remove(LightFlags::FLICKER | LightFlags::FLICKER_SLOW),
+        FlickerMode::ToPulse => {
+            if flags.contains(LightFlags::FLICKER) {
+                flags.remove(LightFlags::FLICKER);
+                flags.insert(LightFlags::PULSE);
+            }
+
+            if flags.contains(LightFlags::FLICKER_SLOW) {
+                flags.remove(LightFlags::FLICKER_SLOW);
+                flags.insert(LightFlags::PULSE_SLOW);
+            }
+        }
+        FlickerMode::ToPulseSlow => {
+            if flags.intersects(LightFlags::FLICKER | LightFlags::FLICKER_SLOW) {
+                flags.remove(LightFlags::FLICKER | LightFlags::FLICKER_SLOW);
+                flags.insert(LightFlags::PULSE_SLOW);
+            }
+        }
+    }
+}
+
+/// Removes the looping sound of lights matching `strip_sounds_ids`,
+/// then applies the sound of a matching override, where `none` removes it
+pub fn apply_sound(
+    light_config: &LightConfig,
+    replacement: Option<&CustomLightData>,
+    light_id: &str,
+    light: &mut Light,
+) {
+    if light_config.is_sound_stripped(light_id) {
+        light.sound.clear();
+    }
+
+    if let Some(sound) = replacement.and_then(|replacement| replacement.sound.as_ref()) {
+        if sound.eq_ignore_ascii_case("none") {
+            light.sound.clear();
+        } else {
+            light.sound = sound.to_owned();
+        }
+    }
+}
+
+/// Replaces the mesh and icon of a light with those of a matching override
+pub fn apply_model(replacement: Option<&CustomLightData>, light: &mut Light) {
+    let Some(replacement) = replacement else {
+        return;
+    };
+
+    if let Some(mesh) = &replacement.mesh {
+        light.mesh = mesh.to_owned();
+    }
+
+    if let Some(icon) = &replacement.icon {
+        light.icon = icon.to_owned();
+    }
+}
+
+/// Which of the config's categories a light's global multipliers come from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightCategory {
+    /// Lights with a warm, fire-like hue, using the `standard_*` settings
+    Standard,
+    /// Red, purple, blue, green, and yellow lights, using the `colored_*` settings
+    Colored,
+}
+
+impl LightCategory {
+    /// Classifies a light by its color, the same way processing does
+    pub fn of(light_data: &tes3::esp::LightData) -> Self {
+        match light_to_hsv(light_data, false).1 {
+            true => Self::Colored,
+            false => Self::Standard,
+        }
+    }
+}
+
+/// Everything processing would write onto a light record, along with how it got there
+#[derive(Clone, Debug)]
+pub struct ProcessedLight {
+    pub color: [u8; 4],
+    pub radius: u32,
+    pub time: i32,
+    pub flags: LightFlags,
+    pub weight: f32,
+    pub value: u32,
+    pub sound: String,
+    pub mesh: String,
+    pub icon: String,
+    /// The category the light's global multipliers came from
+    pub category: LightCategory,
+    /// Key of the highest priority light override which matched, if any
+    pub override_key: Option<String>,
+    /// Whether the light would be written into the generated plugin
+    pub should_emit: bool,
+}
+
+impl ProcessedLight {
+    /// Writes the processed values onto a light record
+    pub fn apply_to(&self, light: &mut Light) {
+        light.data.color = self.color;
+        light.data.radius = self.radius;
+        light.data.time = self.time;
+        light.data.flags = self.flags;
+        light.data.weight = self.weight;
+        light.data.value = self.value;
+        light.sound.clone_from(&self.sound);
+        light.mesh.clone_from(&self.mesh);
+        light.icon.clone_from(&self.icon);
+    }
+}
+
+/// Works out what processing would do to a light, without touching it.
+/// Overrides limited to a plugin, or to interiors or exteriors, never match here;
+/// use [`simulate_light_in`] to take those into account
+pub fn simulate_light(light_config: &LightConfig, light: &Light) -> ProcessedLight {
+    simulate_light_in(light_config, light, "", None)
+}
+
+/// Works out what processing would do to a light from the given plugin, without touching it.
+/// `placement` is where the light is placed, as required by `interior_only` and `exterior_only`
+pub fn simulate_light_in(
+    light_config: &LightConfig,
+    light: &Light,
+    plugin_name: &str,
+    placement: Option<LightPlacement>,
+) -> ProcessedLight {
+    let matched = light_config.matched_override(light, plugin_name, placement);
+
+    let mut processed = simulate_light_with(
+        light_config,
+        light,
+        matched.as_ref().map(|(_, light_data)| light_data.as_ref()),
+    );
+
+    processed.override_key = matched.map(|(key, _)| key.to_owned());
+    processed
+}
+
+/// Works out what processing would do to a light, given the override already selected for it.
+/// This is the core [`process_light`] runs on, so the two can never disagree
+pub fn simulate_light_with(
+    light_config: &LightConfig,
+    light: &Light,
+    replacement_light_data: Option<&CustomLightData>,
+) -> ProcessedLight {
+    let mut processed = light.clone();
+    let should_emit = apply_processing(light_config, &mut processed, replacement_light_data);
+
+    ProcessedLight {
+        color: processed.data.color,
+        radius: processed.data.radius,
+        time: processed.data.time,
+        flags: processed.data.flags,
+        weight: processed.data.weight,
+        value: processed.data.value,
+        sound: processed.sound,
+        mesh: processed.mesh,
+        icon: processed.icon,
+        category: LightCategory::of(&light.data),
+        override_key: None,
+        should_emit,
+    }
+}
+
+/// Applies the light config to a light record
+/// Returns whether the light should be written into the generated plugin
+/// `replacement_light_data` is the override selected for the light by [`LightConfig::light_override`]
+pub fn process_light(
+    light_config: &LightConfig,
+    light: &mut Light,
+    replacement_light_data: Option<&CustomLightData>,
+) -> bool {
+    let processed = simulate_light_with(light_config, light, replacement_light_data);
+    processed.apply_to(light);
+    processed.should_emit
+}
+
+fn apply_processing(
+    light_config: &LightConfig,
+    light: &mut Light,
+    replacement_light_data: Option<&CustomLightData>,
+) -> bool {
+    let original_flags = light.data.flags;
+    let light_id = light.editor_id_ascii_lowercase().into_owned();
+
+    // Disabled lights are nulled out the same way negative lights are
+    if replacement_light_data.is_some_and(CustomLightData::is_disabled) {
+        light.data.radius = 0;
+        light.data.color = [0, 0, 0, 0];
+        light.data.flags = LightFlags::empty();
+        return true;
+    }
+
+    if light.data.flags.contains(LightFlags::NEGATIVE) {
+        match light_config.negative_light_mode {
+            NegativeLightMode::Zero => {
+                light.data.flags.remove(LightFlags::NEGATIVE);
+                light.data.radius = 0;
+                light.data.color = [0, 0, 0, 0];
+                return true;
+            }
+            // Kept lights are only worth emitting if their flags were cleaned up
+            NegativeLightMode::Keep => {
+                clean_animation_flags(light_config, light);
+                return light.data.flags != original_flags;
+            }
+            NegativeLightMode::RemoveFlag => light.data.flags.remove(LightFlags::NEGATIVE),
+        }
+    }
+
+    clean_animation_flags(light_config, light);
+
+    // Override flags are applied after flicker and pulse cleanup, so they always win
+    if let Some(replacement) = replacement_light_data
+        && let Some(flag) = &replacement.flag
+    {
+        flag.apply_to(
+            &mut light.data.flags,
+            replacement.replace_flags.unwrap_or(false),
+        );
+    }
+
+    if light_config.is_forced_off_by_default(&light_id) {
+        light.data.flags.insert(LightFlags::OFF_BY_DEFAULT);
+    }
+
+    // Lights are only worth emitting in flags_only mode if their flags, sound, or model were changed
+    if light_config.flags_only {
+        let (original_sound, original_mesh, original_icon) =
+            (light.sound.clone(), light.mesh.clone(), light.icon.clone());
+
+        apply_sound(light_config, replacement_light_data, &light_id, light);
+        apply_model(replacement_light_data, light);
+
+        return light.data.flags != original_flags
+            || light.sound != original_sound
+            || light.mesh != original_mesh
+            || light.icon != original_icon;
+    }
+
+    let (mut light_as_hsv, is_colored) = light_to_hsv(&light.data, light_config.linear_color);
+    let original_time = light.data.time;
+
+    let (
+        global_radius,
+        global_radius_add,
+        global_hue,
+        global_saturation,
+        global_value,
+        normalize_value,
+        global_duration,
+        global_tint,
+    ) = match is_colored {
+        // Red, purple, blue, green, yellow
+        true => (
+            light_config.colored_radius,
+            light_config.colored_radius_add,
+            light_config.colored_hue,
+            light_config.colored_saturation,
+            light_config.colored_value,
+            light_config.colored_normalize_value,
+            light_config.colored_duration_mult,
+            light_config
+                .colored_tint
+                .map(|tint| (tint, light_config.colored_tint_strength)),
+        ),
+        // Everything else
+        false => (
+            light_config.standard_radius,
+            light_config.standard_radius_add,
+            light_config.standard_hue,
+            light_config.standard_saturation,
+            light_config.standard_value,
+            light_config.standard_normalize_value,
+            light_config.standard_duration_mult,
+            light_config
+                .standard_tint
+                .map(|tint| (tint, light_config.standard_tint_strength)),
+        ),
+    };
+
+    let global_duration = global_duration.unwrap_or(light_config.duration_mult);
+
+    // Carryable lights get their own multipliers on top of their category's
+    let (carryable_radius, carryable_value, carryable_duration) =
+        match original_flags.contains(LightFlags::CAN_CARRY) {
+            true => (
+                light_config.carryable_radius_mult,
+                light_config.carryable_value_mult,
+                light_config.carryable_duration_mult,
+            ),
+            false => (1.0, 1.0, 1.0),
+        };
+
+    let global_radius = global_radius * carryable_radius;
+
+    // The radius curve takes the place of the plain category multiplier when it's configured
+    let scale_global_radius = |radius: u32| match light_config.radius_exponent {
+        Some(exponent) => scale_radius_curved(
+            radius,
+            light_config.radius_curve_base,
+            exponent,
+            global_radius,
+            global_radius_add,
+        ),
+        None => scale_radius(radius, global_radius, global_radius_add),
+    };
+    let global_duration = global_duration * carryable_duration;
+
+    // A real-time target takes the place of the duration multipliers when it's configured
+    let scale_global_duration = |time: i32| match light_config.target_real_minutes {
+        Some(real_minutes) => {
+            real_minutes_to_duration(time, real_minutes, light_config.effective_timescale())
+        }
+        None => scale_duration(time, global_duration),
+    };
+
+    // Brightness normalization takes the place of the category's value multiplier
+    let global_value = match normalize_value {
+        Some(target) => value_normalization_factor(
+            light.data.color,
+            target,
+            light_config.normalize_max_adjustment,
+            light_config.linear_color,
+        ),
+        None => global_value,
+    } * carryable_value;
+
+    if let Some(replacement) = replacement_light_data {
+        if let Some(hue_mult) = replacement.hue_mult {
+            let new_hue = multiply_hue(light_as_hsv.hue, hue_mult, light_config.hue_overflow);
+            light_as_hsv.set_hue(new_hue);
+        } else if let Some(fixed_hue) = replacement.hue {
+            light_as_hsv.set_hue(RgbHue::from_degrees(fixed_hue as f32));
+        } else if let Some(hue_shift) = replacement.hue_shift {
+            let new_hue = (light_as_hsv.hue.into_positive_degrees() + hue_shift).rem_euclid(360.0);
+            light_as_hsv.set_hue(RgbHue::from_degrees(new_hue));
+        } else {
+            let new_hue = multiply_hue(light_as_hsv.hue, global_hue, light_config.hue_overflow);
+            light_as_hsv.set_hue(new_hue);
+        }
+
+        if let Some(saturation_mult) = replacement.saturation_mult {
+            light_as_hsv.saturation *= saturation_mult;
+        } else if let Some(fixed_saturation) = replacement.saturation {
+            light_as_hsv.saturation = fixed_saturation;
+        } else if let Some(saturation_add) = replacement.saturation_add {
+            light_as_hsv.saturation = (light_as_hsv.saturation + saturation_add).clamp(0.0, 1.0);
+        } else {
+            light_as_hsv.saturation *= global_saturation;
+        }
+
+        if let Some(value_mult) = replacement.value_mult {
+            light_as_hsv.value *= value_mult;
+        } else if let Some(fixed_value) = replacement.value {
+            light_as_hsv.value = fixed_value;
+        } else if let Some(value_add) = replacement.value_add {
+            light_as_hsv.value = (light_as_hsv.value + value_add).clamp(0.0, 1.0);
+        } else {
+            light_as_hsv.value *= global_value;
+        }
+
+        if let Some(duration_mult) = replacement.duration_mult {
+            light.data.time = scale_duration(light.data.time, duration_mult);
+        } else if let Some(fixed_duration) = replacement.duration {
+            light.data.time = fixed_duration.round() as i32;
+        } else if let Some(real_minutes) = replacement.target_real_minutes {
+            light.data.time = real_minutes_to_duration(
+                light.data.time,
+                real_minutes,
+                light_config.effective_timescale(),
+            );
+        } else {
+            light.data.time = scale_global_duration(light.data.time);
+        }
+
+        if let Some(weight_mult) = replacement.weight_mult {
+            light.data.weight = scale_weight(light.data.weight, weight_mult);
+        } else if let Some(fixed_weight) = replacement.weight {
+            light.data.weight = fixed_weight.max(0.0);
+        } else {
+            light.data.weight = scale_weight(light.data.weight, light_config.weight_mult);
+        }
+
+        if let Some(gold_value_mult) = replacement.gold_value_mult {
+            light.data.value = scale_gold_value(light.data.value, gold_value_mult);
+        } else if let Some(fixed_gold_value) = replacement.gold_value {
+            light.data.value = fixed_gold_value;
+        } else {
+            light.data.value = scale_gold_value(light.data.value, light_config.gold_value_mult);
+        }
+
+        if let Some(radius_mult) = replacement.radius_mult {
+            light.data.radius = scale_radius(light.data.radius, radius_mult, 0);
+        } else if let Some(fixed_radius) = replacement.radius {
+            light.data.radius = fixed_radius;
+        } else if let Some(radius_add) = replacement.radius_add {
+            light.data.radius = scale_radius(light.data.radius, 1.0, radius_add);
+        } else {
+            light.data.radius = scale_global_radius(light.data.radius);
+        }
+    } else {
+        let new_hue = multiply_hue(light_as_hsv.hue, global_hue, light_config.hue_overflow);
+
+        light_as_hsv.set_hue(new_hue);
+        light_as_hsv.saturation *= global_saturation;
+        light_as_hsv.value *= global_value;
+
+        light.data.radius = scale_global_radius(light.data.radius);
+        light.data.time = scale_global_duration(light.data.time);
+        light.data.weight = scale_weight(light.data.weight, light_config.weight_mult);
+        light.data.value = scale_gold_value(light.data.value, light_config.gold_value_mult);
+    }
+
+    // Jitter is left out of any channel an override fixes outright, and happens before the bounds below
+    if let Some(hue_jitter) = light_config.hue_jitter
+        && replacement_light_data.is_none_or(|replacement| replacement.hue.is_none())
+    {
+        let new_hue = offset_hue(
+            light_as_hsv.hue,
+            jitter(&light_id, "hue", hue_jitter),
+            light_config.hue_overflow,
+        );
+        light_as_hsv.set_hue(new_hue);
+    }
+
+    if let Some(value_jitter) = light_config.value_jitter
+        && replacement_light_data.is_none_or(|replacement| replacement.value.is_none())
+    {
+        light_as_hsv.value =
+            (light_as_hsv.value + jitter(&light_id, "value", value_jitter)).clamp(0.0, 1.0);
+    }
+
+    if let Some(radius_jitter) = light_config.radius_jitter
+        && replacement_light_data.is_none_or(|replacement| replacement.radius.is_none())
+    {
+        let offset = jitter(&light_id, "radius", radius_jitter).round() as i32;
+        light.data.radius = scale_radius(light.data.radius, 1.0, offset);
+    }
+
+    // Keep very dim lights from rounding down to pure black
+    light_as_hsv.value = light_as_hsv.value.max(light_config.min_value);
+
+    if let Some(min_saturation) = light_config.min_saturation {
+        light_as_hsv.saturation = light_as_hsv.saturation.max(min_saturation);
+    }
+
+    if let Some(max_saturation) = light_config.max_saturation {
+        light_as_hsv.saturation = light_as_hsv.saturation.min(max_saturation);
+    }
+
+    // Lights with no positive duration burn forever, and must stay that way
+    if let Some(min_duration) = light_config.min_duration
+        && original_time > 0
+    {
+        light.data.time = light.data.time.max(min_duration);
+    }
+
+    if let Some(min_radius) = light_config.min_radius {
+        light.data.radius = light.data.radius.max(min_radius);
+    }
+
+    if let Some(max_radius) = light_config.max_radius {
+        light.data.radius = light.data.radius.min(max_radius);
+    }
+
+    // An override's tint replaces its category's, and overrides fixing a color channel are never tinted
+    let tint = match replacement_light_data {
+        Some(replacement) if replacement.tint.is_some() => replacement.tint.map(|tint| {
+            (
+                tint,
+                replacement
+                    .tint_strength
+                    .unwrap_or_else(crate::default::tint_strength),
+            )
+        }),
+        Some(replacement)
+            if replacement.hue.is_some()
+                || replacement.saturation.is_some()
+                || replacement.value.is_some() =>
+        {
+            None
+        }
+        _ => global_tint,
+    };
+
+    if let Some((tint, strength)) = tint {
+        light_as_hsv = tint_hsv(light_as_hsv, tint.0, strength, light_config.linear_color);
+    }
+
+    light.data.color = hsv_to_color(light_as_hsv, light_config.linear_color);
+
+    apply_sound(light_config, replacement_light_data, &light_id, light);
+    apply_model(replacement_light_data, light);
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TintColor;
+    use crate::test_fixtures::{light, light_override, neutral_config};
+
+    /// Hue of about 30 degrees, which counts as a standard light
+    const ORANGE: [u8; 3] = [255, 160, 64];
+
+    #[test]
+    fn additive_offsets_are_clamped_into_range() {
+        let replacement: CustomLightData = "saturation_add=-2,value_add=0.5".parse().unwrap();
+        let processed = simulate_light_with(
+            &neutral_config(),
+            &light("red_light", [255, 0, 0], 128),
+            Some(&replacement),
+        );
+
+        // No saturation and full value is white, rather than anything out of range
+        assert_eq!(processed.color, [255, 255, 255, 0]);
+    }
+
+    #[test]
+    fn hue_shift_wraps_around() {
+        let replacement: CustomLightData = "hue_shift=180".parse().unwrap();
+        let processed = simulate_light_with(
+            &neutral_config(),
+            &light("blue_light", [0, 0, 255], 128),
+            Some(&replacement),
+        );
+
+        // 240 + 180 degrees wraps around to yellow
+        assert_eq!(processed.color, [255, 255, 0, 0]);
+    }
+
+    #[test]
+    fn radius_offsets_never_underflow() {
+        assert_eq!(scale_radius(32, 1.5, 16), 64);
+        assert_eq!(scale_radius(32, 1.0, -100), 0);
+        assert_eq!(scale_radius(32, 1.0, i32::MIN), 0);
+    }
+
+    #[test]
+    fn negative_radius_add_stops_at_zero() {
+        let orange_light = light("orange_light", ORANGE, 128);
+
+        let light_config = LightConfig {
+            standard_radius_add: -500,
+            ..neutral_config()
+        };
+        assert_eq!(simulate_light(&light_config, &orange_light).radius, 0);
+
+        let replacement: CustomLightData = "radius_add=-200".parse().unwrap();
+        let processed = simulate_light_with(&neutral_config(), &orange_light, Some(&replacement));
+        assert_eq!(processed.radius, 0);
+    }
+
+    #[test]
+    fn radius_clamps_apply_after_multipliers() {
+        let light_config = LightConfig {
+            standard_radius: 2.0,
+            min_radius: Some(100),
+            max_radius: Some(400),
+            ..neutral_config()
+        };
+
+        // 32 * 2 lands below the floor, and 300 * 2 above the ceiling
+        let small = simulate_light(&light_config, &light("small_light", ORANGE, 32));
+        let large = simulate_light(&light_config, &light("large_light", ORANGE, 300));
+
+        assert_eq!(small.radius, 100);
+        assert_eq!(large.radius, 400);
+    }
+
+    #[test]
+    fn zeroed_negative_lights_ignore_min_radius() {
+        let light_config = LightConfig {
+            min_radius: Some(100),
+            ..neutral_config()
+        };
+
+        let mut negative_light = light("negative_light", ORANGE, 256);
+        negative_light.data.flags = LightFlags::NEGATIVE;
+
+        assert_eq!(simulate_light(&light_config, &negative_light).radius, 0);
+    }
+
+    #[test]
+    fn min_value_keeps_dim_lights_from_turning_black() {
+        let dim_light = light("dim_light", [1, 1, 0], 128);
+
+        let light_config = LightConfig {
+            standard_value: 0.3,
+            ..Default::default()
+        };
+        assert_eq!(
+            simulate_light(&light_config, &dim_light).color,
+            [0, 0, 0, 0]
+        );
+
+        let light_config = LightConfig {
+            standard_value: 0.3,
+            min_value: 0.2,
+            ..Default::default()
+        };
+        assert_ne!(
+            simulate_light(&light_config, &dim_light).color,
+            [0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn zeroed_negative_lights_ignore_min_value() {
+        let light_config = LightConfig {
+            min_value: 0.2,
+            ..neutral_config()
+        };
+
+        let mut negative_light = light("negative_light", ORANGE, 256);
+        negative_light.data.flags = LightFlags::NEGATIVE;
+
+        assert_eq!(
+            simulate_light(&light_config, &negative_light).color,
+            [0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn saturation_is_clamped_only_when_configured() {
+        let neon_light = light("neon_light", [255, 0, 0], 128);
+        let grey_light = light("grey_light", [130, 128, 128], 128);
+
+        let saturation_of = |light_config: &LightConfig, light: &Light| -> f32 {
+            color_to_hsv(simulate_light(light_config, light).color, false).saturation
+        };
+
+        let unclamped = neutral_config();
+        assert!(saturation_of(&unclamped, &neon_light) > 0.99);
+        assert!(saturation_of(&unclamped, &grey_light) < 0.05);
+
+        let clamped = LightConfig {
+            min_saturation: Some(0.5),
+            max_saturation: Some(0.6),
+            ..neutral_config()
+        };
+        assert!((saturation_of(&clamped, &neon_light) - 0.6).abs() < 0.01);
+        assert!((saturation_of(&clamped, &grey_light) - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn min_duration_leaves_infinite_lights_alone() {
+        let light_config = LightConfig {
+            min_duration: Some(30),
+            ..neutral_config()
+        };
+
+        for time in [-1, 0] {
+            let mut infinite_light = light("infinite_light", ORANGE, 128);
+            infinite_light.data.time = time;
+
+            assert_eq!(simulate_light(&light_config, &infinite_light).time, time);
+        }
+    }
+
+    #[test]
+    fn short_lights_never_round_down_to_zero() {
+        assert_eq!(scale_duration(1, 0.4), 1);
+
+        let light_config = LightConfig {
+            duration_mult: 0.1,
+            min_duration: Some(30),
+            ..neutral_config()
+        };
+
+        let mut short_light = light("short_light", ORANGE, 128);
+        short_light.data.time = 1;
+
+        assert_eq!(simulate_light(&light_config, &short_light).time, 30);
+    }
+
+    #[test]
+    fn durations_keep_their_sign_and_saturate() {
+        assert_eq!(scale_duration(-1, 5.0), -1);
+        assert_eq!(scale_duration(0, 5.0), 0);
+        assert_eq!(scale_duration(300, 1.5), 450);
+        assert_eq!(scale_duration(3, 0.5), 2);
+        assert_eq!(scale_duration(300, 1e30), i32::MAX);
+        assert_eq!(scale_duration(300, f32::NAN), 300);
+    }
+
+    #[test]
+    fn fixed_durations_apply_to_infinite_lights() {
+        let mut infinite_light = light("infinite_light", ORANGE, 128);
+        infinite_light.data.time = -1;
+
+        let replacement: CustomLightData = "duration=120".parse().unwrap();
+        let processed = simulate_light_with(&neutral_config(), &infinite_light, Some(&replacement));
+        assert_eq!(processed.time, 120);
+
+        let replacement: CustomLightData = "duration_mult=4".parse().unwrap();
+        let processed = simulate_light_with(&neutral_config(), &infinite_light, Some(&replacement));
+        assert_eq!(processed.time, -1);
+    }
+
+    #[test]
+    fn radius_rounds_and_saturates() {
+        assert_eq!(scale_radius(10, 0.25, 0), 3);
+        assert_eq!(scale_radius(10, 0.24, 0), 2);
+        assert_eq!(scale_radius(100, -1.0, 0), 0);
+        assert_eq!(scale_radius(u32::MAX, 2.0, 0), u32::MAX);
+        assert_eq!(scale_radius(100, f32::NAN, 0), 100);
+        assert_eq!(scale_radius(100, f32::INFINITY, 0), 100);
+    }
+
+    #[test]
+    fn radius_is_monotone_in_the_multiplier() {
+        for radius in [0, 1, 7, 32, 256, 4096, u32::MAX / 2, u32::MAX] {
+            let mut previous = 0;
+
+            for step in 0..=4000 {
+                let mult = step as f32 * 0.005;
+                let scaled = scale_radius(radius, mult, 0);
+
+                assert!(
+                    scaled >= previous,
+                    "radius {radius} fell from {previous} to {scaled} at multiplier {mult}"
+                );
+                previous = scaled;
+            }
+        }
+    }
+
+    #[test]
+    fn multiplied_hues_wrap_or_clamp() {
+        let hue = RgbHue::from_degrees(350.0);
+
+        let wrapped = multiply_hue(hue, 1.3, HueOverflow::Wrap).into_raw_degrees();
+        let clamped = multiply_hue(hue, 1.3, HueOverflow::Clamp).into_raw_degrees();
+
+        assert!((wrapped - 95.0).abs() < 0.01, "{wrapped}");
+        assert!((clamped - 360.0).abs() < 0.01, "{clamped}");
+    }
+
+    #[test]
+    fn clamped_hues_keep_red_lights_red() {
+        // About 350 degrees
+        let red_light = light("red_light", [255, 0, 43], 128);
+
+        let light_config = LightConfig {
+            colored_hue: 1.3,
+            hue_overflow: HueOverflow::Clamp,
+            ..neutral_config()
+        };
+
+        assert_eq!(
+            simulate_light(&light_config, &red_light).color,
+            [255, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn linear_color_decodes_before_converting_to_hsv() {
+        let grey = [128, 128, 128, 0];
+
+        assert!((color_to_hsv(grey, false).value - 0.502).abs() < 0.001);
+        assert!((color_to_hsv(grey, true).value - 0.216).abs() < 0.001);
+
+        for color in [grey, [255, 160, 64, 0], [12, 200, 90, 0]] {
+            for linear in [false, true] {
+                assert_eq!(hsv_to_color(color_to_hsv(color, linear), linear), color);
+            }
+        }
+    }
+
+    #[test]
+    fn halving_linear_value_halves_emitted_light() {
+        let white_light = light("white_light", [255, 255, 255], 128);
+
+        let light_config = LightConfig {
+            colored_value: 0.5,
+            ..neutral_config()
+        };
+        assert_eq!(
+            simulate_light(&light_config, &white_light).color,
+            [128, 128, 128, 0]
+        );
+
+        // Half of the linear light is re-encoded into a much larger sRGB value
+        let light_config = LightConfig {
+            colored_value: 0.5,
+            linear_color: true,
+            ..neutral_config()
+        };
+        let color = simulate_light(&light_config, &white_light).color;
+        assert!((187..=188).contains(&color[0]), "{color:?}");
+    }
+
+    #[test]
+    fn bright_and_dim_lights_converge_on_the_normalize_target() {
+        let light_config = LightConfig {
+            colored_normalize_value: Some(0.6),
+            colored_hue: 1.0,
+            colored_saturation: 1.0,
+            ..Default::default()
+        };
+
+        for color in [[255, 255, 255], [60, 60, 60]] {
+            let original = perceived_brightness([color[0], color[1], color[2], 0]);
+            let processed = perceived_brightness(
+                simulate_light(&light_config, &light("grey_light", color, 128)).color,
+            );
+
+            assert!(
+                (processed - 0.6).abs() < (original - 0.6).abs(),
+                "{color:?} went from {original} to {processed}"
+            );
+        }
+
+        // Lights already at the target are barely touched
+        let processed = simulate_light(&light_config, &light("grey_light", [153, 153, 153], 128));
+        assert!(
+            processed.color[0].abs_diff(153) <= 1,
+            "{:?}",
+            processed.color
+        );
+    }
+
+    #[test]
+    fn normalization_is_bounded_by_max_adjustment() {
+        assert_eq!(
+            value_normalization_factor([20, 20, 20, 0], 0.6, 2.0, false),
+            2.0
+        );
+        assert_eq!(
+            value_normalization_factor([255, 255, 255, 0], 0.1, 2.0, false),
+            0.5
+        );
+        assert_eq!(
+            value_normalization_factor([0, 0, 0, 0], 0.6, 2.0, false),
+            1.0
+        );
+    }
+
+    #[test]
+    fn negative_light_modes() {
+        let mut negative_light = light("negative_light", ORANGE, 128);
+        negative_light.data.flags = LightFlags::NEGATIVE;
+
+        let config_with = |negative_light_mode| LightConfig {
+            negative_light_mode,
+            standard_radius: 2.0,
+            ..neutral_config()
+        };
+
+        let zeroed = simulate_light(&config_with(NegativeLightMode::Zero), &negative_light);
+        assert!(zeroed.should_emit);
+        assert_eq!((zeroed.radius, zeroed.color), (0, [0, 0, 0, 0]));
+        assert!(!zeroed.flags.contains(LightFlags::NEGATIVE));
+
+        let kept = simulate_light(&config_with(NegativeLightMode::Keep), &negative_light);
+        assert!(!kept.should_emit);
+        assert_eq!(kept.radius, 128);
+
+        let processed =
+            simulate_light(&config_with(NegativeLightMode::RemoveFlag), &negative_light);
+        assert!(processed.should_emit);
+        assert_eq!(processed.radius, 256);
+        assert!(!processed.flags.contains(LightFlags::NEGATIVE));
+    }
+
+    #[test]
+    fn kept_negative_lights_are_emitted_when_their_flags_change() {
+        let mut flickering_light = light("flickering_light", ORANGE, 128);
+        flickering_light.data.flags = LightFlags::NEGATIVE | LightFlags::FLICKER;
+
+        let light_config = LightConfig {
+            negative_light_mode: NegativeLightMode::Keep,
+            disable_flickering: true,
+            ..neutral_config()
+        };
+        let kept = simulate_light(&light_config, &flickering_light);
+
+        assert!(kept.should_emit);
+        assert_eq!(kept.flags, LightFlags::NEGATIVE);
+        assert_eq!(kept.color, [ORANGE[0], ORANGE[1], ORANGE[2], 0]);
+    }
+
+    #[test]
+    fn matching_ids_are_forced_off_by_default() {
+        let light_config = LightConfig {
+            force_off_by_default_regexes: vec![regex::Regex::new("^deco_").unwrap()],
+            ..neutral_config()
+        };
+
+        let forced = simulate_light(&light_config, &light("deco_candle", ORANGE, 128));
+        let untouched = simulate_light(&light_config, &light("torch", ORANGE, 128));
+
+        assert!(forced.flags.contains(LightFlags::OFF_BY_DEFAULT));
+        assert!(!untouched.flags.contains(LightFlags::OFF_BY_DEFAULT));
+    }
+
+    #[test]
+    fn override_flags_keep_unrelated_bits_unless_replacing() {
+        let mut lantern = light("lantern", ORANGE, 128);
+        lantern.data.flags = LightFlags::FLICKER | LightFlags::FIRE;
+
+        let replacement: CustomLightData = "flag=cancarry".parse().unwrap();
+        let processed = simulate_light_with(&neutral_config(), &lantern, Some(&replacement));
+        assert_eq!(
+            processed.flags,
+            LightFlags::FLICKER | LightFlags::FIRE | LightFlags::CAN_CARRY
+        );
+
+        let replacement: CustomLightData = "flag=cancarry,replace_flags=true".parse().unwrap();
+        let processed = simulate_light_with(&neutral_config(), &lantern, Some(&replacement));
+        assert_eq!(processed.flags, LightFlags::CAN_CARRY);
+    }
+
+    #[test]
+    fn override_flags_win_over_disable_flickering() {
+        let light_config = LightConfig {
+            disable_flickering: true,
+            ..neutral_config()
+        };
+
+        let mut flickering_light = light("flickering_light", ORANGE, 128);
+        flickering_light.data.flags = LightFlags::FLICKER | LightFlags::DYNAMIC;
+
+        assert_eq!(
+            simulate_light(&light_config, &flickering_light).flags,
+            LightFlags::DYNAMIC
+        );
+
+        let replacement: CustomLightData = "flag=flicker".parse().unwrap();
+        let processed = simulate_light_with(&light_config, &flickering_light, Some(&replacement));
+        assert_eq!(processed.flags, LightFlags::FLICKER | LightFlags::DYNAMIC);
+    }
+
+    #[test]
+    fn flicker_converts_into_matching_pulse() {
+        let cleaned_flags = |flicker_mode, flags| {
+            let light_config = LightConfig {
+                flicker_mode: Some(flicker_mode),
+                ..neutral_config()
+            };
+
+            let mut flickering_light = light("flickering_light", ORANGE, 128);
+            flickering_light.data.flags = flags;
+            clean_animation_flags(&light_config, &mut flickering_light);
+
+            flickering_light.data.flags
+        };
+
+        assert_eq!(
+            cleaned_flags(FlickerMode::ToPulse, LightFlags::FLICKER),
+            LightFlags::PULSE
+        );
+        assert_eq!(
+            cleaned_flags(FlickerMode::ToPulse, LightFlags::FLICKER_SLOW),
+            LightFlags::PULSE_SLOW
+        );
+        assert_eq!(
+            cleaned_flags(FlickerMode::ToPulseSlow, LightFlags::FLICKER),
+            LightFlags::PULSE_SLOW
+        );
+        assert_eq!(
+            cleaned_flags(FlickerMode::Keep, LightFlags::FLICKER),
+            LightFlags::FLICKER
+        );
+        assert_eq!(
+            cleaned_flags(FlickerMode::Remove, LightFlags::FLICKER_SLOW),
+            LightFlags::empty()
+        );
+    }
+
+    #[test]
+    fn flicker_mode_takes_precedence_over_disable_flickering() {
+        let light_config = LightConfig {
+            disable_flickering: true,
+            flicker_mode: Some(FlickerMode::Keep),
+            ..neutral_config()
+        };
+
+        assert!(matches!(
+            light_config.effective_flicker_mode(),
+            FlickerMode::Keep
+        ));
+    }
+
+    #[test]
+    fn override_flags_win_over_flicker_conversion() {
+        let light_config = LightConfig {
+            flicker_mode: Some(FlickerMode::ToPulse),
+            ..neutral_config()
+        };
+
+        let mut flickering_light = light("flickering_light", ORANGE, 128);
+        flickering_light.data.flags = LightFlags::FLICKER;
+
+        let replacement: CustomLightData = "flag=flickerslow".parse().unwrap();
+        let processed = simulate_light_with(&light_config, &flickering_light, Some(&replacement));
+        assert_eq!(processed.flags, LightFlags::FLICKER_SLOW);
+    }
+
+    #[test]
+    fn overrides_replace_mesh_and_icon() {
+        let mut lantern = light("paper_lantern", ORANGE, 128);
+        lantern.mesh = r"l\light_paper_lantern_glow.nif".into();
+        lantern.icon = r"l\paper_lantern.dds".into();
+
+        let replacement: CustomLightData = r"mesh=l\light_paper_lantern_01.nif".parse().unwrap();
+        let processed = simulate_light_with(&neutral_config(), &lantern, Some(&replacement));
+
+        assert_eq!(processed.mesh, r"l\light_paper_lantern_01.nif");
+        assert_eq!(processed.icon, r"l\paper_lantern.dds");
+    }
+
+    #[test]
+    fn duration_multipliers_follow_the_light_category() {
+        let light_config = LightConfig {
+            standard_duration_mult: Some(4.0),
+            colored_duration_mult: Some(0.5),
+            ..neutral_config()
+        };
+
+        let candle = simulate_light(&light_config, &light("candle", ORANGE, 128));
+        let glow = simulate_light(&light_config, &light("magic_glow", [0, 0, 255], 128));
+
+        assert_eq!(candle.category, LightCategory::Standard);
+        assert_eq!(candle.time, 1200);
+        assert_eq!(glow.category, LightCategory::Colored);
+        assert_eq!(glow.time, 150);
+    }
+
+    #[test]
+    fn duration_mult_applies_to_categories_without_their_own() {
+        let light_config = LightConfig {
+            duration_mult: 2.0,
+            colored_duration_mult: Some(0.5),
+            ..neutral_config()
+        };
+
+        assert_eq!(
+            simulate_light(&light_config, &light("candle", ORANGE, 128)).time,
+            600
+        );
+    }
+
+    #[test]
+    fn carryable_lights_diverge_from_static_ones() {
+        let light_config = LightConfig {
+            carryable_radius_mult: 0.5,
+            carryable_value_mult: 0.5,
+            carryable_duration_mult: 2.0,
+            ..neutral_config()
+        };
+
+        let sconce = light("torch", [255, 255, 255], 200);
+        let mut torch = sconce.clone();
+        torch.data.flags = LightFlags::CAN_CARRY;
+
+        let sconce = simulate_light(&light_config, &sconce);
+        let torch = simulate_light(&light_config, &torch);
+
+        assert_eq!((sconce.radius, sconce.time), (200, 300));
+        assert_eq!((torch.radius, torch.time), (100, 600));
+        assert_eq!(sconce.color, [255, 255, 255, 0]);
+        assert_eq!(torch.color, [128, 128, 128, 0]);
+    }
+
+    #[test]
+    fn overrides_take_precedence_over_carryable_multipliers() {
+        let light_config = LightConfig {
+            carryable_radius_mult: 0.5,
+            ..neutral_config()
+        };
+
+        let mut torch = light("torch", ORANGE, 200);
+        torch.data.flags = LightFlags::CAN_CARRY;
+
+        let replacement: CustomLightData = "radius=300".parse().unwrap();
+        let processed = simulate_light_with(&light_config, &torch, Some(&replacement));
+        assert_eq!(processed.radius, 300);
+    }
+
+    #[test]
+    fn jitter_is_deterministic_and_bounded() {
+        for index in 0..500 {
+            let light_id = format!("light_{index}");
+
+            for (channel, max) in [("hue", 15.0), ("value", 0.1), ("radius", 32.0)] {
+                let offset = jitter(&light_id, channel, max);
+
+                assert_eq!(offset, jitter(&light_id, channel, max));
+                assert!(
+                    offset.abs() <= max,
+                    "{light_id} {channel} jittered by {offset}"
+                );
+            }
+        }
+
+        assert_eq!(jitter("light_0", "hue", 0.0), 0.0);
+        assert_ne!(
+            jitter("light_0", "hue", 15.0),
+            jitter("light_0", "value", 15.0)
+        );
+    }
+
+    #[test]
+    fn offset_hues_wrap_or_clamp() {
+        let offset = |degrees: f32, offset: f32, overflow| {
+            offset_hue(RgbHue::from_degrees(degrees), offset, overflow).into_raw_degrees()
+        };
+
+        assert!((offset(350.0, 20.0, HueOverflow::Wrap) - 10.0).abs() < 0.01);
+        assert!((offset(350.0, 20.0, HueOverflow::Clamp) - 360.0).abs() < 0.01);
+        assert!((offset(10.0, -20.0, HueOverflow::Wrap) - 350.0).abs() < 0.01);
+        assert!(offset(10.0, -20.0, HueOverflow::Clamp).abs() < 0.01);
+    }
+
+    #[test]
+    fn jitter_skips_fixed_channels_and_respects_bounds() {
+        let light_config = LightConfig {
+            value_jitter: Some(0.5),
+            radius_jitter: Some(64.0),
+            ..neutral_config()
+        };
+        let white_light = light("white_light", [255, 255, 255], 32);
+
+        let replacement: CustomLightData = "value=0.5,radius=100".parse().unwrap();
+        let fixed = simulate_light_with(&light_config, &white_light, Some(&replacement));
+        assert_eq!((fixed.color, fixed.radius), ([128, 128, 128, 0], 100));
+
+        for index in 0..100 {
+            let jittered = simulate_light(
+                &light_config,
+                &light(&format!("light_{index}"), [255, 255, 255], 32),
+            );
+            assert!(jittered.radius <= 96);
+        }
+    }
+
+    #[test]
+    fn radius_curve_pivots_around_its_base() {
+        // With an exponent of 0.5, lights grow or shrink by the square root of their distance from the base
+        for (radius, curved) in [(16, 64), (64, 128), (256, 256), (1024, 512)] {
+            assert_eq!(scale_radius_curved(radius, 256.0, 0.5, 1.0, 0), curved);
+        }
+
+        assert_eq!(scale_radius_curved(64, 256.0, 0.5, 1.5, 8), 200);
+        assert_eq!(scale_radius_curved(64, 256.0, 1.0, 1.0, 0), 64);
+        assert_eq!(scale_radius_curved(64, 0.0, 0.5, 1.0, 0), 64);
+    }
+
+    #[test]
+    fn radius_curve_composes_with_overrides_and_clamps() {
+        let light_config = LightConfig {
+            radius_exponent: Some(0.5),
+            max_radius: Some(400),
+            ..neutral_config()
+        };
+
+        let candle = simulate_light(&light_config, &light("candle", ORANGE, 64));
+        let bonfire = simulate_light(&light_config, &light("bonfire", ORANGE, 1024));
+        assert_eq!((candle.radius, bonfire.radius), (128, 400));
+
+        let replacement: CustomLightData = "radius=90".parse().unwrap();
+        let fixed = simulate_light_with(
+            &light_config,
+            &light("candle", ORANGE, 64),
+            Some(&replacement),
+        );
+        assert_eq!(fixed.radius, 90);
+    }
+
+    #[test]
+    fn real_minutes_scale_with_the_timescale() {
+        // Ten real minutes are 600 game seconds at the vanilla timescale, but only 200 at a third of it
+        assert_eq!(real_minutes_to_duration(300, 10.0, 30.0), 600);
+        assert_eq!(real_minutes_to_duration(300, 10.0, 10.0), 200);
+
+        assert_eq!(real_minutes_to_duration(-1, 10.0, 10.0), -1);
+        assert_eq!(real_minutes_to_duration(0, 10.0, 10.0), 0);
+        assert_eq!(real_minutes_to_duration(300, 0.001, 10.0), 1);
+        assert_eq!(real_minutes_to_duration(300, f32::MAX, 30.0), i32::MAX);
+    }
+
+    #[test]
+    fn real_minutes_replace_the_duration_multiplier() {
+        let light_config = LightConfig {
+            duration_mult: 5.0,
+            target_real_minutes: Some(10.0),
+            timescale: Some(10.0),
+            ..neutral_config()
+        };
+        assert_eq!(
+            simulate_light(&light_config, &light("torch", ORANGE, 128)).time,
+            200
+        );
+
+        let replacement: CustomLightData = "target_real_minutes=3".parse().unwrap();
+        let processed = simulate_light_with(
+            &light_config,
+            &light("torch", ORANGE, 128),
+            Some(&replacement),
+        );
+        assert_eq!(processed.time, 60);
+    }
+
+    #[test]
+    fn simulating_leaves_the_light_untouched() {
+        let light_config = LightConfig {
+            standard_radius: 2.0,
+            ..neutral_config()
+        };
+        let torch = light("torch", ORANGE, 128);
+
+        let processed = simulate_light(&light_config, &torch);
+        assert_eq!(processed.radius, 256);
+        assert_eq!(processed.override_key, None);
+        assert_eq!(torch.data.radius, 128);
+
+        // Processing writes exactly what simulating gives back
+        let mut processed_torch = torch.clone();
+        assert!(process_light(&light_config, &mut processed_torch, None));
+        assert_eq!(processed_torch.data.radius, processed.radius);
+        assert_eq!(processed_torch.data.color, processed.color);
+        assert_eq!(processed_torch.data.time, processed.time);
+    }
+
+    #[test]
+    fn simulating_reports_the_matched_override() {
+        let light_config = LightConfig {
+            light_regexes: vec![light_override("^torch", "radius=300")],
+            ..neutral_config()
+        };
+
+        let processed = simulate_light(&light_config, &light("torch_01", ORANGE, 128));
+        assert_eq!(processed.radius, 300);
+        assert_eq!(processed.override_key.as_deref(), Some("^torch"));
+        assert_eq!(processed.category, LightCategory::Standard);
+    }
+
+    #[test]
+    fn tints_blend_in_rgb_space() {
+        let red = color_to_hsv([255, 0, 0, 0], false);
+        let blue = [0, 0, 255, 0];
+
+        assert_eq!(
+            hsv_to_color(tint_hsv(red, blue, 0.5, false), false),
+            [128, 0, 128, 0]
+        );
+        assert_eq!(
+            hsv_to_color(tint_hsv(red, blue, 0.0, false), false),
+            [255, 0, 0, 0]
+        );
+        assert_eq!(hsv_to_color(tint_hsv(red, blue, 4.0, false), false), blue);
+    }
+
+    #[test]
+    fn override_tints_replace_category_tints() {
+        let light_config = LightConfig {
+            colored_tint: Some(TintColor([0, 255, 0, 0])),
+            colored_tint_strength: 1.0,
+            ..neutral_config()
+        };
+        let red_light = light("red_light", [255, 0, 0], 128);
+
+        assert_eq!(
+            simulate_light(&light_config, &red_light).color,
+            [0, 255, 0, 0]
+        );
+
+        let replacement: CustomLightData = "tint=#0000ff,tint_strength=0.5".parse().unwrap();
+        let processed = simulate_light_with(&light_config, &red_light, Some(&replacement));
+        assert_eq!(processed.color, [128, 0, 128, 0]);
+    }
+
+    #[test]
+    fn flags_only_leaves_color_radius_and_duration_alone() {
+        let light_config = LightConfig {
+            flags_only: true,
+            disable_flickering: true,
+            standard_radius: 2.0,
+            standard_value: 0.5,
+            duration_mult: 3.0,
+            ..neutral_config()
+        };
+
+        let mut flickering_torch = light("torch", ORANGE, 128);
+        flickering_torch.data.flags = LightFlags::FLICKER | LightFlags::FIRE;
+        let processed = simulate_light(&light_config, &flickering_torch);
+
+        assert!(processed.should_emit);
+        assert_eq!(processed.flags, LightFlags::FIRE);
+        assert_eq!(processed.color, [ORANGE[0], ORANGE[1], ORANGE[2], 0]);
+        assert_eq!(processed.radius, 128);
+        assert_eq!(processed.time, 300);
+    }
+
+    #[test]
+    fn flags_only_skips_lights_it_would_not_change() {
+        let light_config = LightConfig {
+            flags_only: true,
+            standard_radius: 2.0,
+            ..neutral_config()
+        };
+
+        let steady_torch = light("torch", ORANGE, 128);
+        assert!(!simulate_light(&light_config, &steady_torch).should_emit);
+
+        let replacement: CustomLightData = "sound=fire 50".parse().unwrap();
+        let processed = simulate_light_with(&light_config, &steady_torch, Some(&replacement));
+        assert!(processed.should_emit);
+        assert_eq!(processed.sound, "fire 50");
+        assert_eq!(processed.radius, 128);
+    }
+
+    /// A torch with a looping fire sound
+    fn crackling_torch() -> Light {
+        Light {
+            sound: "Fire".into(),
+            ..light("torch", ORANGE, 128)
+        }
+    }
+
+    #[test]
+    fn override_sounds_replace_or_remove() {
+        let replacement: CustomLightData = "sound=Fire 40".parse().unwrap();
+        let processed =
+            simulate_light_with(&neutral_config(), &crackling_torch(), Some(&replacement));
+        assert_eq!(processed.sound, "Fire 40");
+
+        for none in ["none", "None", "NONE"] {
+            let replacement: CustomLightData = format!("sound={none}").parse().unwrap();
+            let processed =
+                simulate_light_with(&neutral_config(), &crackling_torch(), Some(&replacement));
+            assert_eq!(processed.sound, "", "{none}");
+        }
+
+        let processed = simulate_light(&neutral_config(), &crackling_torch());
+        assert_eq!(processed.sound, "Fire");
+    }
+
+    #[test]
+    fn stripped_sounds_may_be_replaced_by_overrides() {
+        let light_config = LightConfig {
+            strip_sounds_regexes: vec![regex::Regex::new("^torch").unwrap()],
+            ..neutral_config()
+        };
+
+        let processed = simulate_light(&light_config, &crackling_torch());
+        assert_eq!(processed.sound, "");
+
+        let replacement: CustomLightData = "sound=Fire 40".parse().unwrap();
+        let processed = simulate_light_with(&light_config, &crackling_torch(), Some(&replacement));
+        assert_eq!(processed.sound, "Fire 40");
+
+        let lantern = Light {
+            id: "lantern".into(),
+            ..crackling_torch()
+        };
+        assert_eq!(simulate_light(&light_config, &lantern).sound, "Fire");
+    }
+
+    #[test]
+    fn weight_never_drops_below_zero() {
+        assert_eq!(scale_weight(2.0, 1.5), 3.0);
+        assert_eq!(scale_weight(2.0, -1.0), 0.0);
+        assert_eq!(scale_weight(2.0, f32::INFINITY), 2.0);
+        assert_eq!(scale_weight(2.0, f32::NAN), 2.0);
+    }
+
+    #[test]
+    fn gold_value_rounds_and_saturates() {
+        assert_eq!(scale_gold_value(10, 1.25), 13);
+        assert_eq!(scale_gold_value(10, 1.24), 12);
+        assert_eq!(scale_gold_value(10, -1.0), 0);
+        assert_eq!(scale_gold_value(u32::MAX, 2.0), u32::MAX);
+        assert_eq!(scale_gold_value(10, f32::NAN), 10);
+    }
+
+    #[test]
+    fn weight_and_value_follow_overrides_then_globals() {
+        let light_config = LightConfig {
+            weight_mult: 2.0,
+            gold_value_mult: 0.5,
+            ..neutral_config()
+        };
+        let mut lantern = light("lantern", ORANGE, 128);
+        lantern.data.weight = 3.0;
+        lantern.data.value = 20;
+
+        let processed = simulate_light(&light_config, &lantern);
+        assert_eq!((processed.weight, processed.value), (6.0, 10));
+
+        let replacement: CustomLightData = "weight=1.5,gold_value=100".parse().unwrap();
+        let processed = simulate_light_with(&light_config, &lantern, Some(&replacement));
+        assert_eq!((processed.weight, processed.value), (1.5, 100));
+
+        let replacement: CustomLightData = "weight_mult=0.5,gold_value_mult=3".parse().unwrap();
+        let processed = simulate_light_with(&light_config, &lantern, Some(&replacement));
+        assert_eq!((processed.weight, processed.value), (1.5, 60));
+
+        let replacement: CustomLightData = "weight=-4".parse().unwrap();
+        let processed = simulate_light_with(&light_config, &lantern, Some(&replacement));
+        assert_eq!(processed.weight, 0.0);
+    }
+
+    #[test]
+    fn disabled_lights_are_nulled_out() {
+        let mut flickering_torch = light("torch", ORANGE, 128);
+        flickering_torch.data.flags = LightFlags::FLICKER | LightFlags::FIRE;
+
+        let replacement: CustomLightData = "disabled=true".parse().unwrap();
+        let processed =
+            simulate_light_with(&neutral_config(), &flickering_torch, Some(&replacement));
+
+        assert!(processed.should_emit);
+        assert_eq!(processed.radius, 0);
+        assert_eq!(processed.color, [0, 0, 0, 0]);
+        assert_eq!(processed.flags, LightFlags::empty());
+
+        let replacement: CustomLightData = "disabled=false".parse().unwrap();
+        let processed =
+            simulate_light_with(&neutral_config(), &flickering_torch, Some(&replacement));
+        assert_eq!(processed.radius, 128);
+    }
+
+    #[test]
+    fn disabled_lights_may_not_override_anything_else() {
+        assert!(
+            "disabled=true,radius=64"
+                .parse::<CustomLightData>()
+                .is_err()
+        );
+        assert!("hue=30,disabled=true".parse::<CustomLightData>().is_err());
+        assert!("disabled=yes".parse::<CustomLightData>().is_err());
+    }
+
+    fn red_effect() -> MagicEffect {
+        let mut effect = MagicEffect::default();
+        effect.data.color = [255, 0, 0];
+        effect
+    }
+
+    #[test]
+    fn magic_effects_prefer_their_own_multipliers() {
+        let desaturating = LightConfig {
+            colored_saturation: 0.0,
+            ..neutral_config()
+        };
+
+        let mut effect = red_effect();
+        assert!(process_magic_effect(&desaturating, &mut effect));
+        assert_eq!(effect.data.color, [255, 255, 255]);
+
+        let keeping_magic = LightConfig {
+            magic_saturation: Some(1.0),
+            ..desaturating
+        };
+
+        let mut effect = red_effect();
+        assert!(!process_magic_effect(&keeping_magic, &mut effect));
+        assert_eq!(effect.data.color, [255, 0, 0]);
+    }
+}
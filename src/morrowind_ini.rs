@@ -0,0 +1,165 @@
+use std::{
+    fs::read,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Characters of windows-1252 in the 0x80..=0x9F range, where it differs from latin-1.
+/// Bytes it leaves undefined are kept as their latin-1 control character
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// Decodes text written by the original engine and its launcher, which use windows-1252 rather than UTF-8
+fn decode_windows_1252(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| match byte {
+            0x80..=0x9F => WINDOWS_1252_HIGH[(byte - 0x80) as usize],
+            _ => byte as char,
+        })
+        .collect()
+}
+
+/// Whether a path given for openmw.cfg is actually a Morrowind.ini, going by its extension
+pub fn is_morrowind_ini(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ini"))
+}
+
+/// The load order of an installation of the original engine, such as one using MGE XE, which has no openmw.cfg
+#[derive(Debug)]
+pub struct MorrowindIni {
+    pub path: PathBuf,
+    /// The Data Files directory next to Morrowind.ini, which is the only place plugins are loaded from
+    pub data_files: PathBuf,
+    /// The `GameFile` entries of the `[Game Files]` section, by their number
+    pub game_files: Vec<String>,
+}
+
+impl MorrowindIni {
+    pub const DATA_FILES_NAME: &str = "Data Files";
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        // Data Files is found next to it, which a bare file name has no parent to find
+        let path = path.canonicalize()?;
+        let contents = decode_windows_1252(&read(&path)?);
+
+        let mut in_game_files = false;
+        let mut game_files: Vec<(u32, String)> = Vec::new();
+
+        for line in contents.lines() {
+            let line = line.trim();
+
+            if let Some(section) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                in_game_files = section.trim().eq_ignore_ascii_case("Game Files");
+                continue;
+            }
+
+            if !in_game_files || line.starts_with(';') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+
+            let key = key.trim();
+            let value = value.trim();
+
+            // The launcher leaves GameFileN= lines empty, or the key cased however it was written by hand
+            if value.is_empty()
+                || key.len() < 8
+                || !key.is_char_boundary(8)
+                || !key[..8].eq_ignore_ascii_case("GameFile")
+            {
+                continue;
+            }
+
+            if let Ok(index) = key[8..].parse::<u32>() {
+                game_files.push((index, value.to_string()));
+            }
+        }
+
+        game_files.sort_by_key(|(index, _)| *index);
+
+        let data_files = path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(Self::DATA_FILES_NAME);
+
+        Ok(MorrowindIni {
+            data_files,
+            path,
+            game_files: game_files.into_iter().map(|(_, name)| name).collect(),
+        })
+    }
+
+    /// Directory holding Morrowind.ini, where lightConfig.toml and the other files lightfixes keeps are read from and written to
+    pub fn config_dir(&self) -> PathBuf {
+        self.path.parent().unwrap_or(&self.path).to_path_buf()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::temp_dir;
+
+    #[test]
+    fn windows_1252_differs_from_latin_1_only_in_its_high_range() {
+        assert_eq!(decode_windows_1252(b"Caf\xe9 \x80\x93"), "Café €“");
+        assert_eq!(decode_windows_1252(b"\x81"), "\u{81}");
+    }
+
+    #[test]
+    fn ini_paths_are_recognized_by_extension() {
+        assert!(is_morrowind_ini(Path::new("Morrowind.INI")));
+        assert!(!is_morrowind_ini(Path::new("openmw.cfg")));
+        assert!(!is_morrowind_ini(Path::new("Morrowind")));
+    }
+
+    #[test]
+    fn game_files_are_read_in_order() {
+        let dir = temp_dir("morrowind-ini");
+        let ini_path = dir.join("Morrowind.ini");
+        std::fs::write(
+            &ini_path,
+            b"[General]\r\n\
+              GameFile0=Not A Plugin.esp\r\n\
+              [Game Files]\r\n\
+              GameFile2=Tribunal.esm\r\n\
+              ;GameFile3=Commented.esp\r\n\
+              gamefile1 = Morrowind.esm\r\n\
+              GameFile10=Caf\xe9.esp\r\n\
+              GameFile4=\r\n\
+              GameFileX=Unnumbered.esp\r\n\
+              [Archives]\r\n\
+              Archive 0=Tribunal.bsa\r\n",
+        )
+        .unwrap();
+
+        let ini = MorrowindIni::load(&ini_path).unwrap();
+
+        assert_eq!(
+            ini.game_files,
+            ["Morrowind.esm", "Tribunal.esm", "Café.esp"]
+        );
+        assert_eq!(ini.config_dir(), dir.canonicalize().unwrap());
+        assert_eq!(
+            ini.data_files,
+            dir.canonicalize()
+                .unwrap()
+                .join(MorrowindIni::DATA_FILES_NAME)
+        );
+    }
+
+    #[test]
+    fn missing_ini_is_an_error() {
+        assert!(MorrowindIni::load(&temp_dir("missing-ini").join("Morrowind.ini")).is_err());
+    }
+}
@@ -0,0 +1,226 @@
+use std::{collections::HashMap, fmt};
+
+use tes3::esp::{Cell, CellFlags, EditorId, MagicEffect, Plugin, TES3Object};
+
+use crate::ExteriorGrid;
+
+/// Lowercased name of a magic effect, which are known by their index rather than an editor id
+pub fn magic_effect_id(effect: &MagicEffect) -> String {
+    format!("{:?}", effect.effect_id).to_ascii_lowercase()
+}
+
+/// Lowercased id of a cell, which exterior cells don't have a unique one of.
+/// They're known by their grid coordinates instead, as in `ext:2,-9`
+pub fn cell_key(cell: &Cell) -> String {
+    match cell.data.flags.contains(CellFlags::IS_INTERIOR) {
+        true => cell.editor_id_ascii_lowercase().into_owned(),
+        false => {
+            let (grid_x, grid_y) = cell.data.grid;
+            format!("{}{grid_x},{grid_y}", ExteriorGrid::PREFIX)
+        }
+    }
+}
+
+/// The kind and lowercased id of a record the generated plugin can hold, which is what matches it between runs
+pub fn record_key(object: &TES3Object) -> Option<(&'static str, String)> {
+    match object {
+        TES3Object::Light(light) => Some(("light", light.editor_id_ascii_lowercase().into_owned())),
+        TES3Object::Cell(cell) => Some(("cell", cell_key(cell))),
+        TES3Object::MagicEffect(effect) => Some(("magic effect", magic_effect_id(effect))),
+        _ => None,
+    }
+}
+
+/// What changed between a previously generated plugin and its replacement, by record.
+/// Each list holds records as `kind id`, in the order of the new plugin, or the old one for removed records
+#[derive(Debug, Default)]
+pub struct PluginDiff {
+    pub color_changed: Vec<String>,
+    pub radius_changed: Vec<String>,
+    /// Records which changed in some other way, such as the flags of a light or the fog of a cell
+    pub other_changed: Vec<String>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl PluginDiff {
+    pub fn between(old: &Plugin, new: &Plugin) -> Self {
+        let mut diff = PluginDiff::default();
+
+        let mut old_records: HashMap<(&'static str, String), &TES3Object> = old
+            .objects
+            .iter()
+            .filter_map(|object| Some((record_key(object)?, object)))
+            .collect();
+
+        for object in &new.objects {
+            let Some(key) = record_key(object) else {
+                continue;
+            };
+
+            let name = format!("{} {}", key.0, key.1);
+
+            let Some(old_object) = old_records.remove(&key) else {
+                diff.added.push(name);
+                continue;
+            };
+
+            let (color_changed, radius_changed, other_changed) = match (old_object, object) {
+                (TES3Object::Light(old), TES3Object::Light(new)) => (
+                    old.data.color != new.data.color,
+                    old.data.radius != new.data.radius,
+                    old.data.time != new.data.time
+                        || old.data.flags != new.data.flags
+                        || old.data.weight != new.data.weight
+                        || old.data.value != new.data.value
+                        || old.sound != new.sound
+                        || old.mesh != new.mesh
+                        || old.icon != new.icon,
+                ),
+                (TES3Object::Cell(old), TES3Object::Cell(new)) => {
+                    let atmosphere_changed = match (&old.atmosphere_data, &new.atmosphere_data) {
+                        (Some(old), Some(new)) => {
+                            old.ambient_color != new.ambient_color
+                                || old.sunlight_color != new.sunlight_color
+                                || old.fog_color != new.fog_color
+                                || old.fog_density != new.fog_density
+                        }
+                        (None, None) => false,
+                        _ => true,
+                    };
+
+                    (
+                        atmosphere_changed,
+                        false,
+                        old.references.len() != new.references.len(),
+                    )
+                }
+                (TES3Object::MagicEffect(old), TES3Object::MagicEffect(new)) => {
+                    (old.data.color != new.data.color, false, false)
+                }
+                _ => (false, false, false),
+            };
+
+            if color_changed {
+                diff.color_changed.push(name.clone());
+            }
+
+            if radius_changed {
+                diff.radius_changed.push(name.clone());
+            }
+
+            if other_changed {
+                diff.other_changed.push(name);
+            }
+        }
+
+        diff.removed = old
+            .objects
+            .iter()
+            .filter_map(record_key)
+            .filter(|key| old_records.contains_key(key))
+            .map(|(kind, id)| format!("{kind} {id}"))
+            .collect();
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.color_changed.is_empty()
+            && self.radius_changed.is_empty()
+            && self.other_changed.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+    }
+
+    /// One line for each record which changed, saying how
+    pub fn details(&self) -> Vec<String> {
+        [
+            (&self.color_changed, "changed color"),
+            (&self.radius_changed, "changed radius"),
+            (&self.other_changed, "changed otherwise"),
+            (&self.added, "was added"),
+            (&self.removed, "was removed"),
+        ]
+        .into_iter()
+        .flat_map(|(records, change)| {
+            records
+                .iter()
+                .map(move |record| format!("{record} {change}"))
+        })
+        .collect()
+    }
+}
+
+impl fmt::Display for PluginDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} records changed color, {} changed radius, {} changed otherwise, {} added, {} removed",
+            self.color_changed.len(),
+            self.radius_changed.len(),
+            self.other_changed.len(),
+            self.added.len(),
+            self.removed.len(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{interior_cell, light};
+
+    fn plugin_of(records: Vec<TES3Object>) -> Plugin {
+        let mut plugin = Plugin::new();
+        plugin.objects = records;
+        plugin
+    }
+
+    #[test]
+    fn records_are_matched_by_kind_and_id() {
+        let old = plugin_of(vec![
+            light("Torch", [255, 160, 64], 128).into(),
+            light("lantern", [255, 160, 64], 128).into(),
+            light("candle", [255, 160, 64], 128).into(),
+            interior_cell("Balmora, Temple", [80, 80, 120], 0.4).into(),
+        ]);
+        let new = plugin_of(vec![
+            light("torch", [255, 200, 64], 128).into(),
+            light("lantern", [255, 160, 64], 256).into(),
+            light("brazier", [255, 160, 64], 128).into(),
+            interior_cell("Balmora, Temple", [80, 80, 120], 0.8).into(),
+        ]);
+
+        let diff = PluginDiff::between(&old, &new);
+
+        assert_eq!(diff.color_changed, ["light torch", "cell balmora, temple"]);
+        assert_eq!(diff.radius_changed, ["light lantern"]);
+        assert!(diff.other_changed.is_empty());
+        assert_eq!(diff.added, ["light brazier"]);
+        assert_eq!(diff.removed, ["light candle"]);
+
+        assert_eq!(
+            diff.to_string(),
+            "2 records changed color, 1 changed radius, 0 changed otherwise, 1 added, 1 removed"
+        );
+        assert_eq!(
+            diff.details(),
+            [
+                "light torch changed color",
+                "cell balmora, temple changed color",
+                "light lantern changed radius",
+                "light brazier was added",
+                "light candle was removed",
+            ]
+        );
+    }
+
+    #[test]
+    fn identical_plugins_have_an_empty_diff() {
+        let plugin = plugin_of(vec![light("torch", [255, 160, 64], 128).into()]);
+
+        assert!(PluginDiff::between(&plugin, &plugin).is_empty());
+        assert!(!PluginDiff::between(&Plugin::new(), &plugin).is_empty());
+    }
+}
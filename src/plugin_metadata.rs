@@ -0,0 +1,157 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tes3::esp::{Cell, Header, Light, Plugin};
+
+use crate::{LightConfig, fnv1a};
+
+/// Bumped whenever a field of `PluginMetadata` is changed or removed
+pub const METADATA_VERSION: u32 = 1;
+
+/// Extension added to the name of a generated plugin for its metadata sidecar
+pub const METADATA_EXTENSION: &str = ".meta.json";
+
+/// A master of the generated plugin, as written into its header
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MasterMetadata {
+    pub name: String,
+    pub size: u64,
+}
+
+/// Describes how a generated plugin was made, so that tools can tell when it's stale without parsing it
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PluginMetadata {
+    pub schema_version: u32,
+    pub tool_version: String,
+    /// Seconds since the Unix epoch
+    pub generated_at: u64,
+    /// FNV-1a hash of the effective light config, as TOML
+    pub config_hash: String,
+    pub openmw_cfg: PathBuf,
+    pub masters: Vec<MasterMetadata>,
+    pub lights_modified: usize,
+    pub cells_modified: usize,
+}
+
+impl PluginMetadata {
+    pub fn new(plugin: &Plugin, light_config: &LightConfig, openmw_cfg: &Path) -> io::Result<Self> {
+        let config_toml = toml::to_string(light_config)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        let masters = plugin
+            .objects_of_type::<Header>()
+            .flat_map(|header| &header.masters)
+            .map(|(name, size)| MasterMetadata {
+                name: name.clone(),
+                size: *size,
+            })
+            .collect();
+
+        Ok(PluginMetadata {
+            schema_version: METADATA_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            generated_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default(),
+            config_hash: format!("{:016x}", fnv1a(config_toml.into_bytes())),
+            openmw_cfg: openmw_cfg.to_path_buf(),
+            masters,
+            lights_modified: plugin.objects_of_type::<Light>().count(),
+            cells_modified: plugin.objects_of_type::<Cell>().count(),
+        })
+    }
+
+    /// Path of the sidecar belonging to a plugin, as in `S3LightFixes.omwaddon.meta.json`
+    pub fn path_for(plugin_path: &Path) -> PathBuf {
+        let mut metadata_name = plugin_path.file_name().unwrap_or_default().to_os_string();
+        metadata_name.push(METADATA_EXTENSION);
+        plugin_path.with_file_name(metadata_name)
+    }
+
+    /// Writes the sidecar next to the plugin it describes
+    pub fn save(&self, plugin_path: &Path) -> io::Result<()> {
+        let metadata_json = serde_json::to_string_pretty(self).map_err(io::Error::from)?;
+        let mut file = File::create(Self::path_for(plugin_path))?;
+        file.write_all(metadata_json.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        PLUGIN_NAME, generate_lightfixes,
+        test_fixtures::{light, neutral_config, plugin},
+    };
+
+    fn generated_fires(light_config: &LightConfig) -> Plugin {
+        let plugins = vec![plugin(
+            "Fires.esp",
+            [
+                light("torch", [255, 160, 64], 128),
+                light("lantern", [200, 220, 255], 256),
+            ],
+        )];
+        generate_lightfixes(plugins, light_config).unwrap()
+    }
+
+    #[test]
+    fn sidecar_is_named_after_its_plugin() {
+        assert_eq!(
+            PluginMetadata::path_for(&Path::new("output").join(PLUGIN_NAME)),
+            Path::new("output").join("S3LightFixes.omwaddon.meta.json")
+        );
+    }
+
+    #[test]
+    fn metadata_describes_the_plugin() {
+        let light_config = neutral_config();
+        let metadata = PluginMetadata::new(
+            &generated_fires(&light_config),
+            &light_config,
+            Path::new("openmw"),
+        )
+        .unwrap();
+
+        assert_eq!(metadata.schema_version, METADATA_VERSION);
+        assert_eq!(metadata.tool_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(metadata.openmw_cfg, Path::new("openmw"));
+        assert_eq!(metadata.lights_modified, 2);
+        assert_eq!(metadata.cells_modified, 0);
+
+        let [master] = metadata.masters.as_slice() else {
+            panic!("Expected a single master, not {}", metadata.masters.len());
+        };
+        assert!(master.name.eq_ignore_ascii_case("Fires.esp"));
+    }
+
+    #[test]
+    fn config_hash_follows_the_config() {
+        let config_hash = |light_config: &LightConfig| {
+            PluginMetadata::new(
+                &generated_fires(light_config),
+                light_config,
+                Path::new("openmw"),
+            )
+            .unwrap()
+            .config_hash
+        };
+
+        let dimmer = LightConfig {
+            standard_value: 0.5,
+            ..neutral_config()
+        };
+
+        assert_eq!(
+            config_hash(&neutral_config()),
+            config_hash(&neutral_config())
+        );
+        assert_ne!(config_hash(&neutral_config()), config_hash(&dimmer));
+    }
+}
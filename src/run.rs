@@ -0,0 +1,2707 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env::current_dir,
+    fs::{File, create_dir_all, metadata, remove_file},
+    io::{self, Write},
+    mem::take as TakeAndSwitch,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tes3::esp::{Cell, Header, Light, MagicEffect, Plugin, TES3Object};
+use vfstool_lib::VFS;
+
+use crate::{
+    CACHE_NAME, CELLS_SUFFIX, FRAGMENT_NAME, Generation, LIGHTS_SUFFIX, LOG_NAME, LightArgs,
+    LightColorMult, LightConfig, LightFixesError, METADATA_EXTENSION, MorrowindIni,
+    OUTPUT_DATA_DIR_NAME, OutputFormat, PLUGIN_NAMES, PluginDiff, PluginMeta, PluginMetadata,
+    RunCache, RunLock, SaveOutcome, WEATHER_FALLBACKS_NAME, finalize_header, fnv1a, fragment_path,
+    generate, get_config_path, is_fixable_plugin, is_generated_plugin, is_generated_plugin_name,
+    is_json_plugin, is_morrowind_ini, load_json_plugin, print_notification, record_key,
+    remap_references, remember_config_path, revert_fragment, save_json_plugin, save_plugin,
+    save_plugin_to_writer, suffixed_plugin_name, verify_plugin, write_fragment,
+};
+
+/// Parses a fallback value made of three comma-separated 8-bit color channels
+fn parse_fallback_color(value: &str) -> Option<[u8; 3]> {
+    let channels = value
+        .split(',')
+        .map(|channel| channel.trim().parse::<u8>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+
+    channels.try_into().ok()
+}
+
+/// A weather fallback as it was before lightfixes first changed it, and as lightfixes last wrote it
+#[derive(Debug, Deserialize, Serialize)]
+struct WeatherFallback {
+    original: String,
+    adjusted: String,
+}
+
+/// Every weather fallback lightfixes changed, by key
+type WeatherFallbacks = BTreeMap<String, WeatherFallback>;
+
+fn load_weather_fallbacks(path: &Path) -> WeatherFallbacks {
+    std::fs::read(path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+fn save_weather_fallbacks(fallbacks: &WeatherFallbacks, path: &Path) -> io::Result<()> {
+    let fallbacks_json = serde_json::to_string_pretty(fallbacks).map_err(io::Error::from)?;
+    std::fs::write(path, fallbacks_json)
+}
+
+/// Scales the `Weather_*_Ambient_*` color fallbacks of openmw.cfg, leaving any which aren't RGB triples alone.
+/// Fallbacks are always scaled from their original values, which are recorded in `fallbacks`,
+/// so that running again doesn't scale them twice. One changed by hand since it was last written is a new original.
+/// Returns a log line holding the original value of each fallback which changed, so they can be restored by hand
+fn adjust_weather_fallbacks(
+    config: &mut openmw_config::OpenMWConfiguration,
+    light_config: &LightConfig,
+    fallbacks: &mut WeatherFallbacks,
+) -> Vec<String> {
+    let weather_mult = LightColorMult {
+        hue: light_config.weather_hue,
+        saturation: light_config.weather_saturation,
+        value: light_config.weather_value,
+    };
+
+    let mut weather_log = Vec::new();
+
+    let game_settings: Vec<String> = config
+        .game_settings()
+        .map(|setting| {
+            let key = setting.key().to_string();
+            let value = setting.value().to_string();
+            let lowercase_key = key.to_ascii_lowercase();
+
+            if !lowercase_key.starts_with("weather_") || !lowercase_key.contains("_ambient_") {
+                return format!("{key},{value}");
+            }
+
+            let original = match fallbacks.get(&key) {
+                Some(fallback) if fallback.adjusted == value => fallback.original.clone(),
+                _ => value.clone(),
+            };
+
+            let Some([red, green, blue]) = parse_fallback_color(&original) else {
+                return format!("{key},{value}");
+            };
+
+            let [red, green, blue, _] = weather_mult.apply(
+                [red, green, blue, 0],
+                light_config.linear_color,
+                light_config.hue_overflow,
+            );
+            let new_value = format!("{red},{green},{blue}");
+
+            if new_value != value {
+                weather_log.push(format!(
+                    "[ WEATHER ]: fallback={key},{original} was changed to {new_value}"
+                ));
+            }
+
+            fallbacks.insert(
+                key.clone(),
+                WeatherFallback {
+                    original,
+                    adjusted: new_value.clone(),
+                },
+            );
+
+            format!("{key},{new_value}")
+        })
+        .collect();
+
+    if !weather_log.is_empty() {
+        config.set_game_settings(Some(game_settings));
+    }
+
+    weather_log
+}
+
+/// Puts back the original value of each weather fallback lightfixes changed, then forgets them.
+/// Fallbacks changed by hand since lightfixes last wrote them are left alone
+fn restore_weather_fallbacks(
+    config: &mut openmw_config::OpenMWConfiguration,
+    fallbacks_path: &Path,
+) -> Result<(), LightFixesError> {
+    let fallbacks = load_weather_fallbacks(fallbacks_path);
+
+    if fallbacks.is_empty() {
+        return Ok(());
+    }
+
+    let game_settings: Vec<String> = config
+        .game_settings()
+        .map(|setting| {
+            let key = setting.key().to_string();
+            let value = setting.value().to_string();
+
+            match fallbacks.get(&key) {
+                Some(fallback) if fallback.adjusted == value => {
+                    format!("{key},{}", fallback.original)
+                }
+                _ => format!("{key},{value}"),
+            }
+        })
+        .collect();
+
+    config.set_game_settings(Some(game_settings));
+    config.save_user().map_err(LightFixesError::ConfigSave)?;
+
+    let _ = remove_file(fallbacks_path);
+
+    Ok(())
+}
+
+/// Whether a plugin has an extension only OpenMW can load
+fn is_openmw_plugin(plugin_path: &std::path::Path) -> bool {
+    plugin_path.extension().is_some_and(|extension| {
+        extension.eq_ignore_ascii_case("omwaddon") || extension.eq_ignore_ascii_case("omwgame")
+    })
+}
+
+/// Every file directly inside the given data directories, by lowercased name, in the order of the directories.
+/// The VFS only keeps the last of several files with the same name, so this is how the ones it shadows are found
+fn data_directory_files(directories: &[&PathBuf]) -> HashMap<String, Vec<PathBuf>> {
+    let mut files: HashMap<String, Vec<PathBuf>> = HashMap::new();
+
+    for directory in directories {
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            files
+                .entry(entry.file_name().to_string_lossy().to_ascii_lowercase())
+                .or_default()
+                .push(entry.path());
+        }
+    }
+
+    files
+}
+
+/// Resolves a directory to compare it with others, keeping it as given if it doesn't exist
+fn canonical_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
+
+/// Identifies a directory however it's written, for comparisons only.
+/// Canonical paths take the `\\?\` form on Windows, where case doesn't matter either
+fn directory_key(path: &Path) -> PathBuf {
+    let canonical = canonical_path(path);
+
+    match cfg!(windows) {
+        true => PathBuf::from(canonical.to_string_lossy().to_lowercase()),
+        false => canonical,
+    }
+}
+
+/// Undoes `--output-as-data-dir`, taking its data directory and every generated plugin out of openmw.cfg.
+/// The plugins and their sidecars are deleted from the directory, which is removed too if that empties it.
+/// When they were enabled through a fragment, they're taken out of that instead.
+/// Weather fallbacks are put back as they were before lightfixes changed them
+fn revert_output_data_dir(
+    config: &mut openmw_config::OpenMWConfiguration,
+    output_data_dir: &Path,
+    fragment: Option<&Path>,
+    weather_fallbacks_path: &Path,
+    hooks: &mut impl RunHooks,
+) -> Result<(), LightFixesError> {
+    let (enabled_plugins, reverted_path) = match fragment {
+        Some(fragment) => match revert_fragment(fragment, output_data_dir) {
+            Ok(removed_plugins) => (removed_plugins, fragment.to_path_buf()),
+            Err(source) => {
+                return Err(LightFixesError::FragmentRevert {
+                    path: fragment.to_path_buf(),
+                    source,
+                });
+            }
+        },
+        None => {
+            let enabled_plugins: Vec<String> = config
+                .content_files()
+                .iter()
+                .filter(|content_file| is_generated_plugin_name(content_file))
+                .map(|content_file| content_file.to_string())
+                .collect();
+
+            for content_file in &enabled_plugins {
+                config.remove_content_file(content_file);
+            }
+
+            config.remove_data_directory(output_data_dir);
+
+            config.save_user().map_err(LightFixesError::ConfigSave)?;
+
+            (enabled_plugins, config.user_config_path())
+        }
+    };
+
+    restore_weather_fallbacks(config, weather_fallbacks_path)?;
+
+    if let Ok(entries) = std::fs::read_dir(output_data_dir) {
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let plugin_name = file_name
+                .strip_suffix(METADATA_EXTENSION)
+                .unwrap_or(&file_name);
+
+            if is_generated_plugin_name(plugin_name) {
+                let _ = remove_file(entry.path());
+            }
+        }
+
+        // Only succeeds once the directory is empty, leaving anything the user put there alone
+        let _ = std::fs::remove_dir(output_data_dir);
+    }
+
+    hooks.notify(
+        "Lightfixes reverted!",
+        &format!(
+            "Removed {} and {} from {}",
+            output_data_dir.display(),
+            match enabled_plugins.is_empty() {
+                true => "no plugins".to_string(),
+                false => enabled_plugins.join(", "),
+            },
+            reverted_path.display()
+        ),
+    );
+
+    Ok(())
+}
+
+/// Splits the records of the generated plugin into `part_count` plugins, by the index of the part `part_of` picks
+/// from the master index of the plugin each record came from. Each part only has the masters its own records need,
+/// including those of the references of cells, and parts left empty are skipped
+fn split_records<F>(
+    records: Vec<(TES3Object, String)>,
+    header: &Header,
+    part_count: usize,
+    part_of: F,
+) -> Vec<(usize, Plugin)>
+where
+    F: Fn(usize, &TES3Object) -> usize,
+{
+    let master_names: Vec<String> = header
+        .masters
+        .iter()
+        .map(|(name, _)| name.to_ascii_lowercase())
+        .collect();
+
+    // References of carried over cells already point at the masters of the unsplit plugin
+    let source_names: Vec<String> = std::iter::once(String::new())
+        .chain(master_names.iter().cloned())
+        .collect();
+
+    let mut parts: Vec<(HashSet<usize>, Vec<TES3Object>)> =
+        (0..part_count).map(|_| Default::default()).collect();
+
+    for (record, owner) in records {
+        let Some(owner_index) = master_names.iter().position(|name| *name == owner) else {
+            continue;
+        };
+
+        let (part_masters, part_records) = &mut parts[part_of(owner_index, &record)];
+        part_masters.insert(owner_index);
+
+        if let TES3Object::Cell(cell) = &record {
+            part_masters.extend(
+                cell.references
+                    .values()
+                    .filter_map(|reference| (reference.mast_index as usize).checked_sub(1)),
+            );
+        }
+
+        part_records.push(record);
+    }
+
+    parts
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (_, part_records))| !part_records.is_empty())
+        .map(|(part, (part_masters, part_records))| {
+            let mut part_header = header.clone();
+            part_header.masters = header
+                .masters
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| part_masters.contains(index))
+                .map(|(_, master)| master.clone())
+                .collect();
+            part_header.num_objects = part_records.len() as u32;
+
+            let part_master_names: Vec<String> = part_header
+                .masters
+                .iter()
+                .map(|(name, _)| name.to_ascii_lowercase())
+                .collect();
+
+            let mut part_plugin = Plugin::new();
+
+            for mut record in part_records {
+                if let TES3Object::Cell(cell) = &mut record {
+                    remap_references(cell, &source_names, &part_master_names);
+                }
+
+                part_plugin.objects.push(record);
+            }
+
+            part_plugin.objects.push(TES3Object::Header(part_header));
+            part_plugin.sort_objects();
+            (part, part_plugin)
+        })
+        .collect()
+}
+
+/// Warnings and outputs of a run, which the command line prints as porcelain records
+#[derive(Debug, Default)]
+pub struct RunSummary {
+    /// Each plugin saved, or left alone as it was up to date
+    pub outputs: Vec<RunOutput>,
+    /// Whether every plugin was already up to date, and was left alone
+    pub up_to_date: bool,
+    /// Content files which couldn't be found in any data directory
+    pub missing: Vec<String>,
+    /// Generated plugins found outside of the output directory
+    pub stale: Vec<PathBuf>,
+    /// Plugins which couldn't be loaded or saved, which didn't stop the run
+    pub warnings: Vec<LightFixesError>,
+}
+
+/// A plugin written by a run
+#[derive(Debug)]
+pub struct RunOutput {
+    pub path: PathBuf,
+    /// How many lights and cells the plugin overrides, unknown when the run was skipped as nothing changed
+    pub lights_modified: Option<usize>,
+    pub cells_modified: Option<usize>,
+}
+
+/// How `run_with` reaches whoever is running lightfixes. Every method defaults to doing so without dialogs
+pub trait RunHooks {
+    /// Called once lightconfig.toml is loaded, as it may disable notifications
+    fn config_loaded(&mut self, _light_config: &LightConfig) {}
+
+    /// Tells the user something, such as where the plugin was written
+    fn notify(&mut self, _title: &str, message: &str) {
+        print_notification(message);
+    }
+
+    /// Asks a yes or no question, giving None when there's nobody to ask
+    fn confirm(&mut self, _title: &str, _message: &str) -> Option<bool> {
+        None
+    }
+
+    /// Asks where openmw.cfg is, when it couldn't be found
+    fn locate_config(&mut self, _error: &LightFixesError) -> Option<PathBuf> {
+        None
+    }
+
+    /// Where `--output -` streams the plugin to
+    fn plugin_stream(&mut self) -> Box<dyn Write + '_> {
+        Box::new(io::stdout().lock())
+    }
+}
+
+/// Hooks which print notifications and never ask anything
+pub struct NoHooks;
+
+impl RunHooks for NoHooks {}
+
+/// Generates, saves and optionally enables the plugin, as the command line does, without any dialogs
+pub fn run(mut args: LightArgs) -> Result<RunSummary, LightFixesError> {
+    args.no_notifications = true;
+    run_with(args, &mut NoHooks)
+}
+
+/// Generates, saves and optionally enables the plugin, reaching whoever is running it through hooks.
+/// Whatever stops it is returned, rather than exiting
+pub fn run_with(
+    mut args: LightArgs,
+    hooks: &mut impl RunHooks,
+) -> Result<RunSummary, LightFixesError> {
+    let mut summary = RunSummary::default();
+    let to_stdout = args.output.as_deref() == Some(Path::new("-"));
+
+    // Installations of the original engine only have a Morrowind.ini, which may be given in place of openmw.cfg
+    if args.openmw_cfg.as_deref().is_some_and(is_morrowind_ini) {
+        args.morrowind_ini = args.openmw_cfg.take();
+    }
+
+    let morrowind_ini = match args.morrowind_ini.take() {
+        None => None,
+        Some(ini_path) => match MorrowindIni::load(&ini_path) {
+            Ok(ini) => Some(ini),
+            Err(source) => {
+                return Err(LightFixesError::MorrowindIni {
+                    path: ini_path,
+                    source,
+                });
+            }
+        },
+    };
+
+    if let Some(ini) = &morrowind_ini {
+        if !ini.data_files.is_dir() {
+            return Err(LightFixesError::MissingDataFiles {
+                ini_path: ini.path.clone(),
+            });
+        }
+
+        if args.output_as_data_dir || args.revert || args.enable_via_fragment.is_some() {
+            return Err(LightFixesError::NeedsOpenMWConfig);
+        }
+    }
+
+    // The file the load order comes from, which is recorded in the plugin's metadata
+    let config_dir = match &morrowind_ini {
+        Some(ini) => ini.path.clone(),
+        None => match get_config_path(&mut args) {
+            Ok(config_dir) => config_dir,
+            // Such as when lightfixes is double-clicked in a downloads folder, which has no openmw.cfg
+            Err(err) => match hooks.locate_config(&err) {
+                Some(picked_path) => {
+                    let cwd = current_dir()?;
+
+                    if hooks.confirm(
+                        "Remember openmw.cfg?",
+                        &format!(
+                            "Use {} whenever lightfixes is run from {}?",
+                            picked_path.display(),
+                            cwd.display()
+                        ),
+                    ) == Some(true)
+                        && let Err(err) = remember_config_path(&cwd, &picked_path)
+                    {
+                        eprintln!(
+                            "[ WARNING ]: Couldn't remember {} in lightconfig.toml: {err}",
+                            picked_path.display()
+                        );
+                    }
+
+                    picked_path
+                }
+                None => return Err(LightFixesError::ConfigNotFound(err)),
+            },
+        },
+    };
+
+    // If the openmw.cfg path is provided by the user, force the crate to use
+    // whatever they've provided
+    let mut config = match &morrowind_ini {
+        Some(_) => None,
+        None => match openmw_config::OpenMWConfiguration::new(Some(config_dir.clone())) {
+            Ok(config) => Some(config),
+            Err(error) => return Err(LightFixesError::ConfigRead(error.to_string())),
+        },
+    };
+
+    let load_order_name = match morrowind_ini {
+        Some(_) => "Morrowind.ini",
+        None => "openmw.cfg",
+    };
+
+    // Everything else read from openmw.cfg. Morrowind.ini has a single data directory, Data Files, which is data-local as well
+    let (user_config_dir, data_local, base_content_files, base_data_dirs, groundcover_files): (
+        PathBuf,
+        Option<PathBuf>,
+        Vec<String>,
+        Vec<PathBuf>,
+        Vec<String>,
+    ) = match (&morrowind_ini, &mut config) {
+        (Some(ini), _) => (
+            ini.config_dir(),
+            Some(ini.data_files.clone()),
+            ini.game_files.clone(),
+            vec![ini.data_files.clone()],
+            Vec::new(),
+        ),
+        (None, Some(config)) => (
+            config.user_config_path(),
+            config.data_local().map(|dir| dir.parsed().to_owned()),
+            config
+                .content_files()
+                .iter()
+                .map(|plugin| plugin.to_string())
+                .collect(),
+            config.data_directories().into_iter().cloned().collect(),
+            config
+                .groundcover()
+                .iter()
+                .map(|plugin| plugin.to_string())
+                .collect(),
+        ),
+        (None, None) => unreachable!("openmw.cfg is read whenever there's no Morrowind.ini"),
+    };
+
+    // Held until run_with returns, or is released by exit
+    let _run_lock = match RunLock::acquire(
+        &user_config_dir,
+        args.wait_lock.map(std::time::Duration::from_secs),
+    ) {
+        Ok(run_lock) => run_lock,
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+            return Err(LightFixesError::AlreadyRunning(err));
+        }
+        Err(source) => {
+            return Err(LightFixesError::Lock {
+                dir: user_config_dir,
+                source,
+            });
+        }
+    };
+
+    let output_data_dir = user_config_dir.join(OUTPUT_DATA_DIR_NAME);
+    let enable_fragment = args
+        .enable_via_fragment
+        .take()
+        .map(|path| fragment_path(&path));
+
+    if args.revert
+        && let Some(config) = &mut config
+    {
+        revert_output_data_dir(
+            config,
+            &output_data_dir,
+            enable_fragment.as_deref(),
+            &user_config_dir.join(WEATHER_FALLBACKS_NAME),
+            hooks,
+        )?;
+
+        return Ok(summary);
+    }
+
+    let output_as_data_dir = args.output_as_data_dir;
+    let force = args.force;
+    let vfs_debug = args.vfs_debug;
+    let extra_data_dirs = std::mem::take(&mut args.data_dirs);
+    let extra_content = std::mem::take(&mut args.content);
+
+    if let Some(missing_dir) = extra_data_dirs.iter().find(|dir| !dir.is_dir()) {
+        return Err(LightFixesError::MissingDataDir(missing_dir.to_owned()));
+    }
+
+    // The stream isn't a directory, which the light config would reject as an output directory
+    if to_stdout {
+        args.output = None;
+    }
+
+    let output_dir = match args.output {
+        // Only ever used to look for files the stream doesn't replace
+        _ if to_stdout => current_dir().map_err(LightFixesError::NoWorkingDirectory)?,
+        _ if output_as_data_dir => {
+            if let Err(err) = create_dir_all(&output_data_dir) {
+                return Err(LightFixesError::OutputDir {
+                    path: output_data_dir,
+                    source: Some(err),
+                });
+            }
+
+            output_data_dir
+        }
+        Some(ref dir) => {
+            if dir.is_dir() {
+                dir.to_owned()
+            } else {
+                return Err(LightFixesError::OutputDir {
+                    path: dir.to_owned(),
+                    source: None,
+                });
+            }
+        }
+
+        None => match &data_local {
+            Some(dir) => dir.to_owned(),
+            None => current_dir().map_err(LightFixesError::NoWorkingDirectory)?,
+        },
+    };
+
+    let print_config = args.print_config;
+    let mut light_config = LightConfig::get(
+        args,
+        &user_config_dir,
+        data_local.clone(),
+        &config_dir,
+        config.as_ref(),
+    )?;
+    hooks.config_loaded(&light_config);
+
+    if print_config {
+        match &light_config.applied_profile {
+            Some(profile_name) => println!("# Profile {profile_name} was applied"),
+            None => println!("# No profile was applied"),
+        }
+
+        print!(
+            "{}",
+            toml::to_string_pretty(&light_config).map_err(LightFixesError::ConfigSerialize)?
+        );
+
+        return Ok(summary);
+    }
+
+    if morrowind_ini.is_some() && light_config.adjust_weather_fallbacks {
+        eprintln!(
+            "[ WARNING ]: --adjust-weather-fallbacks changes the fallbacks of openmw.cfg, so it's skipped with Morrowind.ini"
+        );
+    }
+
+    // Each of these works on a plugin saved to disk, or the openmw.cfg which would load it
+    if to_stdout {
+        light_config.auto_enable = false;
+        light_config.adjust_weather_fallbacks = false;
+        light_config.verify = false;
+        light_config.write_metadata = false;
+        light_config.emit_json = None;
+    }
+
+    if light_config.debug {
+        dbg!(&light_config, &config, &morrowind_ini);
+    }
+
+    // Extra content files go last, as if they'd been enabled after everything in openmw.cfg
+    let mut content_files: Vec<String> = base_content_files.clone();
+
+    for plugin in extra_content {
+        if content_files
+            .iter()
+            .any(|content_file| content_file.eq_ignore_ascii_case(&plugin))
+        {
+            print_notification(&format!(
+                "[ NOTE ]: {plugin} given with --content is already enabled in {load_order_name}"
+            ));
+            continue;
+        }
+
+        content_files.push(plugin);
+    }
+
+    if content_files.is_empty() {
+        return Err(LightFixesError::NoPlugins { load_order_name });
+    }
+
+    let mut directories: Vec<&PathBuf> = base_data_dirs.iter().collect();
+    directories.extend(&extra_data_dirs);
+
+    let (ignored_dirs, directories): (Vec<&PathBuf>, Vec<&PathBuf>) = directories
+        .into_iter()
+        .partition(|directory| light_config.is_ignored_data_dir(directory));
+
+    if light_config.debug {
+        for directory in &ignored_dirs {
+            print_notification(&format!(
+                "[ DEBUG ]: Ignoring data directory {}",
+                directory.display()
+            ));
+        }
+    }
+
+    // A directory listed twice, such as through a symlink and its target, would be scanned twice,
+    // and it'd be unclear which of its copies of a file wins
+    let mut seen_dirs: HashSet<PathBuf> = HashSet::new();
+    let directories: Vec<&PathBuf> = directories
+        .into_iter()
+        .filter(|directory| {
+            let is_new = seen_dirs.insert(directory_key(directory));
+
+            if !is_new && light_config.debug {
+                print_notification(&format!(
+                    "[ DEBUG ]: Skipping data directory {}, as it's the same as an earlier one",
+                    directory.display()
+                ));
+            }
+
+            is_new
+        })
+        .collect();
+
+    if light_config.debug {
+        print_notification("[ DEBUG ]: Data directories, from lowest to highest priority:");
+        for directory in &directories {
+            print_notification(&format!("[ DEBUG ]:     {}", directory.display()));
+        }
+    }
+
+    // Data directories are read a second time for this, so it's only done when asked for
+    let vfs_log: Vec<String> = match light_config.vfs_report || light_config.debug {
+        false => Vec::new(),
+        true => {
+            let directory_files = data_directory_files(&directories);
+
+            content_files
+                .iter()
+                .flat_map(|plugin| {
+                    let candidates = directory_files
+                        .get(&plugin.to_ascii_lowercase())
+                        .map(Vec::as_slice)
+                        .unwrap_or_default();
+
+                    match candidates.split_last() {
+                        None => vec![format!("[ VFS ]: {plugin} isn't in any data directory")],
+                        Some((winner, shadowed)) => std::iter::once(format!(
+                            "[ VFS ]: {plugin} is loaded from {}",
+                            winner.display()
+                        ))
+                        .chain(shadowed.iter().rev().map(|path| {
+                            format!(
+                                "[ VFS ]: {plugin} shadows {}, which isn't used",
+                                path.display()
+                            )
+                        }))
+                        .collect(),
+                    }
+                })
+                .collect()
+        }
+    };
+
+    for line in &vfs_log {
+        print_notification(line);
+    }
+
+    let vfs = VFS::from_directories(directories, None);
+
+    if vfs_debug {
+        for plugin in &content_files {
+            let Some(vfs_file) = vfs.get_file(plugin) else {
+                print_notification(&format!("{plugin}: not found in any data directory"));
+                continue;
+            };
+
+            let path = vfs_file.path();
+            let size = metadata(path)
+                .map(|metadata| format!("{} bytes", metadata.len()))
+                .unwrap_or_else(|err| format!("unreadable size ({err})"));
+            let excluded_by = match light_config.excluding_plugin_pattern(path) {
+                Some(pattern) => format!("excluded by {pattern}"),
+                None => "not excluded".to_string(),
+            };
+
+            print_notification(&format!(
+                "{plugin}: found at {}, {size}, {}, {excluded_by}",
+                path.display(),
+                match is_fixable_plugin(path) {
+                    true => "fixable",
+                    false => "not fixable",
+                },
+            ));
+        }
+
+        return Ok(summary);
+    }
+
+    // Missing models aren't fatal, since they may be provided by an archive or mod installed later
+    for (pattern, _, light_data) in &light_config.light_regexes {
+        for (folder, path) in [("meshes", &light_data.mesh), ("icons", &light_data.icon)] {
+            let Some(path) = path else {
+                continue;
+            };
+
+            let vfs_path = format!("{folder}/{}", path.replace('\\', "/"));
+
+            if vfs.get_file(&vfs_path).is_none() {
+                eprintln!(
+                    "[ WARNING ]: Light override {pattern} uses {vfs_path}, which does not exist in the VFS!"
+                );
+            }
+        }
+    }
+
+    // The generated plugins are left out, as enabling them would otherwise count as a change
+    let content_paths: Vec<PathBuf> = content_files
+        .iter()
+        .filter(|plugin| !is_generated_plugin_name(plugin))
+        .filter_map(|plugin| vfs.get_file(plugin))
+        .map(|vfs_file| vfs_file.path().to_path_buf())
+        .collect();
+
+    let cache_path = user_config_dir.join(CACHE_NAME);
+    let run_cache = RunCache::new(
+        content_paths.iter().map(PathBuf::as_path),
+        &light_config,
+        Vec::new(),
+    );
+
+    if !force
+        && !to_stdout
+        && let Ok(run_cache) = &run_cache
+        && let Some(cached) = RunCache::load(&cache_path)
+        && run_cache.is_up_to_date(&cached, &output_dir)
+    {
+        hooks.notify("Lightfixes up to date!",
+            &format!(
+                "Nothing changed since {} was generated in {}, so it was left alone. Use --force to generate it anyway",
+                cached.outputs.join(", "),
+                output_dir.display()
+            ));
+
+        summary.outputs = cached
+            .outputs
+            .iter()
+            .map(|output_name| RunOutput {
+                path: output_dir.join(output_name),
+                lights_modified: None,
+                cells_modified: None,
+            })
+            .collect();
+        summary.up_to_date = true;
+
+        return Ok(summary);
+    }
+
+    // Script entries are listed as content files too, but were never meant to be found as plugins
+    let missing_content: Vec<String> = content_files
+        .iter()
+        .filter(|plugin| {
+            !plugin.to_ascii_lowercase().ends_with(".omwscripts") && vfs.get_file(plugin).is_none()
+        })
+        .map(|plugin| plugin.to_string())
+        .collect();
+
+    // Groundcover plugins are full of references the parser chokes on, and have no lights worth fixing anyway
+    let groundcover: HashSet<String> = match light_config.process_groundcover {
+        true => HashSet::new(),
+        false => groundcover_files
+            .iter()
+            .map(|plugin| plugin.to_ascii_lowercase())
+            .collect(),
+    };
+
+    let load_warnings = Mutex::new(Vec::new());
+    let plugins = content_files
+    .par_iter()
+    .filter_map(|plugin| {
+        if groundcover.contains(&plugin.to_ascii_lowercase()) {
+            if light_config.debug {
+                print_notification(&format!("[ DEBUG ]: Skipping {plugin}, as it's registered as groundcover"));
+            }
+
+            return None;
+        }
+
+        let vfs_file = vfs.get_file(plugin)?;
+        let path = vfs_file.path();
+
+        if !is_fixable_plugin(path) || light_config.is_excluded_plugin(&path) {
+            return None;
+        }
+
+        // An old or renamed copy of the output would have its changes applied twice
+        if is_generated_plugin(path) {
+            if light_config.debug {
+                print_notification(&format!(
+                    "[ DEBUG ]: Skipping {}, as its header says lightfixes generated it",
+                    path.display()
+                ));
+            }
+
+            return None;
+        }
+
+        // The original engine can't load these, so they can't be masters of its plugin
+        if light_config.output_format == OutputFormat::Vanilla && is_openmw_plugin(path) {
+            return None;
+        }
+
+        // Headers are kept for their masters, which the references of carried over cells are indexed by
+        let loaded = match is_json_plugin(path) {
+            true => load_json_plugin(path),
+            false => Plugin::from_path_filtered(path, |tag| {
+                matches!(&tag, Header::TAG | Cell::TAG | Light::TAG)
+                    || (light_config.process_magic_effects && &tag == MagicEffect::TAG)
+            }),
+        };
+
+        match loaded {
+            Ok(plugin) => Some((
+                plugin,
+                PluginMeta {
+                    file_name: path.file_name()?.to_string_lossy().into_owned(),
+                    size: metadata(path).ok()?.len(),
+                },
+            )),
+            Err(source) => {
+                let err = LightFixesError::PluginLoad {
+                    path: path.to_path_buf(),
+                    source,
+                };
+
+                eprintln!(
+                    "[ WARNING ]: {err}. Continuing light fixes without this mod .  . . Everything will be okay. Yes, it's still working.\n"
+                );
+                if let Ok(mut load_warnings) = load_warnings.lock() {
+                    load_warnings.push(err);
+                }
+                None
+            }
+        }
+    })
+    .collect::<Vec<_>>();
+
+    summary.warnings = load_warnings
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner);
+
+    let Generation {
+        plugin: mut generated_plugin,
+        header,
+        mut record_owners,
+        master_names,
+        disabled_ids,
+        instance_log,
+    } = generate(plugins, &light_config)?;
+
+    let missing_log: Vec<String> = missing_content
+        .iter()
+        .map(|plugin| {
+            match ignored_dirs
+                .iter()
+                .find(|directory| directory.join(plugin).is_file())
+            {
+                Some(directory) => format!(
+                    "[ MISSING ]: {plugin} is enabled in {load_order_name}, but is only in {}, which is ignored",
+                    directory.display()
+                ),
+                None => format!(
+                    "[ MISSING ]: {plugin} is enabled in {load_order_name}, but isn't in any data directory"
+                ),
+            }
+        })
+        .collect();
+
+    if !missing_content.is_empty() {
+        for line in &missing_log {
+            eprintln!("{line}");
+        }
+
+        eprintln!(
+            "[ WARNING ]: {} content files couldn't be found, so their lights weren't fixed. Check that the data directories of {load_order_name} hold them.",
+            missing_content.len()
+        );
+
+        if light_config.strict {
+            return Err(LightFixesError::MissingContent {
+                load_order_name,
+                missing: missing_content,
+            });
+        }
+    }
+
+    summary.missing = missing_content;
+
+    let plugin_name = light_config
+        .output_file_type
+        .plugin_name(light_config.output_format);
+
+    let existing_path = output_dir.join(plugin_name);
+    if !to_stdout && !light_config.preserve_id_regexes.is_empty() && existing_path.is_file() {
+        match Plugin::from_path(&existing_path) {
+            Err(error) => eprintln!(
+                "[ WARNING ]: Couldn't load {} to preserve records from it: {error}",
+                existing_path.display()
+            ),
+            Ok(existing_plugin) => {
+                // References of preserved cells still point at the masters of the existing plugin
+                let existing_source_names: Vec<String> =
+                    std::iter::once(plugin_name.to_ascii_lowercase())
+                        .chain(
+                            existing_plugin
+                                .objects
+                                .iter()
+                                .find_map(|object| match object {
+                                    TES3Object::Header(existing_header) => Some(
+                                        existing_header
+                                            .masters
+                                            .iter()
+                                            .map(|(name, _)| name.to_ascii_lowercase())
+                                            .collect::<Vec<_>>(),
+                                    ),
+                                    _ => None,
+                                })
+                                .unwrap_or_default(),
+                        )
+                        .collect();
+
+                for mut object in existing_plugin.objects {
+                    let Some((kind, id)) = record_key(&object) else {
+                        continue;
+                    };
+
+                    if !light_config
+                        .preserve_id_regexes
+                        .iter()
+                        .any(|pattern| pattern.is_match(&id))
+                    {
+                        continue;
+                    }
+
+                    if let TES3Object::Cell(cell) = &mut object {
+                        remap_references(cell, &existing_source_names, &master_names);
+                    }
+
+                    let generated_index = generated_plugin.objects.iter().position(|generated| {
+                        record_key(generated).is_some_and(|(generated_kind, generated_id)| {
+                            generated_kind == kind && generated_id == id
+                        })
+                    });
+
+                    match generated_index {
+                        Some(index) => generated_plugin.objects[index] = object,
+                        None => {
+                            eprintln!(
+                                "[ WARNING ]: Preserved {kind} {id} is no longer generated, but was carried over from {}",
+                                existing_path.display()
+                            );
+                            generated_plugin.objects.push(object);
+                            record_owners.push(master_names.last().cloned().unwrap_or_default());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let too_many_masters = header.masters.len() > light_config.max_masters;
+
+    if too_many_masters {
+        hooks.notify("Too many masters!",
+            &format!(
+                "The generated plugin would have {} masters, which is more than the {} allowed by max_masters! {}",
+                header.masters.len(),
+                light_config.max_masters,
+                match light_config.split_masters {
+                    true => "It will be split into several plugins, which must all be enabled.",
+                    false =>
+                        "The engine may fail to load it. Use --split-masters to split it into several plugins instead.",
+                }
+            ));
+    }
+
+    // Objects are pushed in load order, so records sorting the same keep a stable order between runs
+    let split_by_masters = too_many_masters && light_config.split_masters;
+
+    let mut outputs: Vec<(String, Plugin)> = if split_by_masters || light_config.split_output {
+        let records = TakeAndSwitch(&mut generated_plugin.objects)
+            .into_iter()
+            .zip(record_owners)
+            .collect();
+
+        let (master_parts, masters_per_part) = match split_by_masters {
+            true => (
+                header.masters.len().div_ceil(light_config.max_masters),
+                light_config.max_masters,
+            ),
+            false => (1, usize::MAX),
+        };
+
+        let type_suffixes: &[&str] = match light_config.split_output {
+            true => &[LIGHTS_SUFFIX, CELLS_SUFFIX],
+            false => &[""],
+        };
+
+        // Parts are grouped by record type first, then by their share of the masters
+        split_records(
+            records,
+            &header,
+            type_suffixes.len() * master_parts,
+            |owner_index, record| {
+                let type_index =
+                    usize::from(light_config.split_output && matches!(record, TES3Object::Cell(_)));
+                type_index * master_parts + owner_index / masters_per_part
+            },
+        )
+        .into_iter()
+        .map(|(part, plugin)| {
+            let mut part_name = plugin_name.to_string();
+
+            if light_config.split_output {
+                part_name = suffixed_plugin_name(&part_name, type_suffixes[part / master_parts]);
+            }
+
+            if split_by_masters {
+                part_name = suffixed_plugin_name(&part_name, part % master_parts + 1);
+            }
+
+            (part_name, plugin)
+        })
+        .collect()
+    } else {
+        generated_plugin.objects.push(TES3Object::Header(header));
+        generated_plugin.sort_objects();
+        vec![(plugin_name.to_string(), generated_plugin)]
+    };
+
+    // The count kept while generating records is only a cross-check, and the real one is always written
+    for (output_name, output_plugin) in &mut outputs {
+        if !finalize_header(output_plugin) && light_config.debug {
+            return Err(LightFixesError::BadRecordCount {
+                plugin_name: output_name.clone(),
+            });
+        }
+    }
+
+    // Nothing else is done with a plugin which is streamed out, as it isn't saved anywhere lightfixes knows of
+    if to_stdout {
+        let [(output_name, output_plugin)] = outputs.as_mut_slice() else {
+            return Err(LightFixesError::SplitStream);
+        };
+
+        save_plugin_to_writer(&mut hooks.plugin_stream(), output_plugin)
+            .map_err(LightFixesError::Stream)?;
+
+        hooks.notify(
+            "Lightfixes successful!",
+            &format!("{output_name} generated and written to stdout"),
+        );
+
+        return Ok(summary);
+    }
+
+    // If the old plugin format exists, remove it. When data_local is the output directory,
+    // old plugins are removed below instead, once the new one has been saved
+    if let Some(dir) = &data_local
+        && canonical_path(dir) != canonical_path(&output_dir)
+    {
+        for old_plug_name in PLUGIN_NAMES {
+            let old_plug_path = dir.join(old_plug_name);
+            if old_plug_path.is_file() {
+                let _ = remove_file(PluginMetadata::path_for(&old_plug_path));
+                let _ = remove_file(old_plug_path);
+            }
+        }
+    }
+
+    // Copies left in other data directories, such as by an earlier run with another --output, would be loaded twice.
+    // The output directory itself is cleaned up below
+    let mut stale_log = Vec::new();
+    let mut stale_paths = Vec::new();
+    let mut scanned_dirs = HashSet::from([canonical_path(&output_dir)]);
+    let data_dirs: Vec<PathBuf> = base_data_dirs
+        .iter()
+        .cloned()
+        .chain(data_local.clone())
+        .collect();
+
+    for dir in data_dirs {
+        if !scanned_dirs.insert(canonical_path(&dir)) {
+            continue;
+        }
+
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let stale_name = entry.file_name().to_string_lossy().into_owned();
+            if is_generated_plugin_name(&stale_name) {
+                stale_paths.push(entry.path());
+            }
+        }
+    }
+
+    // Without --clean-stale, whoever is running lightfixes is asked instead, if there's a dialog to ask them with
+    let clean_stale = light_config.clean_stale
+        || (!stale_paths.is_empty()
+            && hooks.confirm(
+                "Remove stale plugins?",
+                &format!(
+                    "These generated plugins are in other data directories, and would be loaded twice:\n{}\nRemove them?",
+                    stale_paths
+                        .iter()
+                        .map(|stale_path| stale_path.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                ),
+            ) == Some(true));
+
+    for stale_path in stale_paths {
+        let line = match clean_stale {
+            true => match remove_file(&stale_path) {
+                Ok(()) => {
+                    let _ = remove_file(PluginMetadata::path_for(&stale_path));
+                    format!("[ STALE ]: Found and removed {}", stale_path.display())
+                }
+                Err(err) => format!(
+                    "[ STALE ]: Found {}, but couldn't remove it: {err}",
+                    stale_path.display()
+                ),
+            },
+            false => format!(
+                "[ STALE ]: Found {}, left in place. Use --clean-stale to remove it",
+                stale_path.display()
+            ),
+        };
+
+        eprintln!("{line}");
+        summary.stale.push(stale_path);
+        stale_log.push(line);
+    }
+
+    // A plugin left behind under another name would apply the same changes twice.
+    // They're only removed once the new plugin has been saved, so a failed save doesn't leave none at all
+    let mut replaced_plugins = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(&output_dir) {
+        for entry in entries.flatten() {
+            let stale_name = entry.file_name().to_string_lossy().into_owned();
+
+            if !is_generated_plugin_name(&stale_name)
+                || outputs
+                    .iter()
+                    .any(|(output_name, _)| output_name.eq_ignore_ascii_case(&stale_name))
+            {
+                continue;
+            }
+
+            replaced_plugins.push((entry.path(), stale_name));
+        }
+    }
+
+    let mut up_to_date = true;
+    let mut save_failed = false;
+    // Parts of a split plugin can't share one JSON path
+    let json_per_output = outputs.len() > 1;
+    let mut diff_log = Vec::new();
+
+    for (output_name, output_plugin) in &mut outputs {
+        // The previous plugin is only read, and is replaced by save_plugin as usual afterwards
+        let previous_path = output_dir.join(output_name.as_str());
+        if previous_path.is_file() {
+            match Plugin::from_path(&previous_path) {
+                Ok(previous_plugin) => {
+                    let diff = PluginDiff::between(&previous_plugin, output_plugin);
+                    let summary = match diff.is_empty() {
+                        true => format!("[ DIFF ]: {output_name}: nothing changed"),
+                        false => format!("[ DIFF ]: {output_name}: {diff}"),
+                    };
+                    print_notification(&summary);
+                    diff_log.push(summary);
+
+                    if light_config.diff_details {
+                        for line in diff.details() {
+                            let line = format!("[ DIFF ]: {line}");
+                            print_notification(&line);
+                            diff_log.push(line);
+                        }
+                    }
+                }
+                Err(err) => eprintln!(
+                    "[ WARNING ]: Couldn't read the previous {output_name} to compare against it: {err}"
+                ),
+            }
+        }
+
+        match save_plugin(&output_dir, output_name, output_plugin, &light_config) {
+            Ok(outcome) => {
+                up_to_date &= matches!(outcome, SaveOutcome::UpToDate(_));
+
+                summary.outputs.push(RunOutput {
+                    path: outcome.path().to_path_buf(),
+                    lights_modified: Some(output_plugin.objects_of_type::<Light>().count()),
+                    cells_modified: Some(output_plugin.objects_of_type::<Cell>().count()),
+                });
+
+                if light_config.verify
+                    && let Err(err) = verify_plugin(outcome.path(), &vfs)
+                {
+                    return Err(LightFixesError::VerifyFailed {
+                        path: outcome.path().to_path_buf(),
+                        source: err,
+                    });
+                }
+
+                if let Some(emit_json) = &light_config.emit_json {
+                    let json_path = match emit_json.is_dir() {
+                        true => emit_json.join(format!("{output_name}.json")),
+                        false if json_per_output => {
+                            emit_json.with_file_name(format!("{output_name}.json"))
+                        }
+                        false => emit_json.to_owned(),
+                    };
+
+                    if let Err(err) = save_json_plugin(output_plugin, &json_path) {
+                        eprintln!("[ WARNING ]: Couldn't write {}: {err}", json_path.display());
+                    }
+                }
+
+                let metadata_path = PluginMetadata::path_for(outcome.path());
+                if light_config.write_metadata {
+                    if let Err(err) = PluginMetadata::new(output_plugin, &light_config, &config_dir)
+                        .and_then(|metadata| metadata.save(outcome.path()))
+                    {
+                        eprintln!(
+                            "[ WARNING ]: Couldn't write {}: {err}",
+                            metadata_path.display()
+                        );
+                    }
+                } else if metadata_path.is_file() {
+                    // Metadata left over from an earlier run would describe a different plugin
+                    let _ = remove_file(metadata_path);
+                }
+
+                // Identical inputs write identical bytes, so distributors can compare this between runs
+                if light_config.debug {
+                    let hash = fnv1a(std::fs::read(outcome.path())?);
+                    print_notification(&format!(
+                        "[ DEBUG ]: {outcome:?} with FNV-1a hash {hash:016x}",
+                    ));
+                }
+            }
+            Err(err @ LightFixesError::PluginExists(_)) => return Err(err),
+            Err(err) => {
+                up_to_date = false;
+                save_failed = true;
+                hooks.notify(err.title(), &err.to_string());
+                summary.warnings.push(err);
+            }
+        };
+    }
+
+    if !save_failed {
+        for (replaced_path, replaced_name) in replaced_plugins {
+            let _ = remove_file(&replaced_path);
+            let _ = remove_file(PluginMetadata::path_for(&replaced_path));
+
+            if base_content_files
+                .iter()
+                .any(|content_file| content_file.eq_ignore_ascii_case(&replaced_name))
+            {
+                eprintln!(
+                    "[ WARNING ]: Removed {replaced_name}, which is still enabled in {load_order_name}! It should be replaced by {plugin_name}."
+                );
+            }
+        }
+    }
+
+    let output_names = outputs
+        .iter()
+        .map(|(output_name, _)| output_name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let is_vanilla = light_config.output_format == OutputFormat::Vanilla;
+
+    if (is_vanilla || morrowind_ini.is_some()) && light_config.auto_enable {
+        let reason = match morrowind_ini {
+            Some(_) => "Morrowind.ini is left alone",
+            None => "it's made for the original engine",
+        };
+
+        hooks.notify("Enable lightfixes manually!",
+            &format!(
+                "{output_names} wasn't enabled, as {reason}. Enable it in the Morrowind launcher instead."
+            ));
+    }
+
+    // Without --auto-enable, whoever is running lightfixes is asked instead, if there's a dialog to ask them with
+    let mut enable_log = Vec::new();
+    if !light_config.auto_enable
+        && !is_vanilla
+        && !up_to_date
+        && !output_as_data_dir
+        && enable_fragment.is_none()
+        && let Some(config) = &config
+        && outputs
+            .iter()
+            .any(|(output_name, _)| !config.has_content_file(output_name))
+        && let Some(answer) = hooks.confirm(
+            "Enable lightfixes?",
+            &format!("Enable {output_names} in openmw.cfg now?"),
+        )
+    {
+        light_config.auto_enable = answer;
+
+        let line = format!(
+            "[ ENABLE ]: Asked whether to enable {output_names} in {}, and the answer was {}",
+            config.user_config_path().display(),
+            match answer {
+                true => "yes",
+                false => "no",
+            }
+        );
+
+        print_notification(&line);
+        enable_log.push(line);
+    }
+
+    let mut enabled_any = false;
+    let mut reordered: Vec<String> = Vec::new();
+
+    // The data directory goes last, so that the plugin can't be shadowed by a copy elsewhere
+    if output_as_data_dir
+        && !is_vanilla
+        && enable_fragment.is_none()
+        && let Some(config) = &mut config
+        && !config
+            .data_directories()
+            .iter()
+            .any(|dir| canonical_path(dir) == canonical_path(&output_dir))
+    {
+        config.add_data_directory(&output_dir);
+        enabled_any = true;
+    }
+
+    // Handle this arg via clap
+    // Split plugins are enabled in the order of their parts
+    if (light_config.auto_enable || output_as_data_dir)
+        && !is_vanilla
+        && !up_to_date
+        && enable_fragment.is_none()
+        && let Some(config) = &mut config
+    {
+        // Anything enabled after the plugin overrides the lights it fixes, so it's moved back to the end,
+        // by taking it out and enabling it again below
+        let content_files: Vec<String> = config
+            .content_files()
+            .iter()
+            .map(|content_file| content_file.to_string())
+            .collect();
+
+        let is_last = content_files.len() >= outputs.len()
+            && content_files[content_files.len() - outputs.len()..]
+                .iter()
+                .zip(&outputs)
+                .all(|(content_file, (output_name, _))| {
+                    content_file.eq_ignore_ascii_case(output_name)
+                });
+
+        if !light_config.no_reorder && !is_last {
+            for (output_name, _) in &outputs {
+                if config.has_content_file(output_name) {
+                    config.remove_content_file(output_name);
+                    reordered.push(output_name.clone());
+                }
+            }
+        }
+
+        for (output_name, _) in &outputs {
+            if config.has_content_file(output_name) {
+                continue;
+            }
+
+            if let Err(err) = config.add_content_file(output_name) {
+                return Err(LightFixesError::EnableFailed(err.to_string()));
+            }
+
+            enabled_any = true;
+        }
+    }
+
+    if enabled_any && let Some(config) = &mut config {
+        if let Err(err) = config.save_user() {
+            let err = LightFixesError::ConfigSave(err);
+            hooks.notify(err.title(), &err.to_string());
+        } else {
+            let mut lightfix_enabled_msg = format!(
+                "Wrote user openmw.cfg at {} successfully!",
+                config.user_config_path().display()
+            );
+
+            if !reordered.is_empty() {
+                lightfix_enabled_msg.push_str(&format!(
+                    "\n{} was moved to the end of the load order, as plugins enabled after it would override its lights. Use --no-reorder to leave it in place.",
+                    reordered.join(", ")
+                ));
+            }
+            hooks.notify("Lightfixes enabled!", &lightfix_enabled_msg);
+        }
+    }
+
+    // openmw.cfg is left to whatever manages it, which only has to chain the fragment in once
+    if let Some(fragment) = &enable_fragment
+        && !is_vanilla
+    {
+        let output_names: Vec<String> = outputs
+            .iter()
+            .map(|(output_name, _)| output_name.clone())
+            .collect();
+
+        match write_fragment(
+            fragment,
+            output_as_data_dir.then_some(output_dir.as_path()),
+            &output_names,
+        ) {
+            Ok(changed) => {
+                let fragment_dir = fragment.parent().unwrap_or(fragment);
+                let mut fragment_msg = format!(
+                    "{} {}. Add config=\"{}\" to the end of openmw.cfg to load it, if it isn't there already.",
+                    match changed {
+                        true => "Enabled lightfixes in",
+                        false => "Lightfixes was already enabled in",
+                    },
+                    fragment.display(),
+                    fragment_dir.display(),
+                );
+
+                if !fragment
+                    .file_name()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(FRAGMENT_NAME))
+                {
+                    fragment_msg.push_str(&format!(
+                        "\nOpenMW only reads files named {FRAGMENT_NAME} from directories chained in with config=, so rename it first."
+                    ));
+                }
+
+                hooks.notify("Lightfixes enabled!", &fragment_msg);
+            }
+            Err(err) => hooks.notify(
+                "Failed to write config fragment!",
+                &format!("{} couldn't be written: {err}", fragment.display()),
+            ),
+        }
+    }
+
+    let weather_fallbacks_path = user_config_dir.join(WEATHER_FALLBACKS_NAME);
+    let mut weather_fallbacks = load_weather_fallbacks(&weather_fallbacks_path);
+
+    let weather_log = match &mut config {
+        Some(config) if light_config.adjust_weather_fallbacks && !is_vanilla && !up_to_date => {
+            adjust_weather_fallbacks(config, &light_config, &mut weather_fallbacks)
+        }
+        _ => Vec::new(),
+    };
+
+    if !weather_log.is_empty()
+        && let Some(config) = &mut config
+    {
+        if let Err(err) = config.save_user() {
+            let err = LightFixesError::ConfigSave(err);
+            hooks.notify(err.title(), &err.to_string());
+        } else if let Err(err) = save_weather_fallbacks(&weather_fallbacks, &weather_fallbacks_path)
+        {
+            eprintln!(
+                "[ WARNING ]: Couldn't write {}: {err}",
+                weather_fallbacks_path.display()
+            );
+        } else if light_config.debug {
+            print_notification(&format!(
+                "[ DEBUG ]: Wrote {} adjusted weather fallbacks to {}",
+                weather_log.len(),
+                config.user_config_path().display()
+            ));
+        }
+    }
+
+    if light_config.save_log {
+        let path = user_config_dir.join(LOG_NAME);
+        let mut file = File::create(path)?;
+
+        for light_id in &disabled_ids {
+            let _ = writeln!(
+                file,
+                "[ DISABLED ]: {light_id} was intentionally disabled by a light override"
+            );
+        }
+
+        for line in instance_log
+            .iter()
+            .chain(&enable_log)
+            .chain(&weather_log)
+            .chain(&diff_log)
+            .chain(&stale_log)
+            .chain(&missing_log)
+            .chain(&vfs_log)
+        {
+            let _ = writeln!(file, "{line}");
+        }
+
+        for (_, output_plugin) in &outputs {
+            let _ = write!(file, "{}", format!("{:#?}", output_plugin));
+        }
+    }
+
+    // Only a run which saved everything may let the next one skip itself
+    if !save_failed && let Ok(mut run_cache) = run_cache {
+        run_cache.outputs = outputs
+            .iter()
+            .map(|(output_name, _)| output_name.clone())
+            .collect();
+
+        if let Err(err) = run_cache.save(&cache_path) {
+            eprintln!(
+                "[ WARNING ]: Couldn't write {}: {err}",
+                cache_path.display()
+            );
+        }
+    }
+
+    let lights_fixed = match up_to_date {
+        true => format!(
+            "{output_names} is up to date in {}, and was left alone",
+            output_dir.display()
+        ),
+        false => format!(
+            "{output_names} generated, enabled, and saved in {}",
+            output_dir.display()
+        ),
+    };
+
+    hooks.notify("Lightfixes successful!", &lights_fixed);
+    summary.up_to_date = up_to_date;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    use crate::{
+        PLUGIN_NAME,
+        test_fixtures::{
+            install_args, interior_cell, light, openmw_install, plugin, run_install, temp_dir,
+            write_plugin,
+        },
+    };
+
+    /// Records of the plugin generated into an installation
+    fn generated_plugin(dir: &Path) -> Plugin {
+        Plugin::from_path(&dir.join("output").join(PLUGIN_NAME)).unwrap()
+    }
+
+    fn generated_radius(plugin: &Plugin, light_id: &str) -> Option<u32> {
+        plugin
+            .objects_of_type::<Light>()
+            .find(|light| light.id == light_id)
+            .map(|light| light.data.radius)
+    }
+
+    /// Edits the radius of a light in the plugin generated into an installation, as a user would by hand
+    fn edit_generated_radius(dir: &Path, light_id: &str, radius: u32) {
+        let plugin_path = dir.join("output").join(PLUGIN_NAME);
+        let mut plugin = Plugin::from_path(&plugin_path).unwrap();
+
+        plugin
+            .objects_of_type_mut::<Light>()
+            .find(|light| light.id == light_id)
+            .unwrap()
+            .data
+            .radius = radius;
+
+        plugin.save_path(&plugin_path).unwrap();
+    }
+
+    #[test]
+    fn preserved_records_survive_regeneration() {
+        let dir = openmw_install(
+            "preserve-ids",
+            vec![plugin(
+                "Fires.esp",
+                [
+                    light("torch", [255, 160, 64], 128),
+                    light("candle", [255, 200, 120], 64),
+                ],
+            )],
+        );
+        let args = ["--preserve-ids", "^torch$"];
+
+        run_install(&dir, &args).unwrap();
+        let candle_radius = generated_radius(&generated_plugin(&dir), "candle");
+
+        edit_generated_radius(&dir, "torch", 999);
+        edit_generated_radius(&dir, "candle", 999);
+
+        run_install(&dir, &args).unwrap();
+        let regenerated = generated_plugin(&dir);
+        assert_eq!(generated_radius(&regenerated, "torch"), Some(999));
+        assert_eq!(generated_radius(&regenerated, "candle"), candle_radius);
+
+        // Hand edits are kept through any number of runs
+        run_install(&dir, &args).unwrap();
+        assert_eq!(
+            generated_radius(&generated_plugin(&dir), "torch"),
+            Some(999)
+        );
+    }
+
+    #[test]
+    fn preserved_records_are_kept_once_no_longer_generated() {
+        let dir = openmw_install(
+            "preserve-removed",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+
+        run_install(&dir, &[]).unwrap();
+
+        let plugin_path = dir.join("output").join(PLUGIN_NAME);
+        let mut plugin = Plugin::from_path(&plugin_path).unwrap();
+        plugin
+            .objects
+            .push(light("lantern", [200, 220, 255], 256).into());
+        finalize_header(&mut plugin);
+        plugin.save_path(&plugin_path).unwrap();
+
+        run_install(&dir, &["--preserve-ids", "^lantern$"]).unwrap();
+        let regenerated = generated_plugin(&dir);
+        assert_eq!(generated_radius(&regenerated, "lantern"), Some(256));
+        assert!(generated_radius(&regenerated, "torch").is_some());
+
+        run_install(&dir, &[]).unwrap();
+        assert_eq!(generated_radius(&generated_plugin(&dir), "lantern"), None);
+    }
+
+    /// Adds lines to the openmw.cfg of an installation
+    fn append_to_cfg(dir: &Path, lines: &str) {
+        let cfg_path = dir.join("openmw.cfg");
+        let mut openmw_cfg = std::fs::read_to_string(&cfg_path).unwrap();
+        openmw_cfg.push_str(lines);
+        std::fs::write(cfg_path, openmw_cfg).unwrap();
+    }
+
+    #[test]
+    fn groundcover_plugins_are_skipped() {
+        let dir = openmw_install(
+            "groundcover",
+            vec![
+                plugin("Fires.esp", [light("torch", [255, 160, 64], 128)]),
+                plugin("Grass.esp", [light("glowing_grass", [64, 255, 128], 64)]),
+            ],
+        );
+        append_to_cfg(&dir, "groundcover=Grass.esp\n");
+
+        run_install(&dir, &[]).unwrap();
+        let generated = generated_plugin(&dir);
+        assert!(generated_radius(&generated, "torch").is_some());
+        assert!(generated_radius(&generated, "glowing_grass").is_none());
+
+        run_install(&dir, &["--process-groundcover"]).unwrap();
+        assert!(generated_radius(&generated_plugin(&dir), "glowing_grass").is_some());
+    }
+
+    /// A data directory next to an installation's own, holding the given plugins
+    fn overlay_dir(dir: &Path, plugins: Vec<(Plugin, PluginMeta)>) -> String {
+        let overlay_dir = dir.join("overlay");
+        create_dir_all(&overlay_dir).unwrap();
+
+        for (plugin, plugin_meta) in plugins {
+            write_plugin(&overlay_dir, plugin, &plugin_meta);
+        }
+
+        overlay_dir.display().to_string()
+    }
+
+    #[test]
+    fn extra_data_dirs_shadow_configured_ones() {
+        let dir = openmw_install(
+            "extra-data-dir",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        let overlay_dir = overlay_dir(
+            &dir,
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 512)])],
+        );
+
+        run_install(&dir, &[]).unwrap();
+        assert_eq!(
+            generated_radius(&generated_plugin(&dir), "torch"),
+            Some(128)
+        );
+
+        run_install(&dir, &["--data-dir", &overlay_dir]).unwrap();
+        assert_eq!(
+            generated_radius(&generated_plugin(&dir), "torch"),
+            Some(512)
+        );
+    }
+
+    #[test]
+    fn missing_extra_data_dirs_are_rejected() {
+        let dir = openmw_install(
+            "missing-data-dir",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        let missing_dir = dir.join("nowhere").display().to_string();
+
+        assert!(matches!(
+            run_install(&dir, &["--data-dir", &missing_dir]),
+            Err(LightFixesError::MissingDataDir(_))
+        ));
+    }
+
+    /// Lowercased masters of the plugin generated into an installation
+    fn generated_masters(dir: &Path) -> Vec<String> {
+        generated_plugin(dir)
+            .objects_of_type::<Header>()
+            .next()
+            .unwrap()
+            .masters
+            .iter()
+            .map(|(name, _)| name.to_ascii_lowercase())
+            .collect()
+    }
+
+    #[test]
+    fn extra_content_is_processed_after_the_load_order() {
+        let dir = openmw_install(
+            "extra-content",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        let overlay_dir = overlay_dir(
+            &dir,
+            vec![plugin(
+                "Wip.esp",
+                [
+                    light("torch", [255, 160, 64], 512),
+                    light("wip_lantern", [200, 220, 255], 256),
+                ],
+            )],
+        );
+        let openmw_cfg = std::fs::read_to_string(dir.join("openmw.cfg")).unwrap();
+
+        run_install(&dir, &["--data-dir", &overlay_dir, "--content", "Wip.esp"]).unwrap();
+
+        let generated = generated_plugin(&dir);
+        assert_eq!(generated_radius(&generated, "torch"), Some(512));
+        assert_eq!(generated_radius(&generated, "wip_lantern"), Some(256));
+        assert_eq!(generated_masters(&dir), ["wip.esp"]);
+
+        // Extra content is never written into openmw.cfg
+        assert_eq!(
+            std::fs::read_to_string(dir.join("openmw.cfg")).unwrap(),
+            openmw_cfg
+        );
+    }
+
+    #[test]
+    fn extra_content_already_enabled_is_ignored() {
+        let dir = openmw_install(
+            "duplicate-content",
+            vec![
+                plugin("Fires.esp", [light("torch", [255, 160, 64], 128)]),
+                plugin("Lanterns.esp", [light("lantern", [200, 220, 255], 256)]),
+            ],
+        );
+
+        run_install(&dir, &["--content", "fires.esp"]).unwrap();
+
+        assert_eq!(generated_masters(&dir), ["fires.esp", "lanterns.esp"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_data_dirs_are_the_same_directory() {
+        let dir = temp_dir("symlinked-dir-key");
+        let target = dir.join("target");
+        let link = dir.join("link");
+        create_dir_all(&target).unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        assert_eq!(directory_key(&link), directory_key(&target));
+        assert_eq!(directory_key(&target.join(".")), directory_key(&target));
+        assert_ne!(directory_key(&dir), directory_key(&target));
+
+        // Directories which don't exist are compared as given
+        let missing = dir.join("missing");
+        assert_eq!(directory_key(&missing), missing);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_data_dirs_are_scanned_once() {
+        let dir = openmw_install(
+            "symlinked-data-dirs",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        let link = dir.join("data-link");
+        std::os::unix::fs::symlink(dir.join("data"), &link).unwrap();
+        append_to_cfg(&dir, &format!("data=\"{}\"\n", link.display()));
+
+        run_install(&dir, &[]).unwrap();
+        assert_eq!(
+            generated_radius(&generated_plugin(&dir), "torch"),
+            Some(128)
+        );
+        assert_eq!(generated_masters(&dir), ["fires.esp"]);
+    }
+
+    #[test]
+    fn run_summarizes_the_saved_plugin() {
+        let records: Vec<TES3Object> = vec![
+            light("torch", [255, 160, 64], 128).into(),
+            light("lantern", [200, 220, 255], 256).into(),
+            interior_cell("Balmora, Guild of Mages", [80, 80, 120], 0.4).into(),
+        ];
+        let dir = openmw_install("run-summary", vec![plugin("Balmora.esp", records)]);
+
+        let summary = run_install(&dir, &["--fog-density-mult", "2.0", "--verify"]).unwrap();
+
+        assert!(!summary.up_to_date);
+        assert!(summary.missing.is_empty());
+        assert!(summary.warnings.is_empty());
+
+        let [output] = summary.outputs.as_slice() else {
+            panic!("Expected a single plugin, not {}", summary.outputs.len());
+        };
+        assert_eq!(output.path, dir.join("output").join(PLUGIN_NAME));
+        assert_eq!(output.lights_modified, Some(2));
+        assert_eq!(output.cells_modified, Some(1));
+        assert!(output.path.is_file());
+    }
+
+    #[test]
+    fn run_leaves_an_unchanged_load_order_alone() {
+        let dir = openmw_install(
+            "run-up-to-date",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        let run_unforced = || {
+            crate::run(LightArgs::parse_from([
+                "s3lightfixes",
+                "--openmw-cfg",
+                &dir.display().to_string(),
+                "--output",
+                &dir.join("output").display().to_string(),
+            ]))
+        };
+
+        assert!(!run_unforced().unwrap().up_to_date);
+
+        let summary = run_unforced().unwrap();
+        assert!(summary.up_to_date);
+        assert_eq!(summary.outputs[0].lights_modified, None);
+    }
+
+    #[test]
+    fn run_reports_missing_content() {
+        let dir = openmw_install(
+            "run-missing-content",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        append_to_cfg(&dir, "content=Missing.esp\n");
+
+        let summary = run_install(&dir, &[]).unwrap();
+        assert_eq!(summary.missing, ["Missing.esp"]);
+
+        assert!(matches!(
+            run_install(&dir, &["--strict"]),
+            Err(LightFixesError::MissingContent { .. })
+        ));
+    }
+
+    #[test]
+    fn run_needs_content_files() {
+        let dir = openmw_install("run-no-content", Vec::new());
+
+        assert!(matches!(
+            run_install(&dir, &[]),
+            Err(LightFixesError::NoPlugins { .. })
+        ));
+    }
+
+    #[test]
+    fn fallback_colors_are_rgb_triples() {
+        assert_eq!(parse_fallback_color("255, 128,0"), Some([255, 128, 0]));
+        assert_eq!(parse_fallback_color("255,128"), None);
+        assert_eq!(parse_fallback_color("255,128,0,0"), None);
+        assert_eq!(parse_fallback_color("256,128,0"), None);
+        assert_eq!(parse_fallback_color("0.5,0.5,0.5"), None);
+    }
+
+    #[test]
+    fn weather_fallbacks_are_adjusted_and_logged() {
+        let dir = openmw_install(
+            "weather_fallbacks",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        append_to_cfg(
+            &dir,
+            "fallback=Weather_Clear_Ambient_Day_Color,255,0,0\n\
+             fallback=Weather_Foggy_Ambient_Night_Color,dark\n\
+             fallback=Water_Map_Alpha,255,0,0\n",
+        );
+
+        run_install(
+            &dir,
+            &[
+                "--adjust-weather-fallbacks",
+                "--weather-saturation",
+                "0",
+                "--write-log",
+            ],
+        )
+        .unwrap();
+
+        let openmw_cfg = std::fs::read_to_string(dir.join("openmw.cfg")).unwrap();
+        assert!(openmw_cfg.contains("Weather_Clear_Ambient_Day_Color,255,255,255"));
+        assert!(openmw_cfg.contains("Weather_Foggy_Ambient_Night_Color,dark"));
+        assert!(openmw_cfg.contains("Water_Map_Alpha,255,0,0"));
+
+        // The original value is logged, so the change can be undone by hand
+        let log = std::fs::read_to_string(dir.join(LOG_NAME)).unwrap();
+        assert!(log.contains(
+            "[ WEATHER ]: fallback=Weather_Clear_Ambient_Day_Color,255,0,0 was changed to 255,255,255"
+        ));
+        assert!(!log.contains("Weather_Foggy_Ambient_Night_Color"));
+    }
+
+    #[test]
+    fn weather_fallbacks_are_scaled_from_their_originals_and_reverted() {
+        let dir = openmw_install(
+            "weather_fallbacks_rerun",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        append_to_cfg(
+            &dir,
+            "fallback=Weather_Clear_Ambient_Day_Color,200,100,50\n",
+        );
+
+        let weather_args = ["--adjust-weather-fallbacks", "--weather-value", "0.5"];
+        let day_color = || cfg_lines_with(&dir, "Weather_Clear_Ambient_Day_Color");
+
+        run_install(&dir, &weather_args).unwrap();
+        let adjusted = day_color();
+        assert_eq!(adjusted.len(), 1);
+        assert!(!adjusted[0].ends_with("Weather_Clear_Ambient_Day_Color,200,100,50"));
+
+        run_install(&dir, &weather_args).unwrap();
+        assert_eq!(day_color(), adjusted);
+
+        run_install(&dir, &["--revert"]).unwrap();
+        assert!(day_color()[0].ends_with("Weather_Clear_Ambient_Day_Color,200,100,50"));
+        assert!(!dir.join(WEATHER_FALLBACKS_NAME).exists());
+    }
+
+    #[test]
+    fn vanilla_format_leaves_openmw_alone() {
+        let dir = openmw_install(
+            "vanilla-format",
+            vec![
+                plugin("Fires.esp", [light("torch", [255, 160, 64], 128)]),
+                plugin("Extra.omwaddon", [light("lantern", [255, 160, 64], 128)]),
+            ],
+        );
+        let original_cfg = std::fs::read_to_string(dir.join("openmw.cfg")).unwrap();
+
+        run_install(&dir, &["--format", "vanilla", "--auto-enable"]).unwrap();
+
+        assert!(!dir.join("output").join(PLUGIN_NAME).exists());
+        let generated =
+            Plugin::from_path(&dir.join("output").join(crate::VANILLA_PLUGIN_NAME)).unwrap();
+
+        // The original engine can't load the .omwaddon, so it isn't a master either
+        let masters: Vec<String> = generated
+            .objects_of_type::<Header>()
+            .next()
+            .unwrap()
+            .masters
+            .iter()
+            .map(|(name, _)| name.to_ascii_lowercase())
+            .collect();
+        assert_eq!(masters, ["fires.esp"]);
+        assert!(generated_radius(&generated, "lantern").is_none());
+
+        assert_eq!(
+            std::fs::read_to_string(dir.join("openmw.cfg")).unwrap(),
+            original_cfg
+        );
+    }
+
+    /// Lowercased masters of a plugin written into the output directory of an installation
+    fn output_masters(dir: &Path, output_name: &str) -> Vec<String> {
+        Plugin::from_path(&dir.join("output").join(output_name))
+            .unwrap()
+            .objects_of_type::<Header>()
+            .next()
+            .unwrap()
+            .masters
+            .iter()
+            .map(|(name, _)| name.to_ascii_lowercase())
+            .collect()
+    }
+
+    /// Content files enabled by the openmw.cfg of an installation, in order
+    fn enabled_content(dir: &Path) -> Vec<String> {
+        std::fs::read_to_string(dir.join("openmw.cfg"))
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("content="))
+            .map(str::to_owned)
+            .collect()
+    }
+
+    fn three_fire_plugins() -> Vec<(Plugin, PluginMeta)> {
+        ["A.esp", "B.esp", "C.esp"]
+            .into_iter()
+            .enumerate()
+            .map(|(index, plugin_name)| {
+                plugin(
+                    plugin_name,
+                    [light(&format!("torch_{index}"), [255, 160, 64], 128)],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn too_many_masters_only_warns_unless_split() {
+        let dir = openmw_install("max-masters", three_fire_plugins());
+        run_install(&dir, &["--max-masters", "2"]).unwrap();
+
+        assert_eq!(generated_masters(&dir), ["a.esp", "b.esp", "c.esp"]);
+    }
+
+    #[test]
+    fn split_masters_writes_numbered_parts() {
+        let dir = openmw_install("split-masters", three_fire_plugins());
+        let summary = run_install(
+            &dir,
+            &["--max-masters", "2", "--split-masters", "--auto-enable"],
+        )
+        .unwrap();
+
+        assert_eq!(summary.outputs.len(), 2);
+        assert!(!dir.join("output").join(PLUGIN_NAME).exists());
+        assert_eq!(
+            output_masters(&dir, "S3LightFixes-1.omwaddon"),
+            ["a.esp", "b.esp"]
+        );
+        assert_eq!(output_masters(&dir, "S3LightFixes-2.omwaddon"), ["c.esp"]);
+
+        assert_eq!(
+            enabled_content(&dir),
+            [
+                "A.esp",
+                "B.esp",
+                "C.esp",
+                "S3LightFixes-1.omwaddon",
+                "S3LightFixes-2.omwaddon"
+            ]
+        );
+    }
+
+    #[test]
+    fn split_output_separates_lights_from_cells() {
+        let dir = openmw_install(
+            "split-output",
+            vec![
+                plugin("Fires.esp", [light("torch", [255, 160, 64], 128)]),
+                plugin(
+                    "Guilds.esp",
+                    [interior_cell("Balmora, Guild of Mages", [80, 80, 120], 0.4)],
+                ),
+            ],
+        );
+
+        run_install(
+            &dir,
+            &[
+                "--fog-density-mult",
+                "2.0",
+                "--split-output",
+                "--auto-enable",
+            ],
+        )
+        .unwrap();
+
+        assert!(!dir.join("output").join(PLUGIN_NAME).exists());
+        assert_eq!(
+            output_masters(&dir, "S3LightFixes-Lights.omwaddon"),
+            ["fires.esp"]
+        );
+        assert_eq!(
+            output_masters(&dir, "S3LightFixes-Cells.omwaddon"),
+            ["guilds.esp"]
+        );
+        assert_eq!(
+            enabled_content(&dir),
+            [
+                "Fires.esp",
+                "Guilds.esp",
+                "S3LightFixes-Lights.omwaddon",
+                "S3LightFixes-Cells.omwaddon"
+            ]
+        );
+
+        // Going back to a single plugin replaces both parts
+        run_install(&dir, &["--fog-density-mult", "2.0"]).unwrap();
+
+        assert!(dir.join("output").join(PLUGIN_NAME).exists());
+        assert!(
+            !dir.join("output")
+                .join("S3LightFixes-Lights.omwaddon")
+                .exists()
+        );
+        assert!(
+            !dir.join("output")
+                .join("S3LightFixes-Cells.omwaddon")
+                .exists()
+        );
+    }
+
+    #[test]
+    fn metadata_sidecar_is_written_only_when_asked_for() {
+        let dir = openmw_install(
+            "metadata-sidecar",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        let metadata_path = PluginMetadata::path_for(&dir.join("output").join(PLUGIN_NAME));
+
+        run_install(&dir, &["--write-metadata"]).unwrap();
+
+        let metadata: PluginMetadata =
+            serde_json::from_str(&std::fs::read_to_string(&metadata_path).unwrap()).unwrap();
+        assert_eq!(metadata.lights_modified, 1);
+        assert_eq!(metadata.masters.len(), 1);
+
+        // A sidecar from an earlier run would describe a different plugin
+        run_install(&dir, &[]).unwrap();
+        assert!(!metadata_path.exists());
+    }
+
+    #[test]
+    fn stale_plugins_are_only_removed_when_asked_to() {
+        let dir = openmw_install(
+            "stale-plugins",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        run_install(&dir, &[]).unwrap();
+
+        // Left behind by earlier runs which wrote into the data directory instead
+        let stale_paths = [
+            dir.join("data").join("S3LightFixes-2.omwaddon"),
+            dir.join("data").join(PLUGIN_NAME),
+        ];
+        for stale_path in &stale_paths {
+            std::fs::copy(dir.join("output").join(PLUGIN_NAME), stale_path).unwrap();
+        }
+
+        let stale_found = |summary: &RunSummary| {
+            let mut stale = summary.stale.clone();
+            stale.sort();
+            stale
+        };
+
+        let summary = run_install(&dir, &[]).unwrap();
+        assert_eq!(stale_found(&summary), stale_paths);
+        assert!(stale_paths.iter().all(|stale_path| stale_path.exists()));
+
+        let summary = run_install(&dir, &["--clean-stale"]).unwrap();
+        assert_eq!(stale_found(&summary), stale_paths);
+        assert!(!stale_paths.iter().any(|stale_path| stale_path.exists()));
+        assert!(dir.join("output").join(PLUGIN_NAME).exists());
+    }
+
+    /// Lines of the openmw.cfg of an installation which mention the given text
+    fn cfg_lines_with(dir: &Path, text: &str) -> Vec<String> {
+        std::fs::read_to_string(dir.join("openmw.cfg"))
+            .unwrap()
+            .lines()
+            .filter(|line| line.contains(text))
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn output_data_dir_is_registered_once_and_reverted() {
+        let dir = openmw_install(
+            "output-data-dir",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        let output_data_dir = dir.join(OUTPUT_DATA_DIR_NAME);
+
+        run_install(&dir, &["--output-as-data-dir"]).unwrap();
+        run_install(&dir, &["--output-as-data-dir"]).unwrap();
+
+        assert!(output_data_dir.join(PLUGIN_NAME).is_file());
+        assert!(!dir.join("output").join(PLUGIN_NAME).exists());
+
+        let data_lines: Vec<String> = cfg_lines_with(&dir, "data=");
+        assert_eq!(data_lines.len(), 2);
+        assert!(data_lines[1].contains(OUTPUT_DATA_DIR_NAME));
+        assert_eq!(enabled_content(&dir), ["Fires.esp", PLUGIN_NAME]);
+
+        run_install(&dir, &["--revert"]).unwrap();
+
+        assert!(!output_data_dir.exists());
+        assert!(cfg_lines_with(&dir, OUTPUT_DATA_DIR_NAME).is_empty());
+        assert_eq!(enabled_content(&dir), ["Fires.esp"]);
+    }
+
+    #[test]
+    fn renamed_generated_plugins_are_skipped() {
+        let dir = openmw_install(
+            "renamed-output",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        run_install(&dir, &[]).unwrap();
+
+        std::fs::copy(
+            dir.join("output").join(PLUGIN_NAME),
+            dir.join("data").join("Old Fixes.esp"),
+        )
+        .unwrap();
+        append_to_cfg(&dir, "content=Old Fixes.esp\n");
+
+        run_install(&dir, &[]).unwrap();
+        assert_eq!(generated_masters(&dir), ["fires.esp"]);
+    }
+
+    #[test]
+    fn script_entries_are_not_missing_content() {
+        let dir = openmw_install(
+            "missing-scripts",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        append_to_cfg(&dir, "content=Lanterns.omwscripts\ncontent=Missing.esp\n");
+
+        let summary = run_install(&dir, &["--write-log"]).unwrap();
+        assert_eq!(summary.missing, ["Missing.esp"]);
+
+        let log = std::fs::read_to_string(dir.join(LOG_NAME)).unwrap();
+        assert!(log.contains("[ MISSING ]: Missing.esp is enabled in"));
+        assert!(!log.contains("Lanterns.omwscripts"));
+    }
+
+    #[test]
+    fn data_directory_files_keep_every_copy_in_order() {
+        let dir = temp_dir("data-directory-files");
+        let [low, high] = ["low", "high"].map(|name| dir.join(name));
+
+        for directory in [&low, &high] {
+            create_dir_all(directory).unwrap();
+        }
+        std::fs::write(low.join("Fires.esp"), b"").unwrap();
+        std::fs::write(high.join("FIRES.ESP"), b"").unwrap();
+        std::fs::write(high.join("Lanterns.esp"), b"").unwrap();
+
+        let files = data_directory_files(&[&low, &high, &dir.join("missing")]);
+
+        assert_eq!(
+            files["fires.esp"],
+            [low.join("Fires.esp"), high.join("FIRES.ESP")]
+        );
+        assert_eq!(files["lanterns.esp"], [high.join("Lanterns.esp")]);
+    }
+
+    #[test]
+    fn vfs_report_names_the_winning_copy() {
+        let dir = openmw_install(
+            "vfs-report",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        let overlay_dir = overlay_dir(
+            &dir,
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 512)])],
+        );
+        append_to_cfg(&dir, "content=Missing.esp\n");
+
+        run_install(
+            &dir,
+            &["--data-dir", &overlay_dir, "--vfs-report", "--write-log"],
+        )
+        .unwrap();
+
+        let log = std::fs::read_to_string(dir.join(LOG_NAME)).unwrap();
+        assert!(log.contains(&format!(
+            "[ VFS ]: Fires.esp is loaded from {}",
+            Path::new(&overlay_dir).join("Fires.esp").display()
+        )));
+        assert!(log.contains(&format!(
+            "[ VFS ]: Fires.esp shadows {}, which isn't used",
+            dir.join("data").join("Fires.esp").display()
+        )));
+        assert!(log.contains("[ VFS ]: Missing.esp isn't in any data directory"));
+    }
+
+    #[test]
+    fn vfs_debug_generates_nothing() {
+        let dir = openmw_install(
+            "vfs-debug",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+
+        let summary = run_install(&dir, &["--vfs-debug"]).unwrap();
+
+        assert!(summary.outputs.is_empty());
+        assert!(!dir.join("output").join(PLUGIN_NAME).exists());
+    }
+
+    #[test]
+    fn ignored_data_dirs_leave_their_plugins_missing() {
+        let dir = openmw_install(
+            "ignored-data-dir",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        let overlay_dir = overlay_dir(
+            &dir,
+            vec![plugin(
+                "Lanterns.esp",
+                [light("lantern", [255, 160, 64], 256)],
+            )],
+        );
+        append_to_cfg(
+            &dir,
+            &format!("data=\"{overlay_dir}\"\ncontent=Lanterns.esp\n"),
+        );
+
+        let summary = run_install(&dir, &[]).unwrap();
+        assert!(summary.missing.is_empty());
+        assert_eq!(generated_masters(&dir), ["fires.esp", "lanterns.esp"]);
+
+        let summary = run_install(&dir, &["--ignore-data-dir", "overlay$", "--write-log"]).unwrap();
+        assert_eq!(summary.missing, ["Lanterns.esp"]);
+        assert_eq!(generated_masters(&dir), ["fires.esp"]);
+
+        let log = std::fs::read_to_string(dir.join(LOG_NAME)).unwrap();
+        assert!(log.contains(&format!(
+            "[ MISSING ]: Lanterns.esp is enabled in openmw.cfg, but is only in {overlay_dir}, which is ignored"
+        )));
+    }
+
+    #[test]
+    fn morrowind_ini_installs_are_read_and_left_alone() {
+        let dir = temp_dir("morrowind-ini-run");
+        let data_files = dir.join(MorrowindIni::DATA_FILES_NAME);
+        create_dir_all(&data_files).unwrap();
+
+        let (fires, fires_meta) = plugin("Fires.esp", [light("torch", [255, 160, 64], 128)]);
+        write_plugin(&data_files, fires, &fires_meta);
+
+        let ini_path = dir.join("Morrowind.ini");
+        let ini = "[Game Files]\r\nGameFile0=Fires.esp\r\n";
+        std::fs::write(&ini_path, ini).unwrap();
+        std::fs::write(dir.join(crate::DEFAULT_CONFIG_NAME), "").unwrap();
+
+        // Given in place of openmw.cfg, it's still recognized by its extension
+        crate::run(LightArgs::parse_from([
+            "s3lightfixes",
+            "--openmw-cfg",
+            &ini_path.display().to_string(),
+            "--format",
+            "vanilla",
+            "--auto-enable",
+            "--force",
+        ]))
+        .unwrap();
+
+        let generated = Plugin::from_path(&data_files.join(crate::VANILLA_PLUGIN_NAME)).unwrap();
+        assert_eq!(generated_radius(&generated, "torch"), Some(128));
+        assert_eq!(std::fs::read_to_string(&ini_path).unwrap(), ini);
+    }
+
+    #[test]
+    fn auto_enable_moves_the_plugin_last_unless_told_not_to() {
+        let dir = openmw_install(
+            "auto-enable-reorder",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        let enable_later = |plugin_name: &str| {
+            let (later, later_meta) = plugin(plugin_name, [light("lantern", [255, 160, 64], 256)]);
+            write_plugin(&dir.join("data"), later, &later_meta);
+            append_to_cfg(&dir, &format!("content={plugin_name}\n"));
+        };
+
+        run_install(&dir, &["--auto-enable"]).unwrap();
+        assert_eq!(enabled_content(&dir), ["Fires.esp", PLUGIN_NAME]);
+
+        enable_later("Lanterns.esp");
+        run_install(&dir, &["--auto-enable"]).unwrap();
+        assert_eq!(
+            enabled_content(&dir),
+            ["Fires.esp", "Lanterns.esp", PLUGIN_NAME]
+        );
+
+        enable_later("Candles.esp");
+        run_install(&dir, &["--auto-enable", "--no-reorder"]).unwrap();
+        assert_eq!(
+            enabled_content(&dir),
+            ["Fires.esp", "Lanterns.esp", PLUGIN_NAME, "Candles.esp"]
+        );
+    }
+
+    #[test]
+    fn fragments_enable_the_plugin_instead_of_openmw_cfg() {
+        let dir = openmw_install(
+            "enable-via-fragment",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        let fragment_dir = dir.join("fragment");
+        create_dir_all(&fragment_dir).unwrap();
+        let fragment_dir_arg = fragment_dir.display().to_string();
+
+        run_install(&dir, &["--enable-via-fragment", &fragment_dir_arg]).unwrap();
+
+        assert_eq!(enabled_content(&dir), ["Fires.esp"]);
+        assert_eq!(
+            std::fs::read_to_string(fragment_dir.join(FRAGMENT_NAME)).unwrap(),
+            format!("content={PLUGIN_NAME}\n")
+        );
+
+        run_install(
+            &dir,
+            &["--revert", "--enable-via-fragment", &fragment_dir_arg],
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(fragment_dir.join(FRAGMENT_NAME)).unwrap(),
+            ""
+        );
+    }
+
+    /// Hooks answering whether to enable the plugin as told, and never anything else
+    #[derive(Default)]
+    struct AnsweringHooks {
+        enable: Option<bool>,
+        config_path: Option<PathBuf>,
+        asked: Vec<String>,
+    }
+
+    impl RunHooks for AnsweringHooks {
+        fn confirm(&mut self, title: &str, _message: &str) -> Option<bool> {
+            self.asked.push(title.to_string());
+
+            match title {
+                "Enable lightfixes?" => self.enable,
+                _ => None,
+            }
+        }
+
+        fn locate_config(&mut self, _error: &LightFixesError) -> Option<PathBuf> {
+            self.config_path.clone()
+        }
+    }
+
+    #[test]
+    fn enabling_is_asked_about_unless_auto_enabled() {
+        let answered = |name: &str, enable: Option<bool>, args: &[&str]| {
+            let dir = openmw_install(
+                name,
+                vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+            );
+            let mut hooks = AnsweringHooks {
+                enable,
+                ..Default::default()
+            };
+
+            run_with(install_args(&dir, args), &mut hooks).unwrap();
+            (dir, hooks.asked)
+        };
+
+        let (dir, asked) = answered("confirm-yes", Some(true), &["--write-log"]);
+        assert_eq!(asked, ["Enable lightfixes?"]);
+        assert_eq!(enabled_content(&dir), ["Fires.esp", PLUGIN_NAME]);
+
+        // The answer is kept in the log
+        let log = std::fs::read_to_string(dir.join(LOG_NAME)).unwrap();
+        assert!(log.contains("[ ENABLE ]: Asked whether to enable"));
+        assert!(log.contains("and the answer was yes"));
+
+        let (dir, asked) = answered("confirm-no", Some(false), &[]);
+        assert_eq!(asked, ["Enable lightfixes?"]);
+        assert_eq!(enabled_content(&dir), ["Fires.esp"]);
+
+        // Without a dialog to ask with, nothing is enabled
+        let (dir, _) = answered("confirm-nobody", None, &[]);
+        assert_eq!(enabled_content(&dir), ["Fires.esp"]);
+
+        let (dir, asked) = answered("confirm-auto-enable", Some(false), &["--auto-enable"]);
+        assert!(asked.is_empty());
+        assert_eq!(enabled_content(&dir), ["Fires.esp", PLUGIN_NAME]);
+    }
+
+    #[test]
+    fn missing_openmw_cfg_can_be_located() {
+        let dir = openmw_install(
+            "locate-config",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        let lost_args = || {
+            LightArgs::parse_from([
+                "s3lightfixes",
+                "--openmw-cfg",
+                &dir.join("missing").display().to_string(),
+                "--output",
+                &dir.join("output").display().to_string(),
+                "--force",
+            ])
+        };
+
+        // Cancelling the picker stops with the error it was shown for
+        let mut hooks = AnsweringHooks::default();
+        assert!(matches!(
+            run_with(lost_args(), &mut hooks),
+            Err(LightFixesError::ConfigNotFound(_))
+        ));
+        assert!(!dir.join("output").join(PLUGIN_NAME).exists());
+
+        let mut hooks = AnsweringHooks {
+            config_path: Some(dir.clone()),
+            ..Default::default()
+        };
+        run_with(lost_args(), &mut hooks).unwrap();
+
+        assert!(
+            hooks
+                .asked
+                .iter()
+                .any(|title| title == "Remember openmw.cfg?")
+        );
+        assert_eq!(generated_masters(&dir), ["fires.esp"]);
+    }
+
+    #[test]
+    fn concurrent_runs_are_refused() {
+        let dir = openmw_install(
+            "run-lock",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+
+        let held = File::create(dir.join(crate::LOCK_NAME)).unwrap();
+        held.try_lock().unwrap();
+
+        assert!(matches!(
+            run_install(&dir, &[]),
+            Err(LightFixesError::AlreadyRunning(_))
+        ));
+        assert!(!dir.join("output").join(PLUGIN_NAME).exists());
+
+        drop(held);
+        run_install(&dir, &[]).unwrap();
+    }
+
+    /// Hooks keeping a streamed plugin, instead of writing it to stdout
+    #[derive(Default)]
+    struct StreamHooks {
+        stream: Vec<u8>,
+    }
+
+    impl RunHooks for StreamHooks {
+        fn plugin_stream(&mut self) -> Box<dyn Write + '_> {
+            Box::new(&mut self.stream)
+        }
+    }
+
+    #[test]
+    fn plugins_can_be_streamed() {
+        let dir = openmw_install(
+            "stream-output",
+            vec![plugin("Fires.esp", [light("torch", [255, 160, 64], 128)])],
+        );
+        let light_args = LightArgs::parse_from([
+            "s3lightfixes",
+            "--openmw-cfg",
+            &dir.display().to_string(),
+            "--output",
+            "-",
+            "--force",
+        ]);
+
+        let mut hooks = StreamHooks::default();
+        run_with(light_args, &mut hooks).unwrap();
+
+        let mut streamed = Plugin::new();
+        streamed.load_bytes(&hooks.stream).unwrap();
+        assert_eq!(generated_radius(&streamed, "torch"), Some(128));
+        assert!(!dir.join("output").join(PLUGIN_NAME).exists());
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    fs::{File, metadata},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{LightConfig, fnv1a};
+
+/// Name of the cache written next to the user openmw.cfg after each full run
+pub const CACHE_NAME: &str = "lightconfig.cache.json";
+
+/// A content file as it was when the plugin was last generated
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct ContentFileStamp {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Modification time, since the Unix epoch
+    pub modified_secs: u64,
+    pub modified_nanos: u32,
+}
+
+impl ContentFileStamp {
+    pub fn new(path: &Path) -> io::Result<Self> {
+        let metadata = metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        Ok(ContentFileStamp {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+        })
+    }
+}
+
+/// Everything a run of lightfixes depends on, so that the next one can tell when it would only repeat itself
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct RunCache {
+    pub tool_version: String,
+    /// FNV-1a hash of the effective light config.
+    /// Its debug form is hashed, as that covers the compiled overrides and patterns which the TOML doesn't
+    pub config_hash: String,
+    /// In load order
+    pub content_files: Vec<ContentFileStamp>,
+    /// Names of the plugins which were written into the output directory
+    pub outputs: Vec<String>,
+}
+
+impl RunCache {
+    pub fn new<'a>(
+        content_paths: impl IntoIterator<Item = &'a Path>,
+        light_config: &LightConfig,
+        outputs: Vec<String>,
+    ) -> io::Result<Self> {
+        Ok(RunCache {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            config_hash: format!("{:016x}", fnv1a(format!("{light_config:?}").into_bytes())),
+            content_files: content_paths
+                .into_iter()
+                .map(ContentFileStamp::new)
+                .collect::<io::Result<_>>()?,
+            outputs,
+        })
+    }
+
+    /// Reads the cache of the last run, if there is a readable one
+    pub fn load(path: &Path) -> Option<Self> {
+        serde_json::from_slice(&std::fs::read(path).ok()?).ok()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let cache_json = serde_json::to_string_pretty(self).map_err(io::Error::from)?;
+        let mut file = File::create(path)?;
+        file.write_all(cache_json.as_bytes())
+    }
+
+    /// Whether this run would depend on exactly the same things as the cached one,
+    /// and each plugin the cached run wrote is still in the output directory
+    pub fn is_up_to_date(&self, cached: &RunCache, output_dir: &Path) -> bool {
+        self.tool_version == cached.tool_version
+            && self.config_hash == cached.config_hash
+            && self.content_files == cached.content_files
+            && !cached.outputs.is_empty()
+            && cached
+                .outputs
+                .iter()
+                .all(|output_name| output_dir.join(output_name).is_file())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        PLUGIN_NAME,
+        test_fixtures::{neutral_config, temp_dir},
+    };
+
+    /// A directory holding a content file and the plugin generated from it
+    fn cached_run(test_name: &str, light_config: &LightConfig) -> (PathBuf, PathBuf, RunCache) {
+        let dir = temp_dir(test_name);
+        let content_path = dir.join("Fires.esp");
+        std::fs::write(&content_path, b"TES3").unwrap();
+        std::fs::write(dir.join(PLUGIN_NAME), b"TES3").unwrap();
+
+        let run_cache = RunCache::new(
+            [content_path.as_path()],
+            light_config,
+            vec![PLUGIN_NAME.to_string()],
+        )
+        .unwrap();
+
+        let cache_path = dir.join(CACHE_NAME);
+        run_cache.save(&cache_path).unwrap();
+
+        (dir, content_path, RunCache::load(&cache_path).unwrap())
+    }
+
+    #[test]
+    fn cache_round_trips() {
+        let light_config = neutral_config();
+        let (dir, content_path, cached) = cached_run("cache-round-trip", &light_config);
+
+        let run_cache = RunCache::new([content_path.as_path()], &light_config, Vec::new()).unwrap();
+        assert_eq!(run_cache.content_files, cached.content_files);
+        assert!(run_cache.is_up_to_date(&cached, &dir));
+    }
+
+    #[test]
+    fn touched_plugins_invalidate_the_cache() {
+        let light_config = neutral_config();
+        let (dir, content_path, cached) = cached_run("cache-touched", &light_config);
+
+        let modified = metadata(&content_path).unwrap().modified().unwrap();
+        File::options()
+            .write(true)
+            .open(&content_path)
+            .unwrap()
+            .set_modified(modified + Duration::from_secs(60))
+            .unwrap();
+
+        let run_cache = RunCache::new([content_path.as_path()], &light_config, Vec::new()).unwrap();
+        assert!(!run_cache.is_up_to_date(&cached, &dir));
+    }
+
+    #[test]
+    fn resized_plugins_invalidate_the_cache() {
+        let light_config = neutral_config();
+        let (dir, content_path, cached) = cached_run("cache-resized", &light_config);
+
+        let modified = metadata(&content_path).unwrap().modified().unwrap();
+        std::fs::write(&content_path, b"TES3 and then some").unwrap();
+        File::options()
+            .write(true)
+            .open(&content_path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        let run_cache = RunCache::new([content_path.as_path()], &light_config, Vec::new()).unwrap();
+        assert!(!run_cache.is_up_to_date(&cached, &dir));
+    }
+
+    #[test]
+    fn changed_configs_invalidate_the_cache() {
+        let (dir, content_path, cached) = cached_run("cache-config", &neutral_config());
+
+        let light_config = LightConfig {
+            standard_radius: 2.0,
+            ..neutral_config()
+        };
+
+        let run_cache = RunCache::new([content_path.as_path()], &light_config, Vec::new()).unwrap();
+        assert!(!run_cache.is_up_to_date(&cached, &dir));
+    }
+
+    #[test]
+    fn missing_outputs_invalidate_the_cache() {
+        let light_config = neutral_config();
+        let (dir, content_path, cached) = cached_run("cache-missing-output", &light_config);
+        std::fs::remove_file(dir.join(PLUGIN_NAME)).unwrap();
+
+        let run_cache = RunCache::new([content_path.as_path()], &light_config, Vec::new()).unwrap();
+        assert!(!run_cache.is_up_to_date(&cached, &dir));
+    }
+
+    #[test]
+    fn unreadable_caches_are_not_loaded() {
+        let dir = temp_dir("cache-unreadable");
+        let cache_path = dir.join(CACHE_NAME);
+
+        assert!(RunCache::load(&cache_path).is_none());
+
+        std::fs::write(&cache_path, "not json").unwrap();
+        assert!(RunCache::load(&cache_path).is_none());
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{self, Read, Seek, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// Name of the lock file kept next to the user openmw.cfg while lightfixes runs
+pub const LOCK_NAME: &str = "s3lightfixes.lock";
+
+/// How often a held lock is checked again while waiting for it
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Every lock file this process holds locked, by path, so that `release_run_lock` can unlock them from any exit path.
+/// Each is a handle to the same open file as the one its `RunLock` keeps, so unlocking either releases the lock
+static HELD_LOCKS: Mutex<Vec<(PathBuf, File)>> = Mutex::new(Vec::new());
+
+fn held_locks() -> MutexGuard<'static, Vec<(PathBuf, File)>> {
+    HELD_LOCKS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Unlocks a lock file, leaving the file itself in place,
+/// as another run may already have it open and be waiting to lock it
+fn unlock(file: &File) {
+    let _ = file.set_len(0);
+    let _ = file.unlock();
+}
+
+/// Unlocks every lock this process holds, as `exit` skips the destructors which would otherwise do it
+pub fn release_run_lock() {
+    for (_, file) in held_locks().drain(..) {
+        unlock(&file);
+    }
+}
+
+/// The PID written by the run holding the lock, which is only used to say who holds it
+fn holder_pid(path: &Path) -> Option<u32> {
+    let mut contents = String::new();
+    File::open(path).ok()?.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
+
+/// Keeps other runs of lightfixes from writing the plugin and openmw.cfg at the same time as this one.
+/// This is an advisory lock of the OS on the lock file, which is released when this is dropped,
+/// by `release_run_lock`, or by the OS when the process ends, so it's never left behind by a crash
+#[derive(Debug)]
+pub struct RunLock {
+    path: PathBuf,
+    file: File,
+}
+
+impl RunLock {
+    /// Locks the lock file in dir, creating it if needed. If another run holds it, this waits up to `wait`
+    /// for it to be released, failing with `WouldBlock` otherwise. The PID of this process is written to it
+    pub fn acquire(dir: &Path, wait: Option<Duration>) -> io::Result<Self> {
+        let path = dir.join(LOCK_NAME);
+        let started = Instant::now();
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) => {}
+                Err(TryLockError::Error(err)) => return Err(err),
+            }
+
+            if started.elapsed() >= wait.unwrap_or_default() {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    format!(
+                        "Another run of lightfixes{} is already using {}. Wait for it to finish, or use --wait-lock to wait for it",
+                        holder_pid(&path)
+                            .map(|pid| format!(", with PID {pid},"))
+                            .unwrap_or_default(),
+                        path.display()
+                    ),
+                ));
+            }
+
+            sleep(LOCK_POLL_INTERVAL);
+        }
+
+        file.set_len(0)?;
+        file.rewind()?;
+        writeln!(file, "{}", std::process::id())?;
+
+        // Paths are unique in the registry, as no other lock on this one can be acquired while it's held
+        if let Ok(registered) = file.try_clone() {
+            held_locks().push((path.clone(), registered));
+        }
+
+        Ok(RunLock { path, file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        held_locks().retain(|(path, _)| *path != self.path);
+        unlock(&self.file);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::temp_dir;
+
+    /// Holds the lock file of dir the way another run would, claiming to be the given PID
+    fn hold_lock(dir: &Path, pid: u32) -> File {
+        let mut file = File::create(dir.join(LOCK_NAME)).unwrap();
+        writeln!(file, "{pid}").unwrap();
+        file.try_lock().unwrap();
+        file
+    }
+
+    #[test]
+    fn held_locks_fail_with_the_holder_pid() {
+        let dir = temp_dir("lock-held");
+        let held = hold_lock(&dir, 4242);
+
+        let started = Instant::now();
+        let err = RunLock::acquire(&dir, Some(Duration::from_millis(300))).unwrap_err();
+
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert!(err.to_string().contains("with PID 4242"));
+
+        // Closing it is enough to release it, as a crashed run would
+        drop(held);
+
+        let run_lock = RunLock::acquire(&dir, None).unwrap();
+        assert_eq!(run_lock.path(), dir.join(LOCK_NAME));
+        assert_eq!(holder_pid(run_lock.path()), Some(std::process::id()));
+    }
+
+    #[test]
+    fn waiting_locks_are_acquired_once_released() {
+        let dir = temp_dir("lock-wait");
+        let held = hold_lock(&dir, 4242);
+
+        let releaser = std::thread::spawn(move || {
+            sleep(Duration::from_millis(300));
+            drop(held);
+        });
+
+        assert!(RunLock::acquire(&dir, Some(Duration::from_secs(10))).is_ok());
+        releaser.join().unwrap();
+    }
+
+    #[test]
+    fn dropping_a_lock_leaves_others_held() {
+        let [first_dir, second_dir] = ["lock-first", "lock-second"].map(temp_dir);
+
+        let first = RunLock::acquire(&first_dir, None).unwrap();
+        let second = RunLock::acquire(&second_dir, None).unwrap();
+        drop(first);
+
+        assert!(RunLock::acquire(&first_dir, None).is_ok());
+        assert_eq!(
+            RunLock::acquire(&second_dir, None).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+        assert_eq!(holder_pid(second.path()), Some(std::process::id()));
+    }
+}
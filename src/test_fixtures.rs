@@ -0,0 +1,176 @@
+//! Records and configs shared by the tests of several modules
+
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use tes3::esp::{
+    AtmosphereData, Cell, CellData, CellFlags, Header, Light, LightData, Plugin, TES3Object,
+};
+
+use crate::{
+    CustomLightData, DEFAULT_CONFIG_NAME, LightArgs, LightConfig, LightFixesError, PluginMeta,
+    RunSummary, finalize_header,
+};
+
+/// A light with the given id, color, and radius, which burns for five minutes
+pub fn light(id: &str, color: [u8; 3], radius: u32) -> Light {
+    Light {
+        id: id.into(),
+        data: LightData {
+            color: [color[0], color[1], color[2], 0],
+            radius,
+            time: 300,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// A config whose global multipliers leave lights as they are, so tests only see what they set themselves
+pub fn neutral_config() -> LightConfig {
+    LightConfig {
+        disable_flickering: false,
+        standard_hue: 1.0,
+        standard_saturation: 1.0,
+        standard_value: 1.0,
+        standard_radius: 1.0,
+        colored_hue: 1.0,
+        colored_saturation: 1.0,
+        colored_value: 1.0,
+        colored_radius: 1.0,
+        duration_mult: 1.0,
+        ..Default::default()
+    }
+}
+
+/// An interior cell whose ambient, sunlight, and fog are all the given color
+pub fn interior_cell(name: &str, color: [u8; 3], fog_density: f32) -> Cell {
+    let color = [color[0], color[1], color[2], 0];
+
+    Cell {
+        name: name.into(),
+        data: CellData {
+            flags: CellFlags::IS_INTERIOR,
+            grid: (0, 0),
+        },
+        atmosphere_data: Some(AtmosphereData {
+            ambient_color: color,
+            sunlight_color: color,
+            fog_color: color,
+            fog_density,
+        }),
+        ..Default::default()
+    }
+}
+
+/// A plugin holding the given records, along with its metadata
+pub fn plugin(
+    file_name: &str,
+    records: impl IntoIterator<Item = impl Into<TES3Object>>,
+) -> (Plugin, PluginMeta) {
+    let mut plugin = Plugin::new();
+    plugin.objects.extend(records.into_iter().map(Into::into));
+
+    (
+        plugin,
+        PluginMeta {
+            file_name: file_name.into(),
+            size: 0,
+        },
+    )
+}
+
+/// A light override for ids matching `pattern` in any plugin, as it would be compiled from lightconfig.toml
+pub fn light_override(
+    pattern: &str,
+    light_data: &str,
+) -> (regex::Regex, Option<regex::Regex>, CustomLightData) {
+    (
+        regex::Regex::new(pattern).unwrap(),
+        None,
+        light_data.parse().unwrap(),
+    )
+}
+
+/// An empty directory only the named test writes into, which is emptied again on every run
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("s3lightfixes-test-{}-{name}", std::process::id()));
+
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
+
+/// Loads a lightconfig.toml holding `contents` the way a run would, given the command line arguments after the program name
+pub fn load_config(name: &str, contents: &str, args: &[&str]) -> LightConfig {
+    let dir = temp_dir(name);
+    std::fs::write(dir.join(DEFAULT_CONFIG_NAME), contents).unwrap();
+
+    let light_args =
+        LightArgs::parse_from(std::iter::once("s3lightfixes").chain(args.iter().copied()));
+
+    LightConfig::get(
+        light_args,
+        &dir,
+        Some(dir.clone()),
+        &dir.join("openmw.cfg"),
+        None,
+    )
+    .unwrap()
+}
+
+/// Saves a plugin into a data directory, with a header in front of its records
+pub fn write_plugin(data_dir: &Path, mut plugin: Plugin, plugin_meta: &PluginMeta) {
+    plugin.objects.insert(0, Header::default().into());
+    finalize_header(&mut plugin);
+    plugin
+        .save_path(data_dir.join(&plugin_meta.file_name))
+        .unwrap();
+}
+
+/// Writes an installation into a fresh directory, with each plugin in `data` and an openmw.cfg enabling them in order.
+/// Plugins are generated into `output`
+pub fn openmw_install(name: &str, plugins: Vec<(Plugin, PluginMeta)>) -> PathBuf {
+    let dir = temp_dir(name);
+    let data_dir = dir.join("data");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    std::fs::create_dir_all(dir.join("output")).unwrap();
+
+    let mut openmw_cfg = format!("data=\"{}\"\n", data_dir.display());
+
+    for (plugin, plugin_meta) in plugins {
+        write_plugin(&data_dir, plugin, &plugin_meta);
+        openmw_cfg.push_str(&format!("content={}\n", plugin_meta.file_name));
+    }
+
+    std::fs::write(dir.join("openmw.cfg"), openmw_cfg).unwrap();
+    std::fs::write(dir.join(DEFAULT_CONFIG_NAME), "").unwrap();
+
+    dir
+}
+
+/// Command line arguments running lightfixes over an installation written by `openmw_install`,
+/// given the ones after its paths. Runs are always forced, so that each one generates the plugin again
+pub fn install_args(dir: &Path, args: &[&str]) -> LightArgs {
+    let openmw_cfg = dir.display().to_string();
+    let output_dir = dir.join("output").display().to_string();
+
+    LightArgs::parse_from(
+        [
+            "s3lightfixes",
+            "--openmw-cfg",
+            &openmw_cfg,
+            "--output",
+            &output_dir,
+            "--force",
+        ]
+        .into_iter()
+        .chain(args.iter().copied()),
+    )
+}
+
+/// Runs lightfixes over an installation written by `openmw_install`, without any dialogs
+pub fn run_install(dir: &Path, args: &[&str]) -> Result<RunSummary, LightFixesError> {
+    crate::run(install_args(dir, args))
+}
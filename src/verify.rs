@@ -0,0 +1,170 @@
+use std::{fmt, fs::metadata, io, path::Path};
+
+use tes3::esp::{Header, Plugin, TES3Object};
+use vfstool_lib::VFS;
+
+/// Something wrong with a plugin after it was written
+#[derive(Debug)]
+pub enum VerifyError {
+    Unreadable(io::Error),
+    MissingHeader,
+    MissingMaster(String),
+    MasterSize {
+        name: String,
+        expected: u64,
+        actual: u64,
+    },
+    RecordCount {
+        expected: u32,
+        actual: u32,
+    },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use VerifyError::*;
+        match self {
+            Unreadable(err) => write!(f, "The plugin couldn't be read back: {err}"),
+            MissingHeader => write!(f, "The plugin has no header"),
+            MissingMaster(name) => write!(f, "Master `{name}` does not exist in the VFS"),
+            MasterSize {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Master `{name}` is {actual} bytes, but the header expects {expected} bytes"
+            ),
+            RecordCount { expected, actual } => write!(
+                f,
+                "The plugin holds {actual} records, but its header counts {expected}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Reads a written plugin back, checking that it parses, that each of its masters exists in the VFS
+/// with the size its header expects, and that its header counts its records correctly
+pub fn verify_plugin(plugin_path: &Path, vfs: &VFS) -> Result<(), VerifyError> {
+    let plugin = Plugin::from_path(plugin_path).map_err(VerifyError::Unreadable)?;
+
+    let header = plugin
+        .objects_of_type::<Header>()
+        .next()
+        .ok_or(VerifyError::MissingHeader)?;
+
+    for (name, expected) in &header.masters {
+        let Some(master) = vfs.get_file(name) else {
+            return Err(VerifyError::MissingMaster(name.clone()));
+        };
+
+        let actual = metadata(master.path())
+            .map_err(VerifyError::Unreadable)?
+            .len();
+
+        if actual != *expected {
+            return Err(VerifyError::MasterSize {
+                name: name.clone(),
+                expected: *expected,
+                actual,
+            });
+        }
+    }
+
+    let record_count = plugin
+        .objects
+        .iter()
+        .filter(|object| !matches!(object, TES3Object::Header(_)))
+        .count() as u32;
+
+    if record_count != header.num_objects {
+        return Err(VerifyError::RecordCount {
+            expected: header.num_objects,
+            actual: record_count,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{
+        PLUGIN_NAME, PluginMeta, generate_lightfixes,
+        test_fixtures::{light, neutral_config, plugin, temp_dir},
+    };
+
+    /// Writes a master of `master_size` bytes into a data directory,
+    /// and a plugin generated from it whose header expects `expected_size` bytes
+    fn saved_plugin(test_name: &str, master_size: usize, expected_size: u64) -> (PathBuf, VFS) {
+        let dir = temp_dir(test_name);
+        let data_dir = dir.join("data");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        std::fs::write(data_dir.join("Fires.esp"), vec![0; master_size]).unwrap();
+
+        let (fires, plugin_meta) = plugin("Fires.esp", [light("torch", [255, 160, 64], 128)]);
+        let plugins = vec![(
+            fires,
+            PluginMeta {
+                size: expected_size,
+                ..plugin_meta
+            },
+        )];
+
+        let mut generated_plugin = generate_lightfixes(plugins, &neutral_config()).unwrap();
+        let plugin_path = dir.join(PLUGIN_NAME);
+        generated_plugin.save_path(&plugin_path).unwrap();
+
+        (plugin_path, VFS::from_directories(vec![&data_dir], None))
+    }
+
+    #[test]
+    fn intact_plugins_pass() {
+        let (plugin_path, vfs) = saved_plugin("verify-intact", 64, 64);
+        assert!(verify_plugin(&plugin_path, &vfs).is_ok());
+    }
+
+    #[test]
+    fn truncated_plugins_are_unreadable() {
+        let (plugin_path, vfs) = saved_plugin("verify-truncated", 64, 64);
+
+        let bytes = std::fs::read(&plugin_path).unwrap();
+        std::fs::write(&plugin_path, &bytes[..bytes.len() - 10]).unwrap();
+
+        assert!(matches!(
+            verify_plugin(&plugin_path, &vfs),
+            Err(VerifyError::Unreadable(_))
+        ));
+    }
+
+    #[test]
+    fn masters_must_match_their_size() {
+        let (plugin_path, vfs) = saved_plugin("verify-master-size", 64, 100);
+
+        assert!(matches!(
+            verify_plugin(&plugin_path, &vfs),
+            Err(VerifyError::MasterSize {
+                expected: 100,
+                actual: 64,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn masters_must_exist() {
+        let (plugin_path, _) = saved_plugin("verify-missing-master", 64, 64);
+        let empty_dir = temp_dir("verify-empty-data");
+        let vfs = VFS::from_directories(vec![&empty_dir], None);
+
+        assert!(matches!(
+            verify_plugin(&plugin_path, &vfs),
+            Err(VerifyError::MissingMaster(name)) if name == "Fires.esp"
+        ));
+    }
+}